@@ -0,0 +1,526 @@
+# English messages for the freeze command line.
+
+## Headers
+
+header-export = 📦 Exporting Snapshot
+header-view = 👀 Viewing Snapshot
+header-check = 🔍 Checking Files
+header-save = 🧊 Freezing Bytes...
+header-restore = ♻️  Restoring From Snapshot
+header-ls = 📋 All Snapshots
+header-mcp = 🧊 Starting MCP Server
+header-diff = 📊 Comparing Snapshots
+header-inspect = 🕵️  Inspecting Evolution
+header-blame = 🔎 Line History
+header-amend = ✏️  Amending Snapshot
+header-grep = 🔎 Searching Contents
+header-bench = ⏱️  Benchmarking
+header-self-update = ⬆️  Self Update
+
+## Shared
+
+no-snapshots-for = No snapshots found for:
+snapshot-details = Snapshot details:
+detail-path = Path: { $value }
+detail-date = Date: { $value }
+detail-size = Size: { $value }
+detail-checksum = Checksum: { $value }
+warning-temp-cleanup = Warning: Failed to cleanup temporary files: { $error }
+bytes = bytes
+
+## Plugins
+
+plugin-missing-name = Missing plugin name
+plugin-unknown = Unknown command '{ $name }' (no { $binary } found on PATH)
+plugin-failed = Failed to run { $binary }: { $error }
+
+## Export and view
+
+export-done = Exported snapshot:
+export-to = to
+view-too-large = File too large to display:
+view-too-large-detail = ({ $size } > { $limit } MB limit)
+view-binary = Binary content detected for:
+view-content = Snapshot Content:
+view-unreadable = Unable to display content for:
+view-lines = Lines { $first }-{ $last }:
+view-no-lines = No lines in this range.
+
+## Save and restore
+
+save-freezing = Freezing:
+save-summary = { $count ->
+    [one] ({ $count } file, { $size })
+   *[other] ({ $count } files, { $size })
+}
+save-done = Snapshot created successfully!
+save-unchanged = { $count ->
+    [one] { $count } file is identical to its latest snapshot, nothing recorded for it (--force records it anyway)
+   *[other] { $count } files are identical to their latest snapshot, nothing recorded for them (--force records them anyway)
+}
+amend-done = Amended
+amend-unchanged = The file and message match the latest snapshot, nothing to amend.
+save-skipped = { $count ->
+    [one] Skipped { $count } file that could not be read:
+   *[other] Skipped { $count } files that could not be read:
+}
+save-too-large = { $count ->
+    [one] Skipped { $count } file above the maximum size:
+   *[other] Skipped { $count } files above the maximum size:
+}
+restore-restoring = Restoring:
+restore-done = Restore completed successfully!
+restore-undo-hint = Run `freeze undo` to roll this restore back.
+restore-dry-run = Dry run, nothing was restored:
+restore-plan-overwrite = overwrite
+restore-plan-create = create
+restore-plan-unchanged = unchanged
+restore-plan-summary = { $overwrite } to overwrite, { $create } to create, { $unchanged } unchanged
+
+## Listing, clearing and searching
+
+ls-empty = No snapshots found.
+cls-empty = No snapshots found in { $dir }.
+cls-title = Snapshots in current directory:
+clear-all-start = Clearing all snapshots...
+clear-all-done = All snapshots cleared!
+clear-current-dir = Clearing snapshots in current directory...
+clear-path = Clearing snapshots for:
+clear-expired-none = No snapshots to delete.
+clear-expired-dry-run = { $count ->
+    [one] Would delete { $count } snapshot ({ $size })
+   *[other] Would delete { $count } snapshots ({ $size })
+}
+clear-expired-confirm = { $count ->
+    [one] Delete { $count } snapshot ({ $size })?
+   *[other] Delete { $count } snapshots ({ $size })?
+}
+clear-expired-done = { $count ->
+    [one] Deleted { $count } snapshot, freeing { $size }
+   *[other] Deleted { $count } snapshots, freeing { $size }
+}
+clear-pinned-kept = { $count ->
+    [one] Kept { $count } pinned snapshot
+   *[other] Kept { $count } pinned snapshots
+}
+rm-no-checksum = No snapshot matches checksum { $checksum }
+rm-ambiguous = Checksum { $checksum } is ambiguous, it matches { $count } contents
+rm-several = Checksum { $checksum } matches several snapshots, choose one by ID: { $ids }
+rm-no-id = No snapshot has ID { $id }
+rm-pinned = Snapshot { $checksum } of { $path } is pinned, unpin it first
+pin-done = Pinned snapshots of { $checksum }:
+unpin-done = Unpinned snapshots of { $checksum }:
+prune-no-schedule = No retention schedule is configured: set `retention` or `retention_rules`, or pass --schedule
+grep-empty = No snapshot content matches:
+grep-summary = { $count ->
+    [one] { $count } matching line
+   *[other] { $count } matching lines
+} in { $snapshots ->
+    [one] { $snapshots } snapshot
+   *[other] { $snapshots } snapshots
+}
+search-empty = No snapshots found matching:
+search-title = Snapshots matching:
+search-content-hint = Contents are only indexed with the content_index setting: run `freeze config set content_index true`, then `freeze db index`.
+
+## Exclusions
+
+exclusion-added = Added exclusion:
+exclusion-removed = Removed exclusion:
+exclusion-empty = No exclusions configured.
+exclusion-title = Current exclusions:
+
+## MCP and web
+
+mcp-listening = MCP server listening on stdin/stdout...
+mcp-listening-http = MCP server listening on http://{ $addr }/mcp
+mcp-stop-hint = Press Ctrl+C to stop.
+key-created = Created API key:
+key-created-detail = ({ $scope }, id { $id })
+key-store-warning = Store this token now, it will not be shown again.
+key-empty = No API keys configured.
+key-title = API keys:
+key-entry-detail = created { $created }, last used { $last_used }
+key-never-used = never
+key-revoked = Revoked API key:
+key-not-found = No API key with id:
+
+## Snapshot selection and tables
+
+select-none = No snapshots available
+select-title = Available snapshots:
+select-entry = { $index }. { $date } ({ $size }) - Checksum: { $checksum }
+select-prompt = Select snapshot number (1-{ $max }):
+select-invalid = Invalid selection
+select-invalid-number = Invalid selection: { $selection }
+select-no-checksum = No snapshot with checksum starting with { $checksum } for { $path }
+select-no-date = No snapshot taken at or before { $date }
+select-no-input = { $path } has { $count } snapshots and input is not available; choose one with --latest, --checksum or --yes
+column-date = Date
+column-path = Path
+column-size = Size
+column-stored = Stored
+column-ratio = Ratio
+column-checksum = Checksum
+column-message = Message
+column-snapshots = Snapshots
+column-deduplicated = Deduplicated
+column-saved = Saved
+column-directory = Directory
+column-files = Files
+column-newest = Newest
+page-invalid = Invalid page number. Must be between 1 and { $total }.
+page-label = Page:
+page-of = of
+pagination-items = { $count ->
+    [one] ({ $count } item)
+   *[other] ({ $count } items)
+}
+page-next = Next: --page { $next }
+page-previous = Previous: --page { $previous }
+page-previous-next = Previous: --page { $previous } | Next: --page { $next }
+
+## Check
+
+path-not-found = Path does not exist: { $path }
+check-no-snapshot = (No snapshot found)
+check-up-to-date = (Up to date)
+check-modified-since = (Modified since last snapshot)
+check-progress = Checking { $path }
+check-modified = (Modified)
+check-new = (New file)
+check-summary = Summary:
+check-files-checked = Files checked:
+check-files-modified = Modified files:
+check-files-new = New files:
+check-files-deleted = Deleted files:
+check-deleted = (Deleted)
+
+## Diff and inspect
+
+diff-identical = ✅ Files are identical
+inspect-title = Evolution of:
+inspect-no-change = (No change)
+inspect-binary-changed = (Binary changed)
+inspect-truncated = ... (diff truncated)
+inspect-initial = (Initial)
+blame-not-saved = Not saved
+checksum-not-found = Checksum not found: { $checksum }
+path-or-checksum-not-found = Path or checksum not found: { $arg }
+binary-differ = Binary files differ
+binary-size-difference = Size difference:
+
+## Copy
+
+header-cp = 📑 Copying History
+cp-same-path = Source and destination are the same path
+cp-nothing = No new snapshots to copy for:
+cp-done = { $count ->
+    [one] Copied { $count } snapshot from
+   *[other] Copied { $count } snapshots from
+}
+
+## Aliases
+
+alias-unknown = Unknown alias: @{ $name }
+alias-invalid-name = Invalid alias name '{ $name }': use letters, digits, '-', '_' or '.'
+alias-added = Added alias:
+alias-removed = Removed alias:
+alias-not-found = No alias named:
+alias-empty = No aliases configured.
+alias-title = Aliases:
+
+## Undo
+
+header-undo = ↩️  Undoing Changes
+undo-done = Reverted
+undo-version-of = to the version of
+undo-restore-done = { $count ->
+    [one] Rolled back { $count } file of the restore of
+   *[other] Rolled back { $count } files of the restore of
+}
+
+## Capture
+
+header-capture = 📸 Capturing Command Output
+capture-missing-command = Missing command to run
+capture-failed = Command `{ $command }` failed with exit code { $code }; nothing was saved
+capture-done = Captured output as
+capture-unchanged = Output unchanged since the last capture of
+
+## Run
+
+header-run = ▶️  Running Command
+run-saved = { $count ->
+    [one] Saved 1 changed file { $phase }
+   *[other] Saved { $count } changed files { $phase }
+}
+run-failed = Command `{ $command }` exited with code { $code }
+
+## Key
+
+header-key = 🔐 Repository Key
+passphrase-prompt = Passphrase:{" "}
+passphrase-new = New passphrase:{" "}
+passphrase-repeat = Repeat the new passphrase:{" "}
+key-init-done = { $count ->
+    [one] Repository protected; 1 stored content encrypted
+   *[other] Repository protected; { $count } stored contents encrypted
+}
+key-init-hint = Keep the passphrase safe: snapshots cannot be read without it.
+key-rotated = { $count ->
+    [one] Key and passphrase replaced; 1 stored content re-encrypted
+   *[other] Key and passphrase replaced; { $count } stored contents re-encrypted
+}
+key-status-protected = Repository protected by a passphrase
+key-status-unprotected = Repository not protected (run `freeze key init`)
+key-status-keyring = The key is stored in the platform keyring
+key-status-database = The database is encrypted with SQLCipher
+key-database-encrypted = Database encrypted; snapshot paths, dates and checksums are no longer readable without the key
+key-database-decrypted = Database decrypted back to a plain SQLite file
+key-stored = Key stored in the platform keyring; commands no longer ask for the passphrase
+key-forgotten = Key removed from the platform keyring
+key-not-in-keyring = The keyring holds no key for this repository
+
+## Daemon
+
+header-daemon = 🛰️  Daemon
+daemon-listening = Listening on
+daemon-running = Daemon running (pid { $pid })
+daemon-not-running = No daemon is running
+daemon-stopped = Daemon stopped
+daemon-watching = Watching
+daemon-no-watches = No watched paths
+daemon-proxied = (saved by the running daemon)
+
+## Metadata
+
+meta-set = Set
+meta-on = on
+meta-not-found = No metadata named '{ $key }'
+meta-empty = No metadata on
+meta-title = Metadata of
+meta-removed = Removed metadata:
+
+## Report
+
+report-title = { $period ->
+    [week] Weekly freeze report
+   *[day] Daily freeze report
+}
+report-snapshots = Snapshots taken
+report-paths = Paths changed
+report-paths-detail = { $count } ({ $new } new)
+report-storage = Storage growth
+report-storage-detail = +{ $added } ({ $total } total)
+report-verify = Verification
+report-verify-ok = OK ({ $count } verified)
+report-verify-failed = { $count ->
+    [one] FAILED (1 path with missing or corrupt content)
+   *[other] FAILED ({ $count } paths with missing or corrupt content)
+}
+report-corrupt-title = Missing or corrupt content:
+report-paths-title = Changed paths:
+report-written = Report written to
+
+## Watch
+
+header-watch = 👁️  Watching for Changes
+watch-started = Watching
+watch-stop-hint = (press Ctrl+C to stop)
+watch-saved = Saved
+watch-error = Watch error:
+
+## Sessions
+
+session-empty = No sessions found. Saving a directory records one.
+session-title = Sessions:
+session-files = { $count ->
+    [one] { $count } file
+   *[other] { $count } files
+}
+session-show-title = Session #{ $id }:
+session-current = current files
+session-identical = No differences.
+session-changes = { $count ->
+    [one] { $count } file changed
+   *[other] { $count } files changed
+}
+
+## Status
+
+status-clean = Nothing changed since the latest snapshots ({ $count ->
+    [one] { $count } file
+   *[other] { $count } files
+} up to date)
+status-summary = { $modified } modified, { $new } new, { $deleted } deleted, { $unchanged } up to date
+
+## Materialize
+
+header-materialize = 🧱 Materializing Deltas
+materialize-done = { $count ->
+    [one] Rewrote { $count } delta as a full copy
+   *[other] Rewrote { $count } deltas as full copies
+}
+
+## Compact
+
+header-compact = 🗜️ Compacting Storage
+compact-done = Recompressed { $recompressed } and migrated { $migrated } contents at zstd level { $level }
+compact-saved = Space saved:
+compact-corrupt = { $count ->
+    [one] { $count } snapshot has content that does not match its checksum, left as is:
+   *[other] { $count } snapshots have content that does not match their checksum, left as is:
+}
+
+## Garbage collection
+
+header-gc = 🧹 Collecting Garbage
+gc-orphans = { $count ->
+    [one] Removed { $count } unreferenced storage file
+   *[other] Removed { $count } unreferenced storage files
+}
+gc-missing = { $count ->
+    [one] { $count } snapshot has missing content:
+   *[other] { $count } snapshots have missing content:
+}
+gc-missing-deleted = { $count ->
+    [one] Deleted { $count } snapshot with missing content
+   *[other] Deleted { $count } snapshots with missing content
+}
+gc-force-hint = Run `freeze gc --force` to delete them.
+gc-vacuumed = Compacted the database
+gc-reclaimed = Space reclaimed:
+
+## Database backups
+
+header-db-backup = 💾 Backing Up the Database
+header-db-restore = 💾 Restoring the Database
+db-backup-done = { $count ->
+    [one] Backed up { $count } snapshot to
+   *[other] Backed up { $count } snapshots to
+}
+db-backup-storage = { $count ->
+    [one] Archived { $count } storage file to
+   *[other] Archived { $count } storage files to
+}
+db-restore-confirm = { $count ->
+    [one] Replace the database and its { $count } snapshot with the backup?
+   *[other] Replace the database and its { $count } snapshots with the backup?
+}
+db-restore-done = { $count ->
+    [one] Restored { $count } snapshot from
+   *[other] Restored { $count } snapshots from
+}
+db-restore-storage = { $count ->
+    [one] Added { $count } storage file
+   *[other] Added { $count } storage files
+}
+header-db-vacuum = 🧽 Compacting the Database
+db-integrity-ok = Integrity check passed
+db-integrity-failed = { $count ->
+    [one] Integrity check found { $count } problem:
+   *[other] Integrity check found { $count } problems:
+}
+db-vacuum-refused = The database is damaged; restore a backup with `freeze db restore` instead of rebuilding it
+db-vacuum-before = Before:
+db-vacuum-after = After:
+db-vacuum-pages = ({ $free } free pages, { $percent }% of the file)
+header-db-import = 📥 Importing a Repository
+db-imported = { $count ->
+    [one] Imported { $count } snapshot
+   *[other] Imported { $count } snapshots
+}
+db-imported-detail = ({ $files } storage files copied, { $skipped } snapshots already present, { $sessions } sessions)
+db-export-written = { $count ->
+    [one] Exported { $count } snapshot to
+   *[other] Exported { $count } snapshots to
+}
+
+header-db-index = 🔎 Indexing Contents
+db-indexed = { $count ->
+    [one] Indexed { $count } content
+   *[other] Indexed { $count } contents
+}
+db-index-dropped = { $count ->
+    [one] Removed { $count } content from the index
+   *[other] Removed { $count } contents from the index
+}
+
+## Stats
+
+header-stats = 📈 Storage Statistics
+stats-snapshots = Snapshots
+stats-paths = Paths
+stats-blobs = Unique contents
+stats-logical = Total size
+stats-unique = Unique content size
+stats-stored = Stored (compressed)
+stats-ratio = Compression ratio
+stats-dedup = Deduplication savings
+stats-top-paths = Largest paths:
+stats-directories = Largest directories:
+stats-references = Snapshot references
+stats-compression = Compression savings
+stats-dedup-paths = Paths reusing the most content:
+stats-by-dir = Directories in { $path }:
+stats-by-dir-empty = No snapshots in { $path }
+
+## Config
+
+config-title = Configuration
+config-unset = (default)
+config-set = Set
+config-unset-done = Reset to default:
+
+## Profiles
+
+profile-title = Profiles
+profile-default = (default)
+profile-unknown = Unknown profile: { $name }; create it with `freeze profile create { $name }`
+profile-created = Created profile { $name }:
+profile-use-hint = (use it with `freeze --profile { $name } ...`)
+profile-deleted = Deleted profile
+profile-purge-confirm = Delete the database and storage of profile { $name }?
+profile-purge-refused = { $path } is outside the profiles directory; delete it by hand
+
+## Read-only mode
+
+read-only-refused = The repository is read-only; this command would change it.
+read-only-overwrite = { $count ->
+    [one] The repository is read-only; restoring would overwrite { $count } file whose content differs.
+   *[other] The repository is read-only; restoring would overwrite { $count } files whose content differs.
+}
+
+## Confirmation
+
+confirm-choices = [y/N]
+confirm-no-input = { $question } Input is not available; pass --yes to proceed.
+secret-no-input = { $question }Input is not available; set { $env } instead.
+confirm-cancelled = Cancelled, nothing was changed.
+restore-confirm-overwrite = { $count ->
+    [one] Overwrite { $count } file whose content differs?
+   *[other] Overwrite { $count } files whose content differs?
+}
+clear-confirm-all = { $count ->
+    [one] Delete every snapshot ({ $count } snapshot, { $size })?
+   *[other] Delete every snapshot ({ $count } snapshots, { $size })?
+}
+clear-confirm = { $count ->
+    [one] Delete the snapshots of { $path } ({ $count } snapshot, { $size })?
+   *[other] Delete the snapshots of { $path } ({ $count } snapshots, { $size })?
+}
+
+## Archives
+
+header-export-archive = 📦 Exporting Archive
+header-import-archive = 📥 Importing Archive
+archive-exported = { $count ->
+    [one] Exported { $count } snapshot to
+   *[other] Exported { $count } snapshots to
+}
+archive-imported = { $count ->
+    [one] Imported { $count } snapshot
+   *[other] Imported { $count } snapshots
+}
+archive-imported-detail = ({ $blobs } new contents, { $skipped } already present)
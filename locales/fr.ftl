@@ -0,0 +1,526 @@
+# Messages français de la ligne de commande freeze.
+
+## Headers
+
+header-export = 📦 Export du snapshot
+header-view = 👀 Affichage du snapshot
+header-check = 🔍 Vérification des fichiers
+header-save = 🧊 Gel des octets...
+header-restore = ♻️  Restauration depuis un snapshot
+header-ls = 📋 Tous les snapshots
+header-mcp = 🧊 Démarrage du serveur MCP
+header-diff = 📊 Comparaison des snapshots
+header-blame = 🔎 Historique des lignes
+header-amend = ✏️  Modification du snapshot
+header-grep = 🔎 Recherche dans les contenus
+header-inspect = 🕵️  Inspection de l'évolution
+header-bench = ⏱️  Banc d'essai
+header-self-update = ⬆️  Mise à jour
+
+## Shared
+
+no-snapshots-for = Aucun snapshot trouvé pour :
+snapshot-details = Détails du snapshot :
+detail-path = Chemin : { $value }
+detail-date = Date : { $value }
+detail-size = Taille : { $value }
+detail-checksum = Somme de contrôle : { $value }
+warning-temp-cleanup = Attention : impossible de nettoyer les fichiers temporaires : { $error }
+bytes = octets
+
+## Plugins
+
+plugin-missing-name = Nom de plugin manquant
+plugin-unknown = Commande inconnue '{ $name }' ({ $binary } introuvable dans le PATH)
+plugin-failed = Impossible d'exécuter { $binary } : { $error }
+
+## Export and view
+
+export-done = Snapshot exporté :
+export-to = vers
+view-too-large = Fichier trop volumineux pour être affiché :
+view-too-large-detail = ({ $size } > limite de { $limit } Mo)
+view-binary = Contenu binaire détecté pour :
+view-content = Contenu du snapshot :
+view-unreadable = Impossible d'afficher le contenu de :
+view-lines = Lignes { $first } à { $last } :
+view-no-lines = Aucune ligne dans cette plage.
+
+## Save and restore
+
+save-freezing = Gel de :
+save-summary = { $count ->
+    [one] ({ $count } fichier, { $size })
+   *[other] ({ $count } fichiers, { $size })
+}
+save-done = Snapshot créé avec succès !
+save-unchanged = { $count ->
+    [one] { $count } fichier est identique à son dernier snapshot, rien n'est enregistré pour lui (--force l'enregistre quand même)
+   *[other] { $count } fichiers sont identiques à leur dernier snapshot, rien n'est enregistré pour eux (--force les enregistre quand même)
+}
+amend-done = Modifié
+amend-unchanged = Le fichier et le message correspondent au dernier snapshot, rien à modifier.
+save-skipped = { $count ->
+    [one] { $count } fichier illisible ignoré :
+   *[other] { $count } fichiers illisibles ignorés :
+}
+save-too-large = { $count ->
+    [one] { $count } fichier au-delà de la taille maximale ignoré :
+   *[other] { $count } fichiers au-delà de la taille maximale ignorés :
+}
+restore-restoring = Restauration de :
+restore-done = Restauration terminée avec succès !
+restore-undo-hint = Lancez `freeze undo` pour annuler cette restauration.
+restore-dry-run = Simulation, rien n'a été restauré :
+restore-plan-overwrite = écraser
+restore-plan-create = créer
+restore-plan-unchanged = inchangé
+restore-plan-summary = { $overwrite } à écraser, { $create } à créer, { $unchanged } inchangé(s)
+
+## Listing, clearing and searching
+
+ls-empty = Aucun snapshot trouvé.
+cls-empty = Aucun snapshot trouvé dans { $dir }.
+cls-title = Snapshots du répertoire courant :
+clear-all-start = Suppression de tous les snapshots...
+clear-all-done = Tous les snapshots ont été supprimés !
+clear-current-dir = Suppression des snapshots du répertoire courant...
+clear-path = Suppression des snapshots de :
+clear-expired-none = Aucun snapshot à supprimer.
+clear-expired-dry-run = { $count ->
+    [one] { $count } snapshot serait supprimé ({ $size })
+   *[other] { $count } snapshots seraient supprimés ({ $size })
+}
+clear-expired-confirm = { $count ->
+    [one] Supprimer { $count } snapshot ({ $size }) ?
+   *[other] Supprimer { $count } snapshots ({ $size }) ?
+}
+clear-expired-done = { $count ->
+    [one] { $count } snapshot supprimé, { $size } libérés
+   *[other] { $count } snapshots supprimés, { $size } libérés
+}
+clear-pinned-kept = { $count ->
+    [one] { $count } snapshot épinglé conservé
+   *[other] { $count } snapshots épinglés conservés
+}
+rm-no-checksum = Aucun snapshot ne correspond à la somme de contrôle { $checksum }
+rm-ambiguous = La somme de contrôle { $checksum } est ambiguë, elle correspond à { $count } contenus
+rm-several = La somme de contrôle { $checksum } correspond à plusieurs snapshots, choisissez-en un par ID : { $ids }
+rm-no-id = Aucun snapshot n'a l'ID { $id }
+rm-pinned = Le snapshot { $checksum } de { $path } est épinglé, désépinglez-le d'abord
+pin-done = Snapshots de { $checksum } épinglés :
+unpin-done = Snapshots de { $checksum } désépinglés :
+prune-no-schedule = Aucun calendrier de rétention n'est configuré : définissez `retention` ou `retention_rules`, ou passez --schedule
+grep-empty = Aucun contenu de snapshot ne correspond à :
+grep-summary = { $count ->
+    [one] { $count } ligne trouvée
+   *[other] { $count } lignes trouvées
+} dans { $snapshots ->
+    [one] { $snapshots } snapshot
+   *[other] { $snapshots } snapshots
+}
+search-empty = Aucun snapshot ne correspond à :
+search-title = Snapshots correspondant à :
+search-content-hint = Les contenus ne sont indexés qu'avec le réglage content_index : lancez `freeze config set content_index true`, puis `freeze db index`.
+
+## Exclusions
+
+exclusion-added = Exclusion ajoutée :
+exclusion-removed = Exclusion supprimée :
+exclusion-empty = Aucune exclusion configurée.
+exclusion-title = Exclusions actuelles :
+
+## MCP and web
+
+mcp-listening = Serveur MCP à l'écoute sur stdin/stdout...
+mcp-listening-http = Serveur MCP à l'écoute sur http://{ $addr }/mcp
+mcp-stop-hint = Appuyez sur Ctrl+C pour arrêter.
+key-created = Clé d'API créée :
+key-created-detail = ({ $scope }, id { $id })
+key-store-warning = Conservez ce jeton maintenant, il ne sera plus affiché.
+key-empty = Aucune clé d'API configurée.
+key-title = Clés d'API :
+key-entry-detail = créée le { $created }, dernière utilisation : { $last_used }
+key-never-used = jamais
+key-revoked = Clé d'API révoquée :
+key-not-found = Aucune clé d'API avec l'id :
+
+## Snapshot selection and tables
+
+select-none = Aucun snapshot disponible
+select-title = Snapshots disponibles :
+select-entry = { $index }. { $date } ({ $size }) - Somme de contrôle : { $checksum }
+select-prompt = Numéro du snapshot (1-{ $max }) :
+select-invalid = Sélection invalide
+select-invalid-number = Sélection invalide : { $selection }
+select-no-checksum = Aucun snapshot dont la somme de contrôle commence par { $checksum } pour { $path }
+select-no-date = Aucun snapshot pris avant le { $date }
+select-no-input = { $path } a { $count } snapshots et aucune saisie n'est possible ; choisissez avec --latest, --checksum ou --yes
+column-date = Date
+column-path = Chemin
+column-size = Taille
+column-stored = Stocké
+column-ratio = Ratio
+column-checksum = Somme de contrôle
+column-message = Message
+column-snapshots = Snapshots
+column-deduplicated = Dédupliqués
+column-saved = Économisé
+column-directory = Dossier
+column-files = Fichiers
+column-newest = Plus récent
+page-invalid = Numéro de page invalide. Il doit être compris entre 1 et { $total }.
+page-label = Page :
+page-of = sur
+pagination-items = { $count ->
+    [one] ({ $count } élément)
+   *[other] ({ $count } éléments)
+}
+page-next = Suivante : --page { $next }
+page-previous = Précédente : --page { $previous }
+page-previous-next = Précédente : --page { $previous } | Suivante : --page { $next }
+
+## Check
+
+path-not-found = Le chemin n'existe pas : { $path }
+check-no-snapshot = (Aucun snapshot trouvé)
+check-up-to-date = (À jour)
+check-modified-since = (Modifié depuis le dernier snapshot)
+check-progress = Vérification de { $path }
+check-modified = (Modifié)
+check-new = (Nouveau fichier)
+check-summary = Résumé :
+check-files-checked = Fichiers vérifiés :
+check-files-modified = Fichiers modifiés :
+check-files-new = Nouveaux fichiers :
+check-files-deleted = Fichiers supprimés :
+check-deleted = (Supprimé)
+
+## Diff and inspect
+
+diff-identical = ✅ Les fichiers sont identiques
+inspect-title = Évolution de :
+inspect-no-change = (Aucun changement)
+inspect-binary-changed = (Binaire modifié)
+inspect-truncated = ... (diff tronqué)
+blame-not-saved = Non sauvegardé
+inspect-initial = (Initial)
+checksum-not-found = Somme de contrôle introuvable : { $checksum }
+path-or-checksum-not-found = Chemin ou somme de contrôle introuvable : { $arg }
+binary-differ = Les fichiers binaires diffèrent
+binary-size-difference = Différence de taille :
+
+## Copy
+
+header-cp = 📑 Copie de l'historique
+cp-same-path = La source et la destination sont identiques
+cp-nothing = Aucun nouveau snapshot à copier pour :
+cp-done = { $count ->
+    [one] { $count } snapshot copié depuis
+   *[other] { $count } snapshots copiés depuis
+}
+
+## Aliases
+
+alias-unknown = Alias inconnu : @{ $name }
+alias-invalid-name = Nom d'alias invalide '{ $name }' : utilisez des lettres, des chiffres, '-', '_' ou '.'
+alias-added = Alias ajouté :
+alias-removed = Alias supprimé :
+alias-not-found = Aucun alias nommé :
+alias-empty = Aucun alias configuré.
+alias-title = Alias :
+
+## Undo
+
+header-undo = ↩️  Annulation des modifications
+undo-done = Fichier
+undo-version-of = ramené à la version du
+undo-restore-done = { $count ->
+    [one] { $count } fichier remis en l'état d'avant la restauration de
+   *[other] { $count } fichiers remis en l'état d'avant la restauration de
+}
+
+## Capture
+
+header-capture = 📸 Capture de la sortie d'une commande
+capture-missing-command = Commande à exécuter manquante
+capture-failed = La commande `{ $command }` a échoué avec le code { $code } ; rien n'a été enregistré
+capture-done = Sortie enregistrée sous
+capture-unchanged = Sortie inchangée depuis la dernière capture de
+
+## Run
+
+header-run = ▶️  Exécution d'une commande
+run-saved = { $count ->
+    [one] 1 fichier modifié enregistré ({ $phase })
+   *[other] { $count } fichiers modifiés enregistrés ({ $phase })
+}
+run-failed = La commande `{ $command }` s'est terminée avec le code { $code }
+
+## Key
+
+header-key = 🔐 Clé du dépôt
+passphrase-prompt = Phrase secrète :{" "}
+passphrase-new = Nouvelle phrase secrète :{" "}
+passphrase-repeat = Répétez la nouvelle phrase secrète :{" "}
+key-init-done = { $count ->
+    [one] Dépôt protégé ; 1 contenu stocké chiffré
+   *[other] Dépôt protégé ; { $count } contenus stockés chiffrés
+}
+key-init-hint = Conservez la phrase secrète : les snapshots sont illisibles sans elle.
+key-rotated = { $count ->
+    [one] Clé et phrase secrète remplacées ; 1 contenu stocké rechiffré
+   *[other] Clé et phrase secrète remplacées ; { $count } contenus stockés rechiffrés
+}
+key-status-protected = Dépôt protégé par une phrase secrète
+key-status-unprotected = Dépôt non protégé (lancez `freeze key init`)
+key-status-keyring = La clé est conservée dans le trousseau du système
+key-status-database = La base de données est chiffrée avec SQLCipher
+key-database-encrypted = Base de données chiffrée ; les chemins, dates et sommes de contrôle des snapshots ne sont plus lisibles sans la clé
+key-database-decrypted = Base de données redevenue un fichier SQLite non chiffré
+key-stored = Clé conservée dans le trousseau du système ; la phrase secrète n'est plus demandée
+key-forgotten = Clé retirée du trousseau du système
+key-not-in-keyring = Le trousseau ne contient aucune clé pour ce dépôt
+
+## Daemon
+
+header-daemon = 🛰️  Démon
+daemon-listening = En écoute sur
+daemon-running = Démon en cours d'exécution (pid { $pid })
+daemon-not-running = Aucun démon n'est en cours d'exécution
+daemon-stopped = Démon arrêté
+daemon-watching = Surveillance de
+daemon-no-watches = Aucun chemin surveillé
+daemon-proxied = (enregistré par le démon en cours d'exécution)
+
+## Metadata
+
+meta-set = Métadonnée
+meta-on = définie sur
+meta-not-found = Aucune métadonnée nommée '{ $key }'
+meta-empty = Aucune métadonnée sur
+meta-title = Métadonnées de
+meta-removed = Métadonnée supprimée :
+
+## Report
+
+report-title = { $period ->
+    [week] Rapport freeze hebdomadaire
+   *[day] Rapport freeze quotidien
+}
+report-snapshots = Snapshots créés
+report-paths = Chemins modifiés
+report-paths-detail = { $count } ({ $new } nouveaux)
+report-storage = Croissance du stockage
+report-storage-detail = +{ $added } ({ $total } au total)
+report-verify = Vérification
+report-verify-ok = OK ({ $count } vérifiés)
+report-verify-failed = { $count ->
+    [one] ÉCHEC (1 chemin au contenu manquant ou corrompu)
+   *[other] ÉCHEC ({ $count } chemins au contenu manquant ou corrompu)
+}
+report-corrupt-title = Contenu manquant ou corrompu :
+report-paths-title = Chemins modifiés :
+report-written = Rapport écrit dans
+
+## Watch
+
+header-watch = 👁️  Surveillance des modifications
+watch-started = Surveillance de
+watch-stop-hint = (Ctrl+C pour arrêter)
+watch-saved = Enregistré
+watch-error = Erreur de surveillance :
+
+## Sessions
+
+session-empty = Aucune session trouvée. Enregistrer un dossier en crée une.
+session-title = Sessions :
+session-files = { $count ->
+    [one] { $count } fichier
+   *[other] { $count } fichiers
+}
+session-show-title = Session n°{ $id } :
+session-current = fichiers actuels
+session-identical = Aucune différence.
+session-changes = { $count ->
+    [one] { $count } fichier modifié
+   *[other] { $count } fichiers modifiés
+}
+
+## Status
+
+status-clean = Aucun changement depuis les derniers snapshots ({ $count ->
+    [one] { $count } fichier à jour
+   *[other] { $count } fichiers à jour
+})
+status-summary = { $modified } modifié(s), { $new } nouveau(x), { $deleted } supprimé(s), { $unchanged } à jour
+
+## Materialize
+
+header-materialize = 🧱 Matérialisation des deltas
+materialize-done = { $count ->
+    [one] { $count } delta réécrit en copie complète
+   *[other] { $count } deltas réécrits en copies complètes
+}
+
+## Compact
+
+header-compact = 🗜️ Compactage du stockage
+compact-done = { $recompressed } contenus recompressés et { $migrated } migrés au niveau zstd { $level }
+compact-saved = Espace gagné :
+compact-corrupt = { $count ->
+    [one] { $count } snapshot a un contenu qui ne correspond pas à son checksum, laissé tel quel :
+   *[other] { $count } snapshots ont un contenu qui ne correspond pas à leur checksum, laissés tels quels :
+}
+
+## Garbage collection
+
+header-gc = 🧹 Nettoyage du stockage
+gc-orphans = { $count ->
+    [one] { $count } fichier de stockage non référencé supprimé
+   *[other] { $count } fichiers de stockage non référencés supprimés
+}
+gc-missing = { $count ->
+    [one] { $count } snapshot a un contenu manquant :
+   *[other] { $count } snapshots ont un contenu manquant :
+}
+gc-missing-deleted = { $count ->
+    [one] { $count } snapshot au contenu manquant supprimé
+   *[other] { $count } snapshots au contenu manquant supprimés
+}
+gc-force-hint = Lancez `freeze gc --force` pour les supprimer.
+gc-vacuumed = Base de données compactée
+gc-reclaimed = Espace récupéré :
+
+## Sauvegardes de la base
+
+header-db-backup = 💾 Sauvegarde de la base de données
+header-db-restore = 💾 Restauration de la base de données
+db-backup-done = { $count ->
+    [one] { $count } snapshot sauvegardé dans
+   *[other] { $count } snapshots sauvegardés dans
+}
+db-backup-storage = { $count ->
+    [one] { $count } fichier de stockage archivé dans
+   *[other] { $count } fichiers de stockage archivés dans
+}
+db-restore-confirm = { $count ->
+    [one] Remplacer la base de données et son { $count } snapshot par la sauvegarde ?
+   *[other] Remplacer la base de données et ses { $count } snapshots par la sauvegarde ?
+}
+db-restore-done = { $count ->
+    [one] { $count } snapshot restauré depuis
+   *[other] { $count } snapshots restaurés depuis
+}
+db-restore-storage = { $count ->
+    [one] { $count } fichier de stockage ajouté
+   *[other] { $count } fichiers de stockage ajoutés
+}
+header-db-vacuum = 🧽 Compactage de la base de données
+db-integrity-ok = Vérification d'intégrité réussie
+db-integrity-failed = { $count ->
+    [one] La vérification d'intégrité a trouvé { $count } problème :
+   *[other] La vérification d'intégrité a trouvé { $count } problèmes :
+}
+db-vacuum-refused = La base de données est endommagée ; restaurez une sauvegarde avec `freeze db restore` au lieu de la reconstruire
+db-vacuum-before = Avant :
+db-vacuum-after = Après :
+db-vacuum-pages = ({ $free } pages libres, { $percent } % du fichier)
+header-db-import = 📥 Import d'un dépôt
+db-imported = { $count ->
+    [one] { $count } snapshot importé
+   *[other] { $count } snapshots importés
+}
+db-imported-detail = ({ $files } fichiers de stockage copiés, { $skipped } snapshots déjà présents, { $sessions } sessions)
+db-export-written = { $count ->
+    [one] { $count } snapshot exporté dans
+   *[other] { $count } snapshots exportés dans
+}
+
+header-db-index = 🔎 Indexation des contenus
+db-indexed = { $count ->
+    [one] { $count } contenu indexé
+   *[other] { $count } contenus indexés
+}
+db-index-dropped = { $count ->
+    [one] { $count } contenu retiré de l'index
+   *[other] { $count } contenus retirés de l'index
+}
+
+## Stats
+
+header-stats = 📈 Statistiques de stockage
+stats-snapshots = Snapshots
+stats-paths = Chemins
+stats-blobs = Contenus uniques
+stats-logical = Taille totale
+stats-unique = Taille des contenus uniques
+stats-stored = Stocké (compressé)
+stats-ratio = Taux de compression
+stats-dedup = Gain de déduplication
+stats-top-paths = Chemins les plus volumineux :
+stats-directories = Dossiers les plus volumineux :
+stats-references = Références de snapshots
+stats-compression = Gain de compression
+stats-dedup-paths = Chemins réutilisant le plus de contenu :
+stats-by-dir = Dossiers de { $path } :
+stats-by-dir-empty = Aucun snapshot dans { $path }
+
+## Config
+
+config-title = Configuration
+config-unset = (par défaut)
+config-set = Défini :
+config-unset-done = Valeur par défaut rétablie :
+
+## Profils
+
+profile-title = Profils
+profile-default = (par défaut)
+profile-unknown = Profil inconnu : { $name } ; créez-le avec `freeze profile create { $name }`
+profile-created = Profil { $name } créé :
+profile-use-hint = (utilisez-le avec `freeze --profile { $name } ...`)
+profile-deleted = Profil supprimé
+profile-purge-confirm = Supprimer la base de données et le stockage du profil { $name } ?
+profile-purge-refused = { $path } est hors du répertoire des profils ; supprimez-le à la main
+
+## Mode lecture seule
+
+read-only-refused = Le dépôt est en lecture seule ; cette commande le modifierait.
+read-only-overwrite = { $count ->
+    [one] Le dépôt est en lecture seule ; la restauration écraserait { $count } fichier dont le contenu diffère.
+   *[other] Le dépôt est en lecture seule ; la restauration écraserait { $count } fichiers dont le contenu diffère.
+}
+
+## Confirmation
+
+confirm-choices = [o/N]
+confirm-no-input = { $question } Aucune saisie n'est possible ; passez --yes pour continuer.
+secret-no-input = { $question }Aucune saisie n'est possible ; définissez plutôt { $env }.
+confirm-cancelled = Annulé, rien n'a été modifié.
+restore-confirm-overwrite = { $count ->
+    [one] Écraser { $count } fichier dont le contenu diffère ?
+   *[other] Écraser { $count } fichiers dont le contenu diffère ?
+}
+clear-confirm-all = { $count ->
+    [one] Supprimer tous les snapshots ({ $count } snapshot, { $size }) ?
+   *[other] Supprimer tous les snapshots ({ $count } snapshots, { $size }) ?
+}
+clear-confirm = { $count ->
+    [one] Supprimer les snapshots de { $path } ({ $count } snapshot, { $size }) ?
+   *[other] Supprimer les snapshots de { $path } ({ $count } snapshots, { $size }) ?
+}
+
+## Archives
+
+header-export-archive = 📦 Export d'archive
+header-import-archive = 📥 Import d'archive
+archive-exported = { $count ->
+    [one] { $count } snapshot exporté vers
+   *[other] { $count } snapshots exportés vers
+}
+archive-imported = { $count ->
+    [one] { $count } snapshot importé
+   *[other] { $count } snapshots importés
+}
+archive-imported-detail = ({ $blobs } nouveaux contenus, { $skipped } déjà présents)
@@ -0,0 +1,393 @@
+/*!
+Snapshot archives for the freeze application.
+
+An archive is a tar file holding a `manifest.json` that lists snapshot
+records, followed by the compressed content of each distinct blob under
+`blobs/<checksum>.<ext>`, the extension naming the compression algorithm as
+in storage. Archives move history between machines: importing
+one recreates the snapshot rows and stores each blob once. Plain tarballs
+can be imported too, every file becoming a snapshot below a chosen path.
+*/
+
+use crate::compression::{self, Compression};
+use crate::db::Database;
+use crate::hash::HashAlgorithm;
+use crate::snapshot::Snapshot;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+const MANIFEST: &str = "manifest.json";
+const BLOBS_DIR: &str = "blobs";
+const FORMAT_VERSION: u32 = 1;
+
+/// The list of snapshots stored in an archive.
+#[derive(Serialize, Deserialize, Debug)]
+struct Manifest {
+    version: u32,
+    snapshots: Vec<ManifestEntry>,
+}
+
+/// A snapshot record of the manifest.
+#[derive(Serialize, Deserialize, Debug)]
+struct ManifestEntry {
+    path: PathBuf,
+    checksum: String,
+    date: String,
+    size: i64,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mtime: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symlink: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    xattrs: Option<crate::xattrs::Xattrs>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mime: Option<String>,
+    #[serde(default)]
+    hash: HashAlgorithm,
+}
+
+/// What an import added to the store.
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    /// Snapshots recorded
+    pub snapshots: usize,
+    /// Snapshots already present, skipped
+    pub skipped: usize,
+    /// Content blobs added to storage
+    pub blobs: usize,
+}
+
+/// Writes the snapshots of `path` (or every snapshot) to an archive.
+///
+/// # Arguments
+///
+/// * `path` - Only export snapshots of this file or directory
+/// * `dest` - Archive file to create
+/// * `db` - Database connection
+///
+/// # Returns
+///
+/// The number of snapshots exported
+///
+/// # Errors
+///
+/// Returns an error if the snapshots cannot be listed, a stored content
+/// cannot be read or the archive cannot be written.
+pub fn export(path: Option<&Path>, dest: &Path, db: &Database) -> Result<usize> {
+    let snapshots: Vec<Snapshot> = db
+        .list_all_snapshot_records()?
+        .into_iter()
+        .filter(|snapshot| path.is_none_or(|path| snapshot.path.starts_with(path)))
+        .collect();
+
+    let manifest = Manifest {
+        version: FORMAT_VERSION,
+        snapshots: snapshots
+            .iter()
+            .map(|snapshot| ManifestEntry {
+                path: snapshot.path.clone(),
+                checksum: snapshot.checksum.clone(),
+                date: snapshot.date.clone(),
+                size: snapshot.size,
+                message: snapshot.message.clone(),
+                mtime: snapshot.mtime,
+                mode: snapshot.mode,
+                symlink: snapshot.symlink.clone(),
+                xattrs: snapshot.xattrs.clone(),
+                mime: snapshot.mime.clone(),
+                hash: snapshot.hash,
+            })
+            .collect(),
+    };
+
+    let mut builder = tar::Builder::new(fs::File::create(dest)?);
+    append(&mut builder, MANIFEST, &serde_json::to_vec_pretty(&manifest)?)?;
+
+    let mut written = HashSet::new();
+    for snapshot in &snapshots {
+        // Symbolic links have no content
+        if snapshot.symlink.is_some() || !written.insert(&snapshot.checksum) {
+            continue;
+        }
+        // Legacy contents were stored uncompressed and large ones as chunks,
+        // both are archived as a single zstd blob
+        let compression = Compression::from_path(&snapshot.content_path);
+        let blob = match compression {
+            Some(_) => crate::crypto::open_content(&snapshot.content_path).and_then(|mut content| {
+                let mut blob = Vec::new();
+                content.read_to_end(&mut blob)?;
+                Ok(blob)
+            }),
+            None => compression::open(&snapshot.content_path).and_then(|mut content| {
+                let mut blob = Vec::new();
+                zstd::stream::copy_encode(&mut content, &mut blob, 0)?;
+                Ok(blob)
+            }),
+        }
+        .with_context(|| format!("Snapshot content missing for {}", snapshot.path.display()))?;
+        append(
+            &mut builder,
+            &format!(
+                "{}/{}.{}",
+                BLOBS_DIR,
+                snapshot.checksum,
+                compression.unwrap_or_default().extension()
+            ),
+            &blob,
+        )?;
+    }
+    builder.into_inner()?;
+    Ok(snapshots.len())
+}
+
+/// Appends an in-memory file to a tar archive.
+fn append<W: std::io::Write>(builder: &mut tar::Builder<W>, name: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Local::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, name, data)?;
+    Ok(())
+}
+
+/// Imports an archive made by [`export`], or a plain tarball below `as_path`.
+///
+/// # Arguments
+///
+/// * `archive` - The tar file to read
+/// * `as_path` - Record every file of a plain tarball below this path
+/// * `db` - Database connection
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The archive cannot be read or is not a freeze archive (without `as_path`)
+/// - A blob does not match its checksum or a manifest entry has no blob
+/// - A tarball entry escapes `as_path`
+/// - A snapshot cannot be stored
+pub fn import(archive: &Path, as_path: Option<&Path>, db: &Database) -> Result<ImportSummary> {
+    match as_path {
+        Some(as_path) => import_tarball(archive, as_path, db),
+        None => import_archive(archive, db),
+    }
+}
+
+/// Imports an archive made by [`export`].
+fn import_archive(archive: &Path, db: &Database) -> Result<ImportSummary> {
+    let storage_dir = Database::storage_dir()?;
+    fs::create_dir_all(&storage_dir)?;
+
+    let mut summary = ImportSummary::default();
+    let mut manifest: Option<Manifest> = None;
+    let mut tar = tar::Archive::new(fs::File::open(archive)?);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+
+        if name == MANIFEST {
+            let parsed: Manifest = serde_json::from_reader(&mut entry)?;
+            if parsed.version > FORMAT_VERSION {
+                bail!("Unsupported archive version {}", parsed.version);
+            }
+            manifest = Some(parsed);
+        } else if let Some(blob_name) = name
+            .strip_prefix(BLOBS_DIR)
+            .and_then(|rest| rest.strip_prefix('/'))
+        {
+            let blob_name = Path::new(blob_name);
+            let (Some(compression), Some(checksum)) = (
+                Compression::from_path(blob_name),
+                blob_name.file_stem().and_then(|stem| stem.to_str()),
+            ) else {
+                continue;
+            };
+            if !crate::utils::is_checksum(checksum) {
+                bail!("Invalid blob name in archive: {}", name);
+            }
+            if compression::find_blob(&storage_dir, checksum).is_some() {
+                continue;
+            }
+            let mut blob = Vec::new();
+            entry.read_to_end(&mut blob)?;
+            // Blobs are named after the checksum of either algorithm
+            let mut matches = false;
+            for algorithm in HashAlgorithm::ALL {
+                matches = matches || blob_checksum(&blob, compression, algorithm)? == checksum;
+            }
+            if !matches {
+                bail!("Blob {} does not match its checksum", checksum);
+            }
+            let content_path = compression::blob_path(&storage_dir, checksum, compression);
+            let temp_path = content_path.with_extension(format!("{}.tmp", compression.extension()));
+            let mut writer = crate::crypto::content_writer(fs::File::create(&temp_path)?)?;
+            writer.write_all(&blob)?;
+            writer.finish()?;
+            fs::rename(&temp_path, &content_path)?;
+            summary.blobs += 1;
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| {
+        anyhow::anyhow!(
+            "{} is not a freeze archive (no {}); use --as <path> to import a plain tarball",
+            archive.display(),
+            MANIFEST
+        )
+    })?;
+
+    for entry in manifest.snapshots {
+        let content_path = match (&entry.symlink, compression::find_blob(&storage_dir, &entry.checksum)) {
+            (Some(_), _) => PathBuf::new(),
+            (None, Some(content_path)) => content_path,
+            (None, None) => bail!("Archive has no content for {} ({})", entry.path.display(), entry.checksum),
+        };
+        let stored_size = compression::blob_size(&content_path);
+        let snapshot = Snapshot {
+            path: entry.path,
+            content_path,
+            checksum: entry.checksum,
+            date: entry.date,
+            size: entry.size,
+            message: entry.message,
+            mtime: entry.mtime,
+            mode: entry.mode,
+            symlink: entry.symlink,
+            xattrs: entry.xattrs,
+            mime: entry.mime,
+            hash: entry.hash,
+            stored_size,
+        };
+        record(snapshot, db, &mut summary)?;
+    }
+    Ok(summary)
+}
+
+/// Imports every regular file and symbolic link of a plain tarball below `as_path`.
+fn import_tarball(archive: &Path, as_path: &Path, db: &Database) -> Result<ImportSummary> {
+    let mut summary = ImportSummary::default();
+    let mut tar = tar::Archive::new(fs::File::open(archive)?);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_symlink() {
+            continue;
+        }
+        let target = entry_target(as_path, &entry.path()?)?;
+        let mtime = entry.header().mtime().ok();
+        let date = mtime
+            .and_then(|mtime| DateTime::from_timestamp(mtime as i64, 0))
+            .map(|date| date.with_timezone(&Local).to_rfc3339())
+            .unwrap_or_else(|| Local::now().to_rfc3339());
+
+        if entry_type.is_symlink() {
+            let Some(link) = entry.link_name()? else {
+                continue;
+            };
+            let link = link.into_owned();
+            let snapshot = Snapshot {
+                checksum: Snapshot::symlink_checksum(&link),
+                path: target,
+                content_path: PathBuf::new(),
+                date,
+                size: link.as_os_str().len() as i64,
+                message: None,
+                mtime: mtime.map(|mtime| mtime as i64 * 1_000_000_000),
+                mode: None,
+                symlink: Some(link),
+                xattrs: None,
+                mime: None,
+                hash: HashAlgorithm::Sha256,
+                stored_size: None,
+            };
+            record(snapshot, db, &mut summary)?;
+            continue;
+        }
+
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        let storage_dir = Database::storage_dir()?;
+        let checksum = crate::config::get().hash().checksum(&content);
+        let is_new_blob = compression::find_blob(&storage_dir, &checksum).is_none();
+
+        let mut snapshot = Snapshot::from_content(&target, &content)?;
+        snapshot.date = date;
+        snapshot.mtime = mtime.map(|mtime| mtime as i64 * 1_000_000_000);
+        snapshot.mode = entry.header().mode().ok().map(|mode| mode & 0o7777);
+        if is_new_blob {
+            summary.blobs += 1;
+        }
+        record(snapshot, db, &mut summary)?;
+    }
+    Ok(summary)
+}
+
+/// Saves a snapshot unless the same content is already recorded for its path.
+fn record(snapshot: Snapshot, db: &Database, summary: &mut ImportSummary) -> Result<()> {
+    let exists = db
+        .get_snapshots_for_path(&snapshot.path)?
+        .iter()
+        .any(|existing| existing.checksum == snapshot.checksum);
+    if exists {
+        summary.skipped += 1;
+    } else {
+        db.save_snapshot(&snapshot)?;
+        summary.snapshots += 1;
+    }
+    Ok(())
+}
+
+/// Joins a tarball entry path onto `root`, rejecting paths that leave it.
+fn entry_target(root: &Path, entry: &Path) -> Result<PathBuf> {
+    let mut target = root.to_path_buf();
+    for component in entry.components() {
+        match component {
+            Component::Normal(part) => target.push(part),
+            Component::CurDir => {}
+            _ => bail!("Unsafe path in archive: {}", entry.display()),
+        }
+    }
+    Ok(target)
+}
+
+/// Checksum of the decompressed content of a blob.
+fn blob_checksum(blob: &[u8], compression: Compression, algorithm: HashAlgorithm) -> Result<String> {
+    algorithm.checksum_reader(compression.decoder(blob)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_target() {
+        let root = Path::new("/imported");
+        assert_eq!(
+            entry_target(root, Path::new("./src/main.rs")).unwrap(),
+            PathBuf::from("/imported/src/main.rs")
+        );
+        assert!(entry_target(root, Path::new("../etc/passwd")).is_err());
+        assert!(entry_target(root, Path::new("/etc/passwd")).is_err());
+    }
+
+    #[test]
+    fn test_blob_checksum() {
+        let blob = zstd::encode_all(&b"hello"[..], 3).unwrap();
+        let checksum = HashAlgorithm::Sha256.checksum(b"hello");
+        assert_eq!(blob_checksum(&blob, Compression::Zstd, HashAlgorithm::Sha256).unwrap(), checksum);
+        assert_eq!(blob_checksum(b"hello", Compression::Store, HashAlgorithm::Sha256).unwrap(), checksum);
+        assert_eq!(
+            blob_checksum(b"hello", Compression::Store, HashAlgorithm::Blake3).unwrap(),
+            HashAlgorithm::Blake3.checksum(b"hello")
+        );
+    }
+}
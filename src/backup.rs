@@ -0,0 +1,103 @@
+/*!
+Repository backups for the freeze application.
+
+`freeze db backup` copies the database with SQLite's online backup API, so
+the copy is consistent even while the daemon or a server writes to it, and
+can also write the stored contents to a tar archive holding the `storage`
+directory. `freeze db restore` puts a backup in place of the database and
+adds the contents of an archive that storage lacks.
+
+Stored contents are named after their checksum, so a file already in
+storage is never overwritten by one from an archive.
+*/
+
+use crate::db::Database;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Name of the storage directory in archives.
+const STORAGE_DIR: &str = "storage";
+
+/// What a backup or restore copied.
+#[derive(Debug, Default, PartialEq)]
+pub struct BackupSummary {
+    /// Snapshots in the database copied
+    pub snapshots: usize,
+    /// Storage files written, to the archive or to storage
+    pub files: usize,
+}
+
+/// Copies the database to `output`, and the stored contents to a tar
+/// archive at `storage` if given.
+///
+/// The database is copied first, so the archive holds every content it
+/// refers to unless one is deleted in between.
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be copied or the archive
+/// cannot be written.
+pub fn backup(output: &Path, storage: Option<&Path>, db: &Database) -> Result<BackupSummary> {
+    db.backup_to(output)
+        .with_context(|| format!("Failed to back up the database to {}", output.display()))?;
+    let mut summary = BackupSummary {
+        snapshots: Database::count_snapshots_in(output)?,
+        files: 0,
+    };
+
+    if let Some(archive) = storage {
+        let storage_dir = Database::storage_dir()?;
+        let mut builder = tar::Builder::new(fs::File::create(archive)?);
+        for entry in walkdir::WalkDir::new(&storage_dir) {
+            let entry = entry?;
+            let path = entry.path();
+            // Temporary files belong to operations still running
+            if !entry.file_type().is_file() || path.extension().is_some_and(|extension| extension == "tmp") {
+                continue;
+            }
+            let name = Path::new(STORAGE_DIR).join(path.strip_prefix(&storage_dir)?);
+            builder.append_path_with_name(path, name)?;
+            summary.files += 1;
+        }
+        builder.finish()?;
+    }
+    Ok(summary)
+}
+
+/// Replaces the database with the backup at `input`, and adds the stored
+/// contents of the tar archive at `storage` if given.
+///
+/// # Errors
+///
+/// Returns an error if the backup is not a freeze database, or if the
+/// database or the archive cannot be read.
+pub fn restore(input: &Path, storage: Option<&Path>, db: &mut Database) -> Result<BackupSummary> {
+    let snapshots = Database::count_snapshots_in(input)
+        .with_context(|| format!("{} is not a freeze database", input.display()))?;
+
+    // Contents first, so the restored snapshots find theirs
+    let mut files = 0;
+    if let Some(archive) = storage {
+        let data_dir = Database::data_dir()?;
+        fs::create_dir_all(&data_dir)?;
+        let mut archive = tar::Archive::new(
+            fs::File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?,
+        );
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.into_owned();
+            if !name.starts_with(STORAGE_DIR) || data_dir.join(&name).exists() {
+                continue;
+            }
+            // Refuses names leaving the data directory
+            if entry.unpack_in(&data_dir)? {
+                files += 1;
+            }
+        }
+    }
+
+    db.restore_from(input)
+        .with_context(|| format!("Failed to restore the database from {}", input.display()))?;
+    Ok(BackupSummary { snapshots, files })
+}
@@ -0,0 +1,211 @@
+/*!
+Throughput benchmarks for the freeze application.
+
+This module generates a synthetic dataset in a temporary directory and
+measures hashing, compression (save) and decompression (restore) speed for
+a set of zstd levels and thread counts. Nothing is written to the freeze
+store.
+*/
+
+use crate::snapshot::Snapshot;
+use crate::utils::format_size;
+use anyhow::Result;
+use console::style;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tabled::{Table, Tabled};
+
+/// Parameters of a benchmark run.
+pub struct BenchOptions {
+    /// Number of synthetic files to generate
+    pub files: usize,
+    /// Size of each synthetic file in bytes
+    pub file_size: usize,
+    /// Zstd compression levels to compare
+    pub levels: Vec<i32>,
+    /// Thread counts to compare
+    pub threads: Vec<usize>,
+}
+
+#[derive(Tabled)]
+struct BenchRow {
+    #[tabled(rename = "Level")]
+    level: i32,
+    #[tabled(rename = "Threads")]
+    threads: usize,
+    #[tabled(rename = "Hash")]
+    hash: String,
+    #[tabled(rename = "Save")]
+    save: String,
+    #[tabled(rename = "Restore")]
+    restore: String,
+    #[tabled(rename = "Ratio")]
+    ratio: String,
+}
+
+/// Removes the benchmark directory when dropped.
+struct BenchDir(PathBuf);
+
+impl Drop for BenchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs the benchmark and prints a comparison table.
+///
+/// # Arguments
+///
+/// * `options` - Dataset shape, compression levels and thread counts
+///
+/// # Errors
+///
+/// Returns an error if the dataset cannot be written or an operation fails.
+pub fn run(options: &BenchOptions) -> Result<()> {
+    if options.files == 0 || options.file_size == 0 {
+        anyhow::bail!("Benchmark needs at least one non-empty file");
+    }
+    if options.threads.contains(&0) {
+        anyhow::bail!("Thread counts must be greater than zero");
+    }
+
+    let dir = BenchDir(std::env::temp_dir().join(format!("freeze-bench-{}", std::process::id())));
+    let source_dir = dir.0.join("source");
+    let stored_dir = dir.0.join("stored");
+    let restored_dir = dir.0.join("restored");
+    fs::create_dir_all(&source_dir)?;
+
+    let total_bytes = (options.files * options.file_size) as i64;
+    println!(
+        "{} {} files of {} ({} total)",
+        style("Dataset:").cyan().bold(),
+        options.files,
+        format_size(options.file_size as i64),
+        format_size(total_bytes)
+    );
+
+    let sources = generate_dataset(&source_dir, options.files, options.file_size)?;
+
+    let mut rows = Vec::new();
+    for &level in &options.levels {
+        for &threads in &options.threads {
+            for d in [&stored_dir, &restored_dir] {
+                let _ = fs::remove_dir_all(d);
+                fs::create_dir_all(d)?;
+            }
+
+            let hash = timed(&sources, threads, |_, src| {
+                Snapshot::calculate_checksum(src).map(|_| ())
+            })?;
+            let save = timed(&sources, threads, |i, src| {
+                Snapshot::compress_and_copy_with_level(
+                    src,
+                    stored_dir.join(format!("{}.zstd", i)),
+                    level,
+                )
+            })?;
+            let restore = timed(&sources, threads, |i, _| {
+                Snapshot::decompress_and_copy(
+                    stored_dir.join(format!("{}.zstd", i)),
+                    restored_dir.join(i.to_string()),
+                )
+            })?;
+
+            let stored_bytes: u64 = fs::read_dir(&stored_dir)?
+                .filter_map(|e| e.ok()?.metadata().ok())
+                .map(|m| m.len())
+                .sum();
+
+            rows.push(BenchRow {
+                level,
+                threads,
+                hash: throughput(total_bytes, hash),
+                save: throughput(total_bytes, save),
+                restore: throughput(total_bytes, restore),
+                ratio: format!("{:.2}x", total_bytes as f64 / stored_bytes.max(1) as f64),
+            });
+        }
+    }
+
+    println!("{}", crate::utils::style_table(&mut Table::new(rows)));
+    Ok(())
+}
+
+/// Writes `count` compressible pseudo-random files of `size` bytes.
+fn generate_dataset(dir: &Path, count: usize, size: usize) -> Result<Vec<PathBuf>> {
+    const WORDS: [&str; 16] = [
+        "freeze", "snapshot", "restore", "storage", "checksum", "zstd", "file", "data",
+        "backup", "version", "diff", "path", "size", "date", "bytes", "store",
+    ];
+
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut next = || {
+        // xorshift64
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let mut paths = Vec::with_capacity(count);
+    for i in 0..count {
+        let mut content = Vec::with_capacity(size + 16);
+        while content.len() < size {
+            let r = next();
+            if r % 4 == 0 {
+                // Sprinkle incompressible bytes among the words
+                content.extend_from_slice(&r.to_le_bytes());
+            } else {
+                content.extend_from_slice(WORDS[(r % 16) as usize].as_bytes());
+                content.push(b' ');
+            }
+        }
+        content.truncate(size);
+
+        let path = dir.join(format!("file_{}.dat", i));
+        fs::write(&path, content)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Applies `op` to every file using `threads` workers and returns the elapsed time.
+fn timed<F>(files: &[PathBuf], threads: usize, op: F) -> Result<Duration>
+where
+    F: Fn(usize, &Path) -> Result<()> + Sync,
+{
+    let chunk_size = files.len().div_ceil(threads);
+    let start = Instant::now();
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = files
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk_index, chunk)| {
+                let op = &op;
+                scope.spawn(move || {
+                    for (offset, file) in chunk.iter().enumerate() {
+                        op(chunk_index * chunk_size + offset, file)?;
+                    }
+                    Ok::<(), anyhow::Error>(())
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle
+                .join()
+                .map_err(|_| anyhow::anyhow!("Benchmark worker panicked"))??;
+        }
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    Ok(start.elapsed())
+}
+
+/// Formats a throughput as a human-readable size per second.
+fn throughput(bytes: i64, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+    format!("{}/s", format_size((bytes as f64 / secs) as i64))
+}
@@ -0,0 +1,154 @@
+/*!
+Line history for the freeze application.
+
+`freeze blame` annotates each line of a file with the snapshot in which it
+last changed. Versions are decompressed oldest first, one at a time, and
+each is compared with the previous one by the line diff `freeze diff` and
+`freeze inspect` use: unchanged lines keep their origin, inserted lines take
+the version's. The current file comes last, so lines changed since the
+latest snapshot show as not saved.
+*/
+
+use crate::db::Database;
+use crate::i18n::t;
+use crate::snapshot::Snapshot;
+use crate::utils::{format_date, symbol};
+use anyhow::Result;
+use console::style;
+use similar::{ChangeTag, TextDiff};
+use std::fs;
+use std::path::Path;
+
+/// A line of the file with the version that last changed it.
+#[derive(Debug, PartialEq)]
+pub struct BlameLine {
+    /// Index in the snapshots of the version that last changed the line,
+    /// `None` for lines changed since the latest snapshot
+    pub origin: Option<usize>,
+    /// The line, without its line ending
+    pub text: String,
+}
+
+/// Carries line origins from the text `old` to the text `new`.
+///
+/// `origins` holds the origin of each line of `old`; lines inserted in `new`
+/// get `origin`.
+fn advance(origins: &[Option<usize>], old: &str, new: &str, origin: Option<usize>) -> Vec<Option<usize>> {
+    let mut old_origins = origins.iter().copied();
+    let mut result = Vec::new();
+    for change in TextDiff::from_lines(old, new).iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Equal => result.push(old_origins.next().flatten()),
+            ChangeTag::Delete => {
+                old_origins.next();
+            }
+            ChangeTag::Insert => result.push(origin),
+        }
+    }
+    result
+}
+
+/// Reads a version as text.
+///
+/// # Errors
+///
+/// Returns an error if the content is binary.
+fn as_text(path: &Path, snapshot: &Snapshot, content: &[u8]) -> Result<String> {
+    if snapshot.is_binary(content) {
+        anyhow::bail!("Cannot blame binary file: {}", path.display());
+    }
+    Ok(String::from_utf8_lossy(content).into_owned())
+}
+
+/// Computes the line history of `path`.
+///
+/// The current file is annotated, or the latest snapshot if the file no
+/// longer exists.
+///
+/// # Returns
+///
+/// The snapshots of the path, oldest first, and the annotated lines
+///
+/// # Errors
+///
+/// Returns an error if the path has no snapshots, a version is binary, or
+/// a version cannot be read.
+pub fn blame(path: &Path, db: &Database) -> Result<(Vec<Snapshot>, Vec<BlameLine>)> {
+    let mut snapshots = db.get_snapshots_for_path(path)?;
+    snapshots.retain(|snapshot| snapshot.symlink.is_none());
+    if snapshots.is_empty() {
+        anyhow::bail!("{} {}", t!("no-snapshots-for"), path.display());
+    }
+    snapshots.sort_by(|a, b| a.date.cmp(&b.date));
+
+    let mut text = String::new();
+    let mut origins = Vec::new();
+    let mut previous: Option<&str> = None;
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        if previous == Some(snapshot.checksum.as_str()) {
+            continue;
+        }
+        previous = Some(&snapshot.checksum);
+        let next = as_text(path, snapshot, &snapshot.get_decompressed_content()?)?;
+        origins = advance(&origins, &text, &next, Some(i));
+        text = next;
+    }
+
+    if path.is_file() {
+        let latest = &snapshots[snapshots.len() - 1];
+        let current = as_text(path, latest, &fs::read(path)?)?;
+        origins = advance(&origins, &text, &current, None);
+        text = current;
+    }
+
+    let lines = text
+        .lines()
+        .zip(origins)
+        .map(|(text, origin)| BlameLine {
+            origin,
+            text: text.to_string(),
+        })
+        .collect();
+    Ok((snapshots, lines))
+}
+
+/// Renders annotated lines: checksum and date of their version, line number
+/// and line.
+pub fn render_text(snapshots: &[Snapshot], lines: &[BlameLine]) -> String {
+    let width = lines.len().to_string().len();
+    let mut out = String::new();
+    for (number, line) in lines.iter().enumerate() {
+        let annotation = match line.origin.map(|origin| &snapshots[origin]) {
+            Some(snapshot) => format!(
+                "{} {}",
+                style(&snapshot.checksum[..8]).yellow(),
+                style(format_date(&snapshot.date)).cyan()
+            ),
+            None => format!("{:<25}", style(t!("blame-not-saved")).red()),
+        };
+        out.push_str(&format!(
+            "{} {} {}\n",
+            annotation,
+            style(format!("{:>width$} {}", number + 1, symbol("│", "|"))).dim(),
+            line.text
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance() {
+        let first = advance(&[], "", "a\nb\nc\n", Some(0));
+        assert_eq!(first, vec![Some(0), Some(0), Some(0)]);
+
+        let second = advance(&first, "a\nb\nc\n", "a\nB\nc\nd\n", Some(1));
+        assert_eq!(second, vec![Some(0), Some(1), Some(0), Some(1)]);
+
+        let current = advance(&second, "a\nB\nc\nd\n", "B\nc\nd\ne", None);
+        assert_eq!(current, vec![Some(1), Some(0), Some(1), None]);
+    }
+}
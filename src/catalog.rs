@@ -0,0 +1,305 @@
+/*!
+Catalog exports for the freeze application.
+
+`freeze db export` dumps the metadata of every snapshot, without contents,
+for auditing, reporting or ingestion into other tools: as one JSON document,
+or as CSV with one row per snapshot. Metadata entries (tags), exclusions and
+sessions can be added on request.
+*/
+
+use crate::db::Database;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Output format of a catalog export.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum CatalogFormat {
+    Json,
+    Csv,
+}
+
+/// Optional parts of a catalog.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum CatalogPart {
+    /// Exclusion rules (JSON only)
+    Exclusions,
+    /// Metadata entries of each snapshot
+    Tags,
+    /// Directory sessions and their files
+    Sessions,
+}
+
+/// A snapshot in a catalog.
+#[derive(Serialize, Debug)]
+pub struct CatalogSnapshot {
+    pub id: i64,
+    pub path: String,
+    pub date: String,
+    /// Size of the file, in bytes
+    pub size: i64,
+    /// Bytes in storage, when recorded
+    pub stored_size: Option<i64>,
+    pub checksum: String,
+    pub mime: Option<String>,
+    pub message: Option<String>,
+    pub pinned: bool,
+    /// Metadata entries, if requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<BTreeMap<String, String>>,
+}
+
+/// An exclusion rule in a catalog.
+#[derive(Serialize, Debug)]
+pub struct CatalogExclusion {
+    pub pattern: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// A file of a directory session in a catalog.
+#[derive(Serialize, Debug)]
+pub struct CatalogSessionFile {
+    pub path: String,
+    pub checksum: String,
+}
+
+/// A directory session in a catalog.
+#[derive(Serialize, Debug)]
+pub struct CatalogSession {
+    pub id: i64,
+    pub root: String,
+    pub date: String,
+    pub message: Option<String>,
+    pub files: Vec<CatalogSessionFile>,
+}
+
+/// The metadata of every snapshot, and the requested parts.
+#[derive(Serialize, Debug)]
+pub struct Catalog {
+    pub snapshots: Vec<CatalogSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclusions: Option<Vec<CatalogExclusion>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<Vec<CatalogSession>>,
+}
+
+/// Builds the catalog of the database.
+///
+/// # Arguments
+///
+/// * `db` - Database connection
+/// * `parts` - Optional parts to include
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn build(db: &Database, parts: &[CatalogPart]) -> Result<Catalog> {
+    let messages = db.list_messages()?;
+    let pinned = db.list_pinned()?;
+    let stored_sizes = db.list_stored_sizes()?;
+    let mime_types = db.list_mime_types()?;
+    let mut metadata = if parts.contains(&CatalogPart::Tags) {
+        Some(db.list_all_metadata()?)
+    } else {
+        None
+    };
+
+    let mut snapshots: Vec<CatalogSnapshot> = db
+        .list_all_snapshots_with_id()?
+        .into_iter()
+        .map(|(id, path, date, size, checksum)| CatalogSnapshot {
+            id,
+            path: path.display().to_string(),
+            date,
+            size,
+            stored_size: stored_sizes.get(&id).copied(),
+            mime: mime_types.get(&id).cloned(),
+            message: messages.get(&id).cloned(),
+            pinned: pinned.contains(&id),
+            tags: metadata
+                .as_mut()
+                .map(|metadata| metadata.remove(&id).unwrap_or_default()),
+            checksum,
+        })
+        .collect();
+    snapshots.sort_by(|a, b| a.path.cmp(&b.path).then(a.date.cmp(&b.date)).then(a.id.cmp(&b.id)));
+
+    let exclusions = if parts.contains(&CatalogPart::Exclusions) {
+        let exclusions = db.list_exclusions()?;
+        Some(
+            exclusions
+                .into_iter()
+                .map(|(pattern, kind)| CatalogExclusion { pattern, kind })
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    let sessions = if parts.contains(&CatalogPart::Sessions) {
+        let mut sessions = Vec::new();
+        for session in db.list_sessions(None)?.into_iter().rev() {
+            let files = db
+                .get_session_files(session.id)?
+                .into_iter()
+                .map(|(path, checksum)| CatalogSessionFile {
+                    path: path.display().to_string(),
+                    checksum,
+                })
+                .collect();
+            sessions.push(CatalogSession {
+                id: session.id,
+                root: session.root.display().to_string(),
+                date: session.date,
+                message: session.message,
+                files,
+            });
+        }
+        Some(sessions)
+    } else {
+        None
+    };
+
+    Ok(Catalog {
+        snapshots,
+        exclusions,
+        sessions,
+    })
+}
+
+/// Quotes a CSV field if it holds a separator, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Renders the snapshots of a catalog as CSV, one row per snapshot.
+///
+/// Tags become a `key=value` list separated by `;`, and sessions the IDs of
+/// the sessions each snapshot belongs to, separated by spaces.
+///
+/// # Errors
+///
+/// Returns an error if the catalog holds exclusions, which have no place
+/// in the rows.
+pub fn render_csv(catalog: &Catalog) -> Result<String> {
+    if catalog.exclusions.is_some() {
+        anyhow::bail!("Exclusions can only be exported as JSON");
+    }
+
+    let mut memberships: HashMap<(&str, &str), Vec<String>> = HashMap::new();
+    for session in catalog.sessions.iter().flatten() {
+        for file in &session.files {
+            memberships
+                .entry((&file.path, &file.checksum))
+                .or_default()
+                .push(session.id.to_string());
+        }
+    }
+
+    let mut header = vec![
+        "id",
+        "path",
+        "date",
+        "size",
+        "stored_size",
+        "checksum",
+        "mime",
+        "message",
+        "pinned",
+    ];
+    let with_tags = catalog.snapshots.iter().any(|snapshot| snapshot.tags.is_some());
+    if with_tags {
+        header.push("tags");
+    }
+    if catalog.sessions.is_some() {
+        header.push("sessions");
+    }
+
+    let mut out = format!("{}\n", header.join(","));
+    for snapshot in &catalog.snapshots {
+        let mut row = vec![
+            snapshot.id.to_string(),
+            csv_field(&snapshot.path),
+            snapshot.date.clone(),
+            snapshot.size.to_string(),
+            snapshot.stored_size.map(|size| size.to_string()).unwrap_or_default(),
+            snapshot.checksum.clone(),
+            csv_field(snapshot.mime.as_deref().unwrap_or_default()),
+            csv_field(snapshot.message.as_deref().unwrap_or_default()),
+            snapshot.pinned.to_string(),
+        ];
+        if with_tags {
+            let tags: Vec<String> = snapshot
+                .tags
+                .iter()
+                .flatten()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            row.push(csv_field(&tags.join(";")));
+        }
+        if catalog.sessions.is_some() {
+            let ids = memberships.get(&(snapshot.path.as_str(), snapshot.checksum.as_str()));
+            row.push(ids.map(|ids| ids.join(" ")).unwrap_or_default());
+        }
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(id: i64, path: &str, message: Option<&str>) -> CatalogSnapshot {
+        CatalogSnapshot {
+            id,
+            path: path.to_string(),
+            date: "2024-01-01T10:00:00+00:00".to_string(),
+            size: 12,
+            stored_size: None,
+            checksum: format!("checksum{:03}", id),
+            mime: Some("text/plain".to_string()),
+            message: message.map(str::to_string),
+            pinned: id == 1,
+            tags: None,
+        }
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let mut catalog = Catalog {
+            snapshots: vec![snapshot(1, "/a.txt", Some("fix, \"quoted\"")), snapshot(2, "/b.txt", None)],
+            exclusions: None,
+            sessions: Some(vec![CatalogSession {
+                id: 7,
+                root: "/".to_string(),
+                date: "2024-01-01T10:00:00+00:00".to_string(),
+                message: None,
+                files: vec![CatalogSessionFile {
+                    path: "/b.txt".to_string(),
+                    checksum: "checksum002".to_string(),
+                }],
+            }]),
+        };
+        catalog.snapshots[0].tags = Some(BTreeMap::from([("env".to_string(), "prod".to_string())]));
+        catalog.snapshots[1].tags = Some(BTreeMap::new());
+
+        let csv = render_csv(&catalog).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "id,path,date,size,stored_size,checksum,mime,message,pinned,tags,sessions");
+        assert_eq!(
+            lines[1],
+            "1,/a.txt,2024-01-01T10:00:00+00:00,12,,checksum001,text/plain,\"fix, \"\"quoted\"\"\",true,env=prod,"
+        );
+        assert_eq!(lines[2], "2,/b.txt,2024-01-01T10:00:00+00:00,12,,checksum002,text/plain,,false,,7");
+
+        catalog.exclusions = Some(Vec::new());
+        assert!(render_csv(&catalog).is_err());
+    }
+}
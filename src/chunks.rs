@@ -0,0 +1,227 @@
+/*!
+Content-defined chunking of large files for the freeze application.
+
+Files from [`CHUNKING_THRESHOLD`] on are cut into chunks with FastCDC, whose
+boundaries depend on the content rather than on offsets, so an edit only
+changes the chunks around it. Each distinct chunk is compressed once into
+`storage/chunks/`, and the snapshot content is a manifest,
+`storage/<checksum>.chunks`, listing the chunks in order. A large file that
+changes slightly between snapshots therefore only stores the changed chunks.
+
+Manifests are JSON and not encrypted: like storage file names, they only
+hold checksums. Chunks are compressed and encrypted like whole contents.
+*/
+
+use crate::compression::{self, Compression};
+use crate::snapshot::Snapshot;
+use anyhow::{bail, Context, Result};
+use fastcdc::v2020::StreamCDC;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Files of at least this size are stored as chunks.
+pub const CHUNKING_THRESHOLD: u64 = 8 * 1024 * 1024;
+/// Extension of chunk manifests in storage.
+pub const MANIFEST_EXTENSION: &str = "chunks";
+/// Directory of the chunks, inside the storage directory.
+pub const CHUNKS_DIR: &str = "chunks";
+
+const MIN_CHUNK_SIZE: u32 = 256 * 1024;
+const AVG_CHUNK_SIZE: u32 = 1024 * 1024;
+const MAX_CHUNK_SIZE: u32 = 4 * 1024 * 1024;
+const FORMAT_VERSION: u32 = 1;
+
+/// The chunks of a content, in order.
+#[derive(Serialize, Deserialize, Debug)]
+struct Manifest {
+    version: u32,
+    chunks: Vec<ChunkRef>,
+}
+
+/// A chunk of a content.
+#[derive(Serialize, Deserialize, Debug)]
+struct ChunkRef {
+    /// File name of the chunk in the chunks directory
+    file: String,
+    /// Uncompressed size of the chunk
+    size: u64,
+}
+
+/// Returns the storage path of the manifest of a chunked content.
+pub fn manifest_path(storage_dir: &Path, checksum: &str) -> PathBuf {
+    storage_dir.join(format!("{}.{}", checksum, MANIFEST_EXTENSION))
+}
+
+/// Returns `true` if `path` is a chunk manifest.
+pub fn is_manifest(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some(MANIFEST_EXTENSION)
+}
+
+/// Cuts a file into chunks, stores the new ones and writes its manifest.
+///
+/// # Arguments
+///
+/// * `source` - The file to store
+/// * `manifest_path` - Where to write the manifest, in the storage directory
+/// * `compression` - Algorithm of new chunks
+///
+/// # Errors
+///
+/// Returns an error if reading, chunking, compression or writing fails.
+pub fn store(source: &Path, manifest_path: &Path, compression: Compression) -> Result<()> {
+    let chunks_dir = chunks_dir(manifest_path);
+    fs::create_dir_all(&chunks_dir)?;
+    let level = crate::config::get().compression_level();
+
+    let mut manifest = Manifest {
+        version: FORMAT_VERSION,
+        chunks: Vec::new(),
+    };
+    let chunker = StreamCDC::new(fs::File::open(source)?, MIN_CHUNK_SIZE, AVG_CHUNK_SIZE, MAX_CHUNK_SIZE);
+    for chunk in chunker {
+        let chunk = chunk?;
+        let checksum = format!("{:x}", Sha256::digest(&chunk.data));
+        // Chunks shared with other contents are stored once
+        let path = match compression::find_blob(&chunks_dir, &checksum) {
+            Some(path) => path,
+            None => {
+                let path = compression::blob_path(&chunks_dir, &checksum, compression);
+                Snapshot::compress_reader(&chunk.data[..], &path, level)?;
+                path
+            }
+        };
+        manifest.chunks.push(ChunkRef {
+            file: file_name(&path),
+            size: chunk.length as u64,
+        });
+    }
+
+    let temp_path = manifest_path.with_extension("tmp");
+    fs::write(&temp_path, serde_json::to_vec(&manifest)?)?;
+    fs::rename(&temp_path, manifest_path)?;
+    Ok(())
+}
+
+/// Opens a chunked content, decompressing and joining its chunks as it is read.
+///
+/// # Errors
+///
+/// Returns an error if the manifest cannot be read. Missing or corrupt
+/// chunks make reading fail.
+pub fn open(manifest_path: &Path) -> Result<Box<dyn Read + Send>> {
+    Ok(Box::new(ChunkReader {
+        chunks: chunk_files(manifest_path)?.into_iter(),
+        current: None,
+    }))
+}
+
+/// Returns the chunk files a manifest refers to, in order.
+///
+/// # Errors
+///
+/// Returns an error if the manifest cannot be read or is not valid.
+pub fn chunk_files(manifest_path: &Path) -> Result<Vec<PathBuf>> {
+    let manifest: Manifest = serde_json::from_slice(&fs::read(manifest_path)?)
+        .with_context(|| format!("Invalid chunk manifest {}", manifest_path.display()))?;
+    if manifest.version > FORMAT_VERSION {
+        bail!("Unsupported chunk manifest version {}", manifest.version);
+    }
+    let chunks_dir = chunks_dir(manifest_path);
+    manifest
+        .chunks
+        .into_iter()
+        .map(|chunk| {
+            // Names come from the file, keep them inside the chunks directory
+            if Path::new(&chunk.file).file_name().and_then(|s| s.to_str()) != Some(chunk.file.as_str()) {
+                bail!("Invalid chunk name in {}: {}", manifest_path.display(), chunk.file);
+            }
+            Ok(chunks_dir.join(chunk.file))
+        })
+        .collect()
+}
+
+/// Directory of the chunks of the manifests in the directory of `manifest_path`.
+fn chunks_dir(manifest_path: &Path) -> PathBuf {
+    manifest_path.parent().unwrap_or(Path::new(".")).join(CHUNKS_DIR)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// Reads the chunks of a content one after the other.
+struct ChunkReader {
+    chunks: std::vec::IntoIter<PathBuf>,
+    current: Option<Box<dyn Read + Send>>,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if let Some(current) = &mut self.current {
+                let read = current.read(buf)?;
+                if read > 0 || buf.is_empty() {
+                    return Ok(read);
+                }
+            }
+            match self.chunks.next() {
+                Some(path) => self.current = Some(compression::open(&path).map_err(std::io::Error::other)?),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random bytes, which chunk like real data.
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 33) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_store_and_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut content = noise(6 * 1024 * 1024, 1);
+        let source = dir.path().join("source");
+        fs::write(&source, &content).unwrap();
+
+        let storage = dir.path().join("storage");
+        fs::create_dir_all(&storage).unwrap();
+        let first = manifest_path(&storage, "first");
+        store(&source, &first, Compression::Zstd).unwrap();
+        let chunks = fs::read_dir(storage.join(CHUNKS_DIR)).unwrap().count();
+        assert!(chunks > 1);
+
+        let mut restored = Vec::new();
+        open(&first).unwrap().read_to_end(&mut restored).unwrap();
+        assert_eq!(restored, content);
+
+        // A small edit only adds the chunks around it
+        content[3 * 1024 * 1024] ^= 0xff;
+        fs::write(&source, &content).unwrap();
+        let second = manifest_path(&storage, "second");
+        store(&source, &second, Compression::Lz4).unwrap();
+        let added = fs::read_dir(storage.join(CHUNKS_DIR)).unwrap().count() - chunks;
+        assert!((1..=2).contains(&added), "{} chunks added", added);
+
+        restored.clear();
+        open(&second).unwrap().read_to_end(&mut restored).unwrap();
+        assert_eq!(restored, content);
+        assert_eq!(
+            compression::stored_files(&second).len(),
+            chunk_files(&second).unwrap().len() + 1
+        );
+    }
+}
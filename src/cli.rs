@@ -1,13 +1,19 @@
 // cli.rs
-use crate::db::Database;
-use crate::snapshot::Snapshot;
+use crate::catalog::CatalogFormat;
+use crate::compression::Compression;
+use crate::config::{self, ColorMode, Config};
+use crate::db::{Database, Retention, SearchFilter, SnapshotFilter, SnapshotSort, SnapshotWithId};
+use crate::retention::{self, Policies, Schedule};
+use crate::i18n::t;
+use crate::snapshot::{LineRange, PlannedRestore, RestoreAction, Selection, Snapshot};
 use crate::utils;
 use crate::utils::check_path;
 use crate::utils::format_size;
 use crate::utils::print_header;
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use console::style;
+use std::collections::HashSet;
 use std::path::Path;
 use std::path::PathBuf;
 use std::{env, fs};
@@ -15,6 +21,33 @@ use std::{env, fs};
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    /// Repository directory holding the configuration, database and storage
+    /// (default: ~/.freeze if it exists, else freeze in the platform data
+    /// directory, such as ~/.local/share/freeze)
+    #[arg(long, global = true, env = "FREEZE_HOME", value_name = "DIR")]
+    pub repo: Option<PathBuf>,
+    /// Profile whose database and storage to use, as listed by `freeze profile list`
+    #[arg(long, global = true, env = "FREEZE_PROFILE", value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Database file to use, or `:memory:` for a throwaway in-memory store
+    #[arg(long, global = true, env = "FREEZE_DB")]
+    pub db: Option<String>,
+    /// Refuse every command that would change the repository, such as save,
+    /// clear or exclusion changes (default: read_only setting)
+    #[arg(long, global = true, env = "FREEZE_READ_ONLY", value_parser = clap::builder::FalseyValueParser::new())]
+    pub read_only: bool,
+    /// Use plain ASCII output (no emoji, box drawing or block characters)
+    #[arg(long, global = true, env = "FREEZE_ASCII", value_parser = clap::builder::FalseyValueParser::new())]
+    pub ascii: bool,
+    /// Answer yes to confirmations and pick the latest snapshot instead of prompting
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+    /// Never read from standard input; fail when an answer would be needed
+    #[arg(long, global = true, env = "FREEZE_NO_INPUT", value_parser = clap::builder::FalseyValueParser::new())]
+    pub no_input: bool,
+    /// Print JSON instead of tables for ls, cls, search, check, inspect and stats (or FREEZE_OUTPUT=json)
+    #[arg(long, global = true)]
+    pub json: bool,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -25,6 +58,93 @@ pub enum Commands {
     Save {
         /// Path to save
         path: String,
+        /// Message describing the snapshot
+        #[arg(short, long)]
+        message: Option<String>,
+        /// Skip files that cannot be read (permissions, vanished files) and
+        /// list them at the end instead of stopping
+        #[arg(long)]
+        keep_going: bool,
+        /// zstd level of new contents, 1-22 (default: compression_level setting, or 3)
+        #[arg(short = 'l', long, value_parser = clap::value_parser!(i32).range(1..=22))]
+        compression_level: Option<i32>,
+        /// Compression algorithm of new contents, for every file (default:
+        /// compression_rules and compression settings, or zstd)
+        #[arg(short = 'c', long, value_enum)]
+        compression: Option<Compression>,
+        /// Store new versions of files as deltas against their latest snapshot
+        /// (default: delta setting)
+        #[arg(long)]
+        delta: bool,
+        /// Re-hash every file, even those whose size and modification time
+        /// match their latest snapshot (default: paranoid setting)
+        #[arg(long)]
+        paranoid: bool,
+        /// Save what symbolic links point to instead of recording the links
+        /// (default: follow_symlinks setting)
+        #[arg(long)]
+        follow_symlinks: bool,
+        /// Do not descend into directories on other file systems (default:
+        /// one_file_system setting)
+        #[arg(long)]
+        one_file_system: bool,
+        /// Skip files ignored by git (.gitignore, .git/info/exclude and the
+        /// global excludes file) in repositories (default: git_aware setting)
+        #[arg(long)]
+        git_aware: bool,
+        /// Record extended attributes and POSIX ACLs of files, set again on
+        /// restore (default: xattrs setting)
+        #[arg(long)]
+        xattrs: bool,
+        /// Skip files larger than this in directories, e.g. 100MB
+        /// (default: max_file_size setting)
+        #[arg(long, value_name = "SIZE")]
+        max_file_size: Option<String>,
+        /// Record a snapshot even of files identical to their latest snapshot
+        #[arg(long)]
+        force: bool,
+    },
+    /// Run a command and save its standard output as a snapshot
+    Capture {
+        /// Path the output is recorded under (does not need to exist)
+        #[arg(long = "as", value_name = "PATH")]
+        as_path: String,
+        /// Command to run, after `--`
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+    /// Save paths before running a command, and optionally after it
+    Run {
+        /// Path to save (repeatable, defaults to the current directory)
+        #[arg(short, long = "path", value_name = "PATH")]
+        paths: Vec<String>,
+        /// Also save the paths once the command has finished
+        #[arg(long)]
+        after: bool,
+        /// Command to run, after `--`
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+    /// Watch a file or directory and save a snapshot whenever content changes
+    Watch {
+        /// Path to watch
+        path: String,
+        /// Quiet time after the last change before saving (in milliseconds)
+        #[arg(short, long, default_value = "500")]
+        debounce: u64,
+        /// Minimum time between two rounds of snapshots (in seconds)
+        #[arg(short, long, default_value = "0")]
+        min_interval: u64,
+        /// zstd level of new contents, 1-22; low levels keep frequent saves cheap
+        #[arg(short = 'l', long, value_parser = clap::value_parser!(i32).range(1..=22))]
+        compression_level: Option<i32>,
+        /// Compression algorithm of new contents, for every file; lz4 keeps
+        /// frequent saves cheaper still
+        #[arg(short = 'c', long, value_enum)]
+        compression: Option<Compression>,
+        /// Store new versions of files as deltas against their latest snapshot
+        #[arg(long)]
+        delta: bool,
     },
     /// Export a snapshot to a specified path
     Export {
@@ -33,6 +153,28 @@ pub enum Commands {
         /// Optional export destination (defaults to current directory)
         #[arg(short, long)]
         destination: Option<String>,
+        /// Leave the exported file's modification time at the time of export
+        /// (default: restore_times setting)
+        #[arg(long)]
+        no_times: bool,
+        #[command(flatten)]
+        choice: SnapshotChoice,
+    },
+    /// Write snapshots and their content to a tar archive
+    ExportArchive {
+        /// Only export snapshots of this file or directory
+        path: Option<String>,
+        /// Archive file to create
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Import an archive made by export-archive, or a plain tarball with --as
+    ImportArchive {
+        /// Archive to import
+        file: String,
+        /// Import a plain tarball, recording its files below this path
+        #[arg(long = "as", value_name = "PATH")]
+        as_path: Option<String>,
     },
     /// View the contents of a snapshot
     View {
@@ -41,34 +183,210 @@ pub enum Commands {
         /// Maximum size to display (in MB)
         #[arg(short, long, default_value = "5")]
         max_size: u64,
+        /// Only show lines FIRST:LAST (1-indexed, either bound optional)
+        #[arg(long, value_name = "FIRST:LAST", conflicts_with_all = ["head", "tail"])]
+        lines: Option<String>,
+        /// Only show the first N lines
+        #[arg(long, value_name = "N", conflicts_with = "tail")]
+        head: Option<usize>,
+        /// Only show the last N lines
+        #[arg(long, value_name = "N")]
+        tail: Option<usize>,
+        #[command(flatten)]
+        choice: SnapshotChoice,
+    },
+    /// Replace the latest snapshot of a file with its current content
+    Amend {
+        /// File to amend
+        path: String,
+        /// New message (default: the message of the replaced snapshot)
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+    /// Print the checksum, date and size of the latest snapshot of a file on
+    /// one line
+    Latest {
+        /// File to query
+        path: String,
+    },
+    /// Write the exact content of a snapshot to standard output
+    Cat {
+        /// Path of the snapshot
+        path: String,
+        /// Use the snapshot whose checksum starts with this prefix instead
+        /// of the latest one
+        #[arg(short, long)]
+        checksum: Option<String>,
     },
     /// Restore file or directory from snapshot
     Restore {
         /// Path to restore
         path: String,
+        /// Restore into this folder instead of overwriting the original files;
+        /// a directory's tree is recreated below it
+        #[arg(long, visible_alias = "into", value_name = "DEST")]
+        to: Option<String>,
+        /// Restore the newest snapshots taken at or before this date (e.g. "2024-06-01 12:00")
+        #[arg(long, value_name = "TIMESTAMP", conflicts_with_all = ["checksum", "latest"])]
+        as_of: Option<String>,
+        /// Show which files would be overwritten, created or left untouched, without restoring
+        #[arg(long)]
+        dry_run: bool,
+        /// Leave restored files' modification times at the time of restore
+        /// instead of the saved ones (default: restore_times setting)
+        #[arg(long)]
+        no_times: bool,
+        #[command(flatten)]
+        choice: SnapshotChoice,
+    },
+    /// Revert a file to its previous version, or roll back the last restore
+    /// when no file is given (a safety snapshot is taken first)
+    Undo {
+        /// File to revert
+        path: Option<String>,
+    },
+    /// Rewrite versions stored as deltas as full copies, so that they no
+    /// longer depend on earlier versions
+    Materialize {
+        /// Only materialize snapshots below this path
+        path: Option<String>,
+    },
+    /// Recompress stored contents at a higher zstd level, and migrate legacy
+    /// uncompressed contents to zstd
+    Compact {
+        /// Only compact the contents of snapshots below this path
+        path: Option<String>,
+        /// zstd level, 1-22 (default: compression_level setting, or 3)
+        #[arg(short = 'l', long, value_parser = clap::value_parser!(i32).range(1..=22))]
+        level: Option<i32>,
+    },
+    /// Remove unreferenced storage files and compact the database
+    Gc {
+        /// Also delete snapshots whose stored content is missing
+        #[arg(long)]
+        force: bool,
+    },
+    /// Back up, restore, merge, export or compact the database
+    Db {
+        #[command(subcommand)]
+        action: DbCommands,
     },
     /// List all snapshots
     Ls {
         /// Page number (10 items per page)
         #[arg(short, long)]
         page: Option<u32>,
+        /// Also show the stored size and compression ratio of each snapshot
+        #[arg(short, long)]
+        long: bool,
+        #[command(flatten)]
+        filter: ListFilter,
     },
     /// List snapshots in current directory
     Cls {
         /// Page number (10 items per page)
         #[arg(short, long)]
         page: Option<u32>,
+        /// Also show the stored size and compression ratio of each snapshot
+        #[arg(short, long)]
+        long: bool,
+        #[command(flatten)]
+        filter: ListFilter,
     },
     /// Clear snapshots
+    #[command(group(clap::ArgGroup::new("retention").args(["older_than", "keep_last"])))]
     Clear {
         #[arg(long)]
         all: bool,
         path: Option<String>,
+        /// Only delete snapshots older than this age (e.g. 12h, 30d, 8w)
+        #[arg(long, value_name = "AGE")]
+        older_than: Option<String>,
+        /// Only delete all but the N most recent snapshots of each file
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        keep_last: Option<u32>,
+        /// List the snapshots that would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+        /// Delete without asking for confirmation, as --yes does
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Delete individual snapshots, chosen by checksum or ID
+    Rm {
+        /// Only snapshots of this file or of the files below this directory
+        path: Option<String>,
+        /// Delete the snapshots whose checksum starts with this prefix
+        /// (repeatable, or comma-separated)
+        #[arg(short, long, value_name = "PREFIX", value_delimiter = ',', required_unless_present = "id")]
+        checksum: Vec<String>,
+        /// Delete the snapshot with this ID (repeatable, or comma-separated)
+        #[arg(long, value_name = "ID", value_delimiter = ',')]
+        id: Vec<i64>,
+        /// List the snapshots that would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+        /// Delete without asking for confirmation, as --yes does
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Delete the snapshots the retention schedules no longer keep
+    Prune {
+        /// Only prune snapshots of this file or below this directory
+        path: Option<String>,
+        /// Schedule to apply instead of the configured ones, e.g. "daily:30d, weekly:1y"
+        #[arg(long, value_name = "SCHEDULE")]
+        schedule: Option<String>,
+        /// List the snapshots that would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Protect the snapshots of a content from clear and retention rules
+    Pin {
+        /// Checksum, or checksum prefix, of the snapshots to pin
+        checksum: String,
+    },
+    /// Let clear and retention rules delete pinned snapshots again
+    Unpin {
+        /// Checksum, or checksum prefix, of the snapshots to unpin
+        checksum: String,
+    },
+    /// Copy the snapshot history of a path to another path
+    Cp {
+        /// File or directory whose history is copied
+        source: String,
+        /// New path receiving the history
+        destination: String,
+    },
+    /// Manage path aliases usable as `@name` in place of a path
+    Alias {
+        #[command(subcommand)]
+        action: AliasCommands,
+    },
+    /// List, restore and compare directory saves as a whole
+    Session {
+        #[command(subcommand)]
+        action: SessionCommands,
+    },
+    /// Attach key-value metadata to snapshots
+    Meta {
+        #[command(subcommand)]
+        action: MetaCommands,
     },
     /// Search snapshots by name
     Search {
-        /// Name pattern to search
+        /// Name pattern to search, or words to find in contents with --content
         pattern: String,
+        #[command(flatten)]
+        filter: SearchOptions,
+        /// Find the snapshots whose content holds every word of the pattern,
+        /// in the index kept with the content_index setting; `word*`
+        /// matches words starting with `word`
+        #[arg(long)]
+        content: bool,
+        /// Most snapshots listed by a content search
+        #[arg(long, requires = "content", default_value_t = crate::fulltext::DEFAULT_LIMIT)]
+        limit: usize,
     },
     /// Manage exclusions
     Exclusion {
@@ -79,6 +397,23 @@ pub enum Commands {
     Check {
         /// Path to check
         path: String,
+        /// Re-hash every file, even those whose size and modification time
+        /// match their latest snapshot (default: paranoid setting)
+        #[arg(long)]
+        paranoid: bool,
+        /// Do not descend into directories on other file systems (default:
+        /// one_file_system setting)
+        #[arg(long)]
+        one_file_system: bool,
+        /// Skip files ignored by git (.gitignore, .git/info/exclude and the
+        /// global excludes file) in repositories (default: git_aware setting)
+        #[arg(long)]
+        git_aware: bool,
+    },
+    /// Show modified, new and deleted files since their latest snapshots
+    Status {
+        /// Directory to inspect (defaults to the current directory)
+        path: Option<String>,
     },
     /// Compare snapshots or files with snapshots
     Diff {
@@ -92,9 +427,105 @@ pub enum Commands {
         /// Path to inspect
         path: String,
     },
+    /// Show the snapshot in which each line of a file last changed
+    Blame {
+        /// File to annotate
+        path: String,
+    },
+    /// Search the contents of snapshots for a text
+    Grep {
+        /// Text to look for
+        pattern: String,
+        /// Only snapshots of this file or of the files below this directory
+        path: Option<String>,
+        /// Match regardless of case
+        #[arg(short, long)]
+        ignore_case: bool,
+        /// Only search the latest snapshot of each file
+        #[arg(long)]
+        latest: bool,
+        #[command(flatten)]
+        filter: ListFilter,
+    },
+    /// Summarize the snapshot activity of the last day or week
+    Report {
+        /// Time span covered by the report
+        #[arg(short, long, value_enum, default_value = "day")]
+        period: crate::report::Period,
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "text")]
+        format: ReportFormat,
+        /// Write the report to a file instead of standard output
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Show or change the settings of config.toml in the repository directory
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommands,
+    },
+    /// Manage profiles, each with its own database and storage
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommands,
+    },
+    /// Show storage usage: snapshot counts, sizes, deduplication and largest paths
+    Stats {
+        /// Number of paths and directories to list
+        #[arg(short = 'n', long, default_value = "10")]
+        top: usize,
+        /// Report deduplication and compression savings instead
+        #[arg(long)]
+        dedup: bool,
+        /// Break down the snapshots below a directory (default: the current
+        /// one, `/` for the top-level directories) per directory inside it
+        #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = ".", conflicts_with = "dedup")]
+        by_dir: Option<String>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: StatsFormat,
+    },
+    /// Measure hash, save and restore throughput on synthetic data
+    Bench {
+        /// Number of files to generate
+        #[arg(short, long, default_value = "200")]
+        files: usize,
+        /// Size of each file (in KB)
+        #[arg(short, long, default_value = "64")]
+        size: usize,
+        /// Compression levels to compare (comma separated)
+        #[arg(short, long, value_delimiter = ',', default_value = "1,3,9")]
+        levels: Vec<i32>,
+        /// Thread counts to compare (comma separated, defaults to 1 and all cores)
+        #[arg(short, long, value_delimiter = ',')]
+        threads: Vec<usize>,
+    },
+    /// Update freeze to the latest release
+    SelfUpdate {
+        /// Only check whether an update is available
+        #[arg(short, long)]
+        check: bool,
+    },
+    /// Print completion candidates for a command argument (used by shell completion scripts)
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        /// Command whose argument is being completed
+        command: String,
+        /// Word typed so far
+        #[arg(default_value = "")]
+        current: String,
+    },
     /// Start MCP server
-    Mcp,
+    Mcp {
+        /// Serve streamable HTTP on this address (e.g. 127.0.0.1:7444) instead of stdio
+        #[arg(long, value_name = "ADDR")]
+        http: Option<std::net::SocketAddr>,
+        /// Let browser pages from this origin (e.g. https://app.example.com) call the HTTP server (repeatable)
+        #[arg(long, value_name = "ORIGIN", requires = "http")]
+        allow_origin: Vec<String>,
+    },
     /// Start the web interface
+    #[command(alias = "serve")]
     Web {
         /// Port to listen on (default: 3000)
         #[arg(short, long)]
@@ -102,58 +533,1275 @@ pub enum Commands {
         /// Open browser automatically
         #[arg(short, long)]
         open: bool,
+        #[command(subcommand)]
+        action: Option<WebCommands>,
     },
+    /// Protect the repository with a passphrase, or change it
+    Key {
+        #[command(subcommand)]
+        action: RepositoryKeyCommands,
+    },
+    /// Run a background process that owns the database and serves a Unix socket
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonCommands,
+    },
+    /// Run an external `freeze-<name>` plugin found on PATH
+    #[command(external_subcommand)]
+    External(Vec<String>),
+}
+
+impl Commands {
+    /// Whether the command changes the repository, and is refused in
+    /// read-only mode. Restores are checked when they run, as only those
+    /// overwriting files are refused.
+    fn modifies_repository(&self) -> bool {
+        match self {
+            Commands::Save { .. }
+            | Commands::Capture { .. }
+            | Commands::Run { .. }
+            | Commands::Watch { .. }
+            | Commands::ImportArchive { .. }
+            | Commands::Amend { .. }
+            | Commands::Undo { .. }
+            | Commands::Materialize { .. }
+            | Commands::Compact { .. }
+            | Commands::Gc { .. }
+            | Commands::Pin { .. }
+            | Commands::Unpin { .. }
+            | Commands::Cp { .. } => true,
+            Commands::Clear { dry_run, .. } | Commands::Rm { dry_run, .. } | Commands::Prune { dry_run, .. } => {
+                !dry_run
+            }
+            Commands::Db { action } => !matches!(action, DbCommands::Backup { .. } | DbCommands::Export { .. }),
+            Commands::Alias { action } => !matches!(action, AliasCommands::List),
+            Commands::Meta { action } => matches!(action, MetaCommands::Set { .. } | MetaCommands::Rm { .. }),
+            Commands::Exclusion { action } => !matches!(action, ExclusionCommands::List),
+            Commands::Web {
+                action: Some(WebCommands::Keys { action }),
+                ..
+            } => !matches!(action, KeyCommands::List),
+            Commands::Key { action } => matches!(
+                action,
+                RepositoryKeyCommands::Init
+                    | RepositoryKeyCommands::Rotate
+                    | RepositoryKeyCommands::EncryptDatabase
+                    | RepositoryKeyCommands::DecryptDatabase
+            ),
+            Commands::Profile {
+                action: ProfileCommands::Delete { purge, .. },
+            } => *purge,
+            Commands::Daemon { action } => matches!(action, DaemonCommands::Watch { .. }),
+            _ => false,
+        }
+    }
+}
+
+/// Non-interactive choice between several snapshots of a path.
+#[derive(clap::Args)]
+pub struct SnapshotChoice {
+    /// Use the snapshot whose checksum starts with this prefix
+    #[arg(short, long, conflicts_with = "latest")]
+    checksum: Option<String>,
+    /// Use the latest snapshot instead of prompting
+    #[arg(long)]
+    latest: bool,
+}
+
+impl SnapshotChoice {
+    fn selection(&self) -> Selection {
+        match (&self.checksum, self.latest) {
+            (Some(prefix), _) => Selection::Checksum(prefix.clone()),
+            (None, true) => Selection::Latest,
+            (None, false) => Selection::Prompt,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum WebCommands {
+    /// Manage API keys for the web API
+    Keys {
+        #[command(subcommand)]
+        action: KeyCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum KeyCommands {
+    /// Generate a new API key
+    Create {
+        /// Name identifying the key
+        name: String,
+        /// Access scope of the key
+        #[arg(short, long, value_enum, default_value = "read-only")]
+        scope: ApiScope,
+    },
+    /// List API keys
+    List,
+    /// Revoke an API key
+    Revoke {
+        /// ID of the key to revoke
+        id: i64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AliasCommands {
+    /// Add or replace an alias
+    Add {
+        /// Alias name (letters, digits, '-', '_' or '.')
+        name: String,
+        /// Path the alias points to
+        path: String,
+    },
+    /// Remove an alias
+    Remove {
+        /// Alias name
+        name: String,
+    },
+    /// List all aliases
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum MetaCommands {
+    /// Set a metadata value, replacing any previous one
+    Set {
+        /// Snapshotted path (the latest snapshot is used unless --checksum is given)
+        path: String,
+        /// Metadata key
+        key: String,
+        /// Metadata value
+        value: String,
+        /// Checksum (or prefix) of the snapshot to use
+        #[arg(short, long)]
+        checksum: Option<String>,
+    },
+    /// Print a metadata value
+    Get {
+        /// Snapshotted path (the latest snapshot is used unless --checksum is given)
+        path: String,
+        /// Metadata key
+        key: String,
+        /// Checksum (or prefix) of the snapshot to use
+        #[arg(short, long)]
+        checksum: Option<String>,
+    },
+    /// List the metadata of a snapshot
+    List {
+        /// Snapshotted path (the latest snapshot is used unless --checksum is given)
+        path: String,
+        /// Checksum (or prefix) of the snapshot to use
+        #[arg(short, long)]
+        checksum: Option<String>,
+    },
+    /// Remove a metadata value
+    Rm {
+        /// Snapshotted path (the latest snapshot is used unless --checksum is given)
+        path: String,
+        /// Metadata key
+        key: String,
+        /// Checksum (or prefix) of the snapshot to use
+        #[arg(short, long)]
+        checksum: Option<String>,
+    },
+}
+
+impl MetaCommands {
+    fn path_mut(&mut self) -> &mut String {
+        match self {
+            MetaCommands::Set { path, .. }
+            | MetaCommands::Get { path, .. }
+            | MetaCommands::List { path, .. }
+            | MetaCommands::Rm { path, .. } => path,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum SessionCommands {
+    /// List sessions, newest first
+    Ls {
+        /// Only list sessions of this directory and its subdirectories
+        path: Option<String>,
+    },
+    /// List the files recorded in a session
+    Show {
+        /// Session ID
+        id: i64,
+    },
+    /// Restore every file of a session
+    Restore {
+        /// Session ID
+        id: i64,
+        /// Restore into this folder instead of overwriting the original files
+        #[arg(long, visible_alias = "into", value_name = "DEST")]
+        to: Option<String>,
+        /// Leave restored files' modification times at the time of restore
+        /// instead of the saved ones (default: restore_times setting)
+        #[arg(long)]
+        no_times: bool,
+    },
+    /// Compare two sessions, or a session with the current files
+    Diff {
+        /// Session ID
+        first: i64,
+        /// Later session ID (defaults to the current state of the directory)
+        second: Option<i64>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Print the value of a setting
+    Get {
+        /// Setting name
+        key: String,
+    },
+    /// Change a setting
+    Set {
+        /// Setting name
+        key: String,
+        /// New value
+        value: String,
+    },
+    /// Reset a setting to its default
+    Unset {
+        /// Setting name
+        key: String,
+    },
+    /// List every setting with its value
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum ProfileCommands {
+    /// List the profiles with their database files
+    List,
+    /// Add a profile
+    Create {
+        /// Name of the profile (letters, digits, - and _)
+        name: String,
+        /// Database file of the profile, relative to the repository directory
+        /// (default: profiles/<name>/data.sql); storage lives next to it
+        #[arg(long, value_name = "FILE")]
+        db: Option<String>,
+    },
+    /// Remove a profile from the configuration
+    Delete {
+        /// Name of the profile
+        name: String,
+        /// Also delete its database and storage, if they live in the
+        /// profiles directory
+        #[arg(long)]
+        purge: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RepositoryKeyCommands {
+    /// Protect the repository with a passphrase and encrypt its contents
+    /// (FREEZE_PASSPHRASE or prompt)
+    Init,
+    /// Replace the repository key and passphrase, re-encrypting every content
+    /// (FREEZE_PASSPHRASE, FREEZE_NEW_PASSPHRASE or prompts)
+    Rotate,
+    /// Keep the repository key in the platform keyring so that commands
+    /// stop asking for the passphrase
+    Store,
+    /// Remove the repository key from the platform keyring
+    Forget,
+    /// Show whether the repository is protected
+    Status,
+    /// Encrypt the database with SQLCipher, hiding snapshot paths, dates and
+    /// checksums too (builds with the sqlcipher feature)
+    EncryptDatabase,
+    /// Turn an encrypted database back into a plain SQLite file
+    DecryptDatabase,
+}
+
+#[derive(Subcommand)]
+pub enum DaemonCommands {
+    /// Run the daemon in the foreground until stopped
+    Start,
+    /// Show whether a daemon is running and what it watches
+    Status,
+    /// Stop the running daemon
+    Stop,
+    /// Have the running daemon watch a file or directory
+    Watch {
+        /// Path to watch
+        path: String,
+        /// Quiet time after the last change before saving (in milliseconds)
+        #[arg(short, long, default_value = "500")]
+        debounce: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DbCommands {
+    /// Copy the database to a file, consistent even while it is in use
+    Backup {
+        /// File receiving the copy
+        output: String,
+        /// Also write the stored contents to this tar archive
+        #[arg(long, value_name = "ARCHIVE")]
+        storage: Option<String>,
+    },
+    /// Replace the database with a backup
+    Restore {
+        /// Backup made by `freeze db backup`
+        backup: String,
+        /// Also add the stored contents of this tar archive
+        #[arg(long, value_name = "ARCHIVE")]
+        storage: Option<String>,
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Check the database file, rebuild it without its free pages and refresh
+    /// its query statistics
+    Vacuum,
+    /// Merge the snapshots and contents of another repository into this one
+    Import {
+        /// Data directory of the other repository (such as a copied
+        /// ~/.freeze), or its database file
+        source: String,
+    },
+    /// Add the text of contents saved before the content_index setting was
+    /// turned on to the index of `search --content`
+    Index {
+        /// Empty the index instead
+        #[arg(long)]
+        drop: bool,
+    },
+    /// Export the metadata of every snapshot, without contents
+    Export {
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "json")]
+        format: crate::catalog::CatalogFormat,
+        /// Write the export to a file instead of standard output
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Also export these parts (comma separated)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        include: Vec<crate::catalog::CatalogPart>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ExclusionCommands {
+    /// Add exclusion pattern
+    Add {
+        /// Pattern to exclude
+        pattern: String,
+        /// Type of exclusion (directory, extension, file), or include to
+        /// only keep files whose name matches the glob pattern
+        #[arg(value_enum)]
+        exclusion_type: ExclusionType,
+    },
+    /// Remove exclusion pattern
+    Remove {
+        /// Pattern to remove
+        pattern: String,
+    },
+    /// List all exclusions
+    List,
+}
+
+/// Filtering and ordering options of `ls` and `cls`.
+#[derive(Args, Clone, Debug)]
+pub struct ListFilter {
+    /// Order of the listing
+    #[arg(long, value_enum, default_value_t = SnapshotSort::Date)]
+    sort: SnapshotSort,
+    /// Only snapshots taken since this date (YYYY-MM-DD [HH:MM], RFC3339, or an age like 7d)
+    #[arg(long, value_name = "DATE")]
+    since: Option<String>,
+    /// Only snapshots taken until this date (YYYY-MM-DD [HH:MM], RFC3339, or an age like 7d)
+    #[arg(long, value_name = "DATE")]
+    until: Option<String>,
+    /// Only snapshots larger than this size (e.g. 500, 10K, 2M, 1G)
+    #[arg(long, value_name = "SIZE")]
+    larger_than: Option<String>,
+    /// Only snapshots whose path contains this text
+    #[arg(long, value_name = "TEXT")]
+    path_contains: Option<String>,
+}
+
+impl ListFilter {
+    /// Builds the database filter, restricted to `dir` if given.
+    fn to_filter(&self, dir: Option<PathBuf>) -> Result<SnapshotFilter> {
+        Ok(SnapshotFilter {
+            dir,
+            since: self
+                .since
+                .as_deref()
+                .map(|since| utils::parse_date_bound(since, true))
+                .transpose()?,
+            until: self
+                .until
+                .as_deref()
+                .map(|until| utils::parse_date_bound(until, false))
+                .transpose()?,
+            larger_than: self.larger_than.as_deref().map(utils::parse_size).transpose()?,
+            path_contains: self.path_contains.clone(),
+            sort: self.sort,
+        })
+    }
+}
+
+/// Filtering options of `search`.
+#[derive(Args, Clone, Debug)]
+pub struct SearchOptions {
+    /// Only snapshots of this type: a MIME type (image/png) or a
+    /// top-level type (image, text, video, audio, application)
+    #[arg(long = "type", value_name = "TYPE")]
+    file_type: Option<String>,
+    /// Only snapshots taken since this date (YYYY-MM-DD [HH:MM], RFC3339, or an age like 7d)
+    #[arg(long, value_name = "DATE", conflicts_with = "content")]
+    since: Option<String>,
+    /// Only snapshots taken until this date (YYYY-MM-DD [HH:MM], RFC3339, or an age like 7d)
+    #[arg(long, value_name = "DATE", conflicts_with = "content")]
+    until: Option<String>,
+    /// Only snapshots larger than this size (e.g. 500, 10K, 2M, 1G)
+    #[arg(long, value_name = "SIZE", conflicts_with = "content")]
+    larger_than: Option<String>,
+    /// Only snapshots smaller than this size (e.g. 500, 10K, 2M, 1G)
+    #[arg(long, value_name = "SIZE", conflicts_with = "content")]
+    smaller_than: Option<String>,
+    /// Only snapshots whose checksum starts with this prefix
+    #[arg(long, value_name = "PREFIX", conflicts_with = "content")]
+    checksum: Option<String>,
+    /// Only files with this extension (e.g. rs or .txt)
+    #[arg(long, value_name = "EXT", conflicts_with = "content")]
+    ext: Option<String>,
+    /// Only snapshots with this metadata entry: a key, or key=value
+    #[arg(long, value_name = "KEY[=VALUE]", conflicts_with = "content")]
+    tag: Option<String>,
+    /// Only the newest matching snapshot of each path
+    #[arg(long, conflicts_with = "content")]
+    latest: bool,
+}
+
+impl SearchOptions {
+    /// Builds the database filter.
+    fn to_filter(&self) -> Result<SearchFilter> {
+        let mut filter = SearchFilter {
+            latest: self.latest,
+            ..SearchFilter::default()
+        };
+        let criteria = [
+            ("type", &self.file_type),
+            ("since", &self.since),
+            ("until", &self.until),
+            ("larger_than", &self.larger_than),
+            ("smaller_than", &self.smaller_than),
+            ("checksum", &self.checksum),
+            ("ext", &self.ext),
+            ("tag", &self.tag),
+        ];
+        for (name, value) in criteria {
+            if let Some(value) = value {
+                filter.set(name, value)?;
+            }
+        }
+        Ok(filter)
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum ExclusionType {
+    Directory,
+    Extension,
+    File,
+    Include,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Html,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum StatsFormat {
+    Table,
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum ApiScope {
+    ReadOnly,
+    ReadWrite,
+}
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::ReadOnly => crate::web::auth::SCOPE_READ_ONLY,
+            ApiScope::ReadWrite => crate::web::auth::SCOPE_READ_WRITE,
+        }
+    }
+}
+
+impl ExclusionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExclusionType::Directory => "directory",
+            ExclusionType::Extension => "extension",
+            ExclusionType::File => "file",
+            ExclusionType::Include => "include",
+        }
+    }
+}
+
+/// Prints the details of a snapshot that cannot be displayed.
+fn print_snapshot_details(snapshot: &Snapshot) {
+    println!("{}", t!("snapshot-details"));
+    println!("{}", t!("detail-path", value = snapshot.path.display().to_string()));
+    println!("{}", t!("detail-date", value = snapshot.date.as_str()));
+    println!("{}", t!("detail-size", value = format_size(snapshot.size)));
+    println!("{}", t!("detail-checksum", value = snapshot.checksum.as_str()));
+}
+
+/// Prints what a restore would do to each file, followed by totals.
+fn print_restore_plan(plan: &[PlannedRestore]) {
+    println!("{}", style(t!("restore-dry-run")).cyan().bold());
+    let short = |checksum: &str| checksum.get(..8).unwrap_or(checksum).to_string();
+    for entry in plan {
+        let snapshot = short(&entry.snapshot.checksum);
+        let current = entry.current.as_deref().map(short).unwrap_or_default();
+        let (marker, label, detail) = match entry.action {
+            RestoreAction::Overwrite => (
+                style("~").yellow(),
+                style(t!("restore-plan-overwrite")).yellow(),
+                format!("{} {} {}", current, utils::symbol("→", "->"), snapshot),
+            ),
+            RestoreAction::Create => (
+                style("+").green(),
+                style(t!("restore-plan-create")).green(),
+                snapshot,
+            ),
+            RestoreAction::Unchanged => (
+                style("=").dim(),
+                style(t!("restore-plan-unchanged")).dim(),
+                current,
+            ),
+        };
+        println!(
+            "{} {:<12} {} {}",
+            marker,
+            label,
+            entry.target.display(),
+            style(format!("({})", detail)).dim()
+        );
+    }
+
+    let count = |action| plan.iter().filter(|entry| entry.action == action).count();
+    println!(
+        "\n{}",
+        t!(
+            "restore-plan-summary",
+            overwrite = count(RestoreAction::Overwrite),
+            create = count(RestoreAction::Create),
+            unchanged = count(RestoreAction::Unchanged)
+        )
+    );
+}
+
+/// Size of the database file and its write-ahead log, in bytes.
+fn db_size() -> u64 {
+    let Ok(path) = Database::db_path() else {
+        return 0;
+    };
+    let mut wal = path.clone().into_os_string();
+    wal.push("-wal");
+    [path, PathBuf::from(wal)]
+        .iter()
+        .filter_map(|file| fs::metadata(file).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Checks the database file, then refreshes its query statistics and
+/// rebuilds it, reporting its size and free pages before and after.
+fn vacuum_database(db: &Database) -> Result<()> {
+    let problems = db.integrity_check()?;
+    if !problems.is_empty() {
+        println!("{}", style(t!("db-integrity-failed", count = problems.len())).red());
+        for problem in &problems {
+            println!("  {}", style(problem).yellow());
+        }
+        anyhow::bail!(t!("db-vacuum-refused"));
+    }
+    println!("{}", style(t!("db-integrity-ok")).green());
+
+    let report = |label: String, size: u64, pages: crate::db::PageStats| {
+        println!(
+            "{:<8} {} {}",
+            style(label).bold(),
+            style(format_size(size as i64)).cyan(),
+            style(t!(
+                "db-vacuum-pages",
+                free = pages.free_pages,
+                percent = format!("{:.1}", pages.fragmentation())
+            ))
+            .dim()
+        );
+    };
+    let size_before = db_size();
+    report(t!("db-vacuum-before"), size_before, db.page_stats()?);
+    db.analyze()?;
+    db.vacuum()?;
+    let size_after = db_size();
+    report(t!("db-vacuum-after"), size_after, db.page_stats()?);
+    println!(
+        "\n{} {}",
+        style(t!("gc-reclaimed")).bold(),
+        style(format_size(size_before.saturating_sub(size_after) as i64)).cyan().bold()
+    );
+    Ok(())
+}
+
+/// Collects garbage: orphaned storage files, snapshots without content
+/// (with `force`) and the free pages of the database.
+fn collect_garbage(force: bool, db: &Database) -> Result<()> {
+    let size_before = db_size();
+
+    let (orphans, orphan_bytes) = db.sweep_orphaned_files()?;
+    println!(
+        "{} {}",
+        style(t!("gc-orphans", count = orphans)).green(),
+        style(format!("({})", format_size(orphan_bytes as i64))).dim()
+    );
+
+    let missing = db.list_missing_content()?;
+    if force {
+        let deleted = db.delete_missing_content()?;
+        println!("{}", style(t!("gc-missing-deleted", count = deleted)).green());
+    } else if !missing.is_empty() {
+        println!("{}", style(t!("gc-missing", count = missing.len())).yellow());
+        for (_, path) in &missing {
+            println!("  {}", style(path.display()).cyan());
+        }
+        println!("{}", style(t!("gc-force-hint")).dim());
+    }
+
+    db.vacuum()?;
+    let db_bytes = size_before.saturating_sub(db_size());
+    println!(
+        "{} {}",
+        style(t!("gc-vacuumed")).green(),
+        style(format!("({})", format_size(db_bytes as i64))).dim()
+    );
+    println!(
+        "\n{} {}",
+        style(t!("gc-reclaimed")).bold(),
+        style(format_size((orphan_bytes + db_bytes) as i64)).cyan().bold()
+    );
+    Ok(())
+}
+
+/// Deletes the snapshots a retention rule expires, below `root` or everywhere.
+///
+/// With `dry_run`, only lists them.
+fn clear_expired(root: Option<&Path>, retention: Retention, dry_run: bool, force: bool, db: &Database) -> Result<()> {
+    delete_expired(&db.expired_snapshots(root, retention)?, dry_run, force, db)
+}
+
+/// Deletes the snapshots the retention schedules no longer keep, below
+/// `root` or everywhere.
+///
+/// With `dry_run`, only lists them.
+fn prune(root: Option<&Path>, schedule: Option<&str>, dry_run: bool, db: &Database) -> Result<()> {
+    let policies = match schedule {
+        Some(spec) => Policies::single(Schedule::parse(spec)?),
+        None => Policies::from_config(crate::config::get())?,
+    };
+    if policies.is_empty() {
+        anyhow::bail!(t!("prune-no-schedule"));
+    }
+    let expired = retention::expired_snapshots(root, &policies, chrono::Local::now(), db)?;
+    delete_expired(&expired, dry_run, false, db)
+}
+
+/// Lists the snapshots a deletion would remove, with their count and size.
+fn print_dry_run(doomed: &[SnapshotWithId], db: &Database) -> Result<()> {
+    if doomed.is_empty() {
+        println!("{}", style(t!("clear-expired-none")).yellow());
+        return Ok(());
+    }
+    utils::print_snapshot_info(doomed, &db.list_messages()?, &db.list_pinned()?);
+    let size: i64 = doomed.iter().map(|(_, _, _, size, _)| size).sum();
+    println!(
+        "{}",
+        style(t!("clear-expired-dry-run", count = doomed.len(), size = format_size(size))).yellow()
+    );
+    Ok(())
+}
+
+/// Confirms then deletes expired snapshots, reporting the space freed.
+///
+/// With `dry_run`, only lists them; with `force`, does not ask.
+fn delete_expired(expired: &[SnapshotWithId], dry_run: bool, force: bool, db: &Database) -> Result<()> {
+    if dry_run || expired.is_empty() {
+        return print_dry_run(expired, db);
+    }
+
+    let size: i64 = expired.iter().map(|(_, _, _, size, _)| size).sum();
+    if !force
+        && !utils::confirm(&t!(
+            "clear-expired-confirm",
+            count = expired.len(),
+            size = format_size(size)
+        ))?
+    {
+        println!("{}", style(t!("confirm-cancelled")).yellow());
+        return Ok(());
+    }
+    let ids: Vec<i64> = expired.iter().map(|(id, ..)| *id).collect();
+    let (deleted, freed) = db.delete_snapshots(&ids)?;
+    println!(
+        "{}",
+        style(t!(
+            "clear-expired-done",
+            count = deleted,
+            size = format_size(freed as i64)
+        ))
+        .green()
+    );
+    Ok(())
+}
+
+/// Pins or unpins the snapshots of a content and lists their paths.
+fn set_pinned(checksum: &str, pinned: bool, db: &Database) -> Result<()> {
+    let snapshots = db.set_pinned(checksum, pinned)?;
+    let checksum = &snapshots[0].1;
+    let key = if pinned { "pin-done" } else { "unpin-done" };
+    println!(
+        "{}",
+        style(t!(key, checksum = checksum.get(..8).unwrap_or(checksum))).green()
+    );
+    for (path, _) in &snapshots {
+        println!("  {}", style(path.display()).cyan());
+    }
+    Ok(())
+}
+
+/// Mentions the pinned snapshots a clear left below `root`, or everywhere.
+fn print_pinned_kept(root: Option<&Path>, db: &Database) -> Result<()> {
+    let count = db.count_pinned(root)?;
+    if count > 0 {
+        println!("{}", style(t!("clear-pinned-kept", count = count)).yellow());
+    }
+    Ok(())
+}
+
+/// Forces colored output on or off, for both terminal styling crates.
+fn apply_color_mode(color: ColorMode) {
+    let enabled = match color {
+        ColorMode::Auto => return,
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+    };
+    console::set_colors_enabled(enabled);
+    console::set_colors_enabled_stderr(enabled);
+    colored::control::set_override(enabled);
+}
+
+/// Saves `path` through the running daemon, if any and not disabled with
+/// `FREEZE_NO_DAEMON`.
+fn save_through_daemon(
+    path: &Path,
+    message: Option<&str>,
+    keep_going: bool,
+) -> Result<Option<crate::snapshot::SaveSummary>> {
+    if env::var_os("FREEZE_NO_DAEMON").is_some() {
+        return Ok(None);
+    }
+    let request = crate::daemon::Request::Save {
+        path: path.to_path_buf(),
+        message: message.map(str::to_string),
+        keep_going,
+    };
+    match crate::daemon::send(&request)? {
+        Some(response) => {
+            let summary = serde_json::from_value(response.into_data()?)?;
+            println!("{}", style(t!("daemon-proxied")).dim());
+            Ok(Some(summary))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Talks to the running daemon.
+fn run_daemon_client(action: &DaemonCommands) -> Result<()> {
+    use crate::daemon::{send, Request};
+
+    let request = match action {
+        DaemonCommands::Start => unreachable!("the daemon is started with an open database"),
+        DaemonCommands::Status => Request::Jobs,
+        DaemonCommands::Stop => Request::Shutdown,
+        DaemonCommands::Watch { path, debounce } => {
+            let path = PathBuf::from(path).canonicalize()?;
+            Request::Watch { path, debounce: *debounce }
+        }
+    };
+    let Some(response) = send(&request)? else {
+        println!("{}", style(t!("daemon-not-running")).yellow());
+        return Ok(());
+    };
+    let data = response.into_data()?;
+
+    match action {
+        DaemonCommands::Status => {
+            let pid = send(&Request::Ping)?
+                .map(|response| response.into_data())
+                .transpose()?
+                .and_then(|data| data["pid"].as_u64())
+                .unwrap_or_default();
+            println!(
+                "{} {}",
+                style(t!("daemon-running", pid = pid)).green(),
+                style(crate::daemon::socket_path()?.display()).dim()
+            );
+            let watches = data["watches"].as_array().cloned().unwrap_or_default();
+            if watches.is_empty() {
+                println!("{}", style(t!("daemon-no-watches")).dim());
+            }
+            for path in watches {
+                println!("  {} {}", t!("daemon-watching"), style(path.as_str().unwrap_or_default()).cyan());
+            }
+        }
+        DaemonCommands::Stop => println!("{}", style(t!("daemon-stopped")).green()),
+        DaemonCommands::Watch { path, .. } => {
+            println!("{} {}", style(t!("daemon-watching")).green(), style(path).cyan())
+        }
+        DaemonCommands::Start => {}
+    }
+    Ok(())
+}
+
+/// Manages the passphrase protecting the repository.
+fn run_key(action: &RepositoryKeyCommands) -> Result<()> {
+    match action {
+        RepositoryKeyCommands::Init => {
+            print_header(&t!("header-key"));
+            let count = crate::crypto::init()?;
+            println!("{}", style(t!("key-init-done", count = count)).green());
+            println!("{}", style(t!("key-init-hint")).dim());
+        }
+        RepositoryKeyCommands::Rotate => {
+            print_header(&t!("header-key"));
+            let count = crate::crypto::rotate()?;
+            println!("{}", style(t!("key-rotated", count = count)).green());
+        }
+        RepositoryKeyCommands::Store => {
+            crate::crypto::store_in_keyring()?;
+            println!("{}", style(t!("key-stored")).green());
+        }
+        RepositoryKeyCommands::Forget => {
+            if crate::crypto::forget_from_keyring()? {
+                println!("{}", style(t!("key-forgotten")).green());
+            } else {
+                println!("{}", style(t!("key-not-in-keyring")).yellow());
+            }
+        }
+        RepositoryKeyCommands::Status => {
+            if crate::crypto::is_enabled()? {
+                println!(
+                    "{} {}",
+                    style(t!("key-status-protected")).green(),
+                    style(crate::crypto::key_file_path()?.display()).dim()
+                );
+                if crate::crypto::is_in_keyring()? {
+                    println!("{}", style(t!("key-status-keyring")).dim());
+                }
+                if crate::crypto::is_database_encrypted()? {
+                    println!("{}", style(t!("key-status-database")).dim());
+                }
+            } else {
+                println!("{}", style(t!("key-status-unprotected")).yellow());
+            }
+        }
+        RepositoryKeyCommands::EncryptDatabase => {
+            print_header(&t!("header-key"));
+            crate::crypto::set_database_encryption(true)?;
+            println!("{}", style(t!("key-database-encrypted")).green());
+        }
+        RepositoryKeyCommands::DecryptDatabase => {
+            print_header(&t!("header-key"));
+            crate::crypto::set_database_encryption(false)?;
+            println!("{}", style(t!("key-database-decrypted")).green());
+        }
+    }
+    Ok(())
+}
+
+/// Manages profiles; `active` is the profile selected for this run.
+fn run_profile(action: &ProfileCommands, active: Option<&str>) -> Result<()> {
+    let mut config = Config::load()?;
+    match action {
+        ProfileCommands::List => {
+            println!(
+                "{} {}",
+                style(t!("profile-title")).cyan().bold(),
+                style(Config::path()?.display()).dim()
+            );
+            let marker = |selected: bool| if selected { style("*").green() } else { style(" ") };
+            let default = config.storage()?.unwrap_or(Database::home()?.join("data.sql").display().to_string());
+            println!(
+                "{} {} {}",
+                marker(active.is_none()),
+                style(format!("{:<16}", t!("profile-default"))).dim(),
+                default
+            );
+            for name in config.profiles.iter().flatten().map(|(name, _)| name) {
+                println!(
+                    "{} {} {}",
+                    marker(active == Some(name.as_str())),
+                    style(format!("{:<16}", name)).cyan(),
+                    config.profile_storage(name)?.unwrap_or_default()
+                );
+            }
+        }
+        ProfileCommands::Create { name, db } => {
+            config.add_profile(name, db.as_deref())?;
+            let path = config.profile_storage(name)?.unwrap_or_default();
+            if let Some(parent) = Path::new(&path).parent() {
+                fs::create_dir_all(parent)?;
+            }
+            config.save()?;
+            println!(
+                "{} {} {}",
+                style(t!("profile-created", name = name.as_str())).green(),
+                style(&path).cyan(),
+                style(t!("profile-use-hint", name = name.as_str())).dim()
+            );
+        }
+        ProfileCommands::Delete { name, purge } => {
+            let path = config
+                .profile_storage(name)?
+                .ok_or_else(|| anyhow::anyhow!(t!("profile-unknown", name = name.as_str())))?;
+            let dir = Database::home()?.join(config::PROFILES_DIR).join(name);
+            if *purge {
+                if Path::new(&path).parent() != Some(dir.as_path()) {
+                    anyhow::bail!(t!("profile-purge-refused", path = path.as_str()));
+                }
+                if !utils::confirm(&t!("profile-purge-confirm", name = name.as_str()))? {
+                    println!("{}", style(t!("confirm-cancelled")).yellow());
+                    return Ok(());
+                }
+            }
+            config.remove_profile(name);
+            config.save()?;
+            if *purge && dir.exists() {
+                fs::remove_dir_all(&dir)?;
+            }
+            println!("{} {}", style(t!("profile-deleted")).green(), style(name).cyan());
+        }
+    }
+    Ok(())
+}
+
+/// Runs a `freeze config` subcommand.
+fn run_config(action: &ConfigCommands) -> Result<()> {
+    let mut config = Config::load()?;
+    match action {
+        ConfigCommands::Get { key } => match config.get(key)? {
+            Some(value) => println!("{}", value),
+            None => println!("{}", style(t!("config-unset")).dim()),
+        },
+        ConfigCommands::Set { key, value } => {
+            config.set(key, Some(value))?;
+            config.save()?;
+            println!(
+                "{} {} = {}",
+                style(t!("config-set")).green(),
+                style(key).cyan(),
+                style(value).yellow()
+            );
+        }
+        ConfigCommands::Unset { key } => {
+            config.set(key, None)?;
+            config.save()?;
+            println!("{} {}", style(t!("config-unset-done")).green(), style(key).cyan());
+        }
+        ConfigCommands::List => {
+            println!(
+                "{} {}",
+                style(t!("config-title")).cyan().bold(),
+                style(Config::path()?.display()).dim()
+            );
+            for (key, description) in config::KEYS {
+                let value = match config.get(key)? {
+                    Some(value) => style(format!("{:<14}", value)).yellow(),
+                    None => style(format!("{:<14}", t!("config-unset"))).dim(),
+                };
+                println!(
+                    "  {} {} {}",
+                    style(format!("{:<18}", key)).cyan(),
+                    value,
+                    style(format!("# {}", description)).dim()
+                );
+            }
+        }
+    }
+    Ok(())
 }
 
-#[derive(Subcommand)]
-pub enum ExclusionCommands {
-    /// Add exclusion pattern
-    Add {
-        /// Pattern to exclude
-        pattern: String,
-        /// Type of exclusion (directory, extension, file)
-        #[arg(value_enum)]
-        exclusion_type: ExclusionType,
-    },
-    /// Remove exclusion pattern
-    Remove {
-        /// Pattern to remove
-        pattern: String,
-    },
-    /// List all exclusions
-    List,
+/// Expands `@alias` arguments in the path arguments of a command.
+fn expand_aliases(command: &mut Commands, db: &Database) -> Result<()> {
+    let paths: Vec<&mut String> = match command {
+        Commands::Save { path, .. }
+        | Commands::Watch { path, .. }
+        | Commands::Check { path, .. }
+        | Commands::Inspect { path }
+        | Commands::Blame { path }
+        | Commands::Grep { path: Some(path), .. } => vec![path],
+        Commands::Export {
+            snapshot_path,
+            destination,
+            ..
+        } => {
+            let mut paths = vec![snapshot_path];
+            paths.extend(destination.as_mut());
+            paths
+        }
+        Commands::View { snapshot_path, .. } => vec![snapshot_path],
+        Commands::Cat { path, .. } | Commands::Latest { path } | Commands::Amend { path, .. } => vec![path],
+        Commands::Restore { path, to, .. } => {
+            let mut paths = vec![path];
+            paths.extend(to.as_mut());
+            paths
+        }
+        Commands::Clear { path, .. }
+        | Commands::Rm { path, .. }
+        | Commands::Prune { path, .. }
+        | Commands::Status { path }
+        | Commands::Undo { path }
+        | Commands::Materialize { path }
+        | Commands::Compact { path, .. } => {
+            path.as_mut().into_iter().collect()
+        }
+        Commands::Cp {
+            source,
+            destination,
+        } => vec![source, destination],
+        Commands::Capture { as_path, .. } => vec![as_path],
+        Commands::Run { paths, .. } => paths.iter_mut().collect(),
+        Commands::Report { output, .. } => output.as_mut().into_iter().collect(),
+        Commands::ExportArchive { path, output } => {
+            let mut paths = vec![output];
+            paths.extend(path.as_mut());
+            paths
+        }
+        Commands::ImportArchive { file, as_path } => {
+            let mut paths = vec![file];
+            paths.extend(as_path.as_mut());
+            paths
+        }
+        Commands::Diff { first, second } => vec![first, second],
+        Commands::Meta { action } => vec![action.path_mut()],
+        Commands::Session {
+            action: SessionCommands::Ls { path: Some(path) } | SessionCommands::Restore { to: Some(path), .. },
+        } => vec![path],
+        _ => Vec::new(),
+    };
+
+    for path in paths {
+        *path = utils::expand_alias(path, db)?;
+    }
+    Ok(())
 }
 
-#[derive(clap::ValueEnum, Clone, Debug)]
-pub enum ExclusionType {
-    Directory,
-    Extension,
-    File,
+/// Finds the snapshot of `path` matching a checksum prefix, or its latest one.
+///
+/// Returns the snapshot ID and checksum.
+fn select_snapshot_id(path: &str, checksum: Option<&str>, db: &Database) -> Result<(i64, String)> {
+    let path = utils::resolve_path(path)?;
+    let snapshots = db.get_snapshots_for_path_with_id(&path)?;
+    if snapshots.is_empty() {
+        anyhow::bail!("{} {}", t!("no-snapshots-for"), path.display());
+    }
+
+    snapshots
+        .into_iter()
+        .find(|(_, _, _, _, sum)| checksum.is_none_or(|prefix| sum.starts_with(prefix)))
+        .map(|(id, _, _, _, sum)| (id, sum))
+        .ok_or_else(|| {
+            anyhow::anyhow!(t!(
+                "select-no-checksum",
+                checksum = checksum.unwrap_or_default(),
+                path = path.display().to_string()
+            ))
+        })
 }
 
-impl ExclusionType {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            ExclusionType::Directory => "directory",
-            ExclusionType::Extension => "extension",
-            ExclusionType::File => "file",
-        }
+/// Saves `paths` around a `freeze run`, tagging every new snapshot with the
+/// command line (`command` metadata) and with `phase` (`before` or `after`).
+fn save_for_run(paths: &[PathBuf], command_line: &str, phase: &str, db: &Database) -> Result<()> {
+    let last_id = db.last_snapshot_id()?;
+    let message = format!("{}: {}", phase, command_line);
+    for path in paths {
+        utils::validate_path(path)?;
+        Snapshot::save_recursive(path, Some(&message), false, false, db)?;
+    }
+
+    let new_id = db.last_snapshot_id()?;
+    for id in last_id + 1..=new_id {
+        db.set_metadata(id, "command", command_line)?;
+        db.set_metadata(id, "phase", phase)?;
+    }
+    println!(
+        "{} {}",
+        style(t!("run-saved", count = new_id - last_id, phase = phase)).green(),
+        style(command_line).cyan()
+    );
+    Ok(())
+}
+
+/// Runs an external `freeze-<name>` binary with the remaining arguments.
+///
+/// The plugin receives the repository locations through `FREEZE_HOME`,
+/// `FREEZE_DB` and `FREEZE_STORAGE`, and its exit code is propagated.
+fn run_external(args: &[String]) -> Result<()> {
+    let (name, plugin_args) = args
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!(t!("plugin-missing-name")))?;
+    let binary = format!("freeze-{}", name);
+
+    let status = std::process::Command::new(&binary)
+        .args(plugin_args)
+        .env("FREEZE_HOME", Database::home()?)
+        .env("FREEZE_DB", Database::db_path()?)
+        .env("FREEZE_STORAGE", Database::storage_dir()?)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => anyhow::bail!(t!(
+            "plugin-unknown",
+            name = name.as_str(),
+            binary = binary.as_str()
+        )),
+        Err(e) => Err(anyhow::anyhow!(t!(
+            "plugin-failed",
+            binary = binary.as_str(),
+            error = e.to_string()
+        ))),
     }
 }
 
 pub async fn run() -> Result<()> {
     let cli = Cli::parse();
+    if let Some(repo) = &cli.repo {
+        Database::set_home(repo)?;
+    }
+    let mut config = Config::load()?;
+    if let Commands::Save { compression_level: Some(level), .. }
+    | Commands::Watch { compression_level: Some(level), .. } = cli.command
+    {
+        config.compression_level = Some(level);
+    }
+    // A per-save algorithm applies to every file, whatever the rules say
+    if let Commands::Save { compression: Some(compression), .. }
+    | Commands::Watch { compression: Some(compression), .. } = cli.command
+    {
+        config.compression = Some(compression);
+        config.compression_rules = None;
+    }
+    if let Commands::Save { delta: true, .. } | Commands::Watch { delta: true, .. } = cli.command {
+        config.delta = Some(true);
+    }
+    if let Commands::Save { paranoid: true, .. } | Commands::Check { paranoid: true, .. } = cli.command {
+        config.paranoid = Some(true);
+    }
+    if let Commands::Save { follow_symlinks: true, .. } = cli.command {
+        config.follow_symlinks = Some(true);
+    }
+    if let Commands::Save { one_file_system: true, .. } | Commands::Check { one_file_system: true, .. } = cli.command {
+        config.one_file_system = Some(true);
+    }
+    if let Commands::Save { git_aware: true, .. } | Commands::Check { git_aware: true, .. } = cli.command {
+        config.git_aware = Some(true);
+    }
+    if let Commands::Save { xattrs: true, .. } = cli.command {
+        config.xattrs = Some(true);
+    }
+    if let Commands::Save { max_file_size: Some(size), .. } = &cli.command {
+        utils::parse_size(size)?;
+        config.max_file_size = Some(size.clone());
+    }
+    if let Commands::Restore { no_times: true, .. }
+    | Commands::Export { no_times: true, .. }
+    | Commands::Session {
+        action: SessionCommands::Restore { no_times: true, .. },
+    } = cli.command
+    {
+        config.restore_times = Some(false);
+    }
+    utils::set_ascii_mode(cli.ascii || config.ascii.unwrap_or(false));
+    utils::set_interaction(cli.yes, cli.no_input);
+    if let Some(color) = config.color {
+        apply_color_mode(color);
+    }
+    if cli.read_only {
+        config.read_only = Some(true);
+    }
+    let profile_db = match &cli.profile {
+        Some(name) => Some(
+            config
+                .profile_storage(name)?
+                .ok_or_else(|| anyhow::anyhow!(t!("profile-unknown", name = name.as_str())))?,
+        ),
+        None => None,
+    };
+    if let Some(db) = cli.db.clone().or(profile_db).or(config.storage()?) {
+        Database::set_location(&db)?;
+    }
+    config::init(config);
+    if config::get().read_only() && cli.command.modifies_repository() {
+        anyhow::bail!(t!("read-only-refused"));
+    }
+
+    // Clean up any orphaned temporary files at startup
+    if !config::get().read_only()
+        && let Err(e) = Snapshot::cleanup_temp_files()
+    {
+        eprintln!("{}", t!("warning-temp-cleanup", error = e.to_string()));
+    }
+
+    match &cli.command {
+        Commands::External(args) => return run_external(args),
+        Commands::Config { action } => return run_config(action),
+        Commands::Profile { action } => return run_profile(action, cli.profile.as_deref()),
+        Commands::Key { action } => return run_key(action),
+        Commands::Daemon { action } if !matches!(action, DaemonCommands::Start) => {
+            return run_daemon_client(action);
+        }
+        _ => {}
+    }
+    let json = cli.json || env::var("FREEZE_OUTPUT").is_ok_and(|v| v.eq_ignore_ascii_case("json"));
     let db = Database::new()?;
+    let mut command = cli.command;
+    expand_aliases(&mut command, &db)?;
 
-    match cli.command {
+    match command {
         Commands::Export {
             snapshot_path,
             destination,
+            choice,
+            ..
         } => {
-            print_header("📦 Exporting Snapshot");
+            print_header(&t!("header-export"));
 
             // Convert snapshot path to absolute path
-            let snapshot_path = PathBuf::from(snapshot_path).canonicalize()?;
+            let snapshot_path = utils::resolve_path(&snapshot_path)?;
 
             // Get snapshots for the specific path
             let snapshots = db.get_snapshots_for_path(&snapshot_path)?;
@@ -161,14 +1809,14 @@ pub async fn run() -> Result<()> {
             if snapshots.is_empty() {
                 println!(
                     "{} {}",
-                    style("No snapshots found for:").yellow(),
+                    style(t!("no-snapshots-for")).yellow(),
                     style(snapshot_path.display()).cyan()
                 );
                 return Ok(());
             }
 
             // If multiple snapshots, let user choose
-            let snapshot = utils::select_snapshot(&snapshots)?;
+            let snapshot = utils::select_snapshot(&snapshots, &choice.selection())?;
 
             // Determine export destination
             let export_path = match destination {
@@ -211,28 +1859,89 @@ pub async fn run() -> Result<()> {
                 fs::create_dir_all(parent)?;
             }
 
-            // Copy file directly from storage
-            fs::copy(&snapshot.content_path, &export_path)?;
+            snapshot.export(&export_path)?;
 
             println!(
                 "{} {} {} {}",
-                style("Exported snapshot:").green(),
+                style(t!("export-done")).green(),
                 style(snapshot.path.display()).cyan(),
-                style("to").green(),
+                style(t!("export-to")).green(),
                 style(export_path.display()).cyan()
             );
 
             Ok(())
         }
 
+        Commands::Amend { path, message } => {
+            print_header(&t!("header-amend"));
+            let path = utils::resolve_path(&path)?;
+            utils::validate_path(&path)?;
+            match Snapshot::amend(&path, message.as_deref(), &db)? {
+                Some((replaced, snapshot)) => println!(
+                    "{} {} {} {} {}",
+                    style(t!("amend-done")).green(),
+                    style(path.display()).cyan(),
+                    style(&replaced[..8]).dim(),
+                    utils::symbol("→", "->"),
+                    style(&snapshot.checksum[..8]).yellow()
+                ),
+                None => println!("{}", style(t!("amend-unchanged")).yellow()),
+            }
+            Ok(())
+        }
+
+        Commands::Latest { path } => {
+            let path = utils::resolve_path(&path)?;
+            let Some(snapshot) = db.get_snapshots_for_path_with_id(&path)?.into_iter().next() else {
+                anyhow::bail!("{} {}", t!("no-snapshots-for"), path.display());
+            };
+            if json {
+                let entries = utils::snapshot_entries(&[snapshot], &db.list_messages()?, &db.list_pinned()?);
+                return utils::print_json(&entries[0]);
+            }
+            let (_, _, date, size, checksum) = snapshot;
+            println!("{}\t{}\t{}", checksum, date, size);
+            Ok(())
+        }
+
+        Commands::Cat { path, checksum } => {
+            let path = utils::resolve_path(&path)?;
+            let snapshots = db.get_snapshots_for_path(&path)?;
+            if snapshots.is_empty() {
+                anyhow::bail!("{} {}", t!("no-snapshots-for"), path.display());
+            }
+            let selection = checksum.map_or(Selection::Latest, Selection::Checksum);
+            let snapshot = utils::select_snapshot(&snapshots, &selection)?;
+            match snapshot.write_content(&mut std::io::stdout().lock()) {
+                // The reader stopped early, as `head` does
+                Err(e)
+                    if e.downcast_ref::<std::io::Error>()
+                        .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe) =>
+                {
+                    Ok(())
+                }
+                result => result.map(|_| ()),
+            }
+        }
+
         Commands::View {
             snapshot_path,
             max_size,
+            lines,
+            head,
+            tail,
+            choice,
         } => {
-            print_header("👀 Viewing Snapshot");
+            print_header(&t!("header-view"));
+            let range = match (lines, head, tail) {
+                (Some(lines), _, _) => Some(LineRange::parse(&lines)?),
+                (_, Some(head), _) => Some(LineRange::Head(head)),
+                (_, _, Some(tail)) => Some(LineRange::Tail(tail)),
+                _ => None,
+            };
 
             // Convert snapshot path to absolute path
-            let snapshot_path = PathBuf::from(snapshot_path).canonicalize()?;
+            let snapshot_path = utils::resolve_path(&snapshot_path)?;
 
             // Get snapshots for the specific path
             let snapshots = db.get_snapshots_for_path(&snapshot_path)?;
@@ -240,61 +1949,74 @@ pub async fn run() -> Result<()> {
             if snapshots.is_empty() {
                 println!(
                     "{} {}",
-                    style("No snapshots found for:").yellow(),
+                    style(t!("no-snapshots-for")).yellow(),
                     style(snapshot_path.display()).cyan()
                 );
                 return Ok(());
             }
 
             // If multiple snapshots, let user choose
-            let snapshot = utils::select_snapshot(&snapshots)?;
+            let snapshot = utils::select_snapshot(&snapshots, &choice.selection())?;
+
+            // A slice is streamed, so the size limit does not apply
+            if let Some(range) = range {
+                let (first, lines) = snapshot.read_lines(range)?;
+                if lines.is_empty() {
+                    println!("{}", style(t!("view-no-lines")).yellow());
+                } else {
+                    println!(
+                        "{}",
+                        style(t!("view-lines", first = first, last = first + lines.len() - 1)).cyan().bold()
+                    );
+                    for line in lines {
+                        println!("{}", line);
+                    }
+                }
+                return Ok(());
+            }
 
             // Check file size before loading (use original size)
             let max_bytes = (max_size * 1024 * 1024) as i64;
 
             if snapshot.size > max_bytes {
                 println!(
-                    "{} {} ({} > {} MB limit)",
-                    style("File too large to display:").yellow(),
+                    "{} {} {}",
+                    style(t!("view-too-large")).yellow(),
                     style(snapshot_path.display()).cyan(),
-                    style(format_size(snapshot.size)).yellow(),
-                    style(max_size).yellow()
+                    style(t!(
+                        "view-too-large-detail",
+                        size = format_size(snapshot.size),
+                        limit = max_size
+                    ))
+                    .yellow()
                 );
-                println!("Snapshot details:");
-                println!("Path: {}", snapshot.path.display());
-                println!("Date: {}", snapshot.date);
-                println!("Size: {}", format_size(snapshot.size));
-                println!("Checksum: {}", snapshot.checksum);
+                print_snapshot_details(snapshot);
                 return Ok(());
             }
 
             // Decompress content
             let content = snapshot.get_decompressed_content()?;
             
-            if utils::is_binary(&content) {
+            if snapshot.is_binary(&content) {
                 println!(
                     "{} {}",
-                    style("Binary content detected for:").yellow(),
+                    style(t!("view-binary")).yellow(),
                     style(snapshot_path.display()).cyan()
                 );
-                println!("Snapshot details:");
-                println!("Path: {}", snapshot.path.display());
-                println!("Date: {}", snapshot.date);
-                println!("Size: {}", format_size(snapshot.size));
-                println!("Checksum: {}", snapshot.checksum);
+                print_snapshot_details(snapshot);
                 return Ok(());
             }
 
             // Attempt to convert content to UTF-8 string
             match String::from_utf8(content) {
                 Ok(content_str) => {
-                    println!("{}", style("Snapshot Content:").cyan().bold());
+                    println!("{}", style(t!("view-content")).cyan().bold());
                     println!("{}", content_str);
                 }
                 Err(_) => {
                     println!(
                         "{} {}",
-                        style("Unable to display content for:").yellow(),
+                        style(t!("view-unreadable")).yellow(),
                         style(snapshot_path.display()).cyan()
                     );
                 }
@@ -303,103 +2025,600 @@ pub async fn run() -> Result<()> {
             Ok(())
         }
 
-        Commands::Check { path } => {
-            print_header("🔍 Checking Files");
-            check_path(&path, &db)?;
-            Ok(())
+        Commands::Watch {
+            path,
+            debounce,
+            min_interval,
+            ..
+        } => {
+            print_header(&t!("header-watch"));
+            let path = PathBuf::from(path).canonicalize()?;
+            utils::validate_path(&path)?;
+            crate::watch::run(
+                &path,
+                &crate::watch::WatchOptions {
+                    debounce: std::time::Duration::from_millis(debounce),
+                    min_interval: std::time::Duration::from_secs(min_interval),
+                },
+                &db,
+            )
+        }
+
+        Commands::Check { path, .. } if json => {
+            let path = PathBuf::from(path).canonicalize()?;
+            let report: Vec<_> = utils::check_report(&path, &db, |_| {})?
+                .into_iter()
+                .map(|(path, status)| serde_json::json!({ "path": path, "status": status }))
+                .collect();
+            utils::print_json(&report)
+        }
+
+        Commands::Check { path, .. } => {
+            print_header(&t!("header-check"));
+            check_path(&path, &db)?;
+            Ok(())
+        }
+
+        Commands::Status { path } => {
+            let dir = match path {
+                Some(path) => PathBuf::from(path).canonicalize()?,
+                None => env::current_dir()?,
+            };
+            utils::print_status(&dir, &db)
+        }
+
+        Commands::Save {
+            path,
+            message,
+            keep_going,
+            compression_level,
+            compression,
+            delta,
+            paranoid,
+            follow_symlinks,
+            one_file_system,
+            git_aware,
+            xattrs,
+            max_file_size,
+            force,
+        } => {
+            print_header(&t!("header-save"));
+            let path = PathBuf::from(path).canonicalize()?;
+            utils::validate_path(&path)?;
+
+            println!(
+                "{} {}",
+                style(t!("save-freezing")).cyan().bold(),
+                style(path.display()).green()
+            );
+
+            // The daemon compresses and hashes with its own settings
+            let walk_options = follow_symlinks || one_file_system || git_aware || max_file_size.is_some();
+            let proxied = match (compression_level, compression, delta || paranoid || xattrs || force || walk_options) {
+                (None, None, false) => save_through_daemon(&path, message.as_deref(), keep_going)?,
+                _ => None,
+            };
+            let summary = match proxied {
+                Some(summary) => summary,
+                None => Snapshot::save_recursive(&path, message.as_deref(), keep_going, force, &db)?,
+            };
+            let progress = summary.progress;
+
+            if progress.files_unchanged < progress.files_done {
+                println!(
+                    "{} {}",
+                    style(t!("save-done")).green(),
+                    style(t!(
+                        "save-summary",
+                        count = progress.files_done - progress.files_unchanged,
+                        size = format_size(progress.bytes_done as i64)
+                    ))
+                    .dim()
+                );
+            }
+            if progress.files_unchanged > 0 {
+                println!(
+                    "{}",
+                    style(t!("save-unchanged", count = progress.files_unchanged)).yellow()
+                );
+            }
+            if !summary.skipped.is_empty() {
+                println!(
+                    "\n{}",
+                    style(t!("save-skipped", count = summary.skipped.len())).yellow().bold()
+                );
+                for skipped in &summary.skipped {
+                    println!(
+                        "  {} {}",
+                        style(skipped.path.display()).cyan(),
+                        style(&skipped.error).dim()
+                    );
+                }
+            }
+            if !summary.too_large.is_empty() {
+                println!(
+                    "\n{}",
+                    style(t!("save-too-large", count = summary.too_large.len())).yellow().bold()
+                );
+                for (path, size) in &summary.too_large {
+                    println!(
+                        "  {} {}",
+                        style(path.display()).cyan(),
+                        style(format_size(*size as i64)).dim()
+                    );
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Capture { as_path, command } => {
+            print_header(&t!("header-capture"));
+            let path = utils::resolve_path(&as_path)?;
+            let (program, args) = command
+                .split_first()
+                .ok_or_else(|| anyhow::anyhow!(t!("capture-missing-command")))?;
+
+            let output = std::process::Command::new(program)
+                .args(args)
+                .stdin(std::process::Stdio::inherit())
+                .stderr(std::process::Stdio::inherit())
+                .output()
+                .map_err(|e| {
+                    anyhow::anyhow!(t!("plugin-failed", binary = program.as_str(), error = e.to_string()))
+                })?;
+            if !output.status.success() {
+                anyhow::bail!(t!(
+                    "capture-failed",
+                    command = command.join(" "),
+                    code = output.status.code().unwrap_or(-1)
+                ));
+            }
+
+            let snapshot = Snapshot::from_content(&path, &output.stdout)?;
+            let unchanged = db
+                .get_snapshots_for_path(&path)?
+                .first()
+                .is_some_and(|latest| latest.checksum == snapshot.checksum);
+            db.save_snapshot(&snapshot)?;
+
+            let message = if unchanged {
+                t!("capture-unchanged")
+            } else {
+                t!("capture-done")
+            };
+            println!(
+                "{} {} ({}, {})",
+                style(message).green(),
+                style(path.display()).cyan(),
+                style(format_size(snapshot.size)).yellow(),
+                style(&snapshot.checksum[..8]).dim()
+            );
+            Ok(())
+        }
+
+        Commands::Run { paths, after, command } => {
+            print_header(&t!("header-run"));
+            let paths = if paths.is_empty() {
+                vec![std::env::current_dir()?]
+            } else {
+                paths
+                    .iter()
+                    .map(|path| utils::resolve_path(path))
+                    .collect::<Result<Vec<_>>>()?
+            };
+            let (program, args) = command
+                .split_first()
+                .ok_or_else(|| anyhow::anyhow!(t!("capture-missing-command")))?;
+            let command_line = command.join(" ");
+
+            save_for_run(&paths, &command_line, "before", &db)?;
+            let status = std::process::Command::new(program)
+                .args(args)
+                .status()
+                .map_err(|e| {
+                    anyhow::anyhow!(t!("plugin-failed", binary = program.as_str(), error = e.to_string()))
+                })?;
+            if after {
+                save_for_run(&paths, &command_line, "after", &db)?;
+            }
+
+            if !status.success() {
+                let code = status.code().unwrap_or(1);
+                eprintln!(
+                    "{}",
+                    style(t!("run-failed", command = command_line.as_str(), code = code)).red()
+                );
+                std::process::exit(code);
+            }
+            Ok(())
+        }
+
+        Commands::Restore {
+            path,
+            to,
+            as_of,
+            dry_run,
+            choice,
+            ..
+        } => {
+            print_header(&t!("header-restore"));
+            let path = utils::resolve_path(&path)?;
+            let to = to.as_deref().map(utils::resolve_path).transpose()?;
+            let selection = match as_of {
+                Some(as_of) => Selection::AsOf(utils::parse_timestamp(&as_of)?),
+                None => choice.selection(),
+            };
+
+            // Settle which snapshot of a single file is restored before
+            // planning, so that the plan matches what gets written
+            let selection = match selection {
+                Selection::Prompt if !path.is_dir() => {
+                    let snapshots = db.get_snapshots_for_path(&path)?;
+                    if snapshots.is_empty() {
+                        Selection::Prompt
+                    } else {
+                        let snapshot = utils::select_snapshot(&snapshots, &Selection::Prompt)?;
+                        Selection::Checksum(snapshot.checksum.clone())
+                    }
+                }
+                selection => selection,
+            };
+
+            let plan = Snapshot::plan_restore(&path, to.as_deref(), &selection, &db)?;
+            if dry_run {
+                print_restore_plan(&plan);
+                return Ok(());
+            }
+
+            let overwrites = plan
+                .iter()
+                .filter(|entry| entry.action == RestoreAction::Overwrite)
+                .count();
+            if overwrites > 0 && config::get().read_only() {
+                anyhow::bail!(t!("read-only-overwrite", count = overwrites));
+            }
+            if overwrites > 0 && !utils::confirm(&t!("restore-confirm-overwrite", count = overwrites))? {
+                println!("{}", style(t!("confirm-cancelled")).yellow());
+                return Ok(());
+            }
+
+            match &to {
+                Some(to) => println!(
+                    "{} {} {} {}",
+                    style(t!("restore-restoring")).cyan().bold(),
+                    style(path.display()).green(),
+                    style(t!("export-to")).cyan().bold(),
+                    style(to.display()).green()
+                ),
+                None => println!(
+                    "{} {}",
+                    style(t!("restore-restoring")).cyan().bold(),
+                    style(path.display()).green()
+                ),
+            }
+
+            Snapshot::restore(&path, to.as_deref(), &selection, &db)?;
+            println!("{}", style(t!("restore-done")).green().bold());
+            // Read-only restores are not recorded
+            if !config::get().read_only() && plan.iter().any(|entry| entry.action != RestoreAction::Unchanged) {
+                println!("{}", style(t!("restore-undo-hint")).dim());
+            }
+            Ok(())
+        }
+
+        Commands::ExportArchive { path, output } => {
+            print_header(&t!("header-export-archive"));
+            let path = path.as_deref().map(utils::resolve_path).transpose()?;
+            let output = utils::resolve_path(&output)?;
+            let count = crate::archive::export(path.as_deref(), &output, &db)?;
+            println!(
+                "{} {}",
+                style(t!("archive-exported", count = count)).green(),
+                style(output.display()).cyan()
+            );
+            Ok(())
+        }
+
+        Commands::ImportArchive { file, as_path } => {
+            print_header(&t!("header-import-archive"));
+            let as_path = as_path.as_deref().map(utils::resolve_path).transpose()?;
+            let summary = crate::archive::import(Path::new(&file), as_path.as_deref(), &db)?;
+            println!(
+                "{} {}",
+                style(t!("archive-imported", count = summary.snapshots)).green(),
+                style(t!(
+                    "archive-imported-detail",
+                    blobs = summary.blobs,
+                    skipped = summary.skipped
+                ))
+                .dim()
+            );
+            Ok(())
+        }
+
+        Commands::Undo { path: None } => {
+            print_header(&t!("header-undo"));
+            let restore = Snapshot::undo_restore(&db)?;
+            println!(
+                "{} {} ({})",
+                style(t!("undo-restore-done", count = restore.files.len())).green().bold(),
+                style(restore.root.display()).cyan(),
+                style(utils::format_date(&restore.date)).yellow()
+            );
+            Ok(())
+        }
+
+        Commands::Undo { path: Some(path) } => {
+            print_header(&t!("header-undo"));
+            let path = utils::resolve_path(&path)?;
+            let restored = Snapshot::undo(&path, &db)?;
+            println!(
+                "{} {} {} {} ({})",
+                style(t!("undo-done")).green().bold(),
+                style(path.display()).cyan(),
+                style(t!("undo-version-of")).green(),
+                style(utils::format_date(&restored.date)).yellow(),
+                style(&restored.checksum[..8]).dim()
+            );
+            Ok(())
+        }
+
+        Commands::Materialize { path } => {
+            print_header(&t!("header-materialize"));
+            let root = path.as_deref().map(utils::resolve_path).transpose()?;
+            let count = Snapshot::materialize(root.as_deref(), &db)?;
+            let (removed, reclaimed) = db.cleanup_orphaned_files()?;
+            println!("{}", style(t!("materialize-done", count = count)).green());
+            println!(
+                "{} {}",
+                style(t!("gc-orphans", count = removed)).green(),
+                style(format!("({})", format_size(reclaimed as i64))).dim()
+            );
+            Ok(())
+        }
+
+        Commands::Compact { path, level } => {
+            print_header(&t!("header-compact"));
+            let root = path.as_deref().map(utils::resolve_path).transpose()?;
+            let level = level.unwrap_or_else(|| crate::config::get().compression_level());
+            let summary = Snapshot::compact(root.as_deref(), level, &db)?;
+            println!(
+                "{}",
+                style(t!(
+                    "compact-done",
+                    recompressed = summary.recompressed,
+                    migrated = summary.migrated,
+                    level = level
+                ))
+                .green()
+            );
+            println!(
+                "{} {}",
+                t!("compact-saved"),
+                style(format!(
+                    "{} → {} ({})",
+                    format_size(summary.before),
+                    format_size(summary.after),
+                    format_size(summary.before - summary.after)
+                ))
+                .dim()
+            );
+            if !summary.corrupt.is_empty() {
+                println!("\n{}", style(t!("compact-corrupt", count = summary.corrupt.len())).red());
+                for path in &summary.corrupt {
+                    println!("  {}", path.display());
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Gc { force } => {
+            print_header(&t!("header-gc"));
+            collect_garbage(force, &db)
+        }
+
+        Commands::Db {
+            action: DbCommands::Backup { output, storage },
+        } => {
+            print_header(&t!("header-db-backup"));
+            let summary = crate::backup::backup(Path::new(&output), storage.as_deref().map(Path::new), &db)?;
+            println!(
+                "{} {}",
+                style(t!("db-backup-done", count = summary.snapshots)).green(),
+                style(&output).cyan()
+            );
+            if let Some(storage) = storage {
+                println!(
+                    "{} {}",
+                    style(t!("db-backup-storage", count = summary.files)).green(),
+                    style(storage).cyan()
+                );
+            }
+            Ok(())
+        }
+
+        Commands::Db {
+            action: DbCommands::Restore { backup, storage, force },
+        } => {
+            print_header(&t!("header-db-restore"));
+            let count = db.list_all_snapshots()?.len();
+            if !force && count > 0 && !utils::confirm(&t!("db-restore-confirm", count = count))? {
+                println!("{}", style(t!("confirm-cancelled")).yellow());
+                return Ok(());
+            }
+            let mut db = db;
+            let summary = crate::backup::restore(Path::new(&backup), storage.as_deref().map(Path::new), &mut db)?;
+            println!(
+                "{} {}",
+                style(t!("db-restore-done", count = summary.snapshots)).green(),
+                style(&backup).cyan()
+            );
+            if storage.is_some() {
+                println!("{}", style(t!("db-restore-storage", count = summary.files)).green());
+            }
+            Ok(())
+        }
+
+        Commands::Db {
+            action: DbCommands::Vacuum,
+        } => {
+            print_header(&t!("header-db-vacuum"));
+            vacuum_database(&db)
         }
 
-        Commands::Save { path } => {
-            print_header("🧊 Freezing Bytes...");
-            let path = PathBuf::from(path).canonicalize()?;
-            utils::validate_path(&path)?;
+        Commands::Db {
+            action: DbCommands::Index { drop },
+        } => {
+            print_header(&t!("header-db-index"));
+            if drop {
+                let count = db.drop_content_index()?;
+                println!("{}", style(t!("db-index-dropped", count = count)).green());
+            } else {
+                let count = crate::fulltext::index_all(&db)?;
+                println!("{}", style(t!("db-indexed", count = count)).green());
+            }
+            Ok(())
+        }
 
+        Commands::Db {
+            action: DbCommands::Import { source },
+        } => {
+            print_header(&t!("header-db-import"));
+            let summary = crate::merge::merge(Path::new(&source), &db)?;
             println!(
                 "{} {}",
-                style("Freezing:").cyan().bold(),
-                style(path.display()).green()
+                style(t!("db-imported", count = summary.snapshots)).green(),
+                style(t!(
+                    "db-imported-detail",
+                    files = summary.files,
+                    skipped = summary.skipped,
+                    sessions = summary.sessions
+                ))
+                .dim()
             );
-
-            let pb = utils::create_progress_bar(1);
-            pb.set_message("Creating snapshot...");
-
-            Snapshot::save_recursive(&path, &db)?;
-
-            pb.finish_with_message("Snapshot created successfully!");
             Ok(())
         }
 
-        Commands::Restore { path } => {
-            print_header("♻️  Restoring From Snapshot");
-            let path_buf = PathBuf::from(&path);
-            let path = if path_buf.is_absolute() {
-                path_buf
-            } else {
-                // Si le chemin est relatif, tenter de le canonicaliser
-                // Si cela échoue (fichier/répertoire supprimé), construire le chemin absolu
-                match path_buf.canonicalize() {
-                    Ok(canonical_path) => canonical_path,
-                    Err(_) => {
-                        // Construire le chemin absolu manuellement
-                        env::current_dir()?.join(&path)
-                    }
-                }
+        Commands::Db {
+            action: DbCommands::Export { format, output, include },
+        } => {
+            let catalog = crate::catalog::build(&db, &include)?;
+            let rendered = match format {
+                CatalogFormat::Json => format!("{}\n", serde_json::to_string_pretty(&catalog)?),
+                CatalogFormat::Csv => crate::catalog::render_csv(&catalog)?,
             };
 
-            println!(
-                "{} {}",
-                style("Restoring:").cyan().bold(),
-                style(path.display()).green()
-            );
-
-            Snapshot::restore(&path, &db)?;
-            println!(
-                "{}",
-                style("Restore completed successfully!").green().bold()
-            );
+            match output {
+                Some(output) => {
+                    fs::write(&output, rendered)?;
+                    println!(
+                        "{} {}",
+                        style(t!("db-export-written", count = catalog.snapshots.len())).green(),
+                        style(&output).cyan()
+                    );
+                }
+                None => print!("{}", rendered),
+            }
             Ok(())
         }
 
-        Commands::Ls { page } => {
-            print_header("📋 All Snapshots");
+        Commands::Ls { page, filter, .. } if json => {
+            let snapshots = db.list_snapshots(&filter.to_filter(None)?)?;
+            let entries = utils::snapshot_entries(&snapshots, &db.list_messages()?, &db.list_pinned()?);
+            utils::print_json(utils::page_of(&entries, page)?)
+        }
 
-            let snapshots = db.list_all_snapshots()?;
+        Commands::Cls { page, filter, .. } if json => {
+            let snapshots = db.list_snapshots(&filter.to_filter(Some(env::current_dir()?))?)?;
+            let entries = utils::snapshot_entries(&snapshots, &db.list_messages()?, &db.list_pinned()?);
+            utils::print_json(utils::page_of(&entries, page)?)
+        }
+
+        Commands::Ls { page, long, filter } => {
+            let filter = filter.to_filter(None)?;
+            print_header(&t!("header-ls"));
+
+            let snapshots = db.list_snapshots(&filter)?;
             if snapshots.is_empty() {
-                println!("{}", style("No snapshots found.").yellow());
+                println!("{}", style(t!("ls-empty")).yellow());
                 return Ok(());
             }
 
-            utils::print_snapshot_info_paginated(&snapshots, page);
+            let stored = if long { Some(db.list_stored_sizes()?) } else { None };
+            utils::print_snapshot_info_paginated(
+                &snapshots,
+                &db.list_messages()?,
+                &db.list_pinned()?,
+                stored.as_ref(),
+                page,
+            );
             Ok(())
         }
 
-        Commands::Cls { page } => {
+        Commands::Cls { page, long, filter } => {
             let current_dir = env::current_dir()?;
-            let snapshots = db.list_current_directory_snapshots(&current_dir)?;
+            let snapshots = db.list_snapshots(&filter.to_filter(Some(current_dir.clone()))?)?;
 
             if snapshots.is_empty() {
                 println!(
                     "{}",
-                    style(format!("No snapshots found in {}.", current_dir.display())).yellow()
+                    style(t!("cls-empty", dir = current_dir.display().to_string())).yellow()
                 );
                 return Ok(());
             }
 
             println!(
                 "{} {}",
-                style("Snapshots in current directory:").cyan().bold(),
+                style(t!("cls-title")).cyan().bold(),
                 style(current_dir.display()).green()
             );
 
-            utils::print_snapshot_info_paginated(&snapshots, page);
+            let stored = if long { Some(db.list_stored_sizes()?) } else { None };
+            utils::print_snapshot_info_paginated(
+                &snapshots,
+                &db.list_messages()?,
+                &db.list_pinned()?,
+                stored.as_ref(),
+                page,
+            );
             Ok(())
         }
 
-        Commands::Clear { all, path } => {
+        Commands::Clear {
+            all,
+            path,
+            older_than,
+            keep_last,
+            dry_run,
+            force,
+        } => {
+            let retention = match (older_than, keep_last) {
+                (Some(age), _) => Some(Retention::OlderThan(utils::parse_age(&age)?)),
+                (None, Some(count)) => Some(Retention::KeepLast(count as usize)),
+                (None, None) => None,
+            };
+            if let Some(retention) = retention {
+                let root = if all {
+                    None
+                } else {
+                    Some(utils::resolve_path(path.as_deref().unwrap_or("."))?)
+                };
+                return clear_expired(root.as_deref(), retention, dry_run, force, &db);
+            }
             if all {
-                println!("{}", style("Clearing all snapshots...").yellow());
+                let doomed = db.clearable_snapshots(None)?;
+                if dry_run {
+                    return print_dry_run(&doomed, &db);
+                }
+                let size = format_size(doomed.iter().map(|(_, _, _, size, _)| size).sum());
+                if !force && !utils::confirm(&t!("clear-confirm-all", count = doomed.len(), size = size))? {
+                    println!("{}", style(t!("confirm-cancelled")).yellow());
+                    return Ok(());
+                }
+                println!("{}", style(t!("clear-all-start")).yellow());
                 db.clear_all_snapshots()?;
-                println!("{}", style("All snapshots cleared!").green());
+                println!("{}", style(t!("clear-all-done")).green());
+                print_pinned_kept(None, &db)?;
             } else {
                 let path = path.unwrap_or_else(|| String::from("./"));
 
@@ -410,30 +2629,402 @@ pub async fn run() -> Result<()> {
                     env::current_dir()?.join(&path).canonicalize()?
                 };
 
+                let doomed = db.clearable_snapshots(Some(&path))?;
+                if dry_run {
+                    return print_dry_run(&doomed, &db);
+                }
+                let size = format_size(doomed.iter().map(|(_, _, _, size, _)| size).sum());
+                if !force
+                    && !utils::confirm(&t!(
+                        "clear-confirm",
+                        path = path.display().to_string(),
+                        count = doomed.len(),
+                        size = size
+                    ))?
+                {
+                    println!("{}", style(t!("confirm-cancelled")).yellow());
+                    return Ok(());
+                }
+
                 if path.to_string_lossy() == env::current_dir()?.to_string_lossy() {
-                    println!(
-                        "{}",
-                        style("Clearing snapshots in current directory...").yellow()
-                    );
+                    println!("{}", style(t!("clear-current-dir")).yellow());
                     db.clear_directory_snapshots(&env::current_dir()?)?;
+                    print_pinned_kept(Some(&env::current_dir()?), &db)?;
                 } else {
                     println!(
                         "{} {}",
-                        style("Clearing snapshots for:").yellow(),
+                        style(t!("clear-path")).yellow(),
+                        style(path.display()).green()
+                    );
+                    if path.is_dir() {
+                        db.clear_directory_snapshots(&path)?;
+                    } else {
+                        db.clear_snapshots(&path)?;
+                    }
+                    print_pinned_kept(Some(&path), &db)?;
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Prune {
+            path,
+            schedule,
+            dry_run,
+        } => {
+            let root = path.as_deref().map(utils::resolve_path).transpose()?;
+            prune(root.as_deref(), schedule.as_deref(), dry_run, &db)
+        }
+
+        Commands::Rm {
+            path,
+            checksum,
+            id,
+            dry_run,
+            force,
+        } => {
+            let root = path.as_deref().map(utils::resolve_path).transpose()?;
+            let removed = db.removed_snapshots(root.as_deref(), &checksum, &id)?;
+            delete_expired(&removed, dry_run, force, &db)
+        }
+
+        Commands::Pin { checksum } => set_pinned(&checksum, true, &db),
+
+        Commands::Unpin { checksum } => set_pinned(&checksum, false, &db),
+
+        Commands::Cp {
+            source,
+            destination,
+        } => {
+            print_header(&t!("header-cp"));
+            let source = utils::resolve_path(&source)?;
+            let destination = utils::resolve_path(&destination)?;
+            if source == destination {
+                anyhow::bail!(t!("cp-same-path"));
+            }
+
+            let copied = db.copy_history(&source, &destination)?;
+            if copied == 0 {
+                println!(
+                    "{} {}",
+                    style(t!("cp-nothing")).yellow(),
+                    style(source.display()).cyan()
+                );
+            } else {
+                println!(
+                    "{} {} {} {}",
+                    style(t!("cp-done", count = copied)).green(),
+                    style(source.display()).cyan(),
+                    style(t!("export-to")).green(),
+                    style(destination.display()).cyan()
+                );
+            }
+            Ok(())
+        }
+
+        Commands::Alias { action } => {
+            match action {
+                AliasCommands::Add { name, path } => {
+                    if !utils::is_valid_alias_name(&name) {
+                        anyhow::bail!(t!("alias-invalid-name", name = name.as_str()));
+                    }
+                    let path = utils::resolve_path(&path)?;
+                    db.add_alias(&name, &path)?;
+                    println!(
+                        "{} {} {} {}",
+                        style(t!("alias-added")).green(),
+                        style(format!("@{}", name)).yellow(),
+                        style(utils::symbol("→", "->")).cyan(),
                         style(path.display()).green()
                     );
-                    db.clear_snapshots(path)?;
                 }
+                AliasCommands::Remove { name } => {
+                    if db.remove_alias(&name)? {
+                        println!(
+                            "{} {}",
+                            style(t!("alias-removed")).green(),
+                            style(format!("@{}", name)).yellow()
+                        );
+                    } else {
+                        println!(
+                            "{} {}",
+                            style(t!("alias-not-found")).yellow(),
+                            style(format!("@{}", name)).cyan()
+                        );
+                    }
+                }
+                AliasCommands::List => {
+                    let aliases = db.list_aliases()?;
+                    if aliases.is_empty() {
+                        println!("{}", style(t!("alias-empty")).yellow());
+                        return Ok(());
+                    }
+
+                    println!("{}", style(t!("alias-title")).cyan().bold());
+                    for (name, path) in aliases {
+                        println!(
+                            "{} {} {} {}",
+                            style(utils::symbol("→", "->")).cyan(),
+                            style(format!("@{}", name)).yellow(),
+                            style("=").dim(),
+                            style(path.display()).green()
+                        );
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Session { action } => {
+            match action {
+                SessionCommands::Ls { path } => {
+                    let root = path.as_deref().map(utils::resolve_path).transpose()?;
+                    let sessions = db.list_sessions(root.as_deref())?;
+                    if sessions.is_empty() {
+                        println!("{}", style(t!("session-empty")).yellow());
+                        return Ok(());
+                    }
+
+                    println!("{}", style(t!("session-title")).cyan().bold());
+                    for session in sessions {
+                        println!(
+                            "{} {} {} {} {}{}",
+                            style(format!("#{}", session.id)).yellow(),
+                            style(utils::format_date(&session.date)).dim(),
+                            style(session.root.display()).green(),
+                            style(utils::symbol("→", "->")).cyan(),
+                            t!("session-files", count = session.files),
+                            session
+                                .message
+                                .map(|message| format!(" {}", style(message).italic()))
+                                .unwrap_or_default()
+                        );
+                    }
+                }
+                SessionCommands::Show { id } => {
+                    let session = crate::session::get(id, &db)?;
+                    println!(
+                        "{} {} {} ({})",
+                        style(t!("session-show-title", id = id)).cyan().bold(),
+                        style(session.root.display()).green(),
+                        style(utils::format_date(&session.date)).dim(),
+                        t!("session-files", count = session.files)
+                    );
+                    if let Some(message) = &session.message {
+                        println!("{}", style(message).italic());
+                    }
+                    for (path, checksum) in db.get_session_files(id)? {
+                        println!(
+                            "  {} {}",
+                            style(&checksum[..8]).dim(),
+                            path.strip_prefix(&session.root).unwrap_or(&path).display()
+                        );
+                    }
+                }
+                SessionCommands::Restore { id, to, .. } => {
+                    print_header(&t!("header-restore"));
+                    let session = crate::session::get(id, &db)?;
+                    let to = to.as_deref().map(utils::resolve_path).transpose()?;
+                    if to.is_none() {
+                        let recorded = db.get_session_files(id)?;
+                        let current = crate::session::current_files(&session.root)?;
+                        let overwrites = crate::session::diff(&recorded, &current)
+                            .iter()
+                            .filter(|(_, change)| *change == crate::session::Change::Modified)
+                            .count();
+                        if overwrites > 0 && config::get().read_only() {
+                            anyhow::bail!(t!("read-only-overwrite", count = overwrites));
+                        }
+                        if overwrites > 0
+                            && !utils::confirm(&t!("restore-confirm-overwrite", count = overwrites))?
+                        {
+                            println!("{}", style(t!("confirm-cancelled")).yellow());
+                            return Ok(());
+                        }
+                    }
+                    match &to {
+                        Some(to) => println!(
+                            "{} {} {} {}",
+                            style(t!("restore-restoring")).cyan().bold(),
+                            style(session.root.display()).green(),
+                            style(t!("export-to")).cyan().bold(),
+                            style(to.display()).green()
+                        ),
+                        None => println!(
+                            "{} {}",
+                            style(t!("restore-restoring")).cyan().bold(),
+                            style(session.root.display()).green()
+                        ),
+                    }
+                    let restored = crate::session::restore(id, to.as_deref(), &db)?;
+                    println!(
+                        "{} {}",
+                        style(t!("restore-done")).green().bold(),
+                        style(t!("session-files", count = restored)).dim()
+                    );
+                }
+                SessionCommands::Diff { first, second } => {
+                    print_header(&t!("header-diff"));
+                    let old = crate::session::get(first, &db)?;
+                    let (label, new_files) = match second {
+                        Some(second) => {
+                            crate::session::get(second, &db)?;
+                            (format!("#{}", second), db.get_session_files(second)?)
+                        }
+                        None => (
+                            t!("session-current"),
+                            crate::session::current_files(&old.root)?,
+                        ),
+                    };
+
+                    println!(
+                        "{} {} {}",
+                        style(format!("#{}", first)).yellow(),
+                        style(utils::symbol("→", "->")).cyan(),
+                        style(label).yellow()
+                    );
+                    let changes = crate::session::diff(&db.get_session_files(first)?, &new_files);
+                    if changes.is_empty() {
+                        println!("{}", style(t!("session-identical")).green());
+                        return Ok(());
+                    }
+                    for (path, change) in &changes {
+                        let path = path.strip_prefix(&old.root).unwrap_or(path).display();
+                        match change {
+                            crate::session::Change::Added => println!("{}", style(format!("+ {}", path)).green()),
+                            crate::session::Change::Removed => println!("{}", style(format!("- {}", path)).red()),
+                            crate::session::Change::Modified => println!("{}", style(format!("~ {}", path)).yellow()),
+                            crate::session::Change::Renamed(from) => println!(
+                                "{}",
+                                style(format!(
+                                    "R {} {} {}",
+                                    from.strip_prefix(&old.root).unwrap_or(from).display(),
+                                    utils::symbol("→", "->"),
+                                    path
+                                ))
+                                .cyan()
+                            ),
+                        }
+                    }
+                    println!("\n{}", style(t!("session-changes", count = changes.len())).dim());
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Meta { action } => {
+            match action {
+                MetaCommands::Set {
+                    path,
+                    key,
+                    value,
+                    checksum,
+                } => {
+                    let (id, sum) = select_snapshot_id(&path, checksum.as_deref(), &db)?;
+                    db.set_metadata(id, &key, &value)?;
+                    println!(
+                        "{} {} = {} {} {}",
+                        style(t!("meta-set")).green(),
+                        style(&key).yellow(),
+                        style(&value).green(),
+                        style(t!("meta-on")).green(),
+                        style(format!("{} ({})", path, &sum[..8])).cyan()
+                    );
+                }
+                MetaCommands::Get {
+                    path,
+                    key,
+                    checksum,
+                } => {
+                    let (id, _) = select_snapshot_id(&path, checksum.as_deref(), &db)?;
+                    match db.get_metadata(id, &key)? {
+                        // Printed bare so that scripts can capture it
+                        Some(value) => println!("{}", value),
+                        None => anyhow::bail!(t!("meta-not-found", key = key.as_str())),
+                    }
+                }
+                MetaCommands::List { path, checksum } => {
+                    let (id, sum) = select_snapshot_id(&path, checksum.as_deref(), &db)?;
+                    let metadata = db.list_metadata(id)?;
+                    if metadata.is_empty() {
+                        println!(
+                            "{} {}",
+                            style(t!("meta-empty")).yellow(),
+                            style(format!("{} ({})", path, &sum[..8])).cyan()
+                        );
+                        return Ok(());
+                    }
+
+                    println!(
+                        "{} {}",
+                        style(t!("meta-title")).cyan().bold(),
+                        style(format!("{} ({})", path, &sum[..8])).green()
+                    );
+                    for (key, value) in metadata {
+                        println!(
+                            "{} {} {} {}",
+                            style(utils::symbol("→", "->")).cyan(),
+                            style(key).yellow(),
+                            style("=").dim(),
+                            style(value).green()
+                        );
+                    }
+                }
+                MetaCommands::Rm {
+                    path,
+                    key,
+                    checksum,
+                } => {
+                    let (id, _) = select_snapshot_id(&path, checksum.as_deref(), &db)?;
+                    if db.remove_metadata(id, &key)? {
+                        println!(
+                            "{} {}",
+                            style(t!("meta-removed")).green(),
+                            style(&key).yellow()
+                        );
+                    } else {
+                        println!("{}", style(t!("meta-not-found", key = key.as_str())).yellow());
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Search {
+            pattern,
+            filter,
+            content: true,
+            limit,
+        } => {
+            let matches = crate::fulltext::search(&pattern, filter.file_type.as_deref(), limit, &db)?;
+            if json {
+                return utils::print_json(&matches);
+            }
+            if matches.is_empty() {
+                println!("{} {}", style(t!("search-empty")).yellow(), style(&pattern).cyan());
+                if !config::get().content_index() {
+                    println!("{}", style(t!("search-content-hint")).dim());
+                }
+                return Ok(());
             }
+            println!("{} {}", style(t!("search-title")).cyan().bold(), style(&pattern).green());
+            print!("{}", crate::fulltext::render_text(&matches));
             Ok(())
         }
 
-        Commands::Search { pattern } => {
-            let snapshots = db.search_snapshots(&pattern)?;
+        Commands::Search { pattern, filter, .. } => {
+            let snapshots = db.search_snapshots(&pattern, &filter.to_filter()?)?;
+            if json {
+                return utils::print_json(&utils::snapshot_entries(
+                    &snapshots,
+                    &db.list_messages()?,
+                    &db.list_pinned()?,
+                ));
+            }
             if snapshots.is_empty() {
                 println!(
                     "{} {}",
-                    style("No snapshots found matching:").yellow(),
+                    style(t!("search-empty")).yellow(),
                     style(&pattern).cyan()
                 );
                 return Ok(());
@@ -441,11 +3032,11 @@ pub async fn run() -> Result<()> {
 
             println!(
                 "{} {}",
-                style("Snapshots matching:").cyan().bold(),
+                style(t!("search-title")).cyan().bold(),
                 style(&pattern).green()
             );
 
-            utils::print_snapshot_info(&snapshots);
+            utils::print_snapshot_info(&snapshots, &db.list_messages()?, &db.list_pinned()?);
             Ok(())
         }
 
@@ -458,7 +3049,7 @@ pub async fn run() -> Result<()> {
                     db.add_exclusion(&pattern, exclusion_type.as_str())?;
                     println!(
                         "{} {} ({})",
-                        style("Added exclusion:").green(),
+                        style(t!("exclusion-added")).green(),
                         style(&pattern).yellow(),
                         style(exclusion_type.as_str()).cyan()
                     );
@@ -467,22 +3058,22 @@ pub async fn run() -> Result<()> {
                     db.remove_exclusion(&pattern)?;
                     println!(
                         "{} {}",
-                        style("Removed exclusion:").green(),
+                        style(t!("exclusion-removed")).green(),
                         style(&pattern).yellow()
                     );
                 }
                 ExclusionCommands::List => {
                     let exclusions = db.list_exclusions()?;
                     if exclusions.is_empty() {
-                        println!("{}", style("No exclusions configured.").yellow());
+                        println!("{}", style(t!("exclusion-empty")).yellow());
                         return Ok(());
                     }
 
-                    println!("{}", style("Current exclusions:").cyan().bold());
+                    println!("{}", style(t!("exclusion-title")).cyan().bold());
                     for (pattern, exc_type) in exclusions {
                         println!(
                             "{} {} ({})",
-                            style("→").cyan(),
+                            style(utils::symbol("→", "->")).cyan(),
                             style(pattern).yellow(),
                             style(exc_type).green()
                         );
@@ -492,31 +3083,276 @@ pub async fn run() -> Result<()> {
             Ok(())
         }
 
-        Commands::Mcp => {
-            print_header("🧊 Starting MCP Server");
-            println!("MCP server listening on stdin/stdout...");
-            println!("Press Ctrl+C to stop.");
-            crate::mcp::run_server().await?;
+        Commands::Mcp { http, allow_origin } => {
+            crate::crypto::unlock()?;
+            print_header(&t!("header-mcp"));
+            match http {
+                Some(addr) => {
+                    println!("{}", t!("mcp-listening-http", addr = addr.to_string()));
+                    println!("{}", t!("mcp-stop-hint"));
+                    crate::mcp::run_http_server(addr, allow_origin).await?;
+                }
+                None => {
+                    println!("{}", t!("mcp-listening"));
+                    println!("{}", t!("mcp-stop-hint"));
+                    crate::mcp::run_server().await?;
+                }
+            }
             Ok(())
         }
 
         Commands::Diff { first, second } => {
-            print_header("📊 Comparing Snapshots");
+            print_header(&t!("header-diff"));
             utils::compare(&first, &second, &db)?;
             Ok(())
         }
 
+        Commands::Inspect { path } if json => {
+            let path = PathBuf::from(path).canonicalize()?;
+            let mut snapshots = db.get_snapshots_for_path_with_id(&path)?;
+            snapshots.sort_by(|a, b| a.2.cmp(&b.2));
+            utils::print_json(&utils::snapshot_entries(&snapshots, &db.list_messages()?, &db.list_pinned()?))
+        }
+
         Commands::Inspect { path } => {
-            print_header("🕵️  Inspecting Evolution");
+            print_header(&t!("header-inspect"));
             let path = PathBuf::from(path).canonicalize()?;
             utils::inspect_file(&path, &db)?;
             Ok(())
         }
 
-        Commands::Web { port, open } => {
+        Commands::Blame { path } => {
+            print_header(&t!("header-blame"));
+            let path = utils::resolve_path(&path)?;
+            let (snapshots, lines) = crate::blame::blame(&path, &db)?;
+            print!("{}", crate::blame::render_text(&snapshots, &lines));
+            Ok(())
+        }
+
+        Commands::Grep {
+            pattern,
+            path,
+            ignore_case,
+            latest,
+            filter,
+        } => {
+            let mut snapshots = db.list_snapshots(&filter.to_filter(None)?)?;
+            if let Some(path) = path {
+                let path = utils::resolve_path(&path)?;
+                snapshots.retain(|(_, snapshot_path, ..)| snapshot_path.starts_with(&path));
+            }
+            let matches = crate::grep::grep(
+                &snapshots,
+                &crate::grep::Pattern::new(&pattern, ignore_case),
+                latest,
+                &db,
+            )?;
+            if json {
+                return utils::print_json(&matches);
+            }
+
+            print_header(&t!("header-grep"));
+            if matches.is_empty() {
+                println!("{} {}", style(t!("grep-empty")).yellow(), style(&pattern).cyan());
+                return Ok(());
+            }
+            print!("{}", crate::grep::render_text(&matches));
+            let matched = matches
+                .iter()
+                .map(|m| (&m.path, &m.checksum))
+                .collect::<HashSet<_>>()
+                .len();
+            println!(
+                "\n{}",
+                style(t!("grep-summary", count = matches.len(), snapshots = matched)).dim()
+            );
+            Ok(())
+        }
+
+        Commands::Web {
+            action: Some(WebCommands::Keys { action }),
+            ..
+        } => {
+            match action {
+                KeyCommands::Create { name, scope } => {
+                    let token = crate::web::auth::generate_token()?;
+                    let id = db.add_api_key(
+                        &name,
+                        &crate::web::auth::hash_token(&token),
+                        scope.as_str(),
+                    )?;
+                    println!(
+                        "{} {} {}",
+                        style(t!("key-created")).green(),
+                        style(&name).yellow(),
+                        t!("key-created-detail", scope = scope.as_str(), id = id)
+                    );
+                    println!("{}", style(&token).bold());
+                    println!("{}", style(t!("key-store-warning")).dim());
+                }
+                KeyCommands::List => {
+                    let keys = db.list_api_keys()?;
+                    if keys.is_empty() {
+                        println!("{}", style(t!("key-empty")).yellow());
+                        return Ok(());
+                    }
+
+                    println!("{}", style(t!("key-title")).cyan().bold());
+                    for (id, name, scope, created, last_used) in keys {
+                        println!(
+                            "{} {} {} ({}) {}",
+                            style(utils::symbol("→", "->")).cyan(),
+                            style(id).dim(),
+                            style(name).yellow(),
+                            style(scope).green(),
+                            t!(
+                                "key-entry-detail",
+                                created = utils::format_date(&created),
+                                last_used = last_used
+                                    .map(|d| utils::format_date(&d))
+                                    .unwrap_or_else(|| t!("key-never-used"))
+                            )
+                        );
+                    }
+                }
+                KeyCommands::Revoke { id } => {
+                    if db.remove_api_key(id)? {
+                        println!("{} {}", style(t!("key-revoked")).green(), style(id).yellow());
+                    } else {
+                        println!("{} {}", style(t!("key-not-found")).yellow(), style(id).cyan());
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Web { port, open, .. } => {
             let port = port.unwrap_or(3000);
+            crate::crypto::unlock()?;
             crate::web::run_server(port, open).await?;
             Ok(())
         }
+
+        Commands::Stats {
+            top,
+            by_dir: Some(root),
+            format,
+            ..
+        } => {
+            let root = utils::resolve_path(&root)?;
+            let directories = db.directory_totals(&root, top)?;
+            match format {
+                StatsFormat::Table if !json => {
+                    print_header(&t!("header-stats"));
+                    print!("{}", crate::stats::render_directories_text(&root, &directories));
+                }
+                _ => utils::print_json(&directories)?,
+            }
+            Ok(())
+        }
+
+        Commands::Stats { top, dedup: true, format, .. } => {
+            let dedup = crate::stats::build_dedup(&db, top)?;
+            match format {
+                StatsFormat::Table if !json => {
+                    print_header(&t!("header-stats"));
+                    print!("{}", crate::stats::render_dedup_text(&dedup));
+                }
+                _ => utils::print_json(&dedup)?,
+            }
+            Ok(())
+        }
+
+        Commands::Stats { top, dedup: false, format, .. } => {
+            let stats = crate::stats::build(&db, top)?;
+            match format {
+                StatsFormat::Table if !json => {
+                    print_header(&t!("header-stats"));
+                    print!("{}", crate::stats::render_text(&stats));
+                }
+                _ => utils::print_json(&stats)?,
+            }
+            Ok(())
+        }
+
+        Commands::Report {
+            period,
+            format,
+            output,
+        } => {
+            let report = crate::report::build(&db, period)?;
+            let rendered = match format {
+                ReportFormat::Text => crate::report::render_text(&report),
+                ReportFormat::Json => format!("{}\n", serde_json::to_string_pretty(&report)?),
+                ReportFormat::Html => crate::report::render_html(&report),
+            };
+
+            match output {
+                Some(output) => {
+                    fs::write(&output, rendered)?;
+                    println!(
+                        "{} {}",
+                        style(t!("report-written")).green(),
+                        style(&output).cyan()
+                    );
+                }
+                None => print!("{}", rendered),
+            }
+            Ok(())
+        }
+
+        Commands::Bench {
+            files,
+            size,
+            levels,
+            mut threads,
+        } => {
+            print_header(&t!("header-bench"));
+            if threads.is_empty() {
+                let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+                threads.push(1);
+                if cores > 1 {
+                    threads.push(cores);
+                }
+            }
+            crate::bench::run(&crate::bench::BenchOptions {
+                files,
+                file_size: size * 1024,
+                levels,
+                threads,
+            })
+        }
+
+        Commands::SelfUpdate { check } => {
+            print_header(&t!("header-self-update"));
+            crate::selfupdate::run(check)
+        }
+
+        Commands::Complete { command, current } => {
+            if let Some(prefix) = current.strip_prefix('@') {
+                for (name, _) in db.list_aliases()? {
+                    if name.starts_with(prefix) {
+                        println!("@{}", name);
+                    }
+                }
+            }
+            let snapshots = db.list_all_snapshots()?;
+            for candidate in
+                utils::completion_candidates(&command, &current, &env::current_dir()?, &snapshots)
+            {
+                println!("{}", candidate);
+            }
+            Ok(())
+        }
+
+        Commands::Daemon { .. } => {
+            print_header(&t!("header-daemon"));
+            crate::crypto::unlock()?;
+            crate::daemon::serve(db)
+        }
+
+        Commands::External(_) | Commands::Config { .. } | Commands::Profile { .. } | Commands::Key { .. } => {
+            unreachable!("external, config, profile and key commands are dispatched before opening the database")
+        }
     }
 }
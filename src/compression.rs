@@ -0,0 +1,222 @@
+/*!
+Compression of stored contents for the freeze application.
+
+Each stored content is compressed with one algorithm, recorded by the
+extension of its file in storage: `.zstd`, `.lz4` or `.raw` (stored as is).
+Files without one of these extensions are legacy uncompressed copies. The
+algorithm of new contents comes from `--compression` on save, else from the
+`compression_rules` setting for the file's extension, else from the
+`compression` setting, else zstd.
+*/
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// A compression algorithm for stored contents.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// zstd at the configured level: small and fast to read
+    #[default]
+    Zstd,
+    /// lz4: larger, but much cheaper to write
+    Lz4,
+    /// No compression, for contents that are already compressed
+    Store,
+}
+
+/// Every algorithm, in the order existing contents are looked up.
+const ALL: [Compression; 3] = [Compression::Zstd, Compression::Lz4, Compression::Store];
+
+impl Compression {
+    /// Extension of the stored files compressed with this algorithm.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Lz4 => "lz4",
+            Self::Store => "raw",
+        }
+    }
+
+    /// Name of the algorithm, as written in the configuration.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Lz4 => "lz4",
+            Self::Store => "store",
+        }
+    }
+
+    /// Returns the algorithm of a stored file, `None` for legacy uncompressed files.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        ALL.into_iter().find(|compression| compression.extension() == extension)
+    }
+
+    /// Compresses everything read from `source` into `dest`.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The content to compress
+    /// * `dest` - Where to write the compressed content
+    /// * `level` - zstd level, ignored by the other algorithms
+    ///
+    /// # Returns
+    ///
+    /// `dest`, once the compressed stream is complete
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading, compression or writing fails.
+    pub fn compress<R: Read, W: Write>(self, source: &mut R, mut dest: W, level: i32) -> Result<W> {
+        match self {
+            Self::Zstd => {
+                zstd::stream::copy_encode(source, &mut dest, level)?;
+                Ok(dest)
+            }
+            Self::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(dest);
+                std::io::copy(source, &mut encoder)?;
+                Ok(encoder.finish()?)
+            }
+            Self::Store => {
+                std::io::copy(source, &mut dest)?;
+                Ok(dest)
+            }
+        }
+    }
+
+    /// Wraps `source` to decompress what is read from it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the decoder cannot be created.
+    pub fn decoder<'a, R: Read + Send + 'a>(self, source: R) -> Result<Box<dyn Read + Send + 'a>> {
+        Ok(match self {
+            Self::Zstd => Box::new(zstd::stream::read::Decoder::new(source)?),
+            Self::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(source)),
+            Self::Store => Box::new(source),
+        })
+    }
+}
+
+/// Returns the storage path of a content compressed with `compression`.
+pub fn blob_path(storage_dir: &Path, checksum: &str, compression: Compression) -> PathBuf {
+    storage_dir.join(format!("{}.{}", checksum, compression.extension()))
+}
+
+/// Finds the stored file of a content, whatever its algorithm, or the
+/// manifest of a chunked content, or its delta.
+///
+/// Full copies come first, so that materialized deltas are read as such.
+pub fn find_blob(storage_dir: &Path, checksum: &str) -> Option<PathBuf> {
+    ALL.into_iter()
+        .map(|compression| blob_path(storage_dir, checksum, compression))
+        .chain([
+            crate::chunks::manifest_path(storage_dir, checksum),
+            crate::delta::delta_path(storage_dir, checksum),
+        ])
+        .find(|path| path.exists())
+}
+
+/// Returns the storage files holding a content: the content file itself,
+/// the chunks of a chunked content and the bases of a delta.
+///
+/// Files referred to by an unreadable manifest or delta are left out.
+pub fn stored_files(content_path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![content_path.to_path_buf()];
+    if crate::chunks::is_manifest(content_path) {
+        files.extend(crate::chunks::chunk_files(content_path).unwrap_or_default());
+    } else if crate::delta::is_delta(content_path) {
+        files.extend(crate::delta::base_files(content_path).unwrap_or_default());
+    }
+    files
+}
+
+/// Bytes a stored content uses on disk: its file, and the chunks of a
+/// chunked content.
+///
+/// The bases of a delta are not counted, as they belong to other contents.
+///
+/// # Returns
+///
+/// The size, `None` if the content is missing from storage
+pub fn blob_size(content_path: &Path) -> Option<i64> {
+    let mut files = vec![content_path.to_path_buf()];
+    if crate::chunks::is_manifest(content_path) {
+        files.extend(crate::chunks::chunk_files(content_path).ok()?);
+    }
+    files
+        .iter()
+        .map(|file| fs::metadata(file).map(|metadata| metadata.len() as i64).ok())
+        .sum()
+}
+
+/// Chooses the algorithm for new content of `path`.
+pub fn for_path(path: &Path) -> Compression {
+    let config = crate::config::get();
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(|extension| config.compression_rule(extension))
+        .or(config.compression)
+        .unwrap_or_default()
+}
+
+/// Opens a stored content and decompresses (and decrypts) it as it is read.
+///
+/// Chunked contents are joined from their chunks, and deltas rebuilt from
+/// their bases.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or decrypted.
+pub fn open(content_path: &Path) -> Result<Box<dyn Read + Send>> {
+    if crate::chunks::is_manifest(content_path) {
+        return crate::chunks::open(content_path);
+    }
+    if crate::delta::is_delta(content_path) {
+        return crate::delta::open(content_path);
+    }
+    match Compression::from_path(content_path) {
+        Some(compression) => compression.decoder(crate::crypto::open_content(content_path)?),
+        // Legacy contents were stored uncompressed
+        None => Ok(Box::new(fs::File::open(content_path)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let content = b"freeze freeze freeze freeze freeze".repeat(100);
+        for compression in ALL {
+            let compressed = compression.compress(&mut &content[..], Vec::new(), 3).unwrap();
+            let mut decompressed = Vec::new();
+            compression
+                .decoder(&compressed[..])
+                .unwrap()
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, content, "{:?}", compression);
+        }
+    }
+
+    #[test]
+    fn test_blob_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(find_blob(dir.path(), "abc"), None);
+
+        let path = blob_path(dir.path(), "abc", Compression::Lz4);
+        fs::write(&path, b"").unwrap();
+        assert_eq!(find_blob(dir.path(), "abc"), Some(path.clone()));
+        assert_eq!(Compression::from_path(&path), Some(Compression::Lz4));
+        assert_eq!(Compression::from_path(Path::new("/storage/legacy")), None);
+    }
+}
@@ -0,0 +1,529 @@
+/*!
+User configuration for the freeze application.
+
+Settings live in `config.toml` in the repository directory (`~/.freeze`
+unless `--repo`, `FREEZE_HOME` or the platform data directory says
+otherwise) and are loaded once at startup.
+Command-line flags and environment variables take precedence over the file,
+which takes precedence over the built-in defaults. The file is edited by
+hand or with `freeze config get/set/unset/list`.
+*/
+
+use crate::compression::Compression;
+use crate::hash::HashAlgorithm;
+use crate::db::MEMORY_DB;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// zstd level used when no level is configured.
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+/// Snapshots per page used when no page size is configured.
+pub const DEFAULT_PAGE_SIZE: usize = 10;
+/// Directory of the profiles created without a database location, in the
+/// repository directory.
+pub const PROFILES_DIR: &str = "profiles";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// When to use colored output.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Color when writing to a terminal
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+/// The settings of `config.toml`. Unset keys fall back to the defaults.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// zstd compression level of new snapshot contents (1-22)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_level: Option<i32>,
+    /// Compression algorithm of new snapshot contents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+    /// Store new versions of files as deltas against their previous version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<bool>,
+    /// Always re-hash files, even when their size and modification time
+    /// match their latest snapshot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paranoid: Option<bool>,
+    /// Give restored and exported files the modification time they were
+    /// saved with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restore_times: Option<bool>,
+    /// Save what symbolic links point to instead of the links themselves
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_symlinks: Option<bool>,
+    /// Keep directory walks on the file system they start on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub one_file_system: Option<bool>,
+    /// Skip what git ignores when walking directories of a repository
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_aware: Option<bool>,
+    /// Record extended attributes and ACLs of saved files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xattrs: Option<bool>,
+    /// Index the text of saved files for `search --content`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_index: Option<bool>,
+    /// Checksum algorithm of new snapshots
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<HashAlgorithm>,
+    /// Size above which directory saves skip files, e.g. `100MB`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<String>,
+    /// Snapshots per page of paginated listings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_size: Option<usize>,
+    /// Database file to use, as with `--db`; storage lives next to it.
+    /// Relative paths are relative to the repository directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<String>,
+    /// Store paths under the project holding the repository directory
+    /// relative to it, so the project can be moved with its history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relative_paths: Option<bool>,
+    /// Refuse every change to the repository, as with `--read-only`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    /// When to use colored output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<ColorMode>,
+    /// Use plain ASCII output, as with `--ascii`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ascii: Option<bool>,
+    /// Shell command run before each save; failing cancels the save
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_save_hook: Option<String>,
+    /// Shell command run after each save
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_save_hook: Option<String>,
+    /// Shell command run before each restore; failing cancels the restore
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_restore_hook: Option<String>,
+    /// Shell command run after each restore
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_restore_hook: Option<String>,
+    /// Compression algorithm by file extension, e.g. `{ jpg = "store" }`,
+    /// taking precedence over `compression`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_rules: Option<BTreeMap<String, Compression>>,
+    /// Retention schedule applied by `freeze prune` and the daemon, e.g.
+    /// `hourly:24h, daily:30d, weekly:1y`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<String>,
+    /// Retention schedule by directory, taking precedence over `retention`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention_rules: Option<BTreeMap<String, String>>,
+    /// Database file of each profile selected with `--profile`, storage
+    /// living next to it. Relative paths are relative to the repository
+    /// directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profiles: Option<BTreeMap<String, String>>,
+}
+
+/// Every configuration key with its description, in file order.
+pub const KEYS: [(&str, &str); 26] = [
+    ("compression_level", "zstd level of new snapshot contents, 1-22 (default 3)"),
+    ("compression", "zstd, lz4 or store (default zstd)"),
+    ("delta", "store new versions as zstd deltas against the previous one, true or false (default false)"),
+    ("paranoid", "re-hash files whose size and modification time are unchanged, true or false (default false)"),
+    ("restore_times", "give restored files their saved modification time, true or false (default true)"),
+    ("follow_symlinks", "save what symbolic links point to instead of the links, true or false (default false)"),
+    ("one_file_system", "do not cross into other file systems when walking directories, true or false (default false)"),
+    ("git_aware", "skip files ignored by git (.gitignore) when walking directories, true or false (default false)"),
+    ("xattrs", "record extended attributes and ACLs of saved files, true or false (default false)"),
+    ("content_index", "index the text of saved files for search --content, true or false (default false)"),
+    ("hash", "checksum algorithm of new snapshots, sha256 or blake3 (default sha256)"),
+    ("max_file_size", "size above which directory saves skip files, e.g. 100MB (default none)"),
+    ("page_size", "snapshots per page of paginated listings (default 10)"),
+    ("storage", "database file, relative to the repository directory; storage lives next to it (default data.sql)"),
+    ("relative_paths", "store paths under the parent of the repository directory relative to it, true or false (default false)"),
+    ("read_only", "refuse saves, deletions and other changes to the repository, true or false (default false)"),
+    ("color", "auto, always or never (default auto)"),
+    ("ascii", "plain ASCII output, true or false (default false)"),
+    ("pre_save_hook", "shell command run before each save; failing cancels it"),
+    ("post_save_hook", "shell command run after each save"),
+    ("pre_restore_hook", "shell command run before each restore; failing cancels it"),
+    ("post_restore_hook", "shell command run after each restore"),
+    ("compression_rules", "algorithm by file extension, e.g. { jpg = \"store\", log = \"lz4\" }"),
+    ("retention", "schedule applied by prune and the daemon, e.g. \"hourly:24h, daily:30d, weekly:1y\" (default none)"),
+    ("retention_rules", "schedule by directory, e.g. { \"~/logs\" = \"daily:7d\" }"),
+    ("profiles", "database file of each profile, e.g. { work = \"profiles/work/data.sql\" }"),
+];
+
+impl Config {
+    /// Returns the path of the configuration file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the home directory cannot be determined.
+    pub fn path() -> Result<PathBuf> {
+        Ok(crate::db::Database::home()?.join("config.toml"))
+    }
+
+    /// Reads the configuration file, or the defaults if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not valid.
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::parse(&fs::read_to_string(&path)?)
+            .map_err(|e| anyhow::anyhow!("Invalid configuration in {}: {}", path.display(), e))
+    }
+
+    /// Parses the content of a configuration file.
+    fn parse(content: &str) -> Result<Self> {
+        let config: Self = toml::from_str(content)?;
+        if let Some(level) = config.compression_level {
+            check_compression_level(level)?;
+        }
+        if let Some(size) = &config.max_file_size {
+            crate::utils::parse_size(size)?;
+        }
+        crate::retention::Policies::from_config(&config)?;
+        if config.page_size == Some(0) {
+            bail!("page_size must be at least 1");
+        }
+        Ok(config)
+    }
+
+    /// Writes the configuration file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Returns the value of a key as written in the file, `None` if unset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is unknown.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        let value = toml::Value::try_from(self)?;
+        check_key(key)?;
+        Ok(value.get(key).map(|v| match v {
+            toml::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }))
+    }
+
+    /// Sets a key from its textual value, or unsets it when `value` is `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is unknown or the value is not valid for it.
+    pub fn set(&mut self, key: &str, value: Option<&str>) -> Result<()> {
+        check_key(key)?;
+        let mut table = match toml::Value::try_from(&*self)? {
+            toml::Value::Table(table) => table,
+            _ => unreachable!("the configuration serializes to a table"),
+        };
+        match value {
+            Some(value) => {
+                // Values that are not TOML literals are taken as strings
+                let parsed = toml::from_str::<toml::Table>(&format!("v = {}", value))
+                    .ok()
+                    .and_then(|mut t| t.remove("v"))
+                    .unwrap_or_else(|| toml::Value::String(value.to_string()));
+                table.insert(key.to_string(), parsed);
+            }
+            None => {
+                table.remove(key);
+            }
+        }
+        *self = Self::parse(&toml::to_string(&table)?)
+            .map_err(|e| anyhow::anyhow!("Invalid value for {}: {}", key, e))?;
+        Ok(())
+    }
+
+    /// zstd level of new snapshot contents.
+    pub fn compression_level(&self) -> i32 {
+        self.compression_level.unwrap_or(DEFAULT_COMPRESSION_LEVEL)
+    }
+
+    /// Compression algorithm configured for files with `extension`, if any.
+    pub fn compression_rule(&self, extension: &str) -> Option<Compression> {
+        let rules = self.compression_rules.as_ref()?;
+        rules
+            .get(extension)
+            .or_else(|| rules.get(&extension.to_lowercase()))
+            .copied()
+    }
+
+    /// Whether new versions are stored as deltas.
+    pub fn delta(&self) -> bool {
+        self.delta.unwrap_or(false)
+    }
+
+    /// Whether files are re-hashed even when their size and modification
+    /// time are unchanged.
+    pub fn paranoid(&self) -> bool {
+        self.paranoid.unwrap_or(false)
+    }
+
+    /// Whether restored and exported files get their saved modification time.
+    pub fn restore_times(&self) -> bool {
+        self.restore_times.unwrap_or(true)
+    }
+
+    /// Whether saves follow symbolic links instead of recording them.
+    pub fn follow_symlinks(&self) -> bool {
+        self.follow_symlinks.unwrap_or(false)
+    }
+
+    /// Whether directory walks stay on the file system they start on.
+    pub fn one_file_system(&self) -> bool {
+        self.one_file_system.unwrap_or(false)
+    }
+
+    /// Whether directory walks skip what git ignores.
+    pub fn git_aware(&self) -> bool {
+        self.git_aware.unwrap_or(false)
+    }
+
+    /// Whether saves record extended attributes of files.
+    pub fn xattrs(&self) -> bool {
+        self.xattrs.unwrap_or(false)
+    }
+
+    /// Whether saves index the text of files for content searches.
+    pub fn content_index(&self) -> bool {
+        self.content_index.unwrap_or(false)
+    }
+
+    /// Checksum algorithm of new snapshots.
+    pub fn hash(&self) -> HashAlgorithm {
+        self.hash.unwrap_or_default()
+    }
+
+    /// Size in bytes above which directory saves skip files, if any.
+    pub fn max_file_size(&self) -> Option<u64> {
+        let size = crate::utils::parse_size(self.max_file_size.as_deref()?).ok()?;
+        Some(size as u64)
+    }
+
+    /// Snapshots per page of paginated listings.
+    pub fn page_size(&self) -> usize {
+        self.page_size.unwrap_or(DEFAULT_PAGE_SIZE)
+    }
+
+    /// Whether paths under the project holding the repository directory
+    /// are stored relative to it.
+    pub fn relative_paths(&self) -> bool {
+        self.relative_paths.unwrap_or(false)
+    }
+
+    /// Whether changes to the repository are refused.
+    pub fn read_only(&self) -> bool {
+        self.read_only.unwrap_or(false)
+    }
+
+    /// Fails if the repository is read-only.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `read_only` is set.
+    pub fn check_writable(&self) -> Result<()> {
+        if self.read_only() {
+            bail!("The repository is read-only");
+        }
+        Ok(())
+    }
+
+    /// The configured database location, with relative paths resolved
+    /// against the repository directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the home directory cannot be determined.
+    pub fn storage(&self) -> Result<Option<String>> {
+        self.storage.as_deref().map(resolve_db).transpose()
+    }
+
+    /// The database location of a profile, with relative paths resolved
+    /// against the repository directory; `None` for an unknown profile.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the home directory cannot be determined.
+    pub fn profile_storage(&self, name: &str) -> Result<Option<String>> {
+        self.profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(name))
+            .map(|spec| resolve_db(spec))
+            .transpose()
+    }
+
+    /// Adds a profile, its database in `profiles/<name>/data.sql` unless
+    /// `db` is given.
+    ///
+    /// # Returns
+    ///
+    /// The database location of the profile, as written in the file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is not made of letters, digits, `-` and
+    /// `_`, or if the profile already exists.
+    pub fn add_profile(&mut self, name: &str, db: Option<&str>) -> Result<String> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            bail!("Invalid profile name: {} (use letters, digits, - and _)", name);
+        }
+        let profiles = self.profiles.get_or_insert_with(BTreeMap::new);
+        if profiles.contains_key(name) {
+            bail!("Profile {} already exists", name);
+        }
+        let db = db.map_or_else(|| format!("{}/{}/data.sql", PROFILES_DIR, name), str::to_string);
+        profiles.insert(name.to_string(), db.clone());
+        Ok(db)
+    }
+
+    /// Removes a profile.
+    ///
+    /// # Returns
+    ///
+    /// The database location the profile had, `None` if there was no such
+    /// profile
+    pub fn remove_profile(&mut self, name: &str) -> Option<String> {
+        let profiles = self.profiles.as_mut()?;
+        let db = profiles.remove(name);
+        if profiles.is_empty() {
+            self.profiles = None;
+        }
+        db
+    }
+}
+
+/// Resolves a database location against the repository directory.
+fn resolve_db(spec: &str) -> Result<String> {
+    if spec != MEMORY_DB && Path::new(spec).is_relative() {
+        Ok(Config::path()?.with_file_name(spec).display().to_string())
+    } else {
+        Ok(spec.to_string())
+    }
+}
+
+/// Fails if `key` is not a configuration key.
+fn check_key(key: &str) -> Result<()> {
+    if KEYS.iter().any(|(name, _)| *name == key) {
+        Ok(())
+    } else {
+        bail!("Unknown configuration key: {}", key)
+    }
+}
+
+/// Fails if `level` is not a valid zstd level.
+fn check_compression_level(level: i32) -> Result<()> {
+    if !(1..=22).contains(&level) {
+        bail!("compression_level must be between 1 and 22");
+    }
+    Ok(())
+}
+
+/// Makes `config` the configuration returned by [`get`].
+///
+/// Must be called before the first call to [`get`]; later calls are ignored.
+pub fn init(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+/// Returns the configuration loaded at startup, or the defaults.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_get_and_parse() {
+        let mut config = Config::default();
+        config.set("compression_level", Some("9")).unwrap();
+        config.set("color", Some("never")).unwrap();
+        config.set("storage", Some("/data/freeze.sql")).unwrap();
+        config.set("ascii", Some("true")).unwrap();
+        config.set("pre_save_hook", Some("pg_dump app > dump.sql")).unwrap();
+        config.set("compression_rules", Some(r#"{ jpg = "store", log = "lz4" }"#)).unwrap();
+        config.set("max_file_size", Some("100MB")).unwrap();
+        config.set("retention", Some("hourly:24h, daily:30d")).unwrap();
+        config.set("read_only", Some("true")).unwrap();
+
+        assert_eq!(config.compression_level(), 9);
+        assert_eq!(config.color, Some(ColorMode::Never));
+        assert_eq!(config.get("storage").unwrap().as_deref(), Some("/data/freeze.sql"));
+        assert_eq!(config.get("ascii").unwrap().as_deref(), Some("true"));
+        assert_eq!(config.get("page_size").unwrap(), None);
+        assert_eq!(config.pre_save_hook.as_deref(), Some("pg_dump app > dump.sql"));
+        assert_eq!(config.compression_rule("JPG"), Some(Compression::Store));
+        assert_eq!(config.compression_rule("txt"), None);
+        assert_eq!(config.max_file_size(), Some(100 * 1024 * 1024));
+        assert!(config.check_writable().is_err());
+
+        let written = toml::to_string(&config).unwrap();
+        assert_eq!(Config::parse(&written).unwrap(), config);
+
+        config.set("compression_level", None).unwrap();
+        assert_eq!(config.compression_level(), DEFAULT_COMPRESSION_LEVEL);
+    }
+
+    #[test]
+    fn test_profiles() {
+        let mut config = Config::default();
+        assert_eq!(config.add_profile("work", None).unwrap(), "profiles/work/data.sql");
+        assert_eq!(config.add_profile("notes", Some("/data/notes.sql")).unwrap(), "/data/notes.sql");
+        assert!(config.add_profile("work", None).is_err());
+        assert!(config.add_profile("../up", None).is_err());
+        assert!(config.add_profile("", None).is_err());
+
+        assert_eq!(config.profile_storage("notes").unwrap().as_deref(), Some("/data/notes.sql"));
+        assert!(config.profile_storage("work").unwrap().unwrap().ends_with("profiles/work/data.sql"));
+        assert_eq!(config.profile_storage("home").unwrap(), None);
+
+        let written = toml::to_string(&config).unwrap();
+        assert_eq!(Config::parse(&written).unwrap(), config);
+
+        assert_eq!(config.remove_profile("work").as_deref(), Some("profiles/work/data.sql"));
+        assert_eq!(config.remove_profile("work"), None);
+        config.remove_profile("notes");
+        assert_eq!(config.profiles, None);
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let mut config = Config::default();
+        assert!(config.set("compression_level", Some("40")).is_err());
+        assert!(config.set("page_size", Some("0")).is_err());
+        assert!(config.set("max_file_size", Some("huge")).is_err());
+        assert!(config.set("color", Some("sometimes")).is_err());
+        assert!(config.set("compression", Some("gzip")).is_err());
+        assert!(config.set("retention", Some("daily")).is_err());
+        assert!(config.set("colour", Some("never")).is_err());
+        assert!(Config::parse("unknown = 1").is_err());
+        assert_eq!(config, Config::default());
+    }
+}
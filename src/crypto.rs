@@ -0,0 +1,854 @@
+/*!
+Passphrase-protected repositories for the freeze application.
+
+`freeze key init` generates a random repository key and encrypts every stored
+content with it. The key itself is kept in `repository.key`, next to the
+database, wrapped by a key derived from a passphrase with Argon2id. Commands
+that read or write content unlock the key on first use, reading the
+passphrase from `FREEZE_PASSPHRASE` or asking for it.
+
+Encrypted contents start with a short header (magic bytes, the key ID and a
+nonce prefix) followed by the compressed stream sealed with XChaCha20-Poly1305 in
+64 KiB chunks, so large files are encrypted and decrypted as streams.
+Contents stored before `key init` or by an unencrypted repository stay
+readable as they are.
+
+`freeze key store` keeps the unlocked key in the platform keyring (Secret
+Service, macOS Keychain or Windows Credential Manager), where it is found
+before asking for a passphrase, so that unattended jobs need neither a
+prompt nor a plaintext passphrase.
+
+Builds with the `sqlcipher` feature can also encrypt the database, and with
+it the paths, dates and checksums of snapshots: `freeze key encrypt-database`
+rewrites it with SQLCipher under a key derived from the repository key, and
+the key file records that every connection must unlock it.
+*/
+
+use anyhow::{bail, Context, Result};
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Name of the wrapped key file, next to the database.
+pub const KEY_FILE: &str = "repository.key";
+/// Keyring service under which repository keys are stored.
+const KEYRING_SERVICE: &str = "freeze";
+/// First bytes of an encrypted content.
+const MAGIC: &[u8; 4] = b"FRZE";
+const KEY_ID_SIZE: usize = 8;
+/// Nonce prefix of the STREAM construction (24-byte nonce minus a 32-bit counter and a flag)
+const NONCE_PREFIX_SIZE: usize = 19;
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+const FORMAT_VERSION: u32 = 1;
+
+static KEY: OnceLock<Option<Key>> = OnceLock::new();
+
+/// A repository key.
+#[derive(Clone)]
+pub struct Key([u8; 32]);
+
+impl Key {
+    /// Generates a random key.
+    fn generate() -> Result<Self> {
+        Ok(Self(random_bytes()?))
+    }
+
+    /// Short identifier stored in content headers, to tell keys apart.
+    fn id(&self) -> [u8; KEY_ID_SIZE] {
+        let digest = Sha256::digest(self.0);
+        let mut id = [0; KEY_ID_SIZE];
+        id.copy_from_slice(&digest[..KEY_ID_SIZE]);
+        id
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.0.into())
+    }
+
+    /// The raw SQLCipher key of the database, in `PRAGMA key` syntax.
+    fn database_key(&self) -> String {
+        let digest = Sha256::new()
+            .chain_update(b"freeze database key")
+            .chain_update(self.0)
+            .finalize();
+        format!("x'{}'", to_hex(&digest))
+    }
+}
+
+impl std::fmt::Debug for Key {
+    // Never print the key itself
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Key({})", to_hex(&self.id()))
+    }
+}
+
+/// Argon2id settings used to derive the wrapping key.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        let params = argon2::Params::default();
+        Self {
+            memory_kib: params.m_cost(),
+            iterations: params.t_cost(),
+            parallelism: params.p_cost(),
+        }
+    }
+}
+
+/// The content of `repository.key`: the repository key wrapped by a passphrase.
+#[derive(Serialize, Deserialize, Debug)]
+struct KeyFile {
+    version: u32,
+    kdf: KdfParams,
+    /// Hex-encoded Argon2 salt
+    salt: String,
+    /// Hex-encoded XChaCha20-Poly1305 nonce
+    nonce: String,
+    /// Hex-encoded encrypted repository key
+    wrapped_key: String,
+    /// Hex-encoded ID of the repository key, to check keys found in the keyring
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_id: Option<String>,
+    /// Whether the database is encrypted with SQLCipher
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    database: bool,
+}
+
+impl KeyFile {
+    /// Wraps `key` with a key derived from `passphrase`.
+    fn wrap(key: &Key, passphrase: &str, kdf: KdfParams) -> Result<Self> {
+        let salt: [u8; 16] = random_bytes()?;
+        let nonce: [u8; 24] = random_bytes()?;
+        let wrapped_key = derive(passphrase, &salt, kdf)?
+            .cipher()
+            .encrypt(XNonce::from_slice(&nonce), &key.0[..])
+            .map_err(|_| anyhow::anyhow!("Failed to wrap the repository key"))?;
+        Ok(Self {
+            version: FORMAT_VERSION,
+            kdf,
+            salt: to_hex(&salt),
+            nonce: to_hex(&nonce),
+            wrapped_key: to_hex(&wrapped_key),
+            key_id: Some(to_hex(&key.id())),
+            database: false,
+        })
+    }
+
+    /// Recovers the repository key with `passphrase`.
+    fn unwrap(&self, passphrase: &str) -> Result<Key> {
+        if self.version > FORMAT_VERSION {
+            bail!("Unsupported key file version {}", self.version);
+        }
+        let nonce = from_hex(&self.nonce)?;
+        if nonce.len() != 24 {
+            bail!("Invalid key file: bad nonce");
+        }
+        let key = derive(passphrase, &from_hex(&self.salt)?, self.kdf)?
+            .cipher()
+            .decrypt(XNonce::from_slice(&nonce), &from_hex(&self.wrapped_key)?[..])
+            .map_err(|_| anyhow::anyhow!("Wrong passphrase"))?;
+        Ok(Key(key
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Invalid key file: bad key length"))?))
+    }
+
+    fn load() -> Result<Option<Self>> {
+        let path = key_file_path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = serde_json::from_str(&fs::read_to_string(&path)?)
+            .with_context(|| format!("Invalid key file {}", path.display()))?;
+        Ok(Some(file))
+    }
+
+    fn save(&self) -> Result<()> {
+        self.save_as(&key_file_path()?)
+    }
+
+    fn save_as(&self, path: &Path) -> Result<()> {
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&temp_path, fs::Permissions::from_mode(0o600))?;
+        }
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Derives a wrapping key from a passphrase.
+fn derive(passphrase: &str, salt: &[u8], kdf: KdfParams) -> Result<Key> {
+    let params = argon2::Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| anyhow::anyhow!("Invalid key derivation settings: {}", e))?;
+    let mut key = [0; 32];
+    argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Key derivation failed: {}", e))?;
+    Ok(Key(key))
+}
+
+/// Returns the path of the wrapped key file of the current repository.
+///
+/// # Errors
+///
+/// Returns an error if the home directory cannot be determined.
+pub fn key_file_path() -> Result<PathBuf> {
+    Ok(crate::db::Database::data_dir()?.join(KEY_FILE))
+}
+
+/// Returns `true` if the current repository is passphrase-protected.
+///
+/// # Errors
+///
+/// Returns an error if the home directory cannot be determined.
+pub fn is_enabled() -> Result<bool> {
+    Ok(key_file_path()?.exists())
+}
+
+/// Returns the repository key, unlocking it on first use.
+///
+/// # Returns
+///
+/// The key, or `None` if the repository is not passphrase-protected
+///
+/// # Errors
+///
+/// Returns an error if the passphrase is wrong or cannot be asked for.
+pub fn key() -> Result<Option<&'static Key>> {
+    if let Some(key) = KEY.get() {
+        return Ok(key.as_ref());
+    }
+    let key = match KeyFile::load()? {
+        Some(file) => match keyring_key(&file) {
+            Some(key) => Some(key),
+            None => Some(file.unwrap(&passphrase("FREEZE_PASSPHRASE")?)?),
+        },
+        None => None,
+    };
+    Ok(KEY.get_or_init(|| key).as_ref())
+}
+
+/// Returns `true` if the database of the current repository is encrypted.
+///
+/// # Errors
+///
+/// Returns an error if the key file cannot be read.
+pub fn is_database_encrypted() -> Result<bool> {
+    Ok(KeyFile::load()?.is_some_and(|file| file.database))
+}
+
+/// Returns the SQLCipher key of the database, unlocking the repository key
+/// on first use.
+///
+/// # Returns
+///
+/// The key in `PRAGMA key` syntax, or `None` if the database is not
+/// encrypted
+///
+/// # Errors
+///
+/// Returns an error if the database is encrypted but this build has no
+/// SQLCipher support, or if the passphrase is wrong or cannot be asked for.
+pub fn database_key() -> Result<Option<String>> {
+    if !is_database_encrypted()? {
+        return Ok(None);
+    }
+    if !crate::db::SQLCIPHER {
+        bail!("The database is encrypted, but this build of freeze has no SQLCipher support; rebuild it with `--features sqlcipher`");
+    }
+    Ok(key()?.map(Key::database_key))
+}
+
+/// Encrypts the database with SQLCipher, or decrypts it back to a plain
+/// SQLite file.
+///
+/// The database is copied first; the key file records the change only once
+/// the copy is complete, and the copy then replaces the database. No other
+/// process may use the repository meanwhile.
+///
+/// # Errors
+///
+/// Returns an error if the repository is not protected, the database is
+/// already in the requested state, this build has no SQLCipher support, or
+/// the database cannot be copied.
+pub fn set_database_encryption(encrypted: bool) -> Result<()> {
+    let Some(mut file) = KeyFile::load()? else {
+        bail!("The repository is not protected by a passphrase; use `freeze key init` first");
+    };
+    match (file.database, encrypted) {
+        (true, true) => bail!("The database is already encrypted"),
+        (false, false) => bail!("The database is not encrypted"),
+        _ => {}
+    }
+    let database_key = key()?
+        .context("The repository key is not available")?
+        .database_key();
+    let copy = if encrypted {
+        crate::db::Database::copy_rekeyed(None, Some(&database_key))?
+    } else {
+        crate::db::Database::copy_rekeyed(Some(&database_key), None)?
+    };
+    file.database = encrypted;
+    if let Err(e) = file.save() {
+        let _ = fs::remove_file(&copy);
+        return Err(e);
+    }
+    crate::db::Database::replace_file(&copy)
+}
+
+/// The keyring entry of the current repository, named after its key file.
+fn keyring_entry() -> Result<keyring::Entry> {
+    let path = key_file_path()?;
+    let path = path.canonicalize().unwrap_or(path);
+    Ok(keyring::Entry::new(KEYRING_SERVICE, &path.display().to_string())?)
+}
+
+/// Returns the key stored in the keyring, if any and if it belongs to `file`.
+///
+/// An unavailable keyring counts as an empty one.
+fn keyring_key(file: &KeyFile) -> Option<Key> {
+    let hex = keyring_entry().ok()?.get_password().ok()?;
+    let key = Key(from_hex(&hex).ok()?.try_into().ok()?);
+    // Keys stored before a rotation no longer match
+    match &file.key_id {
+        Some(id) if *id == to_hex(&key.id()) => Some(key),
+        _ => None,
+    }
+}
+
+/// Stores the repository key in the platform keyring.
+///
+/// # Errors
+///
+/// Returns an error if the repository is not protected, the key cannot be
+/// unlocked or the keyring is not available.
+pub fn store_in_keyring() -> Result<()> {
+    let Some(key) = key()? else {
+        bail!("The repository is not protected by a passphrase; use `freeze key init` first");
+    };
+    keyring_entry()?
+        .set_password(&to_hex(&key.0))
+        .map_err(|e| anyhow::anyhow!("Could not store the key in the keyring: {}", e))
+}
+
+/// Removes the repository key from the platform keyring.
+///
+/// # Returns
+///
+/// `false` if the keyring held no key for the repository
+///
+/// # Errors
+///
+/// Returns an error if the keyring is not available.
+pub fn forget_from_keyring() -> Result<bool> {
+    match keyring_entry()?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(anyhow::anyhow!("Could not remove the key from the keyring: {}", e)),
+    }
+}
+
+/// Returns `true` if the keyring holds the current key of the repository.
+///
+/// # Errors
+///
+/// Returns an error if the key file cannot be read.
+pub fn is_in_keyring() -> Result<bool> {
+    Ok(KeyFile::load()?.is_some_and(|file| keyring_key(&file).is_some()))
+}
+
+/// Unlocks the repository key now rather than on first use.
+///
+/// Long-running servers call this at startup so that they never need to
+/// ask for the passphrase later.
+///
+/// # Errors
+///
+/// Returns an error if the passphrase is wrong or cannot be asked for.
+pub fn unlock() -> Result<()> {
+    key().map(|_| ())
+}
+
+/// Reads the passphrase from `env`, or asks for it.
+fn passphrase(env: &str) -> Result<String> {
+    match std::env::var(env) {
+        Ok(passphrase) => Ok(passphrase),
+        Err(_) => crate::utils::prompt_secret(&crate::i18n::t!("passphrase-prompt"), env),
+    }
+}
+
+/// Reads a new passphrase from `env`, or asks for it twice.
+fn new_passphrase(env: &str) -> Result<String> {
+    let passphrase = match std::env::var(env) {
+        Ok(passphrase) => passphrase,
+        Err(_) => {
+            let first = crate::utils::prompt_secret(&crate::i18n::t!("passphrase-new"), env)?;
+            let second = crate::utils::prompt_secret(&crate::i18n::t!("passphrase-repeat"), env)?;
+            if first != second {
+                bail!("The passphrases do not match");
+            }
+            first
+        }
+    };
+    if passphrase.is_empty() {
+        bail!("The passphrase cannot be empty");
+    }
+    Ok(passphrase)
+}
+
+/// Protects the repository with a passphrase and encrypts its contents.
+///
+/// The new passphrase is read from `FREEZE_PASSPHRASE` or asked for twice.
+///
+/// # Returns
+///
+/// The number of stored contents encrypted
+///
+/// # Errors
+///
+/// Returns an error if the repository is already protected, the passphrase
+/// cannot be read or a content cannot be encrypted.
+pub fn init() -> Result<usize> {
+    if is_enabled()? {
+        bail!("The repository is already protected by a passphrase; use `freeze key rotate` to change it");
+    }
+    let passphrase = new_passphrase("FREEZE_PASSPHRASE")?;
+    let key = Key::generate()?;
+    let file = KeyFile::wrap(&key, &passphrase, KdfParams::default())?;
+    reencrypt_storage(None, &key, &file)
+}
+
+/// Replaces the repository key and passphrase, re-encrypting every content.
+///
+/// The current passphrase is read from `FREEZE_PASSPHRASE` and the new one
+/// from `FREEZE_NEW_PASSPHRASE`, or both are asked for.
+///
+/// # Returns
+///
+/// The number of stored contents re-encrypted
+///
+/// # Errors
+///
+/// Returns an error if the repository is not protected, a passphrase is wrong
+/// or cannot be read, or a content cannot be re-encrypted.
+pub fn rotate() -> Result<usize> {
+    let Some(current) = KeyFile::load()? else {
+        bail!("The repository is not protected by a passphrase; use `freeze key init` first");
+    };
+    let old_key = match KEY.get() {
+        Some(Some(key)) => key.clone(),
+        _ => current.unwrap(&passphrase("FREEZE_PASSPHRASE")?)?,
+    };
+    let passphrase = new_passphrase("FREEZE_NEW_PASSPHRASE")?;
+    let key = Key::generate()?;
+    let mut file = KeyFile::wrap(&key, &passphrase, current.kdf)?;
+    file.database = current.database;
+    let in_keyring = keyring_key(&current).is_some();
+    // An encrypted database is rekeyed along with the contents
+    let database_copy = if current.database {
+        Some(crate::db::Database::copy_rekeyed(
+            Some(&old_key.database_key()),
+            Some(&key.database_key()),
+        )?)
+    } else {
+        None
+    };
+    let count = match reencrypt_storage(Some(&old_key), &key, &file) {
+        Ok(count) => count,
+        Err(e) => {
+            if let Some(copy) = &database_copy {
+                let _ = fs::remove_file(copy);
+            }
+            return Err(e);
+        }
+    };
+    if let Some(copy) = &database_copy {
+        crate::db::Database::replace_file(copy)?;
+    }
+    if in_keyring {
+        keyring_entry()?
+            .set_password(&to_hex(&key.0))
+            .map_err(|e| anyhow::anyhow!("Could not update the key in the keyring: {}", e))?;
+    }
+    Ok(count)
+}
+
+/// Rewrites every compressed content of the storage with `new`, then saves
+/// its wrapped key `file`.
+///
+/// Everything is written to temporary files first, which leaves the
+/// repository as it was if one fails. The new key file is then saved next to
+/// the current one, and only replaces it once every temporary file replaced
+/// its original: an interruption in between keeps both keys, so that every
+/// content can still be decrypted.
+fn reencrypt_storage(old: Option<&Key>, new: &Key, file: &KeyFile) -> Result<usize> {
+    let storage_dir = crate::db::Database::storage_dir()?;
+    let chunks_dir = storage_dir.join(crate::chunks::CHUNKS_DIR);
+    let mut rewritten = Vec::new();
+    for dir in [&storage_dir, &chunks_dir] {
+        if !dir.exists() {
+            continue;
+        }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let header_len = if crate::delta::is_delta(&path) {
+                crate::delta::HEADER_LEN
+            } else if crate::compression::Compression::from_path(&path).is_some() {
+                0
+            } else {
+                continue;
+            };
+            let temp_path = path.with_extension(format!(
+                "{}.tmp",
+                path.extension().unwrap_or_default().to_string_lossy()
+            ));
+            let result = (|| -> Result<()> {
+                let mut source = open_with(&path, header_len, || Ok(old))?;
+                let mut temp = fs::File::create(&temp_path)?;
+                // The plain header of deltas is kept as is
+                std::io::copy(&mut fs::File::open(&path)?.take(header_len), &mut temp)?;
+                let mut writer = EncryptWriter::new(std::io::BufWriter::new(temp), new)?;
+                std::io::copy(&mut source, &mut writer)?;
+                writer.finish()?.flush()?;
+                Ok(())
+            })();
+            if let Err(e) = result {
+                let _ = fs::remove_file(&temp_path);
+                for (_, temp_path) in &rewritten {
+                    let _ = fs::remove_file(temp_path);
+                }
+                return Err(e.context(format!("Failed to encrypt {}", path.display())));
+            }
+            rewritten.push((path, temp_path));
+        }
+    }
+
+    let key_path = key_file_path()?;
+    let pending = key_path.with_extension("key.new");
+    file.save_as(&pending)?;
+    for (path, temp_path) in &rewritten {
+        fs::rename(temp_path, path).with_context(|| {
+            format!(
+                "Failed to replace {}; the new key is kept in {} next to the current one",
+                path.display(),
+                pending.display()
+            )
+        })?;
+    }
+    fs::rename(&pending, &key_path)?;
+    Ok(rewritten.len())
+}
+
+/// Opens a stored content for reading, decrypting it if it is encrypted.
+///
+/// The reader yields the stored (compressed) bytes.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened, or if it is encrypted and
+/// the repository key cannot be unlocked or does not match.
+pub fn open_content(path: &Path) -> Result<Box<dyn Read + Send>> {
+    open_with(path, 0, key)
+}
+
+/// Opens a stored content that starts `offset` bytes into its file, after a
+/// plain header, like [`open_content`].
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened, or if it is encrypted and
+/// the repository key cannot be unlocked or does not match.
+pub fn open_content_at(path: &Path, offset: u64) -> Result<Box<dyn Read + Send>> {
+    open_with(path, offset, key)
+}
+
+/// Opens a stored content, asking `key` for the key only if it is encrypted.
+fn open_with<'a>(
+    path: &Path,
+    offset: u64,
+    key: impl FnOnce() -> Result<Option<&'a Key>>,
+) -> Result<Box<dyn Read + Send>> {
+    let mut file = fs::File::open(path)?;
+    file.seek(std::io::SeekFrom::Start(offset))?;
+    let mut head = Vec::with_capacity(MAGIC.len());
+    (&mut file).take(MAGIC.len() as u64).read_to_end(&mut head)?;
+    if head != MAGIC {
+        return Ok(Box::new(std::io::Cursor::new(head).chain(file)));
+    }
+
+    let mut key_id = [0; KEY_ID_SIZE];
+    let mut nonce_prefix = [0; NONCE_PREFIX_SIZE];
+    file.read_exact(&mut key_id)?;
+    file.read_exact(&mut nonce_prefix)?;
+    let Some(key) = key()? else {
+        bail!("{} is encrypted but the repository has no key", path.display());
+    };
+    if key.id() != key_id {
+        bail!("{} is encrypted with another key", path.display());
+    }
+    Ok(Box::new(DecryptReader {
+        inner: BufReader::new(file),
+        decryptor: Some(DecryptorBE32::new(&key.0.into(), (&nonce_prefix).into())),
+        plain: Vec::new(),
+        position: 0,
+    }))
+}
+
+/// Wraps `inner` to encrypt what is written if the repository has a key.
+///
+/// # Errors
+///
+/// Returns an error if the repository key cannot be unlocked or the header
+/// cannot be written.
+pub fn content_writer<W: Write>(inner: W) -> Result<ContentWriter<W>> {
+    Ok(match key()? {
+        Some(key) => ContentWriter::Encrypted(EncryptWriter::new(inner, key)?),
+        None => ContentWriter::Plain(inner),
+    })
+}
+
+/// A writer of stored content, encrypting when the repository has a key.
+pub enum ContentWriter<W: Write> {
+    Plain(W),
+    Encrypted(EncryptWriter<W>),
+}
+
+impl<W: Write> ContentWriter<W> {
+    /// Writes the end of the content and returns the inner writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the last chunk cannot be written.
+    pub fn finish(self) -> Result<W> {
+        match self {
+            Self::Plain(inner) => Ok(inner),
+            Self::Encrypted(writer) => writer.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for ContentWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(inner) => inner.write(buf),
+            Self::Encrypted(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(inner) => inner.flush(),
+            Self::Encrypted(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Encrypts a stream in chunks. [`EncryptWriter::finish`] must be called
+/// to seal the last chunk.
+pub struct EncryptWriter<W: Write> {
+    inner: W,
+    encryptor: EncryptorBE32<XChaCha20Poly1305>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    fn new(mut inner: W, key: &Key) -> Result<Self> {
+        let nonce_prefix: [u8; NONCE_PREFIX_SIZE] = random_bytes()?;
+        inner.write_all(MAGIC)?;
+        inner.write_all(&key.id())?;
+        inner.write_all(&nonce_prefix)?;
+        Ok(Self {
+            inner,
+            encryptor: EncryptorBE32::new(&key.0.into(), (&nonce_prefix).into()),
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        })
+    }
+
+    fn finish(mut self) -> Result<W> {
+        let chunk = self
+            .encryptor
+            .encrypt_last(&self.buffer[..])
+            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+        self.inner.write_all(&chunk)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        // A full chunk is kept back so that `finish` always has one to seal
+        while self.buffer.len() > CHUNK_SIZE {
+            let chunk = self
+                .encryptor
+                .encrypt_next(&self.buffer[..CHUNK_SIZE])
+                .map_err(|_| std::io::Error::other("Encryption failed"))?;
+            self.inner.write_all(&chunk)?;
+            self.buffer.drain(..CHUNK_SIZE);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts a stream written by [`EncryptWriter`].
+struct DecryptReader<R: Read> {
+    inner: BufReader<R>,
+    /// `None` once the last chunk has been read
+    decryptor: Option<DecryptorBE32<XChaCha20Poly1305>>,
+    plain: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> DecryptReader<R> {
+    /// Decrypts the next chunk into `plain`.
+    fn next_chunk(&mut self) -> std::io::Result<()> {
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE + TAG_SIZE);
+        (&mut self.inner)
+            .take((CHUNK_SIZE + TAG_SIZE) as u64)
+            .read_to_end(&mut chunk)?;
+        let last = chunk.len() < CHUNK_SIZE + TAG_SIZE || self.inner.fill_buf()?.is_empty();
+
+        let authentication_failed =
+            || std::io::Error::new(std::io::ErrorKind::InvalidData, "Encrypted content is corrupt or truncated");
+        let plain = if last {
+            let decryptor = self.decryptor.take().ok_or_else(authentication_failed)?;
+            decryptor.decrypt_last(&chunk[..])
+        } else {
+            let decryptor = self.decryptor.as_mut().ok_or_else(authentication_failed)?;
+            decryptor.decrypt_next(&chunk[..])
+        };
+        self.plain = plain.map_err(|_| authentication_failed())?;
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.plain.len() {
+            if self.decryptor.is_none() {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+        let count = buf.len().min(self.plain.len() - self.position);
+        buf[..count].copy_from_slice(&self.plain[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow::anyhow!("Failed to generate random bytes: {}", e))?;
+    Ok(bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        bail!("Invalid key file: bad hex value");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow::anyhow!("Invalid key file: bad hex value"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypt(key: &Key, plain: &[u8]) -> Vec<u8> {
+        let mut writer = EncryptWriter::new(Vec::new(), key).unwrap();
+        writer.write_all(plain).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn decrypt(key: &Key, stored: &[u8]) -> Result<Vec<u8>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("content.zstd");
+        fs::write(&path, stored)?;
+        let mut plain = Vec::new();
+        open_with(&path, 0, || Ok(Some(key)))?.read_to_end(&mut plain)?;
+        Ok(plain)
+    }
+
+    #[test]
+    fn test_stream_round_trip() {
+        let key = Key::generate().unwrap();
+        for size in [0, 10, CHUNK_SIZE, CHUNK_SIZE * 2 + 7] {
+            let plain: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            let stored = encrypt(&key, &plain);
+            assert_eq!(decrypt(&key, &stored).unwrap(), plain, "size {}", size);
+        }
+
+        // Plain contents are read as they are
+        assert_eq!(decrypt(&key, b"ab").unwrap(), b"ab");
+    }
+
+    #[test]
+    fn test_stream_rejects_tampering() {
+        let key = Key::generate().unwrap();
+        let plain = vec![7; CHUNK_SIZE + 100];
+        let stored = encrypt(&key, &plain);
+
+        let mut flipped = stored.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&key, &flipped).is_err());
+
+        // Dropping the last chunk must not pass for a shorter content
+        let truncated = &stored[..MAGIC.len() + KEY_ID_SIZE + NONCE_PREFIX_SIZE + CHUNK_SIZE + TAG_SIZE];
+        assert!(decrypt(&key, truncated).is_err());
+
+        let other = Key::generate().unwrap();
+        assert!(decrypt(&other, &stored).is_err());
+    }
+
+    #[test]
+    fn test_key_wrapping() {
+        // Cheap settings keep the test fast
+        let kdf = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 };
+        let key = Key::generate().unwrap();
+        let file = KeyFile::wrap(&key, "correct horse", kdf).unwrap();
+
+        assert_eq!(file.unwrap("correct horse").unwrap().0, key.0);
+        assert_eq!(file.key_id, Some(to_hex(&key.id())));
+        assert_eq!(file.unwrap("wrong horse").unwrap_err().to_string(), "Wrong passphrase");
+    }
+
+    #[test]
+    fn test_database_key() {
+        let key = Key::generate().unwrap();
+        let database_key = key.database_key();
+        assert!(database_key.starts_with("x'") && database_key.ends_with('\''));
+        assert_eq!(database_key.len(), 67);
+        assert!(!database_key.contains(&to_hex(&key.0)));
+        assert_ne!(database_key, Key::generate().unwrap().database_key());
+    }
+}
@@ -0,0 +1,364 @@
+/*!
+Background daemon for the freeze application.
+
+`freeze daemon start` runs in the foreground, owns the database connection
+and answers JSON requests on a Unix socket next to the database, one request
+per line and one response per line. It also hosts watch jobs. While a daemon
+runs, `freeze save` hands its work over instead of writing to the database
+itself, so that concurrent saves do not contend for SQLite locks. When a
+retention schedule is configured, the daemon also prunes every hour.
+*/
+
+use crate::db::{Database, SnapshotFilter};
+use crate::snapshot::{Selection, Snapshot};
+use crate::utils;
+use crate::watch::WatchOptions;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Name of the socket file, next to the database.
+const SOCKET_NAME: &str = "daemon.sock";
+/// Time between two applications of the retention schedules.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// A request to the daemon.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Request {
+    /// Checks that the daemon is running
+    Ping,
+    /// Saves a file or directory
+    Save {
+        path: PathBuf,
+        #[serde(default)]
+        message: Option<String>,
+        #[serde(default)]
+        keep_going: bool,
+    },
+    /// Restores a path from its latest snapshots, or a file from the
+    /// snapshot matching a checksum prefix
+    Restore {
+        path: PathBuf,
+        #[serde(default)]
+        checksum: Option<String>,
+    },
+    /// Lists the snapshots below a directory, or every snapshot
+    List {
+        #[serde(default)]
+        path: Option<PathBuf>,
+    },
+    /// Starts watching a file or directory
+    Watch {
+        path: PathBuf,
+        #[serde(default = "default_debounce")]
+        debounce: u64,
+    },
+    /// Lists the watched paths
+    Jobs,
+    /// Stops the daemon
+    Shutdown,
+}
+
+fn default_debounce() -> u64 {
+    500
+}
+
+/// The answer to a request.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default)]
+    pub data: Option<serde_json::Value>,
+    #[serde(default)]
+    pub err: Option<String>,
+}
+
+impl Response {
+    /// Turns an error response into an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns the daemon's error message if the request failed.
+    pub fn into_data(self) -> Result<serde_json::Value> {
+        if self.ok {
+            Ok(self.data.unwrap_or_default())
+        } else {
+            Err(anyhow::anyhow!(self.err.unwrap_or_else(|| "Daemon request failed".to_string())))
+        }
+    }
+}
+
+/// What the daemon shares between connections.
+struct State {
+    db: Mutex<Database>,
+    watches: Mutex<Vec<PathBuf>>,
+}
+
+/// Returns the path of the daemon socket for the current database.
+///
+/// # Errors
+///
+/// Returns an error if the home directory cannot be determined.
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(Database::data_dir()?.join(SOCKET_NAME))
+}
+
+/// Sends a request to the running daemon.
+///
+/// # Returns
+///
+/// The daemon's response, or `None` if no daemon is listening
+///
+/// # Errors
+///
+/// Returns an error if the daemon accepted the connection but the exchange failed.
+#[cfg(unix)]
+pub fn send(request: &Request) -> Result<Option<Response>> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let Ok(mut stream) = UnixStream::connect(socket_path()?) else {
+        return Ok(None);
+    };
+    writeln!(stream, "{}", serde_json::to_string(request)?)?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+/// Sends a request to the running daemon; daemons need Unix sockets.
+#[cfg(not(unix))]
+pub fn send(_request: &Request) -> Result<Option<Response>> {
+    Ok(None)
+}
+
+/// Runs the daemon until a `shutdown` request.
+///
+/// # Arguments
+///
+/// * `db` - The database connection the daemon owns
+///
+/// # Errors
+///
+/// Returns an error if another daemon is running or the socket cannot be created.
+#[cfg(unix)]
+pub fn serve(db: Database) -> Result<()> {
+    use std::os::unix::net::UnixStream;
+
+    let socket = socket_path()?;
+    if UnixStream::connect(&socket).is_ok() {
+        anyhow::bail!("A daemon is already running on {}", socket.display());
+    }
+    // A socket left behind by a daemon that did not shut down cleanly
+    let _ = std::fs::remove_file(&socket);
+    let listener = bind_private(&socket)?;
+
+    let state: &'static State = Box::leak(Box::new(State {
+        db: Mutex::new(db),
+        watches: Mutex::new(Vec::new()),
+    }));
+    println!("{} {}", crate::i18n::t!("daemon-listening"), socket.display());
+
+    let policies = crate::retention::Policies::from_config(crate::config::get())?;
+    if !policies.is_empty() && !crate::config::get().read_only() {
+        std::thread::spawn(move || prune_periodically(&policies, state));
+    }
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                std::thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream, state) {
+                        eprintln!("{}", e);
+                    }
+                });
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Creates a socket at `socket` that only the current user can connect to.
+///
+/// The socket is bound inside a private directory, made owner-only, then
+/// moved into place, so that it is never reachable with looser permissions.
+#[cfg(unix)]
+fn bind_private(socket: &std::path::Path) -> Result<std::os::unix::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let name = socket.file_name().unwrap_or_default();
+    let dir = socket.with_file_name(format!(".{}.{}", name.to_string_lossy(), std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let staged = dir.join(name);
+    let result = std::os::unix::net::UnixListener::bind(&staged)
+        .map_err(anyhow::Error::from)
+        .and_then(|listener| {
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&staged, socket)?;
+            Ok(listener)
+        });
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+/// Runs the daemon; daemons need Unix sockets.
+#[cfg(not(unix))]
+pub fn serve(_db: Database) -> Result<()> {
+    anyhow::bail!("The daemon needs Unix sockets, which this platform does not provide")
+}
+
+/// Applies the retention schedules now, then every [`PRUNE_INTERVAL`].
+#[cfg(unix)]
+fn prune_periodically(policies: &crate::retention::Policies, state: &'static State) {
+    loop {
+        let result = {
+            let db = state.db.lock().unwrap_or_else(|e| e.into_inner());
+            crate::retention::expired_snapshots(None, policies, chrono::Local::now(), &db).and_then(|expired| {
+                let ids: Vec<i64> = expired.iter().map(|(id, ..)| *id).collect();
+                db.delete_snapshots(&ids)
+            })
+        };
+        match result {
+            Ok((0, _)) => {}
+            Ok((deleted, freed)) => println!(
+                "{}",
+                crate::i18n::t!(
+                    "clear-expired-done",
+                    count = deleted,
+                    size = utils::format_size(freed as i64)
+                )
+            ),
+            Err(e) => eprintln!("Warning: pruning failed: {}", e),
+        }
+        std::thread::sleep(PRUNE_INTERVAL);
+    }
+}
+
+/// Answers the requests of one client until it disconnects.
+#[cfg(unix)]
+fn handle_connection(stream: std::os::unix::net::UnixStream, state: &'static State) -> Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = serde_json::from_str::<Request>(&line);
+        let shutdown = matches!(request, Ok(Request::Shutdown));
+        let response = match request.map_err(anyhow::Error::from).and_then(|r| dispatch(r, state)) {
+            Ok(data) => Response { ok: true, data: Some(data), err: None },
+            Err(e) => Response { ok: false, data: None, err: Some(e.to_string()) },
+        };
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+
+        if shutdown {
+            // Let a running operation finish before leaving
+            let _db = state.db.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = std::fs::remove_file(socket_path()?);
+            std::process::exit(0);
+        }
+    }
+    Ok(())
+}
+
+/// Performs a request.
+fn dispatch(request: Request, state: &'static State) -> Result<serde_json::Value> {
+    let db = || state.db.lock().unwrap_or_else(|e| e.into_inner());
+    match request {
+        Request::Ping | Request::Shutdown => Ok(json!({ "pid": std::process::id() })),
+        Request::Save {
+            path,
+            message,
+            keep_going,
+        } => {
+            crate::config::get().check_writable()?;
+            utils::validate_path(&path)?;
+            let summary =
+                Snapshot::save_recursive_with_progress(&path, message.as_deref(), keep_going, false, &db(), |_| {})?;
+            Ok(serde_json::to_value(summary)?)
+        }
+        Request::Restore { path, checksum } => {
+            let selection = checksum.map(Selection::Checksum).unwrap_or(Selection::Latest);
+            Snapshot::restore(&path, None, &selection, &db())?;
+            Ok(serde_json::Value::Null)
+        }
+        Request::List { path } => {
+            let db = db();
+            let snapshots = db.list_snapshots(&SnapshotFilter {
+                dir: path,
+                ..SnapshotFilter::default()
+            })?;
+            Ok(serde_json::to_value(utils::snapshot_entries(&snapshots, &db.list_messages()?, &db.list_pinned()?))?)
+        }
+        Request::Watch { path, debounce } => {
+            crate::config::get().check_writable()?;
+            utils::validate_path(&path)?;
+            {
+                let mut watches = state.watches.lock().unwrap_or_else(|e| e.into_inner());
+                if watches.contains(&path) {
+                    anyhow::bail!("{} is already watched", path.display());
+                }
+                watches.push(path.clone());
+            }
+            std::thread::spawn(move || {
+                let options = WatchOptions {
+                    debounce: Duration::from_millis(debounce),
+                    min_interval: Duration::ZERO,
+                };
+                // The watcher saves through its own connection, as it never returns
+                let result = Database::new().and_then(|db| crate::watch::run(&path, &options, &db));
+                if let Err(e) = result {
+                    eprintln!("{}: {}", path.display(), e);
+                }
+                state
+                    .watches
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .retain(|watched| *watched != path);
+            });
+            Ok(serde_json::Value::Null)
+        }
+        Request::Jobs => {
+            let watches = state.watches.lock().unwrap_or_else(|e| e.into_inner());
+            Ok(json!({ "watches": *watches }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_format() {
+        let request: Request = serde_json::from_str(r#"{"op": "save", "path": "/tmp/project"}"#).unwrap();
+        assert!(matches!(
+            request,
+            Request::Save { ref path, message: None, keep_going: false } if path == &PathBuf::from("/tmp/project")
+        ));
+
+        let request: Request = serde_json::from_str(r#"{"op": "watch", "path": "/tmp/notes"}"#).unwrap();
+        assert!(matches!(request, Request::Watch { debounce: 500, .. }));
+
+        assert_eq!(serde_json::to_string(&Request::Jobs).unwrap(), r#"{"op":"jobs"}"#);
+        assert!(serde_json::from_str::<Request>(r#"{"op": "format_disk"}"#).is_err());
+    }
+
+    #[test]
+    fn test_response_into_data() {
+        let ok = Response { ok: true, data: Some(json!({ "pid": 1 })), err: None };
+        assert_eq!(ok.into_data().unwrap()["pid"], 1);
+
+        let failed = Response { ok: false, data: None, err: Some("No snapshots".to_string()) };
+        assert_eq!(failed.into_data().unwrap_err().to_string(), "No snapshots");
+    }
+}
@@ -5,21 +5,206 @@ This module provides the `Database` struct which handles all SQLite database
 operations including snapshot persistence, retrieval, and exclusion management.
 */
 
-use crate::snapshot::Snapshot;
+use crate::hash::HashAlgorithm;
+use crate::i18n::t;
+use crate::session::Session;
+use crate::snapshot::{RestoreRecord, Snapshot};
 use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
 use console::style;
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 /// Database connection wrapper for freeze snapshot storage.
 ///
 /// Handles all persistence operations for snapshots and exclusions using SQLite.
 pub struct Database {
     conn: Connection,
+    /// Directory that paths stored as `./...` are relative to, when the
+    /// repository stores relative paths
+    root: Option<PathBuf>,
 }
 
-type SnapshotWithId = (i64, PathBuf, String, i64, String);
+/// Where the database and its storage live for this process.
+enum Location {
+    /// `data.sql` with storage in `storage`, in the repository directory
+    Default,
+    /// A custom database file with storage in a sibling `storage` directory
+    File(PathBuf),
+    /// A shared in-memory database with storage in a temporary directory
+    Memory,
+}
+
+static LOCATION: OnceLock<Location> = OnceLock::new();
+
+/// Columns holding file paths, which `relative_paths` stores relative to
+/// the project. The `contents` table follows `snapshots`.
+const PATH_COLUMNS: [(&str, &str); 8] = [
+    ("snapshots", "path"),
+    ("snapshots", "content_path"),
+    ("sessions", "root"),
+    ("session_files", "path"),
+    ("session_dirs", "path"),
+    ("restores", "root"),
+    ("restore_files", "path"),
+    ("aliases", "path"),
+];
+
+/// The repository directory chosen with `--repo` or `FREEZE_HOME`.
+static HOME: OnceLock<PathBuf> = OnceLock::new();
+
+/// How long a connection waits for another process to release a lock
+/// before failing with "database is locked".
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Name of the `--db` value selecting the ephemeral in-memory mode.
+pub const MEMORY_DB: &str = ":memory:";
+
+/// Whether this build links SQLCipher, which can encrypt the database file.
+pub const SQLCIPHER: bool = cfg!(feature = "sqlcipher");
+
+/// Order of snapshot listings.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SnapshotSort {
+    /// Newest first
+    #[default]
+    Date,
+    /// Largest first
+    Size,
+    /// By path, newest first for each path
+    Path,
+}
+
+/// Criteria of a snapshot listing. Unset criteria match every snapshot.
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotFilter {
+    /// Only snapshots of files below this directory
+    pub dir: Option<PathBuf>,
+    /// Only snapshots taken at or after this moment
+    pub since: Option<DateTime<FixedOffset>>,
+    /// Only snapshots taken at or before this moment
+    pub until: Option<DateTime<FixedOffset>>,
+    /// Only snapshots larger than this many bytes
+    pub larger_than: Option<i64>,
+    /// Only snapshots whose path contains this text
+    pub path_contains: Option<String>,
+    /// Order of the results
+    pub sort: SnapshotSort,
+}
+
+/// Criteria of a snapshot search, besides the path pattern. Unset criteria
+/// match every snapshot.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchFilter {
+    /// Only snapshots of this MIME type, or of this top-level type such as
+    /// `image` or `text`
+    pub file_type: Option<String>,
+    /// Only snapshots taken at or after this moment
+    pub since: Option<DateTime<FixedOffset>>,
+    /// Only snapshots taken at or before this moment
+    pub until: Option<DateTime<FixedOffset>>,
+    /// Only snapshots larger than this many bytes
+    pub larger_than: Option<i64>,
+    /// Only snapshots smaller than this many bytes
+    pub smaller_than: Option<i64>,
+    /// Only snapshots whose checksum starts with this prefix
+    pub checksum: Option<String>,
+    /// Only snapshots of paths with this extension, without the dot
+    pub extension: Option<String>,
+    /// Only snapshots with this metadata key, holding this value if given
+    pub tag: Option<(String, Option<String>)>,
+    /// Only the newest matching snapshot of each path
+    pub latest: bool,
+}
+
+impl SearchFilter {
+    /// Sets the criterion `name` from its text form, as given to the web API
+    /// and MCP tools: `type`, `since`, `until` (dates or ages like `7d`),
+    /// `larger_than`, `smaller_than` (sizes like `10K`), `checksum`, `ext`,
+    /// `tag` (`key` or `key=value`) or `latest` (`true` or `false`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is unknown or the value invalid.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let value = value.trim();
+        match name {
+            "type" => self.file_type = Some(value.to_string()).filter(|value| !value.is_empty()),
+            "since" => self.since = Some(crate::utils::parse_date_bound(value, true)?),
+            "until" => self.until = Some(crate::utils::parse_date_bound(value, false)?),
+            "larger_than" => self.larger_than = Some(crate::utils::parse_size(value)?),
+            "smaller_than" => self.smaller_than = Some(crate::utils::parse_size(value)?),
+            "checksum" => self.checksum = Some(value.to_lowercase()),
+            "ext" => self.extension = Some(value.trim_start_matches('.').to_string()).filter(|ext| !ext.is_empty()),
+            "tag" => {
+                self.tag = Some(match value.split_once('=') {
+                    Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                    None => (value.to_string(), None),
+                })
+            }
+            "latest" => {
+                self.latest = value
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid value for latest: {} (expected true or false)", value))?
+            }
+            _ => anyhow::bail!("Unknown search filter: {}", name),
+        }
+        Ok(())
+    }
+}
+
+/// Which snapshots a retention clear removes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Retention {
+    /// Snapshots taken longer ago than this
+    OlderThan(chrono::Duration),
+    /// All but this many most recent snapshots of each path
+    KeepLast(usize),
+}
+
+/// Page usage of the database file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageStats {
+    /// Bytes per page
+    pub page_size: u64,
+    /// Pages in the file
+    pub pages: u64,
+    /// Pages freed by deletions, reused before the file grows
+    pub free_pages: u64,
+}
+
+impl PageStats {
+    /// Share of the file held by free pages, in percent.
+    pub fn fragmentation(&self) -> f64 {
+        if self.pages == 0 {
+            0.0
+        } else {
+            self.free_pages as f64 * 100.0 / self.pages as f64
+        }
+    }
+}
+
+/// Snapshots of the files below one directory.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct DirectoryTotals {
+    /// The directory
+    pub path: PathBuf,
+    /// Number of snapshots
+    pub snapshots: usize,
+    /// Number of distinct files with snapshots
+    pub files: usize,
+    /// Date of the newest snapshot
+    pub newest: String,
+    /// Sum of the original sizes of the snapshots, in bytes
+    pub size: i64,
+}
+
+pub type SnapshotWithId = (i64, PathBuf, String, i64, String);
+type ApiKeyRow = (i64, String, String, String, Option<String>);
+type ContentMatchRow = (PathBuf, String, i64, String, String);
 
 impl Database {
     /// Clears all snapshots for a specific directory and its subdirectories.
@@ -32,13 +217,30 @@ impl Database {
     ///
     /// Returns an error if the database operation fails.
     pub fn clear_directory_snapshots<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
-        let dir_pattern = format!("{}/%", dir.as_ref().to_string_lossy());
-        let dir_path = dir.as_ref().display().to_string();
+        let dir = dir.as_ref();
+        let dir_path = self.stored(dir);
 
+        // Paths below the directory sort between `dir/` and `dir0`, `0`
+        // following `/`; unlike LIKE, this leaves `%` and `_` alone
         let count = self.conn.execute(
-            "DELETE FROM snapshots WHERE path LIKE ? OR path = ?",
-            params![dir_pattern, dir_path],
+            &format!(
+                "DELETE FROM snapshots WHERE (path = ?1 OR {}) AND pinned = 0",
+                self.below("path", "?1", Some(dir))
+            ),
+            params![dir_path],
         )?;
+        let sessions = format!("root = ?1 OR {}", self.below("root", "?1", Some(dir)));
+        for table in ["session_files", "session_dirs"] {
+            self.conn.execute(
+                &format!(
+                    "DELETE FROM {} WHERE session_id IN
+                     (SELECT id FROM sessions WHERE {})",
+                    table, sessions
+                ),
+                params![dir_path],
+            )?;
+        }
+        self.conn.execute(&format!("DELETE FROM sessions WHERE {}", sessions), params![dir_path])?;
 
         if count == 0 {
             println!(
@@ -56,65 +258,235 @@ impl Database {
         }
         Ok(())
     }
-    /// Removes storage files that are no longer referenced by any snapshot.
+    /// Removes the stored contents that no snapshot refers to any more.
+    ///
+    /// Runs after every deletion. Only the files whose count in the
+    /// `contents` table dropped to zero are looked at; those still serving
+    /// as the base of a delta stay until the delta goes, and chunks go with
+    /// the last manifest using them.
+    ///
+    /// # Returns
+    ///
+    /// The number of files removed and the bytes they used
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a manifest or delta cannot be read or a file
+    /// cannot be removed.
+    pub fn cleanup_orphaned_files(&self) -> Result<(usize, u64)> {
+        let tx = self.conn.unchecked_transaction()?;
+        let content_paths = |sql: &str| -> Result<Vec<PathBuf>> {
+            let mut stmt = tx.prepare(sql)?;
+            let paths = stmt
+                .query_map([], |row| Ok(self.resolved(row.get(0)?)))?
+                .collect::<Result<_, _>>()?;
+            Ok(paths)
+        };
+        // The indexed text of contents no snapshot has any more
+        tx.execute_batch(
+            "DELETE FROM content_fts WHERE rowid IN
+                 (SELECT id FROM content_texts WHERE checksum NOT IN (SELECT checksum FROM snapshots));
+             DELETE FROM content_texts WHERE checksum NOT IN (SELECT checksum FROM snapshots);",
+        )?;
+        let unused = content_paths("SELECT content_path FROM contents WHERE refs <= 0")?;
+        if unused.is_empty() {
+            tx.commit()?;
+            return Ok((0, 0));
+        }
+        let used = content_paths("SELECT content_path FROM contents WHERE refs > 0")?;
+
+        let mut bases = HashSet::new();
+        for path in used.iter().filter(|path| crate::delta::is_delta(path) && path.exists()) {
+            bases.extend(crate::delta::base_files(path)?);
+        }
+
+        let storage_dir = Self::storage_dir()?;
+        let mut removed = 0;
+        let mut reclaimed = 0;
+        let mut remove = |path: &Path| -> Result<()> {
+            reclaimed += fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            fs::remove_file(path)?;
+            removed += 1;
+            Ok(())
+        };
+        let mut chunks = HashSet::new();
+        for path in unused.iter().filter(|path| !bases.contains(*path)) {
+            tx.execute(
+                "DELETE FROM contents WHERE content_path = ?",
+                params![self.stored(path)],
+            )?;
+            // Symbolic links and contents moved out of storage have no file to remove
+            if !path.starts_with(&storage_dir) || !path.is_file() {
+                continue;
+            }
+            if crate::chunks::is_manifest(path) {
+                chunks.extend(crate::chunks::chunk_files(path)?);
+            }
+            remove(path)?;
+        }
+        if !chunks.is_empty() {
+            for path in used.iter().filter(|path| crate::chunks::is_manifest(path) && path.exists()) {
+                for chunk in crate::chunks::chunk_files(path)? {
+                    chunks.remove(&chunk);
+                }
+            }
+            for chunk in chunks.iter().filter(|chunk| chunk.is_file()) {
+                remove(chunk)?;
+            }
+        }
+        tx.commit()?;
+        Ok((removed, reclaimed))
+    }
+
+    /// Removes every storage file no snapshot refers to, after counting the
+    /// references of each content again.
     ///
-    /// This is a private method used internally to clean up unused storage files.
+    /// Unlike [`Database::cleanup_orphaned_files`], reads the whole storage
+    /// directory, so it also catches files the counts do not know of, such
+    /// as those of interrupted operations. Runs on `freeze gc`.
+    ///
+    /// # Returns
+    ///
+    /// The number of files removed and the bytes they used
     ///
     /// # Errors
     ///
     /// Returns an error if reading the storage directory or removing files fails.
-    fn cleanup_orphaned_files(&self) -> Result<()> {
+    pub fn sweep_orphaned_files(&self) -> Result<(usize, u64)> {
+        Self::count_contents(&self.conn)?;
         let mut stmt = self
             .conn
             .prepare("SELECT content_path FROM snapshots GROUP BY content_path")?;
 
-        let used_files: Vec<String> = stmt
-            .query_map([], |row| row.get::<_, String>(0))?
+        let mut used_files: HashSet<String> = stmt
+            .query_map([], |row| Ok(self.resolved(row.get(0)?).display().to_string()))?
             .collect::<Result<_, _>>()?;
 
-        let storage_dir = dirs::home_dir()
-            .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?
-            .join(".freeze/storage");
+        let storage_dir = Self::storage_dir()?;
+        if !storage_dir.exists() {
+            return Ok((0, 0));
+        }
+
+        // Chunks are used through the manifests of used contents, and bases
+        // through deltas; a manifest or delta that cannot be read stops the
+        // cleanup rather than lose what it refers to
+        let mut used_chunks = HashSet::new();
+        let mut used_bases = Vec::new();
+        for content_path in &used_files {
+            let content_path = Path::new(content_path);
+            if !content_path.exists() {
+                continue;
+            }
+            if crate::chunks::is_manifest(content_path) {
+                used_chunks.extend(crate::chunks::chunk_files(content_path)?);
+            } else if crate::delta::is_delta(content_path) {
+                used_bases.extend(crate::delta::base_files(content_path)?);
+            }
+        }
+        used_files.extend(used_bases.iter().map(|base| base.display().to_string()));
 
-        for entry in fs::read_dir(storage_dir)? {
+        let mut removed = 0;
+        let mut reclaimed = 0;
+        for entry in fs::read_dir(&storage_dir)? {
             let entry = entry?;
             let path = entry.path();
-            if !used_files.contains(&path.display().to_string()) {
+            // Temporary files are left to `Snapshot::cleanup_temp_files`, as
+            // another process may still be writing them
+            let temporary = path.extension().is_some_and(|extension| extension == "tmp");
+            if entry.file_type()?.is_file() && !temporary && !used_files.contains(&path.display().to_string()) {
+                reclaimed += entry.metadata().map(|m| m.len()).unwrap_or(0);
                 fs::remove_file(path)?;
+                removed += 1;
             }
         }
-        Ok(())
+
+        let chunks_dir = storage_dir.join(crate::chunks::CHUNKS_DIR);
+        if chunks_dir.exists() {
+            for entry in fs::read_dir(&chunks_dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                if !used_chunks.contains(&path) {
+                    reclaimed += entry.metadata().map(|m| m.len()).unwrap_or(0);
+                    fs::remove_file(path)?;
+                    removed += 1;
+                }
+            }
+        }
+        Ok((removed, reclaimed))
     }
     /// Searches for snapshots by path pattern.
     ///
     /// # Arguments
     ///
     /// * `pattern` - The search pattern to match against snapshot paths
+    /// * `filter` - Further criteria the snapshots must meet
     ///
     /// # Returns
     ///
-    /// A vector of tuples containing (path, date, size, checksum) for matching snapshots
+    /// A vector of tuples containing (id, path, date, size, checksum) for
+    /// matching snapshots, newest first
     ///
     /// # Errors
     ///
     /// Returns an error if the database query fails.
-    pub fn search_snapshots(&self, pattern: &str) -> Result<Vec<(PathBuf, String, i64, String)>> {
-        let search_pattern = format!("%{}%", pattern);
+    pub fn search_snapshots(
+        &self,
+        pattern: &str,
+        filter: &SearchFilter,
+    ) -> Result<Vec<SnapshotWithId>> {
+        // A path of the project is looked for the way it is stored
+        let search_pattern = format!("%{}%", escape_like(&self.stored(Path::new(pattern))));
+        let (tag_key, tag_value) = match &filter.tag {
+            Some((key, value)) => (Some(key.as_str()), value.as_deref()),
+            None => (None, None),
+        };
+        // Ranks each path's matches by date, so `latest` keeps the first
         let mut stmt = self.conn.prepare(
-            "SELECT DISTINCT path, date, size, checksum
-             FROM snapshots
-             WHERE path LIKE ?
-             ORDER BY date DESC",
+            "SELECT id, path, date, size, checksum FROM (
+                 SELECT id, path, date, size, checksum,
+                        ROW_NUMBER() OVER (PARTITION BY path ORDER BY julianday(date) DESC, id DESC) AS rank
+                 FROM snapshots s
+                 WHERE path LIKE ?1 ESCAPE '\\'
+                   AND (?2 IS NULL OR mime = ?2 OR (mime >= ?2 || '/' AND mime < ?2 || '0'))
+                   AND (?3 IS NULL OR julianday(date) >= julianday(?3))
+                   AND (?4 IS NULL OR julianday(date) <= julianday(?4))
+                   AND (?5 IS NULL OR size > ?5)
+                   AND (?6 IS NULL OR size < ?6)
+                   AND (?7 IS NULL OR substr(checksum, 1, length(?7)) = ?7)
+                   AND (?8 IS NULL OR lower(substr(path, -length(?8) - 1)) = lower('.' || ?8))
+                   AND (?9 IS NULL OR EXISTS (
+                       SELECT 1 FROM snapshot_metadata m
+                       WHERE m.snapshot_id = s.id AND m.key = ?9 AND (?10 IS NULL OR m.value = ?10)
+                   ))
+             )
+             WHERE NOT ?11 OR rank = 1
+             ORDER BY julianday(date) DESC",
         )?;
 
-        let snapshot_iter = stmt.query_map(params![search_pattern], |row| {
-            Ok((
-                PathBuf::from(row.get::<_, String>(0)?),
-                row.get::<_, String>(1)?,
-                row.get::<_, i64>(2)?,
-                row.get::<_, String>(3)?,
-            ))
-        })?;
+        let snapshot_iter = stmt.query_map(
+            params![
+                search_pattern,
+                filter.file_type,
+                filter.since.map(|date| date.to_rfc3339()),
+                filter.until.map(|date| date.to_rfc3339()),
+                filter.larger_than,
+                filter.smaller_than,
+                filter.checksum,
+                filter.extension,
+                tag_key,
+                tag_value,
+                filter.latest,
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    self.resolved(row.get(1)?),
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            },
+        )?;
 
         let mut snapshots = Vec::new();
         for snapshot in snapshot_iter {
@@ -122,6 +494,118 @@ impl Database {
         }
         Ok(snapshots)
     }
+    /// Returns `true` if the text of the content with `checksum` is indexed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn is_content_indexed(&self, checksum: &str) -> Result<bool> {
+        Ok(self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM content_texts WHERE checksum = ?)",
+            params![checksum],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Adds the text of a content to the full-text index.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content is already indexed or the database
+    /// operation fails.
+    pub fn index_content(&self, checksum: &str, text: &str) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("INSERT INTO content_texts (checksum) VALUES (?)", params![checksum])?;
+        tx.execute(
+            "INSERT INTO content_fts (rowid, body) VALUES (?, ?)",
+            params![tx.last_insert_rowid(), text],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Lists the checksums of the contents not in the full-text index, other
+    /// than symbolic links and contents larger than `max_size` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn unindexed_checksums(&self, max_size: i64) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT checksum FROM snapshots
+             WHERE symlink IS NULL AND size <= ?
+               AND checksum NOT IN (SELECT checksum FROM content_texts)",
+        )?;
+        let checksums = stmt
+            .query_map(params![max_size], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(checksums)
+    }
+
+    /// Searches the full-text index.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - FTS5 query
+    /// * `file_type` - Only snapshots of this MIME type, or of this top-level
+    ///   type such as `text`
+    /// * `limit` - Most snapshots returned
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (path, date, size, checksum, snippet),
+    /// best matches first, the matched terms of each snippet between `**`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query is invalid or the database query fails.
+    pub fn search_content(
+        &self,
+        query: &str,
+        file_type: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<ContentMatchRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT s.path, s.date, s.size, s.checksum, snippet(content_fts, 0, '**', '**', '…', 12)
+             FROM content_fts
+             JOIN content_texts t ON t.id = content_fts.rowid
+             JOIN snapshots s ON s.checksum = t.checksum
+             WHERE content_fts MATCH ?1
+               AND (?2 IS NULL OR s.mime = ?2 OR (s.mime >= ?2 || '/' AND s.mime < ?2 || '0'))
+             ORDER BY rank, s.date DESC
+             LIMIT ?3",
+        )?;
+        let matches = stmt
+            .query_map(params![query, file_type, limit as i64], |row| {
+                Ok((
+                    self.resolved(row.get(0)?),
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(matches)
+    }
+
+    /// Empties the full-text index.
+    ///
+    /// # Returns
+    ///
+    /// The number of contents removed from the index
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub fn drop_content_index(&self) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let count = tx.execute("DELETE FROM content_texts", [])?;
+        tx.execute("DELETE FROM content_fts", [])?;
+        tx.commit()?;
+        Ok(count)
+    }
+
     /// Lists all snapshots within a specific directory.
     ///
     /// # Arguments
@@ -139,18 +623,18 @@ impl Database {
         &self,
         dir: P,
     ) -> Result<Vec<(PathBuf, String, i64, String)>> {
-        let dir_pattern = format!("{}/%", dir.as_ref().to_string_lossy());
-        let mut stmt = self.conn.prepare(
+        let mut stmt = self.conn.prepare(&format!(
             "SELECT DISTINCT path, date, size, checksum FROM snapshots
-             WHERE path LIKE ? OR path = ?
+             WHERE path = ?1 OR {}
              ORDER BY path, date DESC",
-        )?;
+            self.below("path", "?1", Some(dir.as_ref()))
+        ))?;
 
         let snapshot_iter = stmt.query_map(
-            params![dir_pattern, dir.as_ref().display().to_string()],
+            params![self.stored(dir.as_ref())],
             |row| {
                 Ok((
-                    PathBuf::from(row.get::<_, String>(0)?),
+                    self.resolved(row.get(0)?),
                     row.get::<_, String>(1)?,
                     row.get::<_, i64>(2)?,
                     row.get::<_, String>(3)?,
@@ -165,6 +649,37 @@ impl Database {
         Ok(snapshots)
     }
 
+    /// Retrieves the latest snapshot of each file below a directory, or of
+    /// the directory itself when it is a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory path
+    ///
+    /// # Returns
+    ///
+    /// The latest snapshot of each path, by path
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn latest_snapshots_in_dir<P: AsRef<Path>>(&self, dir: P) -> Result<HashMap<PathBuf, Snapshot>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT path, content_path, checksum, date, size, message, mtime, mode, symlink, xattrs, mime, hash, stored_size FROM snapshots
+             WHERE path = ?1 OR {}
+             ORDER BY path, date DESC",
+            self.below("path", "?1", Some(dir.as_ref()))
+        ))?;
+        let rows = stmt.query_map(params![self.stored(dir.as_ref())], |row| self.snapshot_from_row(row))?;
+
+        let mut latest = HashMap::new();
+        for snapshot in rows {
+            let snapshot = snapshot?;
+            latest.entry(snapshot.path.clone()).or_insert(snapshot);
+        }
+        Ok(latest)
+    }
+
     /// Clears all snapshots for a specific path.
     ///
     /// # Arguments
@@ -175,13 +690,13 @@ impl Database {
     ///
     /// Returns an error if the database operation fails.
     pub fn clear_snapshots<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let path_str = path.as_ref().display().to_string();
+        let path_str = self.stored(path.as_ref());
         let deleted = self
             .conn
-            .execute("DELETE FROM snapshots WHERE path = ?", params![path_str])?;
+            .execute("DELETE FROM snapshots WHERE path = ? AND pinned = 0", params![path_str])?;
 
         if deleted > 0 {
-            self.cleanup_orphaned_files()?; // Nettoyage ajouté ici
+            self.cleanup_orphaned_files()?;
         }
         Ok(())
     }
@@ -199,477 +714,2750 @@ impl Database {
     /// - The database cannot be opened
     /// - The schema cannot be initialized
     pub fn new() -> Result<Self> {
-        let data_dir = dirs::home_dir()
-            .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?
-            .join(".freeze");
+        let data_dir = Self::data_dir()?;
         std::fs::create_dir_all(&data_dir)?;
 
-        let db_path = data_dir.join("data.sql");
-        let conn = Connection::open(db_path)?;
+        let conn = if Self::is_ephemeral() {
+            // Every connection of the process shares the same in-memory database,
+            // which lives as long as at least one connection is open.
+            Connection::open_with_flags(
+                format!("file:freeze-{}?mode=memory&cache=shared", std::process::id()),
+                OpenFlags::SQLITE_OPEN_READ_WRITE
+                    | OpenFlags::SQLITE_OPEN_CREATE
+                    | OpenFlags::SQLITE_OPEN_URI,
+            )?
+        } else {
+            Self::open_file(&Self::db_path()?)?
+        };
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Self::init_schema(&conn)?;
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS snapshots (
-                id INTEGER PRIMARY KEY,
-                path TEXT NOT NULL,
-                content_path TEXT NOT NULL,
-                checksum TEXT NOT NULL,
-                date TEXT NOT NULL,
-                size INTEGER NOT NULL
-            )",
-            [],
-        )?;
+        let mut db = Database { conn, root: None };
+        db.apply_path_mode()?;
+        Ok(db)
+    }
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS exclusions (
-                id INTEGER PRIMARY KEY,
-                pattern TEXT NOT NULL,
-                type TEXT NOT NULL
-            )",
-            [],
-        )?;
+    /// Returns the project holding the repository: the parent of its data
+    /// directory, or `None` for the ephemeral database.
+    fn project_root() -> Result<Option<PathBuf>> {
+        if Self::is_ephemeral() {
+            return Ok(None);
+        }
+        Ok(Self::data_dir()?
+            .parent()
+            .filter(|root| root.parent().is_some())
+            .map(Path::to_path_buf))
+    }
 
-        Ok(Database { conn })
+    /// Returns `true` if the database stores the paths under the project
+    /// relative to it.
+    fn has_relative_paths(conn: &Connection) -> Result<bool> {
+        let mode: Option<String> = conn
+            .query_row("SELECT value FROM repository WHERE key = 'paths'", [], |row| row.get(0))
+            .optional()?;
+        Ok(mode.as_deref() == Some("relative"))
     }
 
-    /// Saves a snapshot to the database.
-    ///
-    /// # Arguments
+    /// Rewrites the paths under `root` as `./...` if `relative`, or the
+    /// `./...` paths back below `root` otherwise, and records the mode.
     ///
-    /// * `snapshot` - Reference to the snapshot to save
+    /// The stored contents are counted again under their new paths.
+    fn convert_paths(conn: &Connection, root: &Path, relative: bool) -> Result<()> {
+        let tx = conn.unchecked_transaction()?;
+        for (table, column) in PATH_COLUMNS {
+            let sql = if relative {
+                format!(
+                    "UPDATE {table} SET {column} = '.' || substr({column}, length(?1) + 1)
+                     WHERE {column} = ?1 OR ({column} >= ?1 || '/' AND {column} < ?1 || '0')"
+                )
+            } else {
+                format!(
+                    "UPDATE {table} SET {column} = ?1 || substr({column}, 2)
+                     WHERE {column} = '.' OR ({column} >= './' AND {column} < '.0')"
+                )
+            };
+            tx.execute(&sql, params![root.to_string_lossy()])?;
+        }
+        // The triggers followed the snapshots to their new content paths,
+        // leaving the former ones behind with no reference
+        tx.execute_batch(
+            "DELETE FROM contents;
+             INSERT INTO contents (content_path, checksum, refs, stored_size)
+                 SELECT content_path, MIN(checksum), COUNT(*), MAX(stored_size)
+                 FROM snapshots GROUP BY content_path;",
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO repository (key, value) VALUES ('paths', ?)",
+            params![if relative { "relative" } else { "absolute" }],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Converts the stored paths if the `relative_paths` setting changed,
+    /// unless changes are refused, then reads and writes paths the way the
+    /// database stores them.
+    fn apply_path_mode(&mut self) -> Result<()> {
+        let root = Self::project_root()?;
+        let mut relative = Self::has_relative_paths(&self.conn)?;
+        let wanted = crate::config::get().relative_paths() && root.is_some();
+        if relative != wanted
+            && !crate::config::get().read_only()
+            && let Some(root) = &root
+        {
+            Self::convert_paths(&self.conn, root, wanted)?;
+            relative = wanted;
+        }
+        self.root = root.filter(|_| relative);
+        Ok(())
+    }
+
+    /// Returns the text stored for `path`: relative to the project, as
+    /// `./...`, when the database stores relative paths and the path is
+    /// in the project.
+    fn stored(&self, path: &Path) -> String {
+        match self.root.as_deref().map(|root| path.strip_prefix(root)) {
+            Some(Ok(rest)) if rest.as_os_str().is_empty() => ".".to_string(),
+            Some(Ok(rest)) => format!("./{}", rest.display()),
+            _ => path.display().to_string(),
+        }
+    }
+
+    /// Returns the path of a stored text, the inverse of [`Database::stored`].
+    fn resolved(&self, stored: String) -> PathBuf {
+        match (&self.root, stored.strip_prefix('.')) {
+            (Some(root), Some("")) => root.clone(),
+            (Some(root), Some(rest)) if rest.starts_with('/') => root.join(&rest[1..]),
+            _ => PathBuf::from(stored),
+        }
+    }
+
+    /// Returns the SQL condition matching the `column` paths below the
+    /// directory passed as `param`. When the database stores relative paths
+    /// and the directory holds the project, the paths stored relative to it
+    /// match too.
+    fn below(&self, column: &str, param: &str, dir: Option<&Path>) -> String {
+        let condition = format!("({column} >= {param} || '/' AND {column} < {param} || '0')");
+        match (&self.root, dir) {
+            (Some(root), Some(dir)) if root != dir && root.starts_with(dir) => format!(
+                "{condition} OR {column} = '.' OR ({column} >= './' AND {column} < '.0')"
+            ),
+            _ => condition,
+        }
+    }
+
+    /// Reads a snapshot from a row selecting
+    /// `path, content_path, checksum, date, size, message, mtime, mode, symlink, xattrs, mime, hash, stored_size`.
     ///
-    /// # Errors
+    /// Snapshots without a recorded algorithm predate the setting and use SHA-256.
+    fn snapshot_from_row(&self, row: &rusqlite::Row) -> rusqlite::Result<Snapshot> {
+        Ok(Snapshot {
+            path: self.resolved(row.get(0)?),
+            content_path: self.resolved(row.get(1)?),
+            checksum: row.get(2)?,
+            date: row.get(3)?,
+            size: row.get(4)?,
+            message: row.get(5)?,
+            mtime: row.get(6)?,
+            mode: row.get(7)?,
+            symlink: row.get::<_, Option<String>>(8)?.map(PathBuf::from),
+            xattrs: row
+                .get::<_, Option<String>>(9)?
+                .map(|json| crate::xattrs::from_json(&json))
+                .transpose()
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(9, rusqlite::types::Type::Text, e.into()))?,
+            mime: row.get(10)?,
+            hash: match row.get::<_, Option<String>>(11)? {
+                None => HashAlgorithm::Sha256,
+                Some(name) => HashAlgorithm::from_name(&name).ok_or_else(|| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        11,
+                        rusqlite::types::Type::Text,
+                        format!("Unknown hash algorithm: {}", name).into(),
+                    )
+                })?,
+            },
+            stored_size: row.get(12)?,
+        })
+    }
+
+    /// Opens a database file in write-ahead log mode.
     ///
-    /// Returns an error if the database insert operation fails.
-    pub fn save_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
-        // Check if a snapshot with the same checksum already exists for this path
-        let existing: Option<String> = self
-            .conn
-            .query_row(
-                "SELECT checksum FROM snapshots WHERE path = ?1 AND checksum = ?2 LIMIT 1",
-                params![snapshot.path.to_string_lossy(), snapshot.checksum],
-                |row| row.get(0),
-            )
-            .ok()
-            .flatten();
+    /// Readers and the writer do not block each other, so the CLI, the
+    /// servers and the daemon can share the file; a write only waits for
+    /// the previous one.
+    fn open_file(path: &Path) -> Result<Connection> {
+        let conn = Self::open_keyed(path, OpenFlags::default())?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        Ok(conn)
+    }
 
-        if existing.is_some() {
-            // Snapshot with identical content already exists, skip saving
-            return Ok(());
+    /// Opens a database file of the repository, with its SQLCipher key if
+    /// the database is encrypted.
+    fn open_keyed(path: &Path, flags: OpenFlags) -> Result<Connection> {
+        let conn = Connection::open_with_flags(path, flags)?;
+        if let Some(key) = crate::crypto::database_key()? {
+            // Must come before anything reads the file
+            conn.pragma_update(None, "key", key)?;
         }
-
-        self.conn.execute(
-            "INSERT INTO snapshots (path, content_path, checksum, date, size) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![
-                snapshot.path.to_string_lossy(),
-                snapshot.content_path.to_string_lossy(),
-                snapshot.checksum,
-                snapshot.date,
-                snapshot.size,
-            ],
-        )?;
-        Ok(())
+        Ok(conn)
     }
 
-    /// Retrieves all snapshots for a specific path.
+    /// Selects the database used by every later `Database::new` call.
     ///
-    /// # Arguments
+    /// `:memory:` selects an ephemeral database whose storage lives in a
+    /// temporary directory; any other value is the path of a database file.
+    /// Must be called before the first database access.
     ///
-    /// * `path` - The file path to retrieve snapshots for
+    /// # Errors
     ///
-    /// # Returns
+    /// Returns an error if the location was already set or the current
+    /// directory cannot be determined.
+    pub fn set_location(spec: &str) -> Result<()> {
+        let location = if spec == MEMORY_DB {
+            Location::Memory
+        } else {
+            Location::File(std::env::current_dir()?.join(spec))
+        };
+        LOCATION
+            .set(location)
+            .map_err(|_| anyhow::anyhow!("Database location is already set"))
+    }
+
+    /// Selects the repository directory, holding the configuration file and,
+    /// unless `--db` says otherwise, the database and its storage.
+    /// Must be called before the configuration is loaded.
     ///
-    /// A vector of `Snapshot` instances for the given path, ordered by date descending
+    /// # Errors
+    ///
+    /// Returns an error if the directory was already set or the current
+    /// directory cannot be determined.
+    pub fn set_home(dir: &Path) -> Result<()> {
+        HOME.set(std::env::current_dir()?.join(dir))
+            .map_err(|_| anyhow::anyhow!("Repository directory is already set"))
+    }
+
+    /// Returns the repository directory: the one selected with
+    /// [`Database::set_home`], else `~/.freeze` if it exists, else `freeze`
+    /// in the platform data directory (`$XDG_DATA_HOME`, by default
+    /// `~/.local/share`, on Linux).
     ///
     /// # Errors
     ///
-    /// Returns an error if the database query fails.
-    pub fn get_snapshots_for_path<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Snapshot>> {
-        let path_str = path.as_ref().display().to_string();
-        let mut stmt = self.conn.prepare(
-            "SELECT path, content_path, checksum, date, size FROM snapshots WHERE path = ? ORDER BY date DESC"
-        )?;
+    /// Returns an error if the home directory cannot be determined.
+    pub fn home() -> Result<PathBuf> {
+        if let Some(home) = HOME.get() {
+            return Ok(home.clone());
+        }
+        let legacy = dirs::home_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?
+            .join(".freeze");
+        match dirs::data_dir() {
+            Some(data_dir) if !legacy.exists() => Ok(data_dir.join("freeze")),
+            _ => Ok(legacy),
+        }
+    }
 
-        let snapshot_iter = stmt.query_map(params![path_str], |row| {
-            Ok(Snapshot {
-                path: PathBuf::from(row.get::<_, String>(0)?),
-                content_path: PathBuf::from(row.get::<_, String>(1)?),
-                checksum: row.get(2)?,
-                date: row.get(3)?,
-                size: row.get(4)?,
-            })
-        })?;
+    /// Returns `true` when running on the ephemeral in-memory database.
+    pub fn is_ephemeral() -> bool {
+        matches!(LOCATION.get(), Some(Location::Memory))
+    }
 
-        let mut snapshots = Vec::new();
-        for snapshot in snapshot_iter {
-            snapshots.push(snapshot?);
+    /// Removes the temporary storage of the ephemeral database, if any.
+    pub fn cleanup_ephemeral() {
+        if Self::is_ephemeral()
+            && let Ok(dir) = Self::data_dir()
+        {
+            let _ = fs::remove_dir_all(dir);
         }
-        Ok(snapshots)
+    }
+
+    /// Returns the directory holding the freeze database and storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the home directory cannot be determined.
+    pub fn data_dir() -> Result<PathBuf> {
+        match LOCATION.get().unwrap_or(&Location::Default) {
+            Location::Default => Self::home(),
+            Location::File(path) => Ok(path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| PathBuf::from("."))),
+            Location::Memory => {
+                Ok(std::env::temp_dir().join(format!("freeze-memory-{}", std::process::id())))
+            }
+        }
+    }
+
+    /// Returns the path of the SQLite database file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the home directory cannot be determined.
+    pub fn db_path() -> Result<PathBuf> {
+        match LOCATION.get() {
+            Some(Location::File(path)) => Ok(path.clone()),
+            Some(Location::Memory) => Ok(PathBuf::from(MEMORY_DB)),
+            _ => Ok(Self::data_dir()?.join("data.sql")),
+        }
+    }
+
+    /// Returns the repository's own files, which saves and watches never
+    /// snapshot: its whole directory, or with a custom database file, that
+    /// file with its write-ahead log and shared memory, the key file and the
+    /// storage next to it.
+    pub fn own_paths() -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        if let Ok(db_path) = Self::db_path() {
+            for suffix in ["-wal", "-shm"] {
+                let mut name = db_path.clone().into_os_string();
+                name.push(suffix);
+                paths.push(PathBuf::from(name));
+            }
+            paths.push(db_path);
+        }
+        if let Ok(data_dir) = Self::data_dir() {
+            if matches!(LOCATION.get(), Some(Location::File(_))) {
+                paths.push(data_dir.join(crate::crypto::KEY_FILE));
+                paths.push(data_dir.join("storage"));
+            } else {
+                paths.push(data_dir);
+            }
+        }
+        paths
+    }
+
+    /// Returns the directory holding compressed snapshot contents.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the home directory cannot be determined.
+    pub fn storage_dir() -> Result<PathBuf> {
+        Ok(Self::data_dir()?.join("storage"))
+    }
+
+    /// Counts the snapshots using each stored content from scratch.
+    ///
+    /// Contents no snapshot uses keep a count of zero, for the next cleanup
+    /// to remove.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails.
+    fn count_contents(conn: &Connection) -> Result<()> {
+        conn.execute_batch(
+            "BEGIN IMMEDIATE;
+             UPDATE contents SET refs = 0;
+             INSERT OR IGNORE INTO contents (content_path, checksum, refs, stored_size)
+                 SELECT content_path, MIN(checksum), 0, MAX(stored_size)
+                 FROM snapshots GROUP BY content_path;
+             UPDATE contents SET refs = counts.refs
+                 FROM (SELECT content_path, COUNT(*) AS refs FROM snapshots GROUP BY content_path) AS counts
+                 WHERE counts.content_path = contents.content_path;
+             COMMIT;",
+        )?;
+        Ok(())
+    }
+
+    /// Adds `column` to `table`, declared as `decl`, unless it already
+    /// exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table cannot be inspected or altered.
+    fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+            params![table, column],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl), [])?;
+        }
+        Ok(())
+    }
+
+    /// Creates the tables used by freeze if they do not exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a table cannot be created.
+    fn init_schema(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS snapshots (
+                id INTEGER PRIMARY KEY,
+                path TEXT NOT NULL,
+                content_path TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                date TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                message TEXT,
+                mtime INTEGER,
+                mode INTEGER,
+                symlink TEXT,
+                xattrs TEXT,
+                mime TEXT,
+                hash TEXT,
+                stored_size INTEGER,
+                pinned INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+
+        // Databases created by older versions lack the columns added since;
+        // their snapshots get no value (or the default) for them
+        for (column, decl) in [
+            ("message", "TEXT"),
+            ("mtime", "INTEGER"),
+            ("mode", "INTEGER"),
+            ("symlink", "TEXT"),
+            ("xattrs", "TEXT"),
+            ("mime", "TEXT"),
+            ("hash", "TEXT"),
+            ("stored_size", "INTEGER"),
+            ("pinned", "INTEGER NOT NULL DEFAULT 0"),
+        ] {
+            Self::add_column_if_missing(conn, "snapshots", column, decl)?;
+        }
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS exclusions (
+                id INTEGER PRIMARY KEY,
+                pattern TEXT NOT NULL,
+                type TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS api_keys (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                token_hash TEXT NOT NULL UNIQUE,
+                scope TEXT NOT NULL,
+                created TEXT NOT NULL,
+                last_used TEXT
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS aliases (
+                name TEXT PRIMARY KEY,
+                path TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS snapshot_metadata (
+                snapshot_id INTEGER NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (snapshot_id, key)
+            )",
+            [],
+        )?;
+
+        // Metadata goes away with its snapshot, however the snapshot is deleted
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS snapshot_metadata_cleanup
+             AFTER DELETE ON snapshots
+             BEGIN
+                 DELETE FROM snapshot_metadata WHERE snapshot_id = OLD.id;
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id INTEGER PRIMARY KEY,
+                root TEXT NOT NULL,
+                date TEXT NOT NULL,
+                message TEXT
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS session_files (
+                session_id INTEGER NOT NULL,
+                path TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                PRIMARY KEY (session_id, path)
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS session_dirs (
+                session_id INTEGER NOT NULL,
+                path TEXT NOT NULL,
+                mode INTEGER,
+                PRIMARY KEY (session_id, path)
+            )",
+            [],
+        )?;
+
+        // Stored contents with the number of snapshots using them, kept up
+        // to date by triggers so that a deletion finds what it orphaned
+        let has_contents: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'contents')",
+            [],
+            |row| row.get(0),
+        )?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS contents (
+                content_path TEXT PRIMARY KEY,
+                checksum TEXT NOT NULL,
+                refs INTEGER NOT NULL,
+                stored_size INTEGER
+            );
+            CREATE TRIGGER IF NOT EXISTS contents_insert AFTER INSERT ON snapshots BEGIN
+                INSERT OR IGNORE INTO contents (content_path, checksum, refs, stored_size)
+                    VALUES (NEW.content_path, NEW.checksum, 0, NEW.stored_size);
+                UPDATE contents SET refs = refs + 1 WHERE content_path = NEW.content_path;
+            END;
+            CREATE TRIGGER IF NOT EXISTS contents_delete AFTER DELETE ON snapshots BEGIN
+                UPDATE contents SET refs = refs - 1 WHERE content_path = OLD.content_path;
+            END;
+            CREATE TRIGGER IF NOT EXISTS contents_move AFTER UPDATE OF content_path ON snapshots
+            WHEN OLD.content_path IS NOT NEW.content_path BEGIN
+                UPDATE contents SET refs = refs - 1 WHERE content_path = OLD.content_path;
+                INSERT OR IGNORE INTO contents (content_path, checksum, refs, stored_size)
+                    VALUES (NEW.content_path, NEW.checksum, 0, NEW.stored_size);
+                UPDATE contents SET refs = refs + 1 WHERE content_path = NEW.content_path;
+            END;
+            CREATE TRIGGER IF NOT EXISTS contents_stored_size AFTER UPDATE OF stored_size ON snapshots BEGIN
+                UPDATE contents SET stored_size = NEW.stored_size WHERE content_path = NEW.content_path;
+            END;",
+        )?;
+        if !has_contents {
+            Self::count_contents(conn)?;
+        }
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS restores (
+                id INTEGER PRIMARY KEY,
+                root TEXT NOT NULL,
+                date TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // A NULL checksum marks a file the restore created
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS restore_files (
+                restore_id INTEGER NOT NULL,
+                path TEXT NOT NULL,
+                checksum TEXT,
+                PRIMARY KEY (restore_id, path)
+            )",
+            [],
+        )?;
+
+        // Text of indexed contents for `search --content`, once per checksum;
+        // the rows of the FTS5 table have the IDs of `content_texts`
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS content_texts (
+                id INTEGER PRIMARY KEY,
+                checksum TEXT NOT NULL UNIQUE
+            );
+            CREATE VIRTUAL TABLE IF NOT EXISTS content_fts USING fts5(body);",
+        )?;
+
+        // Settings of the database itself, such as how it stores paths
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS repository (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Lookups by path (newest first) and by checksum or checksum prefix
+        // would otherwise scan every snapshot
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS snapshots_path_date ON snapshots (path, date);
+             CREATE INDEX IF NOT EXISTS snapshots_checksum ON snapshots (checksum);
+             CREATE INDEX IF NOT EXISTS session_files_checksum ON session_files (checksum);",
+        )?;
+
+        Ok(())
+    }
+
+    /// Copies the snapshot history of a file or directory under a new path.
+    ///
+    /// Every snapshot of `from` (or of files below it) is duplicated with its
+    /// path rewritten under `to`. Content files are shared, not copied.
+    /// Snapshots already present at the destination with the same checksum
+    /// are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The file or directory whose history is copied
+    /// * `to` - The new path
+    ///
+    /// # Returns
+    ///
+    /// The number of snapshots copied
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub fn copy_history<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> Result<usize> {
+        let rows: Vec<Snapshot> = {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT path, content_path, checksum, date, size, message, mtime, mode, symlink, xattrs, mime, hash, stored_size FROM snapshots
+                 WHERE path = ?1 OR {}
+                 ORDER BY date",
+                self.below("path", "?1", Some(from.as_ref()))
+            ))?;
+            let rows = stmt.query_map(params![self.stored(from.as_ref())], |row| self.snapshot_from_row(row))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let from = from.as_ref().to_string_lossy().to_string();
+        let to = to.as_ref().to_string_lossy().to_string();
+        let tx = self.conn.unchecked_transaction()?;
+        let mut copied = 0;
+        for snapshot in rows {
+            let path = snapshot.path.to_string_lossy();
+            let new_path = self.stored(Path::new(&format!("{}{}", to, &path[from.len()..])));
+            let exists: bool = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM snapshots WHERE path = ?1 AND checksum = ?2)",
+                params![new_path, snapshot.checksum],
+                |row| row.get(0),
+            )?;
+            if exists {
+                continue;
+            }
+            tx.execute(
+                "INSERT INTO snapshots (path, content_path, checksum, date, size, message, mtime, mode, symlink, xattrs, mime, hash, stored_size)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![
+                    new_path,
+                    self.stored(&snapshot.content_path),
+                    snapshot.checksum,
+                    snapshot.date,
+                    snapshot.size,
+                    snapshot.message,
+                    snapshot.mtime,
+                    snapshot.mode,
+                    snapshot.symlink.as_ref().map(|target| target.to_string_lossy()),
+                    snapshot.xattrs.as_ref().map(crate::xattrs::to_json),
+                    snapshot.mime,
+                    snapshot.hash.as_str(),
+                    snapshot.stored_size,
+                ],
+            )?;
+            copied += 1;
+        }
+        tx.commit()?;
+        Ok(copied)
+    }
+
+    /// Saves a snapshot to the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - Reference to the snapshot to save
+    ///
+    /// # Returns
+    ///
+    /// The ID of the new snapshot, or `None` if the path already has a
+    /// snapshot of this content
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database insert operation fails.
+    pub fn save_snapshot(&self, snapshot: &Snapshot) -> Result<Option<i64>> {
+        // Check if a snapshot with the same checksum already exists for this path
+        let existing: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT checksum FROM snapshots WHERE path = ?1 AND checksum = ?2 LIMIT 1",
+                params![self.stored(&snapshot.path), snapshot.checksum],
+                |row| row.get(0),
+            )
+            .ok()
+            .flatten();
+
+        if existing.is_some() {
+            // Snapshot with identical content already exists, skip saving but
+            // remember the file's new modification time, so that the next
+            // save does not hash it again, its new mode and attributes, and
+            // the type and stored size of contents saved before they were
+            // recorded
+            self.conn.execute(
+                "UPDATE snapshots SET mtime = COALESCE(?1, mtime), mode = COALESCE(?2, mode),
+                 xattrs = COALESCE(?3, xattrs), mime = COALESCE(mime, ?4),
+                 stored_size = COALESCE(stored_size, ?7)
+                 WHERE path = ?5 AND checksum = ?6",
+                params![
+                    snapshot.mtime,
+                    snapshot.mode,
+                    snapshot.xattrs.as_ref().map(crate::xattrs::to_json),
+                    snapshot.mime,
+                    self.stored(&snapshot.path),
+                    snapshot.checksum,
+                    snapshot.stored_size
+                ],
+            )?;
+            return Ok(None);
+        }
+
+        self.insert_snapshot(snapshot).map(Some)
+    }
+
+    /// Records a snapshot, even if the same content is already recorded for
+    /// its path.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the new snapshot
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub fn insert_snapshot(&self, snapshot: &Snapshot) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO snapshots (path, content_path, checksum, date, size, message, mtime, mode, symlink, xattrs, mime, hash, stored_size)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                self.stored(&snapshot.path),
+                self.stored(&snapshot.content_path),
+                snapshot.checksum,
+                snapshot.date,
+                snapshot.size,
+                snapshot.message,
+                snapshot.mtime,
+                snapshot.mode,
+                snapshot.symlink.as_ref().map(|target| target.to_string_lossy()),
+                snapshot.xattrs.as_ref().map(crate::xattrs::to_json),
+                snapshot.mime,
+                snapshot.hash.as_str(),
+                snapshot.stored_size,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Replaces the snapshot `id` with `snapshot` in a single transaction.
+    ///
+    /// The row keeps its ID, so its metadata stays attached to it, and the
+    /// sessions holding the replaced content hold the new one instead,
+    /// unless another snapshot of the path still has that content.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no snapshot has that ID or the database update
+    /// fails.
+    pub fn replace_snapshot(&self, id: i64, snapshot: &Snapshot) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let (path, old_checksum): (String, String) = tx
+            .query_row("SELECT path, checksum FROM snapshots WHERE id = ?", params![id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .optional()?
+            .ok_or_else(|| anyhow::anyhow!("Snapshot not found: {}", id))?;
+        tx.execute(
+            "UPDATE snapshots SET content_path = ?2, checksum = ?3, date = ?4, size = ?5, message = ?6,
+             mtime = ?7, mode = ?8, symlink = ?9, xattrs = ?10, mime = ?11, hash = ?12, stored_size = ?13
+             WHERE id = ?1",
+            params![
+                id,
+                self.stored(&snapshot.content_path),
+                snapshot.checksum,
+                snapshot.date,
+                snapshot.size,
+                snapshot.message,
+                snapshot.mtime,
+                snapshot.mode,
+                snapshot.symlink.as_ref().map(|target| target.to_string_lossy()),
+                snapshot.xattrs.as_ref().map(crate::xattrs::to_json),
+                snapshot.mime,
+                snapshot.hash.as_str(),
+                snapshot.stored_size,
+            ],
+        )?;
+        tx.execute(
+            "UPDATE session_files SET checksum = ?3 WHERE path = ?1 AND checksum = ?2
+             AND NOT EXISTS (SELECT 1 FROM snapshots WHERE path = ?1 AND checksum = ?2)",
+            params![path, old_checksum, snapshot.checksum],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Returns the ID of the most recent snapshot row, 0 if there is none.
+    ///
+    /// Snapshots saved afterwards get higher IDs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn last_snapshot_id(&self) -> Result<i64> {
+        let id = self
+            .conn
+            .query_row("SELECT COALESCE(MAX(id), 0) FROM snapshots", [], |row| row.get(0))?;
+        Ok(id)
+    }
+
+    /// Retrieves all snapshots for a specific path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path to retrieve snapshots for
+    ///
+    /// # Returns
+    ///
+    /// A vector of `Snapshot` instances for the given path, ordered by date descending
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn get_snapshots_for_path<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Snapshot>> {
+        let path_str = self.stored(path.as_ref());
+        let mut stmt = self.conn.prepare(
+            "SELECT path, content_path, checksum, date, size, message, mtime, mode, symlink, xattrs, mime, hash, stored_size FROM snapshots WHERE path = ? ORDER BY date DESC, id DESC"
+        )?;
+
+        let snapshot_iter = stmt.query_map(params![path_str], |row| self.snapshot_from_row(row))?;
+
+        let mut snapshots = Vec::new();
+        for snapshot in snapshot_iter {
+            snapshots.push(snapshot?);
+        }
+        Ok(snapshots)
+    }
+
+    /// Retrieves the newest snapshot of a path taken at or before a moment.
+    ///
+    /// Dates are compared as instants, so snapshots recorded under different
+    /// UTC offsets order correctly.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path
+    /// * `as_of` - Latest acceptable snapshot date
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn get_snapshot_as_of<P: AsRef<Path>>(
+        &self,
+        path: P,
+        as_of: &DateTime<FixedOffset>,
+    ) -> Result<Option<Snapshot>> {
+        let snapshot = self
+            .conn
+            .query_row(
+                "SELECT path, content_path, checksum, date, size, message, mtime, mode, symlink, xattrs, mime, hash, stored_size FROM snapshots
+                 WHERE path = ?1 AND julianday(date) <= julianday(?2)
+                 ORDER BY julianday(date) DESC, id DESC
+                 LIMIT 1",
+                params![self.stored(path.as_ref()), as_of.to_rfc3339()],
+                |row| self.snapshot_from_row(row),
+            )
+            .optional()?;
+        Ok(snapshot)
+    }
+
+    /// Retrieves every snapshot, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_all_snapshot_records(&self) -> Result<Vec<Snapshot>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, content_path, checksum, date, size, message, mtime, mode, symlink, xattrs, mime, hash, stored_size FROM snapshots ORDER BY date"
+        )?;
+
+        let snapshot_iter = stmt.query_map([], |row| self.snapshot_from_row(row))?;
+
+        let mut snapshots = Vec::new();
+        for snapshot in snapshot_iter {
+            snapshots.push(snapshot?);
+        }
+        Ok(snapshots)
+    }
+
+    /// Aggregates the snapshots of each path.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (path, snapshot count, total size),
+    /// largest total size first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn path_totals(&self) -> Result<Vec<(PathBuf, usize, i64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, COUNT(*), SUM(size) FROM snapshots
+             GROUP BY path
+             ORDER BY SUM(size) DESC, path",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                self.resolved(row.get(0)?),
+                row.get::<_, i64>(1)? as usize,
+                row.get::<_, i64>(2)?,
+            ))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Aggregates the snapshots below `root` per directory directly inside
+    /// it. Snapshots of files directly inside `root` are counted under
+    /// `root` itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The directory to break down, `/` for the top-level ones
+    /// * `limit` - Most directories returned
+    ///
+    /// # Returns
+    ///
+    /// The totals of each directory, largest total size first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn directory_totals(&self, root: &Path, limit: usize) -> Result<Vec<DirectoryTotals>> {
+        let root = root.to_string_lossy();
+        let root = root.trim_end_matches('/');
+        // With a single MAX(), SQLite takes `date` from the newest row; paths
+        // stored relative to the project are put back below it first
+        let mut stmt = self.conn.prepare(
+            "SELECT CASE WHEN instr(rest, '/') > 0 THEN ?1 || '/' || substr(rest, 1, instr(rest, '/') - 1) ELSE ?1 END AS dir,
+                    COUNT(*), COUNT(DISTINCT path), MAX(julianday(date)), date, SUM(size)
+             FROM (
+                 SELECT substr(path, length(?1) + 2) AS rest, path, date, size FROM (
+                     SELECT CASE WHEN path = '.' OR (path >= './' AND path < '.0') THEN ?3 || substr(path, 2) ELSE path END AS path,
+                            date, size
+                     FROM snapshots
+                 )
+                 WHERE path >= ?1 || '/' AND path < ?1 || '0'
+             )
+             GROUP BY dir
+             ORDER BY SUM(size) DESC, dir
+             LIMIT ?2",
+        )?;
+        let project = self.root.as_ref().map(|root| root.to_string_lossy().to_string());
+        let rows = stmt.query_map(params![root, limit as i64, project], |row| {
+            let path = row.get::<_, String>(0)?;
+            Ok(DirectoryTotals {
+                path: PathBuf::from(if path.is_empty() { "/" } else { &path }),
+                snapshots: row.get::<_, i64>(1)? as usize,
+                files: row.get::<_, i64>(2)? as usize,
+                newest: row.get(4)?,
+                size: row.get(5)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Lists each stored content blob once with its uncompressed size.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (content path, size)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn content_blobs(&self) -> Result<Vec<(PathBuf, i64)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT content_path, MAX(size) FROM snapshots WHERE symlink IS NULL GROUP BY content_path")?;
+        let rows = stmt.query_map([], |row| Ok((self.resolved(row.get(0)?), row.get::<_, i64>(1)?)))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Totals of the stored contents, for the deduplication report.
+    ///
+    /// Symbolic links have no stored content and are left out.
+    ///
+    /// # Returns
+    ///
+    /// The number of snapshots referencing a content, the number of distinct
+    /// contents, the sum of the sizes of the snapshots, the sum of the sizes
+    /// of the distinct contents, and the sum of their measured sizes on disk
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn dedup_totals(&self) -> Result<(usize, usize, i64, i64, i64)> {
+        let totals = self.conn.query_row(
+            "SELECT
+                (SELECT COUNT(*) FROM snapshots WHERE symlink IS NULL),
+                COUNT(*),
+                (SELECT COALESCE(SUM(size), 0) FROM snapshots WHERE symlink IS NULL),
+                COALESCE(SUM(size), 0),
+                COALESCE(SUM(stored_size), 0)
+             FROM (SELECT MAX(size) AS size, MAX(stored_size) AS stored_size
+                   FROM snapshots WHERE symlink IS NULL GROUP BY content_path)",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)? as usize,
+                    row.get::<_, i64>(1)? as usize,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )?;
+        Ok(totals)
+    }
+
+    /// Lists the paths whose snapshots reuse the most already stored content.
+    ///
+    /// Each content counts as stored by its first snapshot; every later
+    /// snapshot of it, of any path, is a deduplicated reference.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Maximum number of paths
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (path, deduplicated snapshots, bytes not
+    /// stored again), most bytes first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn dedup_paths(&self, limit: usize) -> Result<Vec<(PathBuf, usize, i64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT s.path, COUNT(*), SUM(s.size) FROM snapshots s
+             JOIN (SELECT content_path, MIN(id) AS first FROM snapshots
+                   WHERE symlink IS NULL GROUP BY content_path) f
+             ON f.content_path = s.content_path
+             WHERE s.symlink IS NULL AND s.id > f.first
+             GROUP BY s.path
+             ORDER BY SUM(s.size) DESC, s.path
+             LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            Ok((
+                self.resolved(row.get(0)?),
+                row.get::<_, i64>(1)? as usize,
+                row.get::<_, i64>(2)?,
+            ))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Lists the stored contents whose size on disk was never recorded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn unmeasured_blobs(&self) -> Result<Vec<PathBuf>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT content_path FROM snapshots WHERE symlink IS NULL AND stored_size IS NULL",
+        )?;
+        let rows = stmt.query_map([], |row| Ok(self.resolved(row.get(0)?)))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Records the size on disk of a stored content.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails.
+    pub fn set_stored_size(&self, content_path: &Path, stored_size: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE snapshots SET stored_size = ?1 WHERE content_path = ?2",
+            params![stored_size, self.stored(content_path)],
+        )?;
+        Ok(())
     }
 
     /// Retrieves a snapshot by its ID.
     ///
     /// # Arguments
     ///
-    /// * `id` - The snapshot ID
+    /// * `id` - The snapshot ID
+    ///
+    /// # Returns
+    ///
+    /// The snapshot if found
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn get_snapshot_by_id(&self, id: i64) -> Result<Option<Snapshot>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, content_path, checksum, date, size, message, mtime, mode, symlink, xattrs, mime, hash, stored_size FROM snapshots WHERE id = ?",
+        )?;
+
+        let mut snapshots = Vec::new();
+        let iter = stmt.query_map(params![id], |row| self.snapshot_from_row(row))?;
+
+        for snapshot in iter {
+            snapshots.push(snapshot?);
+        }
+
+        Ok(snapshots.into_iter().next())
+    }
+
+    /// Retrieves a snapshot by its checksum.
+    ///
+    /// # Arguments
+    ///
+    /// * `checksum` - The snapshot checksum
+    ///
+    /// # Returns
+    ///
+    /// The snapshot if found
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn get_snapshot_by_checksum(&self, checksum: &str) -> Result<Option<Snapshot>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, content_path, checksum, date, size, message, mtime, mode, symlink, xattrs, mime, hash, stored_size FROM snapshots WHERE checksum = ? LIMIT 1"
+        )?;
+
+        let mut snapshots = Vec::new();
+        let iter = stmt.query_map(params![checksum], |row| self.snapshot_from_row(row))?;
+
+        for snapshot in iter {
+            snapshots.push(snapshot?);
+        }
+
+        Ok(snapshots.into_iter().next())
+    }
+
+    /// Retrieves the snapshot of `path` with a checksum, or another snapshot
+    /// of that content if the path has none.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path
+    /// * `checksum` - The snapshot checksum
+    ///
+    /// # Returns
+    ///
+    /// The snapshot if found
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn get_path_snapshot<P: AsRef<Path>>(&self, path: P, checksum: &str) -> Result<Option<Snapshot>> {
+        let snapshot = self
+            .conn
+            .query_row(
+                "SELECT path, content_path, checksum, date, size, message, mtime, mode, symlink, xattrs, mime, hash, stored_size FROM snapshots
+                 WHERE checksum = ?1
+                 ORDER BY path = ?2 DESC, date DESC
+                 LIMIT 1",
+                params![checksum, self.stored(path.as_ref())],
+                |row| self.snapshot_from_row(row),
+            )
+            .optional()?;
+        Ok(snapshot)
+    }
+
+    /// Lists all snapshots with their IDs.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (id, path, date, size, checksum)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_all_snapshots_with_id(&self) -> Result<Vec<SnapshotWithId>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, path, date, size, checksum FROM snapshots ORDER BY date DESC")?;
+
+        let snapshot_iter = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                self.resolved(row.get(1)?),
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+
+        let mut snapshots = Vec::new();
+        for snapshot in snapshot_iter {
+            snapshots.push(snapshot?);
+        }
+        Ok(snapshots)
+    }
+
+    /// Lists the snapshots a clear deletes: all but the pinned ones, below
+    /// `root` or everywhere.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (id, path, date, size, checksum), newest
+    /// first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn clearable_snapshots(&self, root: Option<&Path>) -> Result<Vec<SnapshotWithId>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, path, date, size, checksum FROM snapshots
+             WHERE pinned = 0 AND (?1 IS NULL OR path = ?1 OR {})
+             ORDER BY julianday(date) DESC",
+            self.below("path", "?1", root)
+        ))?;
+        let snapshots = stmt
+            .query_map(params![root.map(|root| self.stored(root))], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    self.resolved(row.get(1)?),
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(snapshots)
+    }
+
+    /// Lists snapshots for a specific path with IDs.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (id, path, date, size, checksum)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn get_snapshots_for_path_with_id<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Vec<SnapshotWithId>> {
+        let path_str = self.stored(path.as_ref());
+        let mut stmt = self.conn.prepare(
+            "SELECT id, path, date, size, checksum FROM snapshots WHERE path = ? ORDER BY date DESC, id DESC"
+        )?;
+
+        let snapshot_iter = stmt.query_map(params![path_str], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                self.resolved(row.get(1)?),
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+
+        let mut snapshots = Vec::new();
+        for snapshot in snapshot_iter {
+            snapshots.push(snapshot?);
+        }
+        Ok(snapshots)
+    }
+
+    /// Lists all snapshots in the database.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (id, path, date, size, checksum) for all
+    /// snapshots
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_all_snapshots(&self) -> Result<Vec<SnapshotWithId>> {
+        self.list_snapshots(&SnapshotFilter::default())
+    }
+
+    /// Lists the snapshots matching a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Criteria and order of the listing
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (id, path, date, size, checksum)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_snapshots(&self, filter: &SnapshotFilter) -> Result<Vec<SnapshotWithId>> {
+        let order = match filter.sort {
+            SnapshotSort::Date => "julianday(date) DESC",
+            SnapshotSort::Size => "size DESC, julianday(date) DESC",
+            SnapshotSort::Path => "path ASC, julianday(date) DESC",
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, path, date, size, checksum FROM snapshots
+             WHERE (?1 IS NULL OR {})
+               AND (?2 IS NULL OR julianday(date) >= julianday(?2))
+               AND (?3 IS NULL OR julianday(date) <= julianday(?3))
+               AND (?4 IS NULL OR size > ?4)
+               AND (?5 IS NULL OR instr(path, ?5) > 0)
+             ORDER BY {}",
+            self.below("path", "?1", filter.dir.as_deref()),
+            order
+        ))?;
+
+        let snapshot_iter = stmt.query_map(
+            params![
+                filter.dir.as_deref().map(|dir| self.stored(dir)),
+                filter.since.map(|date| date.to_rfc3339()),
+                filter.until.map(|date| date.to_rfc3339()),
+                filter.larger_than,
+                filter.path_contains.as_deref().map(|text| self.stored(Path::new(text))),
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    self.resolved(row.get(1)?),
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            },
+        )?;
+
+        let mut snapshots = Vec::new();
+        for snapshot in snapshot_iter {
+            snapshots.push(snapshot?);
+        }
+        Ok(snapshots)
+    }
+
+    /// Lists all snapshots in the current working directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_dir` - The current directory path
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (id, path, date, size, checksum) for
+    /// snapshots
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_current_directory_snapshots<P: AsRef<Path>>(
+        &self,
+        current_dir: P,
+    ) -> Result<Vec<SnapshotWithId>> {
+        self.list_snapshots(&SnapshotFilter {
+            dir: Some(current_dir.as_ref().to_path_buf()),
+            ..SnapshotFilter::default()
+        })
+    }
+
+    /// Lists all snapshots in the current working directory with IDs.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_dir` - The current directory path
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (id, path, date, size, checksum) for snapshots
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_current_directory_snapshots_with_id<P: AsRef<Path>>(
+        &self,
+        current_dir: P,
+    ) -> Result<Vec<SnapshotWithId>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, path, date, size, checksum FROM snapshots
+             WHERE {}
+             ORDER BY date DESC",
+            self.below("path", "?1", Some(current_dir.as_ref()))
+        ))?;
+
+        let snapshot_iter = stmt.query_map(params![self.stored(current_dir.as_ref())], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                self.resolved(row.get(1)?),
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+
+        let mut snapshots = Vec::new();
+        for snapshot in snapshot_iter {
+            snapshots.push(snapshot?);
+        }
+        Ok(snapshots)
+    }
+
+    /// Clears all snapshots from the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub fn clear_all_snapshots(&self) -> Result<()> {
+        let count = self.conn.execute("DELETE FROM snapshots WHERE pinned = 0", [])?;
+        // Sessions only point at snapshot content, which is gone now
+        self.conn.execute("DELETE FROM session_files", [])?;
+        self.conn.execute("DELETE FROM session_dirs", [])?;
+        self.conn.execute("DELETE FROM sessions", [])?;
+        if count > 0 {
+            self.cleanup_orphaned_files()?;
+        }
+        Ok(())
+    }
+
+    /// Adds an exclusion pattern to the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to exclude (e.g., ".git", "node_modules")
+    /// * `exclusion_type` - The type of exclusion ("directory", "extension", or "file")
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database insert operation fails.
+    pub fn add_exclusion(&self, pattern: &str, exclusion_type: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO exclusions (pattern, type) VALUES (?1, ?2)",
+            params![pattern, exclusion_type],
+        )?;
+        Ok(())
+    }
+
+    /// Removes an exclusion pattern from the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to remove
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database delete operation fails.
+    pub fn remove_exclusion(&self, pattern: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM exclusions WHERE pattern = ?", params![pattern])?;
+        Ok(())
+    }
+
+    /// Lists all exclusion patterns.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (pattern, exclusion_type)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_exclusions(&self) -> Result<Vec<(String, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT pattern, type FROM exclusions ORDER BY type, pattern")?;
+
+        let exclusion_iter = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut exclusions = Vec::new();
+        for exclusion in exclusion_iter {
+            exclusions.push(exclusion?);
+        }
+        Ok(exclusions)
+    }
+
+    /// Gets all exclusion patterns (alias for list_exclusions).
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (pattern, exclusion_type)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn get_exclusions(&self) -> Result<Vec<(String, String)>> {
+        self.list_exclusions()
+    }
+
+    /// Deletes a snapshot by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The snapshot ID to delete
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot is pinned or the database operation
+    /// fails.
+    pub fn delete_snapshot(&self, id: i64) -> Result<()> {
+        let pinned: bool = self
+            .conn
+            .query_row("SELECT pinned FROM snapshots WHERE id = ?", params![id], |row| row.get(0))
+            .optional()?
+            .unwrap_or(false);
+        if pinned {
+            anyhow::bail!("Snapshot {} is pinned, unpin it first", id);
+        }
+        self.conn
+            .execute("DELETE FROM snapshots WHERE id = ?", params![id])?;
+        self.cleanup_orphaned_files()?;
+        Ok(())
+    }
+
+    /// Lists the snapshots a retention rule would remove.
+    ///
+    /// Pinned snapshots never expire, but still count towards `KeepLast`.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - Only consider snapshots of this file or of files below this directory
+    /// * `retention` - The rule deciding which snapshots expire
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (id, path, date, size, checksum), by path
+    /// then newest first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn expired_snapshots(&self, root: Option<&Path>, retention: Retention) -> Result<Vec<SnapshotWithId>> {
+        let below = self.below("path", "?1", root);
+        let root = root.map(|root| self.stored(root));
+        let (condition, value) = match retention {
+            Retention::OlderThan(age) => ("age > ?2", age.num_seconds()),
+            Retention::KeepLast(count) => ("position > ?2", count as i64),
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, path, date, size, checksum FROM (
+                 SELECT id, path, date, size, checksum, pinned,
+                        ROW_NUMBER() OVER (PARTITION BY path ORDER BY julianday(date) DESC, id DESC) AS position,
+                        (julianday('now') - julianday(date)) * 86400 AS age
+                 FROM snapshots
+                 WHERE ?1 IS NULL OR path = ?1 OR {}
+             )
+             WHERE pinned = 0 AND {}
+             ORDER BY path, julianday(date) DESC",
+            below, condition
+        ))?;
+
+        let snapshot_iter = stmt.query_map(params![root, value], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                self.resolved(row.get(1)?),
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+
+        let mut snapshots = Vec::new();
+        for snapshot in snapshot_iter {
+            snapshots.push(snapshot?);
+        }
+        Ok(snapshots)
+    }
+
+    /// Finds the snapshots `freeze rm` and `freeze_delete_snapshot` delete,
+    /// below `root` or everywhere.
+    ///
+    /// Each checksum prefix must match exactly one snapshot, so that a
+    /// content saved several times is deleted by ID, and each ID a
+    /// snapshot; pinned snapshots cannot be chosen.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (id, path, date, size, checksum)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a checksum or ID matches no snapshot, if a
+    /// checksum matches several, if a chosen snapshot is pinned, or if the
+    /// database query fails.
+    pub fn removed_snapshots(
+        &self,
+        root: Option<&Path>,
+        checksums: &[String],
+        ids: &[i64],
+    ) -> Result<Vec<SnapshotWithId>> {
+        let snapshots: Vec<SnapshotWithId> = self
+            .list_all_snapshots_with_id()?
+            .into_iter()
+            .filter(|(_, path, ..)| root.is_none_or(|root| path.starts_with(root)))
+            .collect();
+
+        let mut removed = Vec::new();
+        for prefix in checksums {
+            let matching: Vec<&SnapshotWithId> = snapshots
+                .iter()
+                .filter(|(.., checksum)| checksum.starts_with(prefix.as_str()))
+                .collect();
+            let contents: HashSet<&str> = matching.iter().map(|(.., checksum)| checksum.as_str()).collect();
+            match (contents.len(), matching.as_slice()) {
+                (0, _) => anyhow::bail!(t!("rm-no-checksum", checksum = prefix.as_str())),
+                (1, [snapshot]) => removed.push((*snapshot).clone()),
+                (1, _) => {
+                    let ids: Vec<String> = matching.iter().map(|(id, ..)| id.to_string()).collect();
+                    anyhow::bail!(t!("rm-several", checksum = prefix.as_str(), ids = ids.join(", ")))
+                }
+                (count, _) => anyhow::bail!(t!("rm-ambiguous", checksum = prefix.as_str(), count = count)),
+            }
+        }
+        for id in ids {
+            let snapshot = snapshots
+                .iter()
+                .find(|(snapshot_id, ..)| snapshot_id == id)
+                .ok_or_else(|| anyhow::anyhow!(t!("rm-no-id", id = *id)))?;
+            removed.push(snapshot.clone());
+        }
+
+        let mut seen = HashSet::new();
+        removed.retain(|(id, ..)| seen.insert(*id));
+        let pinned = self.list_pinned()?;
+        if let Some((_, path, _, _, checksum)) = removed.iter().find(|(id, ..)| pinned.contains(id)) {
+            anyhow::bail!(t!(
+                "rm-pinned",
+                checksum = &checksum[..8],
+                path = path.display().to_string()
+            ));
+        }
+        Ok(removed)
+    }
+
+    /// Deletes snapshots by ID, with their metadata, then removes the
+    /// storage files and session entries nothing refers to anymore.
+    ///
+    /// Pinned snapshots are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - IDs of the snapshots to delete
+    ///
+    /// # Returns
+    ///
+    /// The number of snapshots deleted and the bytes of storage freed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation or the storage cleanup fails.
+    pub fn delete_snapshots(&self, ids: &[i64]) -> Result<(usize, u64)> {
+        if ids.is_empty() {
+            return Ok((0, 0));
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let mut deleted = 0;
+        for id in ids {
+            if tx.execute("DELETE FROM snapshots WHERE id = ? AND pinned = 0", params![id])? > 0 {
+                deleted += 1;
+                tx.execute("DELETE FROM snapshot_metadata WHERE snapshot_id = ?", params![id])?;
+            }
+        }
+        tx.execute(
+            "DELETE FROM session_files WHERE checksum NOT IN (SELECT checksum FROM snapshots)",
+            [],
+        )?;
+        tx.execute(
+            "DELETE FROM sessions WHERE id NOT IN (SELECT session_id FROM session_files)",
+            [],
+        )?;
+        tx.execute(
+            "DELETE FROM session_dirs WHERE session_id NOT IN (SELECT id FROM sessions)",
+            [],
+        )?;
+        tx.commit()?;
+
+        let (_, freed) = self.cleanup_orphaned_files()?;
+        Ok((deleted, freed))
+    }
+
+    /// Lists the snapshots whose compressed content is no longer in storage.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (id, path) for each such snapshot
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_missing_content(&self) -> Result<Vec<(i64, PathBuf)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, path, content_path FROM snapshots WHERE symlink IS NULL ORDER BY path, date")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                self.resolved(row.get(1)?),
+                self.resolved(row.get(2)?),
+            ))
+        })?;
+
+        let mut missing = Vec::new();
+        for row in rows {
+            let (id, path, content_path) = row?;
+            if !crate::compression::stored_files(&content_path).iter().all(|file| file.exists()) {
+                missing.push((id, path));
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Deletes the snapshots whose compressed content is no longer in storage.
+    ///
+    /// Session entries pointing at content that no snapshot holds anymore
+    /// are dropped as well.
+    ///
+    /// # Returns
+    ///
+    /// The number of snapshots deleted
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub fn delete_missing_content(&self) -> Result<usize> {
+        let missing = self.list_missing_content()?;
+        for (id, _) in &missing {
+            self.conn
+                .execute("DELETE FROM snapshots WHERE id = ?", params![id])?;
+        }
+        if !missing.is_empty() {
+            self.conn.execute(
+                "DELETE FROM session_files WHERE checksum NOT IN (SELECT checksum FROM snapshots)",
+                [],
+            )?;
+        }
+        Ok(missing.len())
+    }
+
+    /// Points the snapshots stored in `old` at `new`, which holds the same
+    /// content, and records the size of `new` on disk.
+    ///
+    /// # Returns
+    ///
+    /// The number of snapshots updated
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails.
+    pub fn replace_content_path(&self, old: &Path, new: &Path) -> Result<usize> {
+        Ok(self.conn.execute(
+            "UPDATE snapshots SET content_path = ?1, stored_size = ?3 WHERE content_path = ?2",
+            params![
+                self.stored(new),
+                self.stored(old),
+                crate::compression::blob_size(new)
+            ],
+        )?)
+    }
+
+    /// Copies the database to `dest` with the online backup API, consistent
+    /// even while other connections write.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the copy fails.
+    pub fn backup_to(&self, dest: &Path) -> Result<()> {
+        // An encrypted database is copied with its key
+        let mut dest = Self::open_keyed(dest, OpenFlags::default())?;
+        rusqlite::backup::Backup::new(&self.conn, &mut dest)?.run_to_completion(100, std::time::Duration::ZERO, None)?;
+        Ok(())
+    }
+
+    /// Replaces the database with the copy at `src`, then brings its schema
+    /// up to date.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the copy cannot be read or fails.
+    pub fn restore_from(&mut self, src: &Path) -> Result<()> {
+        let src = Self::open_keyed(src, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        rusqlite::backup::Backup::new(&src, &mut self.conn)?.run_to_completion(100, std::time::Duration::ZERO, None)?;
+        Self::init_schema(&self.conn)?;
+        Self::count_contents(&self.conn)?;
+        // The backup may store its paths the other way
+        self.apply_path_mode()
+    }
+
+    /// Opens an in-memory copy of the freeze database at `path`, its schema
+    /// brought up to date, leaving the file untouched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a freeze
+    /// database.
+    pub fn open_copy(path: &Path) -> Result<Self> {
+        // Another repository, never encrypted with the key of this one
+        Self::count_snapshots(&Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?)?;
+        let mut conn = Connection::open_in_memory()?;
+        conn.restore(rusqlite::MAIN_DB, path, None::<fn(rusqlite::backup::Progress)>)?;
+        Self::init_schema(&conn)?;
+        // Relative paths are relative to the project holding the copy
+        let path = std::path::absolute(path)?;
+        if Self::has_relative_paths(&conn)?
+            && let Some(root) = path.parent().and_then(Path::parent)
+        {
+            Self::convert_paths(&conn, root, false)?;
+        }
+        Ok(Database { conn, root: None })
+    }
+
+    /// Counts the snapshots of the freeze database at `path`, such as a
+    /// backup of this one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file is not a freeze database.
+    pub fn count_snapshots_in(path: &Path) -> Result<usize> {
+        Self::count_snapshots(&Self::open_keyed(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?)
+    }
+
+    fn count_snapshots(conn: &Connection) -> Result<usize> {
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM snapshots", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Writes a copy of the database file next to it, read with the
+    /// SQLCipher key `from` and encrypted with `to`, each `None` for a plain
+    /// file. [`Database::replace_file`] then puts the copy in place.
+    ///
+    /// # Returns
+    ///
+    /// The path of the copy
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if SQLCipher is not available, the database is in
+    /// memory, or the copy fails.
+    pub fn copy_rekeyed(from: Option<&str>, to: Option<&str>) -> Result<PathBuf> {
+        if !SQLCIPHER {
+            anyhow::bail!("This build of freeze has no SQLCipher support; rebuild it with `--features sqlcipher`");
+        }
+        if Self::is_ephemeral() {
+            anyhow::bail!("An in-memory database cannot be encrypted");
+        }
+        let path = Self::db_path()?;
+        let copy = Self::sibling(&path, ".tmp");
+        let _ = fs::remove_file(&copy);
+
+        let conn = Connection::open(&path)?;
+        if let Some(key) = from {
+            conn.pragma_update(None, "key", key)?;
+        }
+        conn.execute(
+            "ATTACH DATABASE ?1 AS rekeyed KEY ?2",
+            params![copy.to_string_lossy(), to.unwrap_or_default()],
+        )?;
+        let result = conn
+            .query_row("SELECT sqlcipher_export('rekeyed')", [], |_| Ok(()))
+            .and_then(|_| conn.execute_batch("DETACH DATABASE rekeyed"));
+        if let Err(e) = result {
+            let _ = fs::remove_file(&copy);
+            return Err(e.into());
+        }
+        Ok(copy)
+    }
+
+    /// Replaces the database file with a copy made by
+    /// [`Database::copy_rekeyed`], dropping the write-ahead log of the
+    /// replaced file, whose content the copy already holds.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the copy cannot be moved into place.
+    pub fn replace_file(copy: &Path) -> Result<()> {
+        let path = Self::db_path()?;
+        fs::rename(copy, &path)?;
+        for suffix in ["-wal", "-shm"] {
+            let _ = fs::remove_file(Self::sibling(&path, suffix));
+        }
+        Ok(())
+    }
+
+    /// `path` with `suffix` appended to its file name.
+    fn sibling(path: &Path, suffix: &str) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        path.with_file_name(name)
+    }
+
+    /// Rebuilds the database file to release the space of deleted rows.
+    ///
+    /// The write-ahead log is then written back and emptied, so the file
+    /// shrinks right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `VACUUM` fails.
+    pub fn vacuum(&self) -> Result<()> {
+        self.conn.execute_batch("VACUUM")?;
+        self.conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        Ok(())
+    }
+
+    /// Reports how the database file uses its pages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pragma cannot be read.
+    pub fn page_stats(&self) -> Result<PageStats> {
+        let pragma = |name: &str| -> Result<u64> {
+            let value: i64 = self.conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))?;
+            Ok(value as u64)
+        };
+        Ok(PageStats {
+            page_size: pragma("page_size")?,
+            pages: pragma("page_count")?,
+            free_pages: pragma("freelist_count")?,
+        })
+    }
+
+    /// Checks the database file for corruption.
+    ///
+    /// # Returns
+    ///
+    /// The problems found, none if the file is sound
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the check cannot run.
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("PRAGMA integrity_check")?;
+        let problems = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(problems.into_iter().filter(|problem| problem != "ok").collect())
+    }
+
+    /// Refreshes the statistics the query planner chooses indexes with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `ANALYZE` fails.
+    pub fn analyze(&self) -> Result<()> {
+        self.conn.execute_batch("ANALYZE")?;
+        Ok(())
+    }
+
+    /// Adds or replaces a path alias.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Alias name, used as `@name` on the command line
+    /// * `path` - Absolute path the alias points to
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database insert operation fails.
+    pub fn add_alias<P: AsRef<Path>>(&self, name: &str, path: P) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO aliases (name, path) VALUES (?1, ?2)",
+            params![name, self.stored(path.as_ref())],
+        )?;
+        Ok(())
+    }
+
+    /// Removes a path alias.
     ///
     /// # Returns
     ///
-    /// The snapshot if found
+    /// `true` if an alias was removed, `false` if no alias had that name
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database delete operation fails.
+    pub fn remove_alias(&self, name: &str) -> Result<bool> {
+        let count = self
+            .conn
+            .execute("DELETE FROM aliases WHERE name = ?", params![name])?;
+        Ok(count > 0)
+    }
+
+    /// Looks up the path of an alias.
     ///
     /// # Errors
     ///
     /// Returns an error if the database query fails.
-    pub fn get_snapshot_by_id(&self, id: i64) -> Result<Option<Snapshot>> {
+    pub fn get_alias(&self, name: &str) -> Result<Option<PathBuf>> {
+        let path = self
+            .conn
+            .query_row(
+                "SELECT path FROM aliases WHERE name = ?",
+                params![name],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        Ok(path.map(|path| self.resolved(path)))
+    }
+
+    /// Lists all path aliases.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing (name, path)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_aliases(&self) -> Result<Vec<(String, PathBuf)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name, path FROM aliases ORDER BY name")?;
+
+        let alias_iter = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, self.resolved(row.get(1)?)))
+        })?;
+
+        let mut aliases = Vec::new();
+        for alias in alias_iter {
+            aliases.push(alias?);
+        }
+        Ok(aliases)
+    }
+
+    /// Sets a metadata entry on a snapshot, replacing any previous value.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot_id` - ID of the snapshot
+    /// * `key` - Metadata key
+    /// * `value` - Metadata value
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot does not exist or the insert fails.
+    pub fn set_metadata(&self, snapshot_id: i64, key: &str, value: &str) -> Result<()> {
+        let exists = self
+            .conn
+            .query_row(
+                "SELECT 1 FROM snapshots WHERE id = ?",
+                params![snapshot_id],
+                |_| Ok(()),
+            )
+            .optional()?;
+        if exists.is_none() {
+            anyhow::bail!("Snapshot not found: {}", snapshot_id);
+        }
+
+        self.conn.execute(
+            "INSERT OR REPLACE INTO snapshot_metadata (snapshot_id, key, value) VALUES (?1, ?2, ?3)",
+            params![snapshot_id, key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Looks up a metadata value of a snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn get_metadata(&self, snapshot_id: i64, key: &str) -> Result<Option<String>> {
+        let value = self
+            .conn
+            .query_row(
+                "SELECT value FROM snapshot_metadata WHERE snapshot_id = ?1 AND key = ?2",
+                params![snapshot_id, key],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        Ok(value)
+    }
+
+    /// Returns the highest value of a numeric metadata key per stored content.
+    ///
+    /// # Returns
+    ///
+    /// A map from content path to value, for contents with the key
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn content_levels(&self, key: &str) -> Result<HashMap<PathBuf, i32>> {
         let mut stmt = self.conn.prepare(
-            "SELECT path, content_path, checksum, date, size FROM snapshots WHERE id = ?",
+            "SELECT s.content_path, MAX(CAST(m.value AS INTEGER)) FROM snapshots s
+             JOIN snapshot_metadata m ON m.snapshot_id = s.id AND m.key = ?1
+             GROUP BY s.content_path",
         )?;
+        let rows = stmt.query_map(params![key], |row| {
+            Ok((self.resolved(row.get(0)?), row.get::<_, i32>(1)?))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
 
-        let mut snapshots = Vec::new();
-        let iter = stmt.query_map(params![id], |row| {
-            Ok(Snapshot {
-                path: PathBuf::from(row.get::<_, String>(0)?),
-                content_path: PathBuf::from(row.get::<_, String>(1)?),
-                checksum: row.get(2)?,
-                date: row.get(3)?,
-                size: row.get(4)?,
-            })
+    /// Sets a metadata key on every snapshot stored in `content_path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails.
+    pub fn set_content_metadata(&self, content_path: &Path, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO snapshot_metadata (snapshot_id, key, value)
+             SELECT id, ?2, ?3 FROM snapshots WHERE content_path = ?1",
+            params![self.stored(content_path), key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Lists the metadata of a snapshot.
+    ///
+    /// # Returns
+    ///
+    /// A map of key to value, sorted by key
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_metadata(&self, snapshot_id: i64) -> Result<BTreeMap<String, String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, value FROM snapshot_metadata WHERE snapshot_id = ?")?;
+
+        let entry_iter = stmt.query_map(params![snapshot_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
         })?;
 
-        for snapshot in iter {
-            snapshots.push(snapshot?);
+        let mut metadata = BTreeMap::new();
+        for entry in entry_iter {
+            let (key, value) = entry?;
+            metadata.insert(key, value);
         }
+        Ok(metadata)
+    }
 
-        Ok(snapshots.into_iter().next())
+    /// Lists the metadata of every snapshot that has some.
+    ///
+    /// # Returns
+    ///
+    /// A map of snapshot ID to its metadata
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_all_metadata(&self) -> Result<HashMap<i64, BTreeMap<String, String>>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT snapshot_id, key, value FROM snapshot_metadata")?;
+
+        let entry_iter = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+
+        let mut metadata: HashMap<i64, BTreeMap<String, String>> = HashMap::new();
+        for entry in entry_iter {
+            let (snapshot_id, key, value) = entry?;
+            metadata.entry(snapshot_id).or_default().insert(key, value);
+        }
+        Ok(metadata)
+    }
+
+    /// Removes a metadata entry from a snapshot.
+    ///
+    /// # Returns
+    ///
+    /// `true` if an entry was removed, `false` if the snapshot had no such key
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database delete operation fails.
+    pub fn remove_metadata(&self, snapshot_id: i64, key: &str) -> Result<bool> {
+        let count = self.conn.execute(
+            "DELETE FROM snapshot_metadata WHERE snapshot_id = ?1 AND key = ?2",
+            params![snapshot_id, key],
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Finds the ID of a snapshot by checksum.
+    ///
+    /// Picks the same row as `get_snapshot_by_checksum`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn get_snapshot_id_by_checksum(&self, checksum: &str) -> Result<Option<i64>> {
+        let id = self
+            .conn
+            .query_row(
+                "SELECT id FROM snapshots WHERE checksum = ? LIMIT 1",
+                params![checksum],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?;
+        Ok(id)
+    }
+
+    /// Lists the messages of every snapshot saved with one.
+    ///
+    /// # Returns
+    ///
+    /// A map of snapshot ID to the snapshot message
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_messages(&self) -> Result<HashMap<i64, String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, message FROM snapshots WHERE message IS NOT NULL")?;
+
+        let entry_iter = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut messages = HashMap::new();
+        for entry in entry_iter {
+            let (key, message) = entry?;
+            messages.insert(key, message);
+        }
+        Ok(messages)
+    }
+
+    /// Pins or unpins the snapshots whose checksum starts with `prefix`.
+    ///
+    /// Pinned snapshots are never deleted by `clear`, retention rules or
+    /// `prune`. Every snapshot of the matched content is updated, whatever
+    /// its path.
+    ///
+    /// # Returns
+    ///
+    /// The (path, checksum) of each updated snapshot
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no snapshot matches, if the prefix matches
+    /// several contents, or if the database operation fails.
+    pub fn set_pinned(&self, prefix: &str, pinned: bool) -> Result<Vec<(PathBuf, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT checksum FROM snapshots WHERE checksum >= ?1 AND checksum < ?1 || char(1114111)",
+        )?;
+        let checksums = stmt
+            .query_map(params![prefix], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let checksum = match checksums.as_slice() {
+            [] => anyhow::bail!("No snapshot matches checksum {}", prefix),
+            [checksum] => checksum,
+            _ => anyhow::bail!(
+                "Checksum {} is ambiguous, it matches {} contents",
+                prefix,
+                checksums.len()
+            ),
+        };
+
+        self.conn.execute(
+            "UPDATE snapshots SET pinned = ? WHERE checksum = ?",
+            params![pinned, checksum],
+        )?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT path FROM snapshots WHERE checksum = ? ORDER BY path")?;
+        let paths = stmt
+            .query_map(params![checksum], |row| {
+                Ok((self.resolved(row.get(0)?), checksum.clone()))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(paths)
+    }
+
+    /// Lists the pinned snapshots.
+    ///
+    /// # Returns
+    ///
+    /// The ID of every pinned snapshot
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn list_pinned(&self) -> Result<HashSet<i64>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM snapshots WHERE pinned = 1")?;
+        let pinned = stmt
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<rusqlite::Result<HashSet<_>>>()?;
+        Ok(pinned)
     }
 
-    /// Retrieves a snapshot by its checksum.
+    /// Counts the pinned snapshots of a file or of the files below a
+    /// directory, or everywhere without a root.
     ///
-    /// # Arguments
+    /// # Errors
     ///
-    /// * `checksum` - The snapshot checksum
+    /// Returns an error if the database query fails.
+    pub fn count_pinned(&self, root: Option<&Path>) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM snapshots
+                 WHERE pinned = 1 AND (?1 IS NULL OR path = ?1 OR {})",
+                self.below("path", "?1", root)
+            ),
+            params![root.map(|root| self.stored(root))],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Lists the size on disk of the content of every snapshot whose size
+    /// was recorded.
     ///
     /// # Returns
     ///
-    /// The snapshot if found
+    /// A map of snapshot ID to the stored size in bytes
     ///
     /// # Errors
     ///
     /// Returns an error if the database query fails.
-    pub fn get_snapshot_by_checksum(&self, checksum: &str) -> Result<Option<Snapshot>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT path, content_path, checksum, date, size FROM snapshots WHERE checksum = ? LIMIT 1"
-        )?;
+    pub fn list_stored_sizes(&self) -> Result<HashMap<i64, i64>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, stored_size FROM snapshots WHERE stored_size IS NOT NULL")?;
 
-        let mut snapshots = Vec::new();
-        let iter = stmt.query_map(params![checksum], |row| {
-            Ok(Snapshot {
-                path: PathBuf::from(row.get::<_, String>(0)?),
-                content_path: PathBuf::from(row.get::<_, String>(1)?),
-                checksum: row.get(2)?,
-                date: row.get(3)?,
-                size: row.get(4)?,
-            })
-        })?;
+        let entry_iter = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
 
-        for snapshot in iter {
-            snapshots.push(snapshot?);
+        let mut sizes = HashMap::new();
+        for entry in entry_iter {
+            let (key, size) = entry?;
+            sizes.insert(key, size);
         }
-
-        Ok(snapshots.into_iter().next())
+        Ok(sizes)
     }
 
-    /// Lists all snapshots with their IDs.
+    /// Lists the detected type of every snapshot saved with one.
     ///
     /// # Returns
     ///
-    /// A vector of tuples containing (id, path, date, size, checksum)
+    /// A map of snapshot ID to the MIME type of the snapshot
     ///
     /// # Errors
     ///
     /// Returns an error if the database query fails.
-    pub fn list_all_snapshots_with_id(&self) -> Result<Vec<SnapshotWithId>> {
+    pub fn list_mime_types(&self) -> Result<HashMap<i64, String>> {
         let mut stmt = self
             .conn
-            .prepare("SELECT id, path, date, size, checksum FROM snapshots ORDER BY date DESC")?;
+            .prepare("SELECT id, mime FROM snapshots WHERE mime IS NOT NULL")?;
 
-        let snapshot_iter = stmt.query_map([], |row| {
-            Ok((
-                row.get::<_, i64>(0)?,
-                PathBuf::from(row.get::<_, String>(1)?),
-                row.get::<_, String>(2)?,
-                row.get::<_, i64>(3)?,
-                row.get::<_, String>(4)?,
-            ))
-        })?;
+        let entry_iter = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
 
-        let mut snapshots = Vec::new();
-        for snapshot in snapshot_iter {
-            snapshots.push(snapshot?);
+        let mut types = HashMap::new();
+        for entry in entry_iter {
+            let (key, mime) = entry?;
+            types.insert(key, mime);
         }
-        Ok(snapshots)
+        Ok(types)
     }
 
-    /// Lists snapshots for a specific path with IDs.
+    /// Records the manifest of a directory save.
     ///
     /// # Arguments
     ///
-    /// * `path` - The file path
+    /// * `root` - The directory that was saved
+    /// * `date` - RFC3339 timestamp of the save
+    /// * `message` - Optional message given at save time
+    /// * `files` - (path, checksum) of every file the directory contained
+    /// * `dirs` - (path, mode) of every directory below it, empty ones included
     ///
     /// # Returns
     ///
-    /// A vector of tuples containing (id, path, date, size, checksum)
+    /// The ID of the new session
     ///
     /// # Errors
     ///
-    /// Returns an error if the database query fails.
-    pub fn get_snapshots_for_path_with_id<P: AsRef<Path>>(
+    /// Returns an error if the database insert operation fails.
+    pub fn create_session(
         &self,
-        path: P,
-    ) -> Result<Vec<SnapshotWithId>> {
-        let path_str = path.as_ref().display().to_string();
-        let mut stmt = self.conn.prepare(
-            "SELECT id, path, date, size, checksum FROM snapshots WHERE path = ? ORDER BY date DESC"
+        root: &Path,
+        date: &str,
+        message: Option<&str>,
+        files: &[(PathBuf, String)],
+        dirs: &[(PathBuf, Option<u32>)],
+    ) -> Result<i64> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO sessions (root, date, message) VALUES (?1, ?2, ?3)",
+            params![self.stored(root), date, message],
         )?;
-
-        let snapshot_iter = stmt.query_map(params![path_str], |row| {
-            Ok((
-                row.get::<_, i64>(0)?,
-                PathBuf::from(row.get::<_, String>(1)?),
-                row.get::<_, String>(2)?,
-                row.get::<_, i64>(3)?,
-                row.get::<_, String>(4)?,
-            ))
-        })?;
-
-        let mut snapshots = Vec::new();
-        for snapshot in snapshot_iter {
-            snapshots.push(snapshot?);
+        let id = tx.last_insert_rowid();
+        for (path, checksum) in files {
+            tx.execute(
+                "INSERT OR REPLACE INTO session_files (session_id, path, checksum) VALUES (?1, ?2, ?3)",
+                params![id, self.stored(path), checksum],
+            )?;
         }
-        Ok(snapshots)
+        for (path, mode) in dirs {
+            tx.execute(
+                "INSERT OR REPLACE INTO session_dirs (session_id, path, mode) VALUES (?1, ?2, ?3)",
+                params![id, self.stored(path), mode],
+            )?;
+        }
+        tx.commit()?;
+        Ok(id)
     }
 
-    /// Lists all snapshots in the database.
+    /// Lists sessions, newest first.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// A vector of tuples containing (path, date, size, checksum) for all snapshots
+    /// * `root` - Only list sessions of this directory or of directories below it
     ///
     /// # Errors
     ///
     /// Returns an error if the database query fails.
-    pub fn list_all_snapshots(&self) -> Result<Vec<(PathBuf, String, i64, String)>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT DISTINCT path, date, size, checksum FROM snapshots ORDER BY date DESC",
-        )?;
+    pub fn list_sessions(&self, root: Option<&Path>) -> Result<Vec<Session>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT s.id, s.root, s.date, s.message, COUNT(f.path) FROM sessions s
+             LEFT JOIN session_files f ON f.session_id = s.id
+             WHERE ?1 IS NULL OR s.root = ?1 OR {}
+             GROUP BY s.id
+             ORDER BY s.date DESC, s.id DESC",
+            self.below("s.root", "?1", root)
+        ))?;
 
-        let snapshot_iter = stmt.query_map([], |row| {
-            Ok((
-                PathBuf::from(row.get::<_, String>(0)?),
-                row.get::<_, String>(1)?,
-                row.get::<_, i64>(2)?,
-                row.get::<_, String>(3)?,
-            ))
+        let session_iter = stmt.query_map(params![root.map(|root| self.stored(root))], |row| {
+            Ok(Session {
+                id: row.get(0)?,
+                root: self.resolved(row.get(1)?),
+                date: row.get(2)?,
+                message: row.get(3)?,
+                files: row.get::<_, i64>(4)? as usize,
+            })
         })?;
 
-        let mut snapshots = Vec::new();
-        for snapshot in snapshot_iter {
-            snapshots.push(snapshot?);
+        let mut sessions = Vec::new();
+        for session in session_iter {
+            sessions.push(session?);
         }
-        Ok(snapshots)
+        Ok(sessions)
     }
 
-    /// Lists all snapshots in the current working directory.
+    /// Retrieves a session by its ID.
     ///
-    /// # Arguments
+    /// # Errors
     ///
-    /// * `current_dir` - The current directory path
+    /// Returns an error if the database query fails.
+    pub fn get_session(&self, id: i64) -> Result<Option<Session>> {
+        let session = self
+            .conn
+            .query_row(
+                "SELECT s.id, s.root, s.date, s.message, COUNT(f.path) FROM sessions s
+                 LEFT JOIN session_files f ON f.session_id = s.id
+                 WHERE s.id = ?
+                 GROUP BY s.id",
+                params![id],
+                |row| {
+                    Ok(Session {
+                        id: row.get(0)?,
+                        root: self.resolved(row.get(1)?),
+                        date: row.get(2)?,
+                        message: row.get(3)?,
+                        files: row.get::<_, i64>(4)? as usize,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(session)
+    }
+
+    /// Lists the files recorded in a session.
     ///
     /// # Returns
     ///
-    /// A vector of tuples containing (path, date, size, checksum) for snapshots
+    /// (path, checksum) pairs sorted by path
     ///
     /// # Errors
     ///
     /// Returns an error if the database query fails.
-    pub fn list_current_directory_snapshots<P: AsRef<Path>>(
-        &self,
-        current_dir: P,
-    ) -> Result<Vec<(PathBuf, String, i64, String)>> {
-        let path_pattern = format!("{}/%", current_dir.as_ref().to_string_lossy());
+    pub fn get_session_files(&self, id: i64) -> Result<Vec<(PathBuf, String)>> {
         let mut stmt = self.conn.prepare(
-            "SELECT DISTINCT path, date, size, checksum FROM snapshots WHERE path LIKE ? ORDER BY date DESC"
+            "SELECT path, checksum FROM session_files WHERE session_id = ? ORDER BY path",
         )?;
 
-        let snapshot_iter = stmt.query_map(params![path_pattern], |row| {
-            Ok((
-                PathBuf::from(row.get::<_, String>(0)?),
-                row.get::<_, String>(1)?,
-                row.get::<_, i64>(2)?,
-                row.get::<_, String>(3)?,
-            ))
+        let file_iter = stmt.query_map(params![id], |row| {
+            Ok((self.resolved(row.get(0)?), row.get::<_, String>(1)?))
         })?;
 
-        let mut snapshots = Vec::new();
-        for snapshot in snapshot_iter {
-            snapshots.push(snapshot?);
+        let mut files = Vec::new();
+        for file in file_iter {
+            files.push(file?);
         }
-        Ok(snapshots)
+        Ok(files)
     }
 
-    /// Lists all snapshots in the current working directory with IDs.
+    /// Lists the directories recorded in a session.
+    ///
+    /// # Returns
+    ///
+    /// (path, mode) pairs sorted by path
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub fn get_session_dirs(&self, id: i64) -> Result<Vec<(PathBuf, Option<u32>)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path, mode FROM session_dirs WHERE session_id = ? ORDER BY path")?;
+        let rows = stmt.query_map(params![id], |row| {
+            Ok((self.resolved(row.get(0)?), row.get(1)?))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Records a restore so that `freeze undo` can roll it back.
     ///
     /// # Arguments
     ///
-    /// * `current_dir` - The current directory path
+    /// * `root` - The restored file or directory
+    /// * `date` - When the restore happened (RFC3339)
+    /// * `files` - Each written file with the checksum of its previous
+    ///   content, `None` if the restore created it
     ///
     /// # Returns
     ///
-    /// A vector of tuples containing (id, path, date, size, checksum) for snapshots
+    /// The ID of the recorded restore
     ///
     /// # Errors
     ///
-    /// Returns an error if the database query fails.
-    pub fn list_current_directory_snapshots_with_id<P: AsRef<Path>>(
-        &self,
-        current_dir: P,
-    ) -> Result<Vec<SnapshotWithId>> {
-        let path_pattern = format!("{}/%", current_dir.as_ref().to_string_lossy());
-        let mut stmt = self.conn.prepare(
-            "SELECT id, path, date, size, checksum FROM snapshots WHERE path LIKE ? ORDER BY date DESC"
+    /// Returns an error if the database insert operation fails.
+    pub fn record_restore(&self, root: &Path, date: &str, files: &[(PathBuf, Option<String>)]) -> Result<i64> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO restores (root, date) VALUES (?1, ?2)",
+            params![self.stored(root), date],
         )?;
-
-        let snapshot_iter = stmt.query_map(params![path_pattern], |row| {
-            Ok((
-                row.get::<_, i64>(0)?,
-                PathBuf::from(row.get::<_, String>(1)?),
-                row.get::<_, String>(2)?,
-                row.get::<_, i64>(3)?,
-                row.get::<_, String>(4)?,
-            ))
-        })?;
-
-        let mut snapshots = Vec::new();
-        for snapshot in snapshot_iter {
-            snapshots.push(snapshot?);
+        let id = tx.last_insert_rowid();
+        for (path, checksum) in files {
+            tx.execute(
+                "INSERT OR REPLACE INTO restore_files (restore_id, path, checksum) VALUES (?1, ?2, ?3)",
+                params![id, self.stored(path), checksum],
+            )?;
         }
-        Ok(snapshots)
+        tx.commit()?;
+        Ok(id)
     }
 
-    /// Clears all snapshots from the database.
+    /// Returns the most recent restore that has not been undone.
     ///
     /// # Errors
     ///
-    /// Returns an error if the database operation fails.
-    pub fn clear_all_snapshots(&self) -> Result<()> {
-        let count = self.conn.execute("DELETE FROM snapshots", [])?;
-        if count > 0 {
-            self.cleanup_orphaned_files()?;
+    /// Returns an error if the database query fails.
+    pub fn last_restore(&self) -> Result<Option<RestoreRecord>> {
+        let restore = self
+            .conn
+            .query_row(
+                "SELECT id, root, date FROM restores ORDER BY id DESC LIMIT 1",
+                [],
+                |row| {
+                    Ok(RestoreRecord {
+                        id: row.get(0)?,
+                        root: self.resolved(row.get(1)?),
+                        date: row.get(2)?,
+                        files: Vec::new(),
+                    })
+                },
+            )
+            .optional()?;
+        let Some(mut restore) = restore else {
+            return Ok(None);
+        };
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path, checksum FROM restore_files WHERE restore_id = ? ORDER BY path")?;
+        let file_iter = stmt.query_map(params![restore.id], |row| {
+            Ok((self.resolved(row.get(0)?), row.get::<_, Option<String>>(1)?))
+        })?;
+        for file in file_iter {
+            restore.files.push(file?);
         }
-        Ok(())
+        Ok(Some(restore))
     }
 
-    /// Adds an exclusion pattern to the database.
-    ///
-    /// # Arguments
-    ///
-    /// * `pattern` - The pattern to exclude (e.g., ".git", "node_modules")
-    /// * `exclusion_type` - The type of exclusion ("directory", "extension", or "file")
+    /// Forgets a recorded restore, once it has been undone.
     ///
     /// # Errors
     ///
-    /// Returns an error if the database insert operation fails.
-    pub fn add_exclusion(&self, pattern: &str, exclusion_type: &str) -> Result<()> {
-        self.conn.execute(
-            "INSERT INTO exclusions (pattern, type) VALUES (?1, ?2)",
-            params![pattern, exclusion_type],
-        )?;
+    /// Returns an error if the database operation fails.
+    pub fn delete_restore(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM restore_files WHERE restore_id = ?", params![id])?;
+        self.conn.execute("DELETE FROM restores WHERE id = ?", params![id])?;
         Ok(())
     }
 
-    /// Removes an exclusion pattern from the database.
+    /// Stores a new API key.
+    ///
+    /// Only the hash of the token is persisted; the clear token is shown once at creation.
     ///
     /// # Arguments
     ///
-    /// * `pattern` - The pattern to remove
+    /// * `name` - Human-readable label for the key
+    /// * `token_hash` - SHA256 hash of the generated token
+    /// * `scope` - Access scope ("read-only" or "read-write")
+    ///
+    /// # Returns
+    ///
+    /// The ID of the new key
     ///
     /// # Errors
     ///
-    /// Returns an error if the database delete operation fails.
-    pub fn remove_exclusion(&self, pattern: &str) -> Result<()> {
-        self.conn
-            .execute("DELETE FROM exclusions WHERE pattern = ?", params![pattern])?;
-        Ok(())
+    /// Returns an error if the database insert operation fails.
+    pub fn add_api_key(&self, name: &str, token_hash: &str, scope: &str) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO api_keys (name, token_hash, scope, created) VALUES (?1, ?2, ?3, ?4)",
+            params![name, token_hash, scope, chrono::Local::now().to_rfc3339()],
+        )?;
+        Ok(self.conn.last_insert_rowid())
     }
 
-    /// Lists all exclusion patterns.
+    /// Lists all API keys.
     ///
     /// # Returns
     ///
-    /// A vector of tuples containing (pattern, exclusion_type)
+    /// A vector of tuples containing (id, name, scope, created, last_used)
     ///
     /// # Errors
     ///
     /// Returns an error if the database query fails.
-    pub fn list_exclusions(&self) -> Result<Vec<(String, String)>> {
+    pub fn list_api_keys(&self) -> Result<Vec<ApiKeyRow>> {
         let mut stmt = self
             .conn
-            .prepare("SELECT pattern, type FROM exclusions ORDER BY type, pattern")?;
+            .prepare("SELECT id, name, scope, created, last_used FROM api_keys ORDER BY id")?;
 
-        let exclusion_iter = stmt.query_map([], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        let key_iter = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
         })?;
 
-        let mut exclusions = Vec::new();
-        for exclusion in exclusion_iter {
-            exclusions.push(exclusion?);
+        let mut keys = Vec::new();
+        for key in key_iter {
+            keys.push(key?);
         }
-        Ok(exclusions)
+        Ok(keys)
     }
 
-    /// Gets all exclusion patterns (alias for list_exclusions).
+    /// Revokes an API key by its ID.
     ///
     /// # Returns
     ///
-    /// A vector of tuples containing (pattern, exclusion_type)
+    /// `true` if a key was removed, `false` if no key had this ID
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub fn remove_api_key(&self, id: i64) -> Result<bool> {
+        let deleted = self
+            .conn
+            .execute("DELETE FROM api_keys WHERE id = ?", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    /// Counts the configured API keys.
     ///
     /// # Errors
     ///
     /// Returns an error if the database query fails.
-    pub fn get_exclusions(&self) -> Result<Vec<(String, String)>> {
-        self.list_exclusions()
+    pub fn count_api_keys(&self) -> Result<i64> {
+        Ok(self
+            .conn
+            .query_row("SELECT COUNT(*) FROM api_keys", [], |row| row.get(0))?)
     }
 
-    /// Deletes a snapshot by its ID.
+    /// Looks up the scope of an API key by token hash and records its use.
     ///
-    /// # Arguments
+    /// # Returns
     ///
-    /// * `id` - The snapshot ID to delete
+    /// The scope of the matching key, or `None` if the token is unknown
     ///
     /// # Errors
     ///
-    /// Returns an error if the database operation fails.
-    pub fn delete_snapshot(&self, id: i64) -> Result<()> {
-        self.conn
-            .execute("DELETE FROM snapshots WHERE id = ?", params![id])?;
-        self.cleanup_orphaned_files()?;
-        Ok(())
+    /// Returns an error if the database query fails.
+    pub fn authenticate_api_key(&self, token_hash: &str) -> Result<Option<String>> {
+        let scope: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT scope FROM api_keys WHERE token_hash = ?",
+                params![token_hash],
+                |row| row.get(0),
+            )
+            .ok();
+
+        if scope.is_some() {
+            self.conn.execute(
+                "UPDATE api_keys SET last_used = ?1 WHERE token_hash = ?2",
+                params![chrono::Local::now().to_rfc3339(), token_hash],
+            )?;
+        }
+        Ok(scope)
+    }
+}
+
+/// Escapes the wildcards of a LIKE pattern, with `\` as escape character.
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
     }
+    escaped
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::snapshot::Snapshot;
-    use std::fs;
     use tempfile::TempDir;
 
     fn create_test_db() -> (Database, TempDir) {
         let temp_dir = TempDir::new().unwrap();
         let db_path = temp_dir.path().join("test_data.sql");
         let conn = Connection::open(&db_path).unwrap();
+        Database::init_schema(&conn).unwrap();
+
+        let db = Database { conn, root: None };
+        (db, temp_dir)
+    }
+
+    fn create_test_snapshot(path: &str, checksum: &str) -> Snapshot {
+        Snapshot {
+            path: PathBuf::from(path),
+            content_path: PathBuf::from("/test/content.zst"),
+            checksum: checksum.to_string(),
+            date: "2024-01-15T10:00:00+00:00".to_string(),
+            size: 1024,
+            message: None,
+            mtime: None,
+            mode: None,
+            symlink: None,
+            xattrs: None,
+            mime: None,
+            hash: HashAlgorithm::Sha256,
+            stored_size: None,
+        }
+    }
 
+    #[test]
+    fn test_init_schema_adds_missing_columns() {
+        let conn = Connection::open_in_memory().unwrap();
         conn.execute(
-            "CREATE TABLE IF NOT EXISTS snapshots (
+            "CREATE TABLE snapshots (
                 id INTEGER PRIMARY KEY,
                 path TEXT NOT NULL,
                 content_path TEXT NOT NULL,
@@ -680,29 +3468,68 @@ mod tests {
             [],
         )
         .unwrap();
-
         conn.execute(
-            "CREATE TABLE IF NOT EXISTS exclusions (
-                id INTEGER PRIMARY KEY,
-                pattern TEXT NOT NULL,
-                type TEXT NOT NULL
-            )",
+            "INSERT INTO snapshots (path, content_path, checksum, date, size)
+             VALUES ('/a', '/store/a', 'abc', '2024-01-15T10:00:00+00:00', 1)",
             [],
         )
         .unwrap();
 
-        let db = Database { conn };
-        (db, temp_dir)
+        Database::init_schema(&conn).unwrap();
+        // A second run finds every column in place
+        Database::init_schema(&conn).unwrap();
+
+        let (message, pinned): (Option<String>, i64) = conn
+            .query_row("SELECT message, pinned FROM snapshots", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(message, None);
+        assert_eq!(pinned, 0);
+    }
+
+    #[test]
+    fn test_delete_missing_content() {
+        let (db, temp_dir) = create_test_db();
+        let stored = temp_dir.path().join("stored.zstd");
+        fs::write(&stored, b"content").unwrap();
+
+        let mut kept = create_test_snapshot("/test/kept.txt", "kept");
+        kept.content_path = stored;
+        db.save_snapshot(&kept).unwrap();
+        db.save_snapshot(&create_test_snapshot("/test/lost.txt", "lost"))
+            .unwrap();
+
+        let missing = db.list_missing_content().unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].1, PathBuf::from("/test/lost.txt"));
+
+        assert_eq!(db.delete_missing_content().unwrap(), 1);
+        assert!(db.list_missing_content().unwrap().is_empty());
+        assert_eq!(db.list_all_snapshots().unwrap().len(), 1);
+        db.vacuum().unwrap();
     }
 
-    fn create_test_snapshot(path: &str, checksum: &str) -> Snapshot {
-        Snapshot {
-            path: PathBuf::from(path),
-            content_path: PathBuf::from("/test/content.zst"),
-            checksum: checksum.to_string(),
-            date: "2024-01-15T10:00:00+00:00".to_string(),
-            size: 1024,
-        }
+    #[test]
+    fn test_path_totals_and_content_blobs() {
+        let (db, _temp_dir) = create_test_db();
+        db.save_snapshot(&create_test_snapshot("/test/a.txt", "one"))
+            .unwrap();
+        db.save_snapshot(&create_test_snapshot("/test/a.txt", "two"))
+            .unwrap();
+        db.save_snapshot(&create_test_snapshot("/test/b.txt", "one"))
+            .unwrap();
+
+        assert_eq!(
+            db.path_totals().unwrap(),
+            vec![
+                (PathBuf::from("/test/a.txt"), 2, 2048),
+                (PathBuf::from("/test/b.txt"), 1, 1024),
+            ]
+        );
+        // Every test snapshot points at the same content file
+        assert_eq!(
+            db.content_blobs().unwrap(),
+            vec![(PathBuf::from("/test/content.zst"), 1024)]
+        );
     }
 
     #[test]
@@ -761,7 +3588,7 @@ mod tests {
         let result = db.list_all_snapshots_with_id().unwrap();
         assert_eq!(result.len(), 2);
 
-        let (id, path, date, size, checksum) = &result[0];
+        let (id, _path, _date, size, checksum) = &result[0];
         assert!(*id > 0);
         assert_eq!(*size, 1024);
         assert!(checksum.starts_with("checksum"));
@@ -805,6 +3632,318 @@ mod tests {
         assert!(!result.is_empty());
     }
 
+    #[test]
+    fn test_list_snapshots_filtered() {
+        let (db, _temp_dir) = create_test_db();
+        let mut old = create_test_snapshot("/project/src/old.rs", "checksum001");
+        old.date = "2024-01-10T10:00:00+00:00".to_string();
+        old.size = 10;
+        let mut big = create_test_snapshot("/project/src/big.rs", "checksum002");
+        big.date = "2024-01-20T10:00:00+01:00".to_string();
+        big.size = 5000;
+        let other = create_test_snapshot("/elsewhere/notes.txt", "checksum003");
+        for snapshot in [&old, &big, &other] {
+            db.save_snapshot(snapshot).unwrap();
+        }
+        let paths = |filter: SnapshotFilter| -> Vec<String> {
+            db.list_snapshots(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|(_, path, ..)| path.display().to_string())
+                .collect()
+        };
+        let at = |date: &str| DateTime::parse_from_rfc3339(date).unwrap();
+
+        assert_eq!(
+            paths(SnapshotFilter::default()),
+            ["/project/src/big.rs", "/elsewhere/notes.txt", "/project/src/old.rs"]
+        );
+        assert_eq!(
+            paths(SnapshotFilter {
+                sort: SnapshotSort::Path,
+                dir: Some(PathBuf::from("/project")),
+                ..SnapshotFilter::default()
+            }),
+            ["/project/src/big.rs", "/project/src/old.rs"]
+        );
+        assert_eq!(
+            paths(SnapshotFilter {
+                since: Some(at("2024-01-12T00:00:00+00:00")),
+                until: Some(at("2024-01-18T00:00:00+00:00")),
+                ..SnapshotFilter::default()
+            }),
+            ["/elsewhere/notes.txt"]
+        );
+        assert_eq!(
+            paths(SnapshotFilter {
+                larger_than: Some(1024),
+                ..SnapshotFilter::default()
+            }),
+            ["/project/src/big.rs"]
+        );
+        assert_eq!(
+            paths(SnapshotFilter {
+                path_contains: Some("src/o".to_string()),
+                sort: SnapshotSort::Size,
+                ..SnapshotFilter::default()
+            }),
+            ["/project/src/old.rs"]
+        );
+    }
+
+    #[test]
+    fn test_expire_older_than() {
+        let (db, _temp_dir) = create_test_db();
+        let mut recent = create_test_snapshot("/project/recent.txt", "checksum001");
+        recent.date = chrono::Local::now().to_rfc3339();
+        let old = create_test_snapshot("/project/old.txt", "checksum002");
+        let outside = create_test_snapshot("/elsewhere/old.txt", "checksum003");
+        for snapshot in [&recent, &old, &outside] {
+            db.save_snapshot(snapshot).unwrap();
+        }
+        let old_id = db.get_snapshots_for_path_with_id("/project/old.txt").unwrap()[0].0;
+        db.set_metadata(old_id, "phase", "before").unwrap();
+
+        let retention = Retention::OlderThan(chrono::Duration::days(30));
+        let expired = db
+            .expired_snapshots(Some(Path::new("/project")), retention)
+            .unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].1, PathBuf::from("/project/old.txt"));
+        assert_eq!(db.expired_snapshots(None, retention).unwrap().len(), 2);
+
+        let ids: Vec<i64> = expired.iter().map(|(id, ..)| *id).collect();
+        assert_eq!(db.delete_snapshots(&ids).unwrap().0, 1);
+        assert!(db.get_snapshots_for_path("/project/old.txt").unwrap().is_empty());
+        assert!(db.list_metadata(old_id).unwrap().is_empty());
+        assert_eq!(db.list_all_snapshots().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_expire_keep_last() {
+        let (db, _temp_dir) = create_test_db();
+        for (day, checksum) in [(10, "checksum001"), (12, "checksum002"), (11, "checksum003")] {
+            let mut snapshot = create_test_snapshot("/project/file.txt", checksum);
+            snapshot.date = format!("2024-01-{}T10:00:00+00:00", day);
+            db.save_snapshot(&snapshot).unwrap();
+        }
+        db.save_snapshot(&create_test_snapshot("/project/other.txt", "checksum004"))
+            .unwrap();
+
+        let expired = db.expired_snapshots(None, Retention::KeepLast(2)).unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].4, "checksum001");
+        assert!(db.expired_snapshots(None, Retention::KeepLast(3)).unwrap().is_empty());
+
+        let expired = db
+            .expired_snapshots(Some(Path::new("/project/file.txt")), Retention::KeepLast(1))
+            .unwrap();
+        let checksums: Vec<&str> = expired.iter().map(|(.., sum)| sum.as_str()).collect();
+        assert_eq!(checksums, ["checksum003", "checksum001"]);
+    }
+
+    #[test]
+    fn test_content_refs() {
+        let (db, _temp_dir) = create_test_db();
+        let refs = |content_path: &str| -> Option<i64> {
+            db.conn
+                .query_row("SELECT refs FROM contents WHERE content_path = ?", params![content_path], |row| row.get(0))
+                .optional()
+                .unwrap()
+        };
+        db.save_snapshot(&create_test_snapshot("/project/a.txt", "checksum001")).unwrap();
+        db.save_snapshot(&create_test_snapshot("/project/b.txt", "checksum001")).unwrap();
+        assert_eq!(refs("/test/content.zst"), Some(2));
+
+        db.replace_content_path(Path::new("/test/content.zst"), Path::new("/test/content.lz4"))
+            .unwrap();
+        assert_eq!(refs("/test/content.zst"), Some(0));
+        assert_eq!(refs("/test/content.lz4"), Some(2));
+
+        // The cleanup forgets contents no snapshot uses any more
+        db.clear_snapshots("/project/a.txt").unwrap();
+        assert_eq!(refs("/test/content.zst"), None);
+        assert_eq!(refs("/test/content.lz4"), Some(1));
+
+        db.conn.execute("UPDATE contents SET refs = 7", []).unwrap();
+        Database::count_contents(&db.conn).unwrap();
+        assert_eq!(refs("/test/content.lz4"), Some(1));
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let (mut db, temp_dir) = create_test_db();
+        db.save_snapshot(&create_test_snapshot("/project/a.txt", "checksum001")).unwrap();
+        let backup = temp_dir.path().join("backup.sql");
+        db.backup_to(&backup).unwrap();
+        assert_eq!(Database::count_snapshots_in(&backup).unwrap(), 1);
+
+        db.save_snapshot(&create_test_snapshot("/project/b.txt", "checksum002")).unwrap();
+        db.restore_from(&backup).unwrap();
+        assert_eq!(db.list_all_snapshots().unwrap().len(), 1);
+        let refs: i64 = db
+            .conn
+            .query_row("SELECT SUM(refs) FROM contents", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(refs, 1);
+
+        let other = temp_dir.path().join("other.sql");
+        Connection::open(&other).unwrap().execute_batch("CREATE TABLE t (x)").unwrap();
+        assert!(Database::count_snapshots_in(&other).is_err());
+        assert!(Database::count_snapshots_in(&temp_dir.path().join("missing.sql")).is_err());
+    }
+
+    #[test]
+    fn test_indexes() {
+        let (db, _temp_dir) = create_test_db();
+        let plan = |sql: &str| -> String {
+            let mut stmt = db.conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql)).unwrap();
+            let details: Vec<String> = stmt
+                .query_map(params!["abc"], |row| row.get(3))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            details.join("; ")
+        };
+        assert!(plan("SELECT * FROM snapshots WHERE path = ?1 ORDER BY date DESC").contains("snapshots_path_date"));
+        assert!(plan("SELECT * FROM snapshots WHERE checksum = ?1").contains("snapshots_checksum"));
+        assert!(
+            plan("SELECT DISTINCT checksum FROM snapshots WHERE checksum >= ?1 AND checksum < ?1 || char(1114111)")
+                .contains("snapshots_checksum")
+        );
+    }
+
+    #[test]
+    fn test_open_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("data.sql");
+        let conn = Database::open_file(&path).unwrap();
+        Database::init_schema(&conn).unwrap();
+        let mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(mode, "wal");
+
+        // A second connection reads while the first one is writing
+        let other = Database::open_file(&path).unwrap();
+        conn.execute_batch("BEGIN IMMEDIATE; INSERT INTO exclusions (pattern, type) VALUES ('*.tmp', 'pattern')")
+            .unwrap();
+        let count: i64 = other.query_row("SELECT COUNT(*) FROM exclusions", [], |row| row.get(0)).unwrap();
+        conn.execute_batch("COMMIT").unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_maintenance() {
+        let (db, _temp_dir) = create_test_db();
+        for i in 0..200 {
+            db.save_snapshot(&create_test_snapshot(&format!("/project/{:0>100}.txt", i), &format!("checksum{:03}", i)))
+                .unwrap();
+        }
+        db.clear_directory_snapshots("/project").unwrap();
+        let before = db.page_stats().unwrap();
+        assert!(before.free_pages > 0);
+        assert!(before.fragmentation() > 0.0);
+
+        assert!(db.integrity_check().unwrap().is_empty());
+        db.vacuum().unwrap();
+        db.analyze().unwrap();
+        let after = db.page_stats().unwrap();
+        assert_eq!(after.free_pages, 0);
+        assert!(after.pages < before.pages);
+    }
+
+    #[test]
+    fn test_content_index() {
+        let (db, _temp_dir) = create_test_db();
+        db.save_snapshot(&create_test_snapshot("/project/a.rs", "checksum001")).unwrap();
+        db.save_snapshot(&create_test_snapshot("/project/b.rs", "checksum002")).unwrap();
+        assert_eq!(db.unindexed_checksums(i64::MAX).unwrap().len(), 2);
+        assert!(db.unindexed_checksums(0).unwrap().is_empty());
+
+        db.index_content("checksum001", "fn parse(config: &Path)\nlet reader = open(path);").unwrap();
+        assert!(db.is_content_indexed("checksum001").unwrap());
+        assert!(db.index_content("checksum001", "again").is_err());
+        assert_eq!(db.unindexed_checksums(i64::MAX).unwrap(), ["checksum002"]);
+
+        let matches = db.search_content("reader", None, 10).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].0, PathBuf::from("/project/a.rs"));
+        assert!(matches[0].4.contains("**reader**"));
+        assert!(db.search_content("reader", Some("image"), 10).unwrap().is_empty());
+        assert!(db.search_content("writer", None, 10).unwrap().is_empty());
+
+        // Deleting the last snapshot of a content drops its text
+        db.clear_snapshots("/project/a.rs").unwrap();
+        assert!(!db.is_content_indexed("checksum001").unwrap());
+        db.index_content("checksum002", "reader").unwrap();
+        assert_eq!(db.drop_content_index().unwrap(), 1);
+        assert!(db.search_content("reader", None, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pinned() {
+        let (db, _temp_dir) = create_test_db();
+        for (day, checksum) in [(10, "checksum001"), (11, "checksum002"), (12, "checksum003")] {
+            let mut snapshot = create_test_snapshot("/project/file.txt", checksum);
+            snapshot.date = format!("2024-01-{}T10:00:00+00:00", day);
+            db.save_snapshot(&snapshot).unwrap();
+        }
+        db.save_snapshot(&create_test_snapshot("/project/copy.txt", "checksum001"))
+            .unwrap();
+
+        assert!(db.set_pinned("checksum", true).is_err());
+        assert!(db.set_pinned("missing", true).is_err());
+        let pinned = db.set_pinned("checksum001", true).unwrap();
+        assert_eq!(pinned.len(), 2);
+        assert_eq!(db.list_pinned().unwrap().len(), 2);
+        assert_eq!(db.count_pinned(Some(Path::new("/project/file.txt"))).unwrap(), 1);
+
+        let expired = db.expired_snapshots(None, Retention::KeepLast(1)).unwrap();
+        let checksums: Vec<&str> = expired.iter().map(|(.., sum)| sum.as_str()).collect();
+        assert_eq!(checksums, ["checksum002"]);
+
+        let clearable = db.clearable_snapshots(Some(Path::new("/project/file.txt"))).unwrap();
+        let checksums: Vec<&str> = clearable.iter().map(|(.., sum)| sum.as_str()).collect();
+        assert_eq!(checksums, ["checksum003", "checksum002"]);
+        assert_eq!(db.clearable_snapshots(None).unwrap().len(), 2);
+        assert!(db.clearable_snapshots(Some(Path::new("/project/file"))).unwrap().is_empty());
+
+        let id = db.get_snapshots_for_path_with_id("/project/copy.txt").unwrap()[0].0;
+        assert!(db.delete_snapshot(id).is_err());
+        assert_eq!(db.delete_snapshots(&[id]).unwrap().0, 0);
+
+        db.clear_all_snapshots().unwrap();
+        assert_eq!(db.list_all_snapshots().unwrap().len(), 2);
+
+        db.set_pinned("checksum001", false).unwrap();
+        assert!(db.list_pinned().unwrap().is_empty());
+        db.clear_snapshots("/project/copy.txt").unwrap();
+        assert_eq!(db.list_all_snapshots().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_restore_journal() {
+        let (db, _temp_dir) = create_test_db();
+        assert!(db.last_restore().unwrap().is_none());
+
+        let files = vec![
+            (PathBuf::from("/project/a.txt"), Some("checksum001".to_string())),
+            (PathBuf::from("/project/new.txt"), None),
+        ];
+        db.record_restore(Path::new("/project"), "2024-01-15T10:00:00+00:00", &files[..1])
+            .unwrap();
+        let id = db
+            .record_restore(Path::new("/project"), "2024-01-16T10:00:00+00:00", &files)
+            .unwrap();
+
+        let last = db.last_restore().unwrap().unwrap();
+        assert_eq!(last.id, id);
+        assert_eq!(last.root, PathBuf::from("/project"));
+        assert_eq!(last.files, files);
+
+        db.delete_restore(id).unwrap();
+        assert_eq!(db.last_restore().unwrap().unwrap().files.len(), 1);
+    }
+
     #[test]
     fn test_snapshot_id_increments() {
         let (db, _temp_dir) = create_test_db();
@@ -812,9 +3951,11 @@ mod tests {
         let snapshot2 = create_test_snapshot("/test/file2.txt", "checksum002");
         let snapshot3 = create_test_snapshot("/test/file3.txt", "checksum003");
 
-        db.save_snapshot(&snapshot1).unwrap();
-        db.save_snapshot(&snapshot2).unwrap();
-        db.save_snapshot(&snapshot3).unwrap();
+        assert_eq!(db.save_snapshot(&snapshot1).unwrap(), Some(1));
+        assert_eq!(db.save_snapshot(&snapshot2).unwrap(), Some(2));
+        assert_eq!(db.save_snapshot(&snapshot3).unwrap(), Some(3));
+        // Same path and content: nothing new is recorded
+        assert_eq!(db.save_snapshot(&snapshot3).unwrap(), None);
 
         let result = db.list_all_snapshots_with_id().unwrap();
         assert_eq!(result.len(), 3);
@@ -842,4 +3983,546 @@ mod tests {
         assert_eq!(result[0].4, "newer");
         assert_eq!(result[1].4, "older");
     }
+
+    #[test]
+    fn test_api_key_lifecycle() {
+        let (db, _temp_dir) = create_test_db();
+        assert_eq!(db.count_api_keys().unwrap(), 0);
+
+        let id = db.add_api_key("ci", "hash123", "read-only").unwrap();
+        assert_eq!(db.count_api_keys().unwrap(), 1);
+        assert_eq!(
+            db.authenticate_api_key("hash123").unwrap().as_deref(),
+            Some("read-only")
+        );
+        assert!(db.authenticate_api_key("unknown").unwrap().is_none());
+
+        let keys = db.list_api_keys().unwrap();
+        assert_eq!(keys[0].1, "ci");
+        assert!(keys[0].4.is_some());
+
+        assert!(db.remove_api_key(id).unwrap());
+        assert!(!db.remove_api_key(id).unwrap());
+        assert_eq!(db.count_api_keys().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_alias_lifecycle() {
+        let (db, _temp_dir) = create_test_db();
+        db.add_alias("thesis", "/home/user/docs/thesis").unwrap();
+        assert_eq!(
+            db.get_alias("thesis").unwrap(),
+            Some(PathBuf::from("/home/user/docs/thesis"))
+        );
+
+        db.add_alias("thesis", "/home/user/thesis").unwrap();
+        assert_eq!(db.list_aliases().unwrap().len(), 1);
+
+        assert!(db.remove_alias("thesis").unwrap());
+        assert!(!db.remove_alias("thesis").unwrap());
+        assert_eq!(db.get_alias("thesis").unwrap(), None);
+    }
+
+    #[test]
+    fn test_metadata_lifecycle() {
+        let (db, _temp_dir) = create_test_db();
+        db.save_snapshot(&create_test_snapshot("/project/a.txt", "checksum001"))
+            .unwrap();
+        let id = db.get_snapshot_id_by_checksum("checksum001").unwrap().unwrap();
+
+        db.set_metadata(id, "ticket", "ABC-1").unwrap();
+        db.set_metadata(id, "ticket", "ABC-2").unwrap();
+        db.set_metadata(id, "build", "42").unwrap();
+        assert_eq!(db.get_metadata(id, "ticket").unwrap().as_deref(), Some("ABC-2"));
+        assert_eq!(
+            db.list_metadata(id).unwrap().keys().collect::<Vec<_>>(),
+            vec!["build", "ticket"]
+        );
+        assert_eq!(db.list_all_metadata().unwrap()[&id].len(), 2);
+        assert!(db.set_metadata(id + 1, "ticket", "ABC-3").is_err());
+
+        assert!(db.remove_metadata(id, "build").unwrap());
+        assert!(!db.remove_metadata(id, "build").unwrap());
+
+        db.conn
+            .execute("DELETE FROM snapshots WHERE id = ?", params![id])
+            .unwrap();
+        assert!(db.list_all_metadata().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_copy_history() {
+        let (db, _temp_dir) = create_test_db();
+        db.save_snapshot(&create_test_snapshot("/project/a.txt", "checksum001"))
+            .unwrap();
+        db.save_snapshot(&create_test_snapshot("/project/sub/b.txt", "checksum002"))
+            .unwrap();
+        db.save_snapshot(&create_test_snapshot("/project-other/c.txt", "checksum003"))
+            .unwrap();
+
+        assert_eq!(db.copy_history("/project", "/fork").unwrap(), 2);
+        assert_eq!(db.get_snapshots_for_path("/fork/a.txt").unwrap().len(), 1);
+        assert_eq!(db.get_snapshots_for_path("/fork/sub/b.txt").unwrap().len(), 1);
+        assert_eq!(db.get_snapshots_for_path("/project/a.txt").unwrap().len(), 1);
+
+        // Copying again does not duplicate snapshots
+        assert_eq!(db.copy_history("/project", "/fork").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_message() {
+        let (db, _temp_dir) = create_test_db();
+        let mut snapshot = create_test_snapshot("/test/file.txt", "abc123");
+        snapshot.message = Some("before upgrading deps".to_string());
+        db.save_snapshot(&snapshot).unwrap();
+        db.save_snapshot(&create_test_snapshot("/test/other.txt", "def456")).unwrap();
+
+        let stored = db.get_snapshot_by_checksum("abc123").unwrap().unwrap();
+        assert_eq!(stored.message.as_deref(), Some("before upgrading deps"));
+
+        let messages = db.list_messages().unwrap();
+        assert_eq!(messages.len(), 1);
+        let (id, ..) = db.get_snapshots_for_path_with_id("/test/file.txt").unwrap()[0].clone();
+        assert_eq!(messages[&id], "before upgrading deps");
+    }
+
+    #[test]
+    fn test_messages_of_repeated_content() {
+        let (db, _temp_dir) = create_test_db();
+        for (checksum, message) in [("aaa111", "first A"), ("bbb222", "then B"), ("aaa111", "back to A")] {
+            let mut snapshot = create_test_snapshot("/test/file.txt", checksum);
+            snapshot.message = Some(message.to_string());
+            db.insert_snapshot(&snapshot).unwrap();
+        }
+
+        let snapshots = db.list_all_snapshots().unwrap();
+        assert_eq!(snapshots.len(), 3);
+        let messages = db.list_messages().unwrap();
+        let mut found: Vec<(&str, &str)> = snapshots
+            .iter()
+            .map(|(id, _, _, _, checksum)| (checksum.as_str(), messages[id].as_str()))
+            .collect();
+        found.sort();
+        assert_eq!(found, [("aaa111", "back to A"), ("aaa111", "first A"), ("bbb222", "then B")]);
+    }
+
+    #[test]
+    fn test_removed_snapshots() {
+        let (db, _temp_dir) = create_test_db();
+        for (path, checksum) in [
+            ("/test/a.txt", "aaa11111"),
+            ("/test/a.txt", "bbb22222"),
+            ("/test/a.txt", "aaa11111"),
+            ("/test/b.txt", "abc33333"),
+            ("/other/c.txt", "ccc44444"),
+        ] {
+            db.insert_snapshot(&create_test_snapshot(path, checksum)).unwrap();
+        }
+        let ids = |snapshots: Vec<SnapshotWithId>| snapshots.into_iter().map(|(id, ..)| id).collect::<Vec<_>>();
+        let removed = |root: Option<&str>, checksums: &[&str], ids: &[i64]| {
+            let checksums: Vec<String> = checksums.iter().map(|c| c.to_string()).collect();
+            db.removed_snapshots(root.map(Path::new), &checksums, ids)
+        };
+
+        assert_eq!(ids(removed(None, &["bbb"], &[]).unwrap()), [2]);
+        assert_eq!(ids(removed(None, &["abc", "ccc"], &[1, 4]).unwrap()), [4, 5, 1]);
+        // Content saved several times is only deleted by ID
+        assert!(removed(None, &["aaa"], &[]).is_err());
+        assert_eq!(ids(removed(None, &[], &[1, 3]).unwrap()), [1, 3]);
+        assert!(removed(None, &["a"], &[]).is_err());
+        assert!(removed(None, &["fff"], &[]).is_err());
+        assert!(removed(None, &[], &[9]).is_err());
+        assert!(removed(Some("/test"), &["ccc"], &[]).is_err());
+
+        db.set_pinned("bbb", true).unwrap();
+        assert!(removed(None, &[], &[2]).is_err());
+    }
+
+    #[test]
+    fn test_session_lifecycle() {
+        let (db, _temp_dir) = create_test_db();
+        let files = vec![
+            (PathBuf::from("/project/a.txt"), "checksum001".to_string()),
+            (PathBuf::from("/project/sub/b.txt"), "checksum002".to_string()),
+        ];
+        let dirs = vec![(PathBuf::from("/project/empty"), Some(0o700)), (PathBuf::from("/project/sub"), None)];
+        let first = db
+            .create_session(Path::new("/project"), "2024-01-15T10:00:00+00:00", None, &files, &dirs)
+            .unwrap();
+        let second = db
+            .create_session(Path::new("/project/sub"), "2024-01-16T10:00:00+00:00", Some("wip"), &files[1..], &[])
+            .unwrap();
+        db.create_session(Path::new("/project-other"), "2024-01-17T10:00:00+00:00", None, &[], &[])
+            .unwrap();
+
+        let sessions = db.list_sessions(Some(Path::new("/project"))).unwrap();
+        assert_eq!(sessions.iter().map(|s| s.id).collect::<Vec<_>>(), vec![second, first]);
+        assert_eq!(sessions[0].message.as_deref(), Some("wip"));
+        assert_eq!(sessions[1].files, 2);
+        assert_eq!(db.list_sessions(None).unwrap().len(), 3);
+
+        assert_eq!(db.get_session_files(first).unwrap(), files);
+        assert_eq!(db.get_session_dirs(first).unwrap(), dirs);
+        assert!(db.get_session_dirs(second).unwrap().is_empty());
+        assert_eq!(db.get_session(second).unwrap().unwrap().files, 1);
+        assert!(db.get_session(42).unwrap().is_none());
+
+        db.clear_all_snapshots().unwrap();
+        assert!(db.list_sessions(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_replace_snapshot() {
+        let (db, _temp_dir) = create_test_db();
+        let id = db.insert_snapshot(&create_test_snapshot("/project/a.txt", "checksum001")).unwrap();
+        db.set_metadata(id, "ticket", "FRZ-12").unwrap();
+        let files = vec![(PathBuf::from("/project/a.txt"), "checksum001".to_string())];
+        let session = db
+            .create_session(Path::new("/project"), "2024-01-15T10:00:00+00:00", None, &files, &[])
+            .unwrap();
+
+        let mut amended = create_test_snapshot("/project/a.txt", "checksum002");
+        amended.message = Some("fixed".to_string());
+        db.replace_snapshot(id, &amended).unwrap();
+
+        let snapshots = db.get_snapshots_for_path_with_id("/project/a.txt").unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!((snapshots[0].0, snapshots[0].4.as_str()), (id, "checksum002"));
+        assert_eq!(db.list_messages().unwrap()[&id], "fixed");
+        assert_eq!(db.get_metadata(id, "ticket").unwrap().as_deref(), Some("FRZ-12"));
+        assert_eq!(
+            db.get_session_files(session).unwrap(),
+            [(PathBuf::from("/project/a.txt"), "checksum002".to_string())]
+        );
+        assert!(db.replace_snapshot(42, &amended).is_err());
+    }
+
+    #[test]
+    fn test_get_snapshot_as_of() {
+        let (db, _temp_dir) = create_test_db();
+        let mut first = create_test_snapshot("/test/file.txt", "checksum001");
+        first.date = "2024-06-01T10:00:00+00:00".to_string();
+        let mut second = create_test_snapshot("/test/file.txt", "checksum002");
+        // 11:30 UTC, recorded under another offset
+        second.date = "2024-06-01T13:30:00+02:00".to_string();
+        db.save_snapshot(&first).unwrap();
+        db.save_snapshot(&second).unwrap();
+
+        let at = |date: &str| DateTime::parse_from_rfc3339(date).unwrap();
+        let found = |date: &str| {
+            db.get_snapshot_as_of("/test/file.txt", &at(date))
+                .unwrap()
+                .map(|s| s.checksum)
+        };
+        assert_eq!(found("2024-06-01T09:59:59+00:00"), None);
+        assert_eq!(found("2024-06-01T10:00:00+00:00").as_deref(), Some("checksum001"));
+        assert_eq!(found("2024-06-01T11:29:00+00:00").as_deref(), Some("checksum001"));
+        assert_eq!(found("2024-06-01T12:00:00+00:00").as_deref(), Some("checksum002"));
+    }
+
+    #[test]
+    fn test_latest_snapshots_in_dir() {
+        let (db, _temp_dir) = create_test_db();
+        let mut old = create_test_snapshot("/test/dir/a.txt", "checksum001");
+        old.date = "2024-01-01T10:00:00+00:00".to_string();
+        let mut latest = create_test_snapshot("/test/dir/a.txt", "checksum002");
+        latest.mtime = Some(1_700_000_000_123_456_789);
+        db.save_snapshot(&old).unwrap();
+        db.save_snapshot(&latest).unwrap();
+        db.save_snapshot(&create_test_snapshot("/test/dir/sub/b.txt", "checksum003")).unwrap();
+        db.save_snapshot(&create_test_snapshot("/test/dirty/c.txt", "checksum004")).unwrap();
+
+        let found = db.latest_snapshots_in_dir("/test/dir").unwrap();
+        assert_eq!(found.len(), 2);
+        let a = &found[Path::new("/test/dir/a.txt")];
+        assert_eq!(a.checksum, "checksum002");
+        assert_eq!(a.mtime, Some(1_700_000_000_123_456_789));
+        assert_eq!(found[Path::new("/test/dir/sub/b.txt")].mtime, None);
+
+        // Saving the same content again refreshes the recorded mtime
+        latest.mtime = Some(1_800_000_000_000_000_000);
+        assert!(db.save_snapshot(&latest).unwrap().is_none());
+        let found = db.latest_snapshots_in_dir("/test/dir").unwrap();
+        assert_eq!(found[Path::new("/test/dir/a.txt")].mtime, latest.mtime);
+    }
+
+    #[test]
+    fn test_insert_snapshot() {
+        let (db, _temp_dir) = create_test_db();
+        let snapshot = create_test_snapshot("/test/a.txt", "checksum001");
+        assert!(db.save_snapshot(&snapshot).unwrap().is_some());
+        assert!(db.save_snapshot(&snapshot).unwrap().is_none());
+        assert_eq!(db.get_snapshots_for_path("/test/a.txt").unwrap().len(), 1);
+
+        // Forced saves record the same content again
+        let id = db.insert_snapshot(&snapshot).unwrap();
+        assert_eq!(db.get_snapshots_for_path("/test/a.txt").unwrap().len(), 2);
+        assert_eq!(db.get_snapshot_by_id(id).unwrap().unwrap().checksum, "checksum001");
+    }
+
+    #[test]
+    fn test_get_path_snapshot() {
+        let (db, _temp_dir) = create_test_db();
+        let mut script = create_test_snapshot("/test/run.sh", "checksum001");
+        script.mode = Some(0o755);
+        let mut copy = create_test_snapshot("/test/copy.txt", "checksum001");
+        copy.mode = Some(0o644);
+        copy.date = "2024-02-01T10:00:00+00:00".to_string();
+        db.save_snapshot(&script).unwrap();
+        db.save_snapshot(&copy).unwrap();
+
+        let found = db.get_path_snapshot("/test/run.sh", "checksum001").unwrap().unwrap();
+        assert_eq!((found.path, found.mode), (PathBuf::from("/test/run.sh"), Some(0o755)));
+        let found = db.get_path_snapshot("/test/copy.txt", "checksum001").unwrap().unwrap();
+        assert_eq!(found.mode, Some(0o644));
+        // Another path with the same content falls back to the newest copy
+        let found = db.get_path_snapshot("/test/other", "checksum001").unwrap().unwrap();
+        assert_eq!(found.path, PathBuf::from("/test/copy.txt"));
+        assert!(db.get_path_snapshot("/test/run.sh", "checksum002").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_search_by_type() {
+        let (db, _temp_dir) = create_test_db();
+        let mut photo = create_test_snapshot("/test/photo.png", "checksum001");
+        photo.mime = Some("image/png".to_string());
+        let mut notes = create_test_snapshot("/test/notes.txt", "checksum002");
+        notes.mime = Some("text/plain".to_string());
+        db.save_snapshot(&photo).unwrap();
+        db.save_snapshot(&notes).unwrap();
+        db.save_snapshot(&create_test_snapshot("/test/old.bin", "checksum003")).unwrap();
+
+        let paths = |file_type: Option<&str>| -> Vec<PathBuf> {
+            let filter = SearchFilter {
+                file_type: file_type.map(str::to_string),
+                ..SearchFilter::default()
+            };
+            db.search_snapshots("test", &filter).unwrap().into_iter().map(|(_, path, ..)| path).collect()
+        };
+        assert_eq!(paths(Some("image")), vec![PathBuf::from("/test/photo.png")]);
+        assert_eq!(paths(Some("text/plain")), vec![PathBuf::from("/test/notes.txt")]);
+        assert!(paths(Some("ima")).is_empty());
+        assert_eq!(paths(None).len(), 3);
+        assert_eq!(db.list_mime_types().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_wildcards_in_paths() {
+        let (db, _temp_dir) = create_test_db();
+        for (path, checksum) in [
+            ("/test/100%/a.txt", "checksum001"),
+            ("/test/1000/b.txt", "checksum002"),
+            ("/test/a_b/c.txt", "checksum003"),
+            ("/test/axb/d.txt", "checksum004"),
+            ("/TEST/a_b/e.txt", "checksum005"),
+        ] {
+            db.save_snapshot(&create_test_snapshot(path, checksum)).unwrap();
+        }
+        let paths = |snapshots: Vec<(PathBuf, String, i64, String)>| -> Vec<PathBuf> {
+            snapshots.into_iter().map(|(path, ..)| path).collect()
+        };
+
+        assert_eq!(escape_like(r"50%_a\b"), r"50\%\_a\\b");
+        assert_eq!(
+            paths(db.list_directory_snapshots("/test/100%").unwrap()),
+            [PathBuf::from("/test/100%/a.txt")]
+        );
+        assert_eq!(
+            paths(db.list_directory_snapshots("/test/a_b").unwrap()),
+            [PathBuf::from("/test/a_b/c.txt")]
+        );
+        assert_eq!(
+            db.search_snapshots("%", &SearchFilter::default())
+                .unwrap()
+                .into_iter()
+                .map(|(_, path, ..)| path)
+                .collect::<Vec<_>>(),
+            [PathBuf::from("/test/100%/a.txt")]
+        );
+        assert_eq!(db.search_snapshots("a_b", &SearchFilter::default()).unwrap().len(), 2);
+
+        db.clear_directory_snapshots("/test/a_b").unwrap();
+        assert!(db.latest_snapshots_in_dir("/test/a_b").unwrap().is_empty());
+        assert_eq!(db.latest_snapshots_in_dir("/test/axb").unwrap().len(), 1);
+        assert_eq!(db.latest_snapshots_in_dir("/TEST/a_b").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_directory_totals() {
+        let (db, _temp_dir) = create_test_db();
+        let snapshot = |path: &str, checksum: &str, date: &str, size: i64| {
+            let mut snapshot = create_test_snapshot(path, checksum);
+            snapshot.date = date.to_string();
+            snapshot.size = size;
+            db.insert_snapshot(&snapshot).unwrap();
+        };
+        snapshot("/home/user/src/main.rs", "checksum001", "2024-01-01T10:00:00+00:00", 100);
+        snapshot("/home/user/src/main.rs", "checksum002", "2024-03-01T10:00:00+00:00", 200);
+        snapshot("/home/user/src/lib/mod.rs", "checksum003", "2024-02-01T10:00:00+00:00", 50);
+        snapshot("/home/user/notes.txt", "checksum004", "2024-04-01T09:00:00-02:00", 1000);
+        snapshot("/home/user2/a.txt", "checksum005", "2024-05-01T10:00:00+00:00", 1);
+
+        let totals = db.directory_totals(Path::new("/home/user/"), 10).unwrap();
+        assert_eq!(
+            totals,
+            [
+                DirectoryTotals {
+                    path: PathBuf::from("/home/user"),
+                    snapshots: 1,
+                    files: 1,
+                    newest: "2024-04-01T09:00:00-02:00".to_string(),
+                    size: 1000,
+                },
+                DirectoryTotals {
+                    path: PathBuf::from("/home/user/src"),
+                    snapshots: 3,
+                    files: 2,
+                    newest: "2024-03-01T10:00:00+00:00".to_string(),
+                    size: 350,
+                },
+            ]
+        );
+        let top: Vec<(PathBuf, usize)> = db
+            .directory_totals(Path::new("/"), 10)
+            .unwrap()
+            .into_iter()
+            .map(|totals| (totals.path, totals.snapshots))
+            .collect();
+        assert_eq!(top, [(PathBuf::from("/home"), 5)]);
+        assert_eq!(db.directory_totals(Path::new("/home"), 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        let (mut db, _temp_dir) = create_test_db();
+        let root = PathBuf::from("/home/user/project");
+        let raw = |db: &Database| -> Vec<(String, String)> {
+            let mut stmt = db.conn.prepare("SELECT path, content_path FROM snapshots ORDER BY id").unwrap();
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        let mut snapshot = create_test_snapshot("/home/user/project/src/main.rs", "checksum001");
+        snapshot.content_path = root.join(".freeze/storage/checksum001.zst");
+        db.save_snapshot(&snapshot).unwrap();
+        db.save_snapshot(&create_test_snapshot("/home/user/other.txt", "checksum002")).unwrap();
+        let files = [(snapshot.path.clone(), "checksum001".to_string())];
+        db.create_session(&root, "2024-01-01T10:00:00+00:00", None, &files, &[]).unwrap();
+
+        // Paths saved before are converted, paths outside the project kept
+        Database::convert_paths(&db.conn, &root, true).unwrap();
+        db.root = Some(root.clone());
+        assert!(Database::has_relative_paths(&db.conn).unwrap());
+        assert_eq!(
+            raw(&db)[0],
+            ("./src/main.rs".to_string(), "./.freeze/storage/checksum001.zst".to_string())
+        );
+        assert_eq!(raw(&db)[1].0, "/home/user/other.txt");
+        assert_eq!(db.stored(&root), ".");
+        assert_eq!(db.resolved(".".to_string()), root);
+
+        // Paths are read and looked up as absolute ones
+        let found = db.get_snapshots_for_path(&snapshot.path).unwrap();
+        assert_eq!((&found[0].path, &found[0].content_path), (&snapshot.path, &snapshot.content_path));
+        assert_eq!(db.list_directory_snapshots(root.join("src")).unwrap().len(), 1);
+        assert_eq!(db.list_directory_snapshots("/home/user").unwrap().len(), 2);
+        assert_eq!(db.list_sessions(Some(Path::new("/home"))).unwrap()[0].root, root);
+        assert_eq!(db.search_snapshots("/home/user/project/src", &SearchFilter::default()).unwrap().len(), 1);
+
+        // Once the project moves, its paths follow
+        let moved = PathBuf::from("/srv/project");
+        db.root = Some(moved.clone());
+        let snapshots = db.get_snapshots_for_path(moved.join("src/main.rs")).unwrap();
+        assert_eq!(snapshots[0].content_path, moved.join(".freeze/storage/checksum001.zst"));
+        assert_eq!(db.get_session_files(1).unwrap()[0].0, moved.join("src/main.rs"));
+
+        Database::convert_paths(&db.conn, &moved, false).unwrap();
+        db.root = None;
+        assert!(!Database::has_relative_paths(&db.conn).unwrap());
+        assert_eq!(raw(&db)[0].0, "/srv/project/src/main.rs");
+        let refs: i64 = db
+            .conn
+            .query_row(
+                "SELECT refs FROM contents WHERE content_path = '/srv/project/.freeze/storage/checksum001.zst'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(refs, 1);
+    }
+
+    #[test]
+    fn test_search_filters() {
+        let (db, _temp_dir) = create_test_db();
+        let snapshot = |path: &str, checksum: &str, date: &str, size: i64| {
+            let mut snapshot = create_test_snapshot(path, checksum);
+            snapshot.date = date.to_string();
+            snapshot.size = size;
+            db.insert_snapshot(&snapshot).unwrap()
+        };
+        snapshot("/test/main.rs", "aaa111", "2024-01-01T10:00:00+00:00", 100);
+        let tagged = snapshot("/test/main.rs", "bbb222", "2024-02-01T10:00:00+00:00", 5000);
+        snapshot("/test/notes.TXT", "aab333", "2024-03-01T10:00:00+02:00", 300);
+        db.set_metadata(tagged, "ticket", "ABC-1").unwrap();
+
+        let search = |criteria: &[(&str, &str)]| -> Vec<String> {
+            let mut filter = SearchFilter::default();
+            for (name, value) in criteria {
+                filter.set(name, value).unwrap();
+            }
+            db.search_snapshots("test", &filter).unwrap().into_iter().map(|(.., checksum)| checksum).collect()
+        };
+        assert_eq!(search(&[]), ["aab333", "bbb222", "aaa111"]);
+        assert_eq!(search(&[("since", "2024-01-15"), ("until", "2024-02-15")]), ["bbb222"]);
+        assert_eq!(search(&[("larger_than", "200"), ("smaller_than", "1K")]), ["aab333"]);
+        assert_eq!(search(&[("checksum", "AA")]), ["aab333", "aaa111"]);
+        assert_eq!(search(&[("ext", ".txt")]), ["aab333"]);
+        assert_eq!(search(&[("tag", "ticket")]), ["bbb222"]);
+        assert_eq!(search(&[("tag", "ticket=ABC-2")]), Vec::<String>::new());
+        assert_eq!(search(&[("latest", "true")]), ["aab333", "bbb222"]);
+        // The newest snapshot among the matching ones
+        assert_eq!(search(&[("latest", "true"), ("smaller_than", "1K")]), ["aab333", "aaa111"]);
+
+        let mut filter = SearchFilter::default();
+        assert!(filter.set("latest", "yes").is_err());
+        assert!(filter.set("larger_than", "big").is_err());
+        assert!(filter.set("color", "red").is_err());
+    }
+
+    #[test]
+    fn test_dedup_stats() {
+        let (db, _temp_dir) = create_test_db();
+        let mut first = create_test_snapshot("/test/a.txt", "checksum001");
+        first.stored_size = Some(100);
+        let mut copy = create_test_snapshot("/test/b.txt", "checksum001");
+        copy.stored_size = Some(100);
+        let mut other = create_test_snapshot("/test/c.txt", "checksum002");
+        other.content_path = PathBuf::from("/test/other.zst");
+        db.save_snapshot(&first).unwrap();
+        db.save_snapshot(&copy).unwrap();
+        db.save_snapshot(&other).unwrap();
+
+        assert_eq!(db.dedup_totals().unwrap(), (3, 2, 3072, 2048, 100));
+        assert_eq!(db.dedup_paths(10).unwrap(), vec![(PathBuf::from("/test/b.txt"), 1, 1024)]);
+
+        assert_eq!(db.unmeasured_blobs().unwrap(), vec![PathBuf::from("/test/other.zst")]);
+        db.set_stored_size(Path::new("/test/other.zst"), 50).unwrap();
+        assert!(db.unmeasured_blobs().unwrap().is_empty());
+        assert_eq!(db.dedup_totals().unwrap().4, 150);
+    }
+
+    #[test]
+    fn test_content_levels() {
+        let (db, _temp_dir) = create_test_db();
+        let first = db.save_snapshot(&create_test_snapshot("/test/a.txt", "checksum001")).unwrap().unwrap();
+        db.save_snapshot(&create_test_snapshot("/test/b.txt", "checksum001")).unwrap();
+        db.set_metadata(first, "compression-level", "3").unwrap();
+        let content = PathBuf::from("/test/content.zst");
+        assert_eq!(db.content_levels("compression-level").unwrap().get(&content), Some(&3));
+
+        db.set_content_metadata(&content, "compression-level", "19").unwrap();
+        assert_eq!(db.content_levels("compression-level").unwrap().get(&content), Some(&19));
+        assert!(db.content_levels("other").unwrap().is_empty());
+    }
 }
@@ -0,0 +1,221 @@
+/*!
+Delta storage of successive versions for the freeze application.
+
+With the `delta` setting (or `freeze save --delta`), a new version of a file
+that already has a snapshot is stored as a zstd patch against the previous
+version (as `zstd --patch-from` does), in `storage/<checksum>.delta`. A small
+edit then costs a few bytes instead of a full compressed copy. Reading a
+delta rebuilds its base first, so versions form chains; chains are capped at
+[`MAX_CHAIN_LENGTH`] and `freeze materialize` rewrites deltas as full copies.
+
+A delta file starts with a plain header (magic bytes, the base checksum and
+the chain length) followed by the patch, compressed and encrypted like any
+content. The header stays readable so that `gc` keeps the bases of deltas
+without the repository key. Files large enough to be chunked are never
+stored as deltas.
+*/
+
+use crate::compression;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Extension of delta files in storage.
+pub const EXTENSION: &str = "delta";
+/// Longest chain of deltas: past it, versions are stored in full.
+pub const MAX_CHAIN_LENGTH: u8 = 16;
+
+const MAGIC: &[u8; 4] = b"FRZD";
+const CHECKSUM_LEN: usize = 64;
+/// Largest zstd window, which decoders accept by default.
+const MAX_WINDOW_LOG: u32 = 27;
+/// Length of the plain header of delta files.
+pub const HEADER_LEN: u64 = (MAGIC.len() + CHECKSUM_LEN + 1) as u64;
+
+/// The plain header of a delta file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    /// Checksum of the content the patch applies to
+    pub base: String,
+    /// Number of deltas in the chain ending with this one
+    pub depth: u8,
+}
+
+/// Returns the storage path of a content stored as a delta.
+pub fn delta_path(storage_dir: &Path, checksum: &str) -> PathBuf {
+    storage_dir.join(format!("{}.{}", checksum, EXTENSION))
+}
+
+/// Returns `true` if `path` is a delta file.
+pub fn is_delta(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some(EXTENSION)
+}
+
+/// Reads the header of a delta file.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or is not a delta.
+pub fn read_header(path: &Path) -> Result<Header> {
+    let mut header = [0; HEADER_LEN as usize];
+    fs::File::open(path)?.read_exact(&mut header)?;
+    let base = std::str::from_utf8(&header[MAGIC.len()..MAGIC.len() + CHECKSUM_LEN]).unwrap_or_default();
+    if &header[..MAGIC.len()] != MAGIC || !crate::utils::is_checksum(base) {
+        bail!("{} is not a delta file", path.display());
+    }
+    Ok(Header {
+        base: base.to_string(),
+        depth: header[HEADER_LEN as usize - 1],
+    })
+}
+
+/// Stores `source` as a patch against the stored content `base`.
+///
+/// # Arguments
+///
+/// * `source` - The new version
+/// * `dest` - Where to write the delta, in the storage directory
+/// * `base` - Storage path of the previous version's content
+/// * `base_checksum` - Checksum of the previous version
+///
+/// # Returns
+///
+/// `false`, writing nothing, if the chain ending with `base` is too long
+///
+/// # Errors
+///
+/// Returns an error if the base cannot be read or the delta cannot be written.
+pub fn store(source: &Path, dest: &Path, base: &Path, base_checksum: &str) -> Result<bool> {
+    let depth = if is_delta(base) { read_header(base)?.depth } else { 0 };
+    if depth >= MAX_CHAIN_LENGTH {
+        return Ok(false);
+    }
+
+    let mut reference = Vec::new();
+    compression::open(base)?.read_to_end(&mut reference)?;
+    let content = fs::read(source)?;
+
+    let temp_path = dest.with_extension("tmp");
+    let result = (|| -> Result<()> {
+        let mut file = std::io::BufWriter::new(fs::File::create(&temp_path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(base_checksum.as_bytes())?;
+        file.write_all(&[depth + 1])?;
+
+        let writer = crate::crypto::content_writer(file)?;
+        let level = crate::config::get().compression_level();
+        let mut encoder = zstd::stream::write::Encoder::with_ref_prefix(writer, level, &reference)?;
+        // The whole reference must stay within reach of back-references
+        encoder.window_log(window_log(reference.len() + content.len()))?;
+        encoder.long_distance_matching(true)?;
+        encoder.write_all(&content)?;
+        encoder.finish()?.finish()?.flush()?;
+        fs::rename(&temp_path, dest)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result.map(|_| true)
+}
+
+/// Opens a delta and rebuilds the content from its base.
+///
+/// The chain of bases is rebuilt in memory, which bounds deltas to files
+/// small enough not to be chunked.
+///
+/// # Errors
+///
+/// Returns an error if the delta or one of its bases is missing or corrupt.
+pub fn open(path: &Path) -> Result<Box<dyn Read + Send>> {
+    let header = read_header(path)?;
+    let storage_dir = path.parent().unwrap_or(Path::new("."));
+    let base_path = compression::find_blob(storage_dir, &header.base)
+        .with_context(|| format!("Delta base {} of {} is missing", header.base, path.display()))?;
+    let mut reference = Vec::new();
+    compression::open(&base_path)?.read_to_end(&mut reference)?;
+
+    let patch = std::io::BufReader::new(crate::crypto::open_content_at(path, HEADER_LEN)?);
+    let mut decoder = zstd::stream::read::Decoder::with_ref_prefix(patch, &reference)?;
+    decoder.window_log_max(MAX_WINDOW_LOG)?;
+    let mut content = Vec::new();
+    decoder.read_to_end(&mut content)?;
+    Ok(Box::new(std::io::Cursor::new(content)))
+}
+
+/// Returns the storage files of the bases a delta depends on, nearest first.
+///
+/// The chain stops at the first missing base.
+///
+/// # Errors
+///
+/// Returns an error if a delta header cannot be read.
+pub fn base_files(path: &Path) -> Result<Vec<PathBuf>> {
+    let storage_dir = path.parent().unwrap_or(Path::new("."));
+    let mut bases = Vec::new();
+    let mut current = path.to_path_buf();
+    while is_delta(&current) {
+        let header = read_header(&current)?;
+        let Some(base) = compression::find_blob(storage_dir, &header.base) else {
+            break;
+        };
+        // A delta of a delta never points back at itself, but a corrupt one could
+        if bases.contains(&base) || base == path {
+            bail!("Delta chain of {} loops", path.display());
+        }
+        bases.push(base.clone());
+        current = base;
+    }
+    Ok(bases)
+}
+
+/// Smallest zstd window log covering `len` bytes.
+fn window_log(len: usize) -> u32 {
+    (usize::BITS - len.max(1).leading_zeros()).clamp(10, MAX_WINDOW_LOG)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn test_store_and_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = dir.path().join("storage");
+        fs::create_dir_all(&storage).unwrap();
+
+        let first: Vec<u8> = (0..200_000u32).flat_map(|i| format!("line {}\n", i).into_bytes()).collect();
+        let mut second = first.clone();
+        second.splice(1000..1000, b"inserted".iter().copied());
+        let checksum = |content: &[u8]| format!("{:x}", Sha256::digest(content));
+
+        let base = compression::blob_path(&storage, &checksum(&first), compression::Compression::Zstd);
+        compression::Compression::Zstd
+            .compress(&mut &first[..], fs::File::create(&base).unwrap(), 3)
+            .unwrap();
+        let source = dir.path().join("source");
+        fs::write(&source, &second).unwrap();
+
+        let delta = delta_path(&storage, &checksum(&second));
+        assert!(store(&source, &delta, &base, &checksum(&first)).unwrap());
+        assert!(fs::metadata(&delta).unwrap().len() < 1000);
+        assert_eq!(read_header(&delta).unwrap(), Header { base: checksum(&first), depth: 1 });
+        assert_eq!(base_files(&delta).unwrap(), vec![base.clone()]);
+
+        let mut rebuilt = Vec::new();
+        open(&delta).unwrap().read_to_end(&mut rebuilt).unwrap();
+        assert_eq!(rebuilt, second);
+
+        fs::remove_file(&base).unwrap();
+        assert!(open(&delta).is_err());
+    }
+
+    #[test]
+    fn test_window_log() {
+        assert_eq!(window_log(0), 10);
+        assert_eq!(window_log(1 << 20), 21);
+        assert_eq!(window_log(usize::MAX), MAX_WINDOW_LOG);
+    }
+}
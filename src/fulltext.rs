@@ -0,0 +1,196 @@
+/*!
+Full-text index of snapshot contents for the freeze application.
+
+With the `content_index` setting, saves add the text of each new content to
+an SQLite FTS5 index, once per checksum, so that `freeze search --content`,
+the web API and the MCP server find the snapshots holding some words without
+reading every content as `freeze grep` does. `freeze db index` adds the
+contents saved before, or empties the index.
+
+Binary contents, symbolic links and contents larger than 4 MiB are left out.
+The index keeps the text in the database, so passphrase-protected
+repositories only build it once their database is encrypted too.
+*/
+
+use crate::db::Database;
+use crate::snapshot::Snapshot;
+use crate::utils::{format_date, symbol};
+use anyhow::{bail, Result};
+use console::style;
+use serde::Serialize;
+use std::io::Read;
+use std::path::PathBuf;
+
+/// Size above which contents are not indexed.
+pub const MAX_INDEXED_SIZE: i64 = 4 * 1024 * 1024;
+
+/// Snapshots returned by a search unless told otherwise.
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// A snapshot whose content matches a search.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ContentMatch {
+    pub path: PathBuf,
+    pub checksum: String,
+    pub date: String,
+    pub size: i64,
+    /// Excerpt of the content, the matched words between `**`
+    pub snippet: String,
+}
+
+/// Whether the index may hold the text of contents: always, unless the
+/// contents are encrypted and the database is not.
+///
+/// # Errors
+///
+/// Returns an error if the key file cannot be read.
+pub fn is_allowed() -> Result<bool> {
+    Ok(!crate::crypto::is_enabled()? || crate::crypto::is_database_encrypted()?)
+}
+
+/// Adds the text of a snapshot to the index, unless it is already there or
+/// is not text.
+///
+/// # Returns
+///
+/// `true` if the content was added
+///
+/// # Errors
+///
+/// Returns an error if the content cannot be read or the database
+/// operation fails.
+pub fn index_snapshot(snapshot: &Snapshot, db: &Database) -> Result<bool> {
+    if snapshot.symlink.is_some() || snapshot.size > MAX_INDEXED_SIZE || db.is_content_indexed(&snapshot.checksum)? {
+        return Ok(false);
+    }
+    let mut content = Vec::new();
+    snapshot
+        .open_content()?
+        .take(MAX_INDEXED_SIZE as u64)
+        .read_to_end(&mut content)?;
+    if snapshot.is_binary(&content) {
+        return Ok(false);
+    }
+    db.index_content(&snapshot.checksum, &String::from_utf8_lossy(&content))?;
+    Ok(true)
+}
+
+/// Indexes a snapshot just saved, if the `content_index` setting asks for it.
+///
+/// # Errors
+///
+/// Returns an error if the content cannot be read or the database
+/// operation fails.
+pub fn index_saved(snapshot: &Snapshot, db: &Database) -> Result<()> {
+    if crate::config::get().content_index() && is_allowed()? {
+        index_snapshot(snapshot, db)?;
+    }
+    Ok(())
+}
+
+/// Indexes every content saved before the index was turned on.
+///
+/// # Returns
+///
+/// The number of contents added
+///
+/// # Errors
+///
+/// Returns an error if the index is not allowed, or a content cannot be
+/// read.
+pub fn index_all(db: &Database) -> Result<usize> {
+    if !is_allowed()? {
+        bail!("Contents are encrypted: encrypt the database with `freeze key encrypt-database` before indexing them");
+    }
+    let mut count = 0;
+    for checksum in db.unindexed_checksums(MAX_INDEXED_SIZE)? {
+        if let Some(snapshot) = db.get_snapshot_by_checksum(&checksum)?
+            && index_snapshot(&snapshot, db)?
+        {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Turns words typed by a user into an FTS5 query matching contents that
+/// hold all of them. A trailing `*` matches words starting with the rest.
+fn fts_query(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| match word.strip_suffix('*').filter(|prefix| !prefix.is_empty()) {
+            Some(prefix) => format!("\"{}\"*", prefix.replace('"', "\"\"")),
+            None => format!("\"{}\"", word.replace('"', "\"\"")),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Finds the snapshots whose content holds every word of `text`.
+///
+/// # Arguments
+///
+/// * `text` - The words to look for
+/// * `file_type` - Only snapshots of this MIME type, or of this top-level
+///   type such as `text`
+/// * `limit` - Most snapshots returned
+/// * `db` - Database connection
+///
+/// # Returns
+///
+/// The matching snapshots, best matches first
+///
+/// # Errors
+///
+/// Returns an error if `text` holds no word or the database query fails.
+pub fn search(text: &str, file_type: Option<&str>, limit: usize, db: &Database) -> Result<Vec<ContentMatch>> {
+    let query = fts_query(text);
+    if query.is_empty() {
+        bail!("Nothing to search for");
+    }
+    Ok(db
+        .search_content(&query, file_type, limit)?
+        .into_iter()
+        .map(|(path, date, size, checksum, snippet)| ContentMatch {
+            path,
+            checksum,
+            date,
+            size,
+            snippet: snippet.split_whitespace().collect::<Vec<_>>().join(" "),
+        })
+        .collect())
+}
+
+/// Renders matches as the path, checksum and date of each snapshot with its
+/// excerpt below, matched words in bold.
+pub fn render_text(matches: &[ContentMatch]) -> String {
+    let mut out = String::new();
+    for m in matches {
+        out.push_str(&format!(
+            "{} {} {}\n",
+            style(m.path.display()).bold(),
+            style(&m.checksum[..8.min(m.checksum.len())]).yellow(),
+            style(format_date(&m.date)).cyan()
+        ));
+        let snippet: String = m
+            .snippet
+            .split("**")
+            .enumerate()
+            .map(|(i, part)| if i % 2 == 1 { style(part).bold().to_string() } else { part.to_string() })
+            .collect();
+        out.push_str(&format!("  {} {}\n", style(symbol("│", "|")).dim(), snippet));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fts_query() {
+        assert_eq!(fts_query("fn  parse"), "\"fn\" \"parse\"");
+        assert_eq!(fts_query("say \"hi\""), "\"say\" \"\"\"hi\"\"\"");
+        assert_eq!(fts_query("conf* *"), "\"conf\"* \"*\"");
+        assert_eq!(fts_query("  "), "");
+    }
+}
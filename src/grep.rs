@@ -0,0 +1,181 @@
+/*!
+Content search for the freeze application.
+
+`freeze grep` looks for a text in the contents of snapshots, where
+`freeze search` only matches their paths. Each content is decompressed as a
+stream, one line at a time, and searched once however many snapshots share
+it. Binary contents and symbolic links are skipped.
+*/
+
+use crate::db::{Database, SnapshotWithId};
+use crate::utils::{format_date, symbol};
+use anyhow::Result;
+use chrono::DateTime;
+use console::style;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The text to look for.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    text: String,
+    ignore_case: bool,
+}
+
+impl Pattern {
+    /// Creates a pattern, compared without regard to case if `ignore_case`.
+    pub fn new(text: &str, ignore_case: bool) -> Self {
+        let text = if ignore_case { text.to_lowercase() } else { text.to_string() };
+        Self { text, ignore_case }
+    }
+
+    /// Whether `line` contains the pattern.
+    pub fn is_match(&self, line: &str) -> bool {
+        if self.ignore_case {
+            line.to_lowercase().contains(&self.text)
+        } else {
+            line.contains(&self.text)
+        }
+    }
+}
+
+/// A line of a snapshot containing the pattern.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GrepMatch {
+    pub path: PathBuf,
+    pub checksum: String,
+    pub date: String,
+    /// Line number, from 1
+    pub line: usize,
+    /// The line, without its line ending
+    pub text: String,
+}
+
+/// Keeps the newest snapshot of each path.
+fn latest_only(snapshots: &[SnapshotWithId]) -> Vec<SnapshotWithId> {
+    let mut latest: HashMap<&Path, &SnapshotWithId> = HashMap::new();
+    for snapshot in snapshots {
+        let date = DateTime::parse_from_rfc3339(&snapshot.2).ok();
+        latest
+            .entry(&snapshot.1)
+            .and_modify(|kept| {
+                if date > DateTime::parse_from_rfc3339(&kept.2).ok() {
+                    *kept = snapshot;
+                }
+            })
+            .or_insert(snapshot);
+    }
+    snapshots
+        .iter()
+        .filter(|snapshot| latest.get(snapshot.1.as_path()).is_some_and(|kept| std::ptr::eq(*kept, *snapshot)))
+        .cloned()
+        .collect()
+}
+
+/// Searches the contents of snapshots for a pattern.
+///
+/// # Arguments
+///
+/// * `snapshots` - The snapshots to search, as (id, path, date, size,
+///   checksum) in the order of the results
+/// * `pattern` - The text to look for
+/// * `latest` - Whether to search only the newest snapshot of each path
+///
+/// # Returns
+///
+/// The matching lines, snapshot by snapshot
+///
+/// # Errors
+///
+/// Returns an error if the database query fails or a content cannot be
+/// read.
+pub fn grep(
+    snapshots: &[SnapshotWithId],
+    pattern: &Pattern,
+    latest: bool,
+    db: &Database,
+) -> Result<Vec<GrepMatch>> {
+    let snapshots = if latest {
+        latest_only(snapshots)
+    } else {
+        snapshots.to_vec()
+    };
+
+    let mut found: HashMap<String, Vec<(usize, String)>> = HashMap::new();
+    let mut matches = Vec::new();
+    for (_, path, date, _, checksum) in snapshots {
+        if !found.contains_key(&checksum) {
+            let lines = match db.get_snapshot_by_checksum(&checksum)? {
+                Some(snapshot) if snapshot.symlink.is_none() => {
+                    snapshot.find_lines(|line| pattern.is_match(line))?
+                }
+                _ => Vec::new(),
+            };
+            found.insert(checksum.clone(), lines);
+        }
+        for (line, text) in &found[&checksum] {
+            matches.push(GrepMatch {
+                path: path.clone(),
+                checksum: checksum.clone(),
+                date: date.clone(),
+                line: *line,
+                text: text.clone(),
+            });
+        }
+    }
+    Ok(matches)
+}
+
+/// Renders matches grouped by snapshot: path, checksum and date, then each
+/// line with its number.
+pub fn render_text(matches: &[GrepMatch]) -> String {
+    let width = matches.iter().map(|m| m.line).max().unwrap_or(0).to_string().len();
+    let mut out = String::new();
+    let mut current: Option<(&Path, &str)> = None;
+    for m in matches {
+        if current != Some((&m.path, &m.checksum)) {
+            if current.is_some() {
+                out.push('\n');
+            }
+            current = Some((&m.path, &m.checksum));
+            out.push_str(&format!(
+                "{} {} {}\n",
+                style(m.path.display()).bold(),
+                style(&m.checksum[..8.min(m.checksum.len())]).yellow(),
+                style(format_date(&m.date)).cyan()
+            ));
+        }
+        out.push_str(&format!(
+            "{} {}\n",
+            style(format!("{:>width$} {}", m.line, symbol("│", "|"))).dim(),
+            m.text
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern() {
+        assert!(Pattern::new("TODO", false).is_match("// TODO: fix"));
+        assert!(!Pattern::new("todo", false).is_match("// TODO: fix"));
+        assert!(Pattern::new("todo", true).is_match("// TODO: fix"));
+    }
+
+    #[test]
+    fn test_latest_only() {
+        let snapshot =
+            |id: i64, path: &str, date: &str| (id, PathBuf::from(path), date.to_string(), 0, date.to_string());
+        let snapshots = [
+            snapshot(1, "/a", "2024-01-01T10:00:00+00:00"),
+            snapshot(2, "/b", "2024-01-02T10:00:00+00:00"),
+            snapshot(3, "/a", "2024-01-03T10:00:00+00:00"),
+        ];
+        let latest = latest_only(&snapshots);
+        assert_eq!(latest, [snapshots[1].clone(), snapshots[2].clone()]);
+    }
+}
@@ -0,0 +1,125 @@
+/*!
+Content checksums for the freeze application.
+
+Snapshots identify their content by a hexadecimal checksum, which also names
+the stored blob. SHA-256 is the default; with the `hash` setting set to
+`blake3`, new snapshots use BLAKE3, which hashes large trees much faster.
+Each snapshot records the algorithm of its checksum, so files are always
+compared with a snapshot using the algorithm it was saved with.
+*/
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+
+/// A checksum algorithm for snapshot contents.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// SHA-256, the algorithm of every snapshot saved before the setting existed
+    #[default]
+    Sha256,
+    /// BLAKE3: same checksum length, several times faster
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Every algorithm.
+    pub const ALL: [HashAlgorithm; 2] = [HashAlgorithm::Sha256, HashAlgorithm::Blake3];
+
+    /// Name of the algorithm, as stored in the database and the configuration.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Parses a name returned by [`as_str`](Self::as_str).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|algorithm| algorithm.as_str() == name)
+    }
+
+    /// Returns a new incremental hasher.
+    pub fn hasher(self) -> Hasher {
+        match self {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    /// Checksum of in-memory content.
+    pub fn checksum(self, content: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(content);
+        hasher.checksum()
+    }
+
+    /// Checksum of everything `reader` yields.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails.
+    pub fn checksum_reader(self, mut reader: impl Read) -> Result<String> {
+        let mut hasher = self.hasher();
+        let mut buffer = [0; 64 * 1024];
+        loop {
+            let bytes_read = reader.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..bytes_read]);
+        }
+        Ok(hasher.checksum())
+    }
+}
+
+/// An incremental hasher of either algorithm.
+#[derive(Clone)]
+pub enum Hasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    /// Adds `data` to the hashed content.
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// Hexadecimal checksum of everything hashed so far.
+    pub fn checksum(&self) -> String {
+        match self {
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.clone().finalize()),
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(
+            HashAlgorithm::Sha256.checksum(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            HashAlgorithm::Blake3.checksum(b"abc"),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        for algorithm in HashAlgorithm::ALL {
+            assert_eq!(algorithm.checksum_reader(&b"abc"[..]).unwrap(), algorithm.checksum(b"abc"));
+            assert_eq!(HashAlgorithm::from_name(algorithm.as_str()), Some(algorithm));
+        }
+        assert_eq!(HashAlgorithm::from_name("md5"), None);
+    }
+}
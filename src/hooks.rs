@@ -0,0 +1,129 @@
+/*!
+Save and restore hooks for the freeze application.
+
+Hooks are shell commands set in `config.toml` (`pre_save_hook`,
+`post_save_hook`, `pre_restore_hook`, `post_restore_hook`). They run through
+`sh -c` (`cmd /C` on Windows) with the operation described by environment
+variables:
+
+- `FREEZE_HOOK`: the hook name, e.g. `pre-save`
+- `FREEZE_OPERATION`: `save` or `restore`
+- `FREEZE_PATH`: the file or directory being saved or restored
+- `FREEZE_CHECKSUM`: the checksum saved or restored, for post hooks on a
+  single file (empty otherwise)
+
+A failing pre hook cancels the operation. Hook output goes to standard error
+so it never mixes with JSON output or the MCP protocol.
+*/
+
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// The operation a hook runs around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Save,
+    Restore,
+}
+
+/// Whether a hook runs before or after the operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Pre,
+    Post,
+}
+
+/// Runs the configured hook for `phase` and `operation`, if any.
+///
+/// # Arguments
+///
+/// * `phase` - Before or after the operation
+/// * `operation` - The operation
+/// * `path` - The file or directory saved or restored
+/// * `checksum` - The content saved or restored, when a single file is involved
+///
+/// # Errors
+///
+/// Returns an error if the hook cannot be started or exits unsuccessfully.
+pub fn run(phase: Phase, operation: Operation, path: &Path, checksum: Option<&str>) -> Result<()> {
+    let config = crate::config::get();
+    let command = match (phase, operation) {
+        (Phase::Pre, Operation::Save) => &config.pre_save_hook,
+        (Phase::Post, Operation::Save) => &config.post_save_hook,
+        (Phase::Pre, Operation::Restore) => &config.pre_restore_hook,
+        (Phase::Post, Operation::Restore) => &config.post_restore_hook,
+    };
+    match command {
+        Some(command) => run_command(command, phase, operation, path, checksum),
+        None => Ok(()),
+    }
+}
+
+/// Runs a hook command with the environment describing the operation.
+fn run_command(
+    command: &str,
+    phase: Phase,
+    operation: Operation,
+    path: &Path,
+    checksum: Option<&str>,
+) -> Result<()> {
+    let operation_name = match operation {
+        Operation::Save => "save",
+        Operation::Restore => "restore",
+    };
+    let hook = match phase {
+        Phase::Pre => format!("pre-{}", operation_name),
+        Phase::Post => format!("post-{}", operation_name),
+    };
+
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let status = shell
+        .arg(command)
+        .env("FREEZE_HOOK", &hook)
+        .env("FREEZE_OPERATION", operation_name)
+        .env("FREEZE_PATH", path)
+        .env("FREEZE_CHECKSUM", checksum.unwrap_or_default())
+        .stdin(Stdio::null())
+        .stdout(std::io::stderr())
+        .status()
+        .map_err(|e| anyhow::anyhow!("Could not run the {} hook: {}", hook, e))?;
+
+    if !status.success() {
+        let code = status.code().map_or_else(|| "a signal".to_string(), |code| format!("code {}", code));
+        match phase {
+            Phase::Pre => bail!("The {} hook exited with {}; nothing was done", hook, code),
+            Phase::Post => bail!("The {} hook exited with {} after the {} completed", hook, code, operation_name),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_command() {
+        let path = Path::new("/tmp/project");
+        run_command(
+            r#"test "$FREEZE_HOOK" = post-save && test "$FREEZE_PATH" = /tmp/project && test "$FREEZE_CHECKSUM" = abc"#,
+            Phase::Post,
+            Operation::Save,
+            path,
+            Some("abc"),
+        )
+        .unwrap();
+
+        let err = run_command("exit 3", Phase::Pre, Operation::Restore, path, None).unwrap_err();
+        assert_eq!(err.to_string(), "The pre-restore hook exited with code 3; nothing was done");
+    }
+}
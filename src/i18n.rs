@@ -0,0 +1,138 @@
+/*!
+Localization of user-facing messages.
+
+Messages live in Fluent files under `locales/` and are embedded in the
+binary. The locale is picked from `FREEZE_LANG`, then `LC_ALL`,
+`LC_MESSAGES` and `LANG`; messages missing from the selected locale fall
+back to English.
+*/
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+type Bundle = FluentBundle<FluentResource>;
+
+/// Locales shipped with freeze, as (language, Fluent source).
+const LOCALES: [(&str, &str); 2] = [
+    ("en", include_str!("../locales/en.ftl")),
+    ("fr", include_str!("../locales/fr.ftl")),
+];
+
+/// The selected bundle (if not English) and the English fallback.
+static BUNDLES: OnceLock<(Option<Bundle>, Bundle)> = OnceLock::new();
+
+/// Builds the bundle for one of the shipped locales.
+fn build_bundle(lang: &str, source: &str) -> Bundle {
+    let langid: LanguageIdentifier = lang.parse().expect("invalid built-in locale");
+    let resource =
+        FluentResource::try_new(source.to_string()).expect("invalid built-in Fluent resource");
+    let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+    // Unicode isolation marks show up as garbage in most terminals
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .expect("duplicate message in built-in Fluent resource");
+    bundle
+}
+
+/// Returns the language requested by the environment, e.g. `fr` for `fr_FR.UTF-8`.
+fn requested_language() -> Option<String> {
+    ["FREEZE_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .split(['_', '-', '.', '@'])
+                .next()
+                .unwrap_or_default()
+                .to_lowercase()
+        })
+}
+
+fn bundles() -> &'static (Option<Bundle>, Bundle) {
+    BUNDLES.get_or_init(|| {
+        let english = build_bundle(LOCALES[0].0, LOCALES[0].1);
+        let selected = requested_language().and_then(|lang| {
+            LOCALES[1..]
+                .iter()
+                .find(|(id, _)| *id == lang)
+                .map(|(id, source)| build_bundle(id, source))
+        });
+        (selected, english)
+    })
+}
+
+/// Formats message `id` from `bundle`, if it defines it.
+fn format(bundle: &Bundle, id: &str, args: Option<&FluentArgs>) -> Option<String> {
+    let pattern = bundle.get_message(id)?.value()?;
+    let mut errors = Vec::new();
+    Some(bundle.format_pattern(pattern, args, &mut errors).into_owned())
+}
+
+/// Returns the localized message `id`, formatted with `args`.
+///
+/// Falls back to English, then to the message id itself.
+pub fn tr(id: &str, args: Option<&FluentArgs>) -> String {
+    let (selected, english) = bundles();
+    selected
+        .as_ref()
+        .and_then(|bundle| format(bundle, id, args))
+        .or_else(|| format(english, id, args))
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// Returns a localized message: `t!("id")` or `t!("id", name = value, ...)`.
+macro_rules! t {
+    ($id:expr) => {
+        $crate::i18n::tr($id, None)
+    };
+    ($id:expr, $($key:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($key), $value);)+
+        $crate::i18n::tr($id, Some(&args))
+    }};
+}
+
+pub(crate) use t;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message_ids(source: &str) -> Vec<&str> {
+        let mut ids: Vec<&str> = source
+            .lines()
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_lowercase()))
+            .filter_map(|line| line.split_once(" =").map(|(id, _)| id))
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_locales_define_the_same_messages() {
+        let english = message_ids(LOCALES[0].1);
+        for (lang, source) in &LOCALES[1..] {
+            assert_eq!(message_ids(source), english, "locale {} is out of sync", lang);
+        }
+    }
+
+    #[test]
+    fn test_format_with_arguments() {
+        let bundle = build_bundle("en", LOCALES[0].1);
+        let mut args = FluentArgs::new();
+        args.set("count", 1);
+        assert_eq!(
+            format(&bundle, "pagination-items", Some(&args)).unwrap(),
+            "(1 item)"
+        );
+        args.set("count", 3);
+        assert_eq!(
+            format(&bundle, "pagination-items", Some(&args)).unwrap(),
+            "(3 items)"
+        );
+    }
+}
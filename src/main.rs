@@ -1,20 +1,43 @@
 // main.rs
+pub mod archive;
+pub mod backup;
+pub mod bench;
+pub mod blame;
+pub mod catalog;
+pub mod chunks;
 pub mod cli;
+pub mod compression;
+pub mod config;
+pub mod crypto;
+pub mod daemon;
+pub mod delta;
+pub mod fulltext;
+pub mod grep;
+pub mod hash;
 pub mod db;
+pub mod hooks;
+pub mod i18n;
 pub mod mcp;
+pub mod merge;
+pub mod report;
+pub mod retention;
+pub mod selfupdate;
+pub mod session;
 pub mod snapshot;
+pub mod stats;
 pub mod utils;
+pub mod watch;
 pub mod web;
+pub mod xattrs;
 
 use anyhow::Result;
 
 fn main() -> Result<()> {
-    // Clean up any orphaned temporary files at startup
-    if let Err(e) = snapshot::Snapshot::cleanup_temp_files() {
-        eprintln!("Warning: Failed to cleanup temporary files: {}", e);
-    }
-    
-    tokio::runtime::Runtime::new()?.block_on(async {
+    let result = tokio::runtime::Runtime::new()?.block_on(async {
         cli::run().await
-    })
+    });
+
+    // Drop the temporary storage of an in-memory database
+    db::Database::cleanup_ephemeral();
+    result
 }
@@ -3,18 +3,32 @@ MCP (Model Context Protocol) server implementation for freeze.
 
 This module implements an MCP server that exposes freeze's functionality
 as MCP tools, allowing AI assistants to interact with the freeze snapshot system.
+Snapshot contents are also offered as MCP resources, at
+`freeze://snapshot/<checksum>`, which clients can attach as context.
 */
 
-use crate::db::Database;
-use crate::snapshot::Snapshot;
-use crate::utils::format_size;
+use crate::db::{Database, SearchFilter};
+use crate::snapshot::{LineRange, SaveProgress, Snapshot};
+use crate::utils::{ascii_mode, format_date, format_ratio, format_size, rule};
 use anyhow::{Context, Result};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderName, HeaderValue, StatusCode};
+use axum::response::sse::{Event, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sha2::{Digest, Sha256};
 use std::fs;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
 use std::io::{BufRead, Write};
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct JsonRpcRequest {
@@ -50,39 +64,339 @@ struct ToolContent {
     text: String,
 }
 
+/// Where the notifications sent while handling a request go.
+#[derive(Clone)]
+enum Notifier {
+    /// Standard output, between the responses of the stdio transport
+    Stdout,
+    /// The event stream answering an HTTP request
+    Stream(tokio::sync::mpsc::UnboundedSender<serde_json::Value>),
+    /// Nowhere, the HTTP client having asked for a plain JSON response
+    Discard,
+}
+
+impl Notifier {
+    fn send(&self, notification: serde_json::Value) {
+        match self {
+            Notifier::Stdout => {
+                let mut stdout = std::io::stdout().lock();
+                let _ = writeln!(stdout, "{}", notification);
+                let _ = stdout.flush();
+            }
+            Notifier::Stream(sender) => {
+                let _ = sender.send(notification);
+            }
+            Notifier::Discard => {}
+        }
+    }
+}
+
+/// What a request may do and where its notifications go, which depend on
+/// the transport and, over HTTP, on the API key of the client.
+#[derive(Clone)]
+struct RequestContext {
+    /// Whether the tools changing the repository are hidden and refused
+    read_only: bool,
+    notifier: Notifier,
+}
+
 pub async fn run_server() -> Result<()> {
     let stdin = std::io::stdin();
     let mut stdout = std::io::stdout();
-    let mut lines = stdin.lock().lines();
 
     let capabilities = json!({
-        "tools": get_tools()
+        "tools": get_tools(),
+        "resources": {}
     });
 
-    loop {
-        if let Some(Ok(line)) = lines.next() {
-            if line.trim().is_empty() {
-                continue;
+    // Stop once the client closes stdin
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<JsonRpcRequest>(&line) {
+            Ok(request) => {
+                let context = RequestContext {
+                    read_only: crate::config::get().read_only(),
+                    notifier: Notifier::Stdout,
+                };
+                let response = handle_request(&request, &capabilities, &context).await;
+                let response_str = serde_json::to_string(&response)?;
+                writeln!(stdout, "{}", response_str)?;
+                stdout.flush()?;
             }
+            Err(e) => {
+                eprintln!("Failed to parse request: {}", e);
+            }
+        }
+    }
+    Ok(())
+}
 
-            match serde_json::from_str::<JsonRpcRequest>(&line) {
-                Ok(request) => {
-                    let response = handle_request(&request, &capabilities).await;
-                    let response_str = serde_json::to_string(&response)?;
-                    writeln!(stdout, "{}", response_str)?;
-                    stdout.flush()?;
-                }
-                Err(e) => {
-                    eprintln!("Failed to parse request: {}", e);
+/// Header carrying the session ID of the HTTP transport.
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Largest request body the HTTP transport accepts.
+const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/// Shared state of the HTTP transport.
+#[derive(Clone)]
+struct HttpState {
+    db: Arc<Mutex<Database>>,
+    /// IDs of the sessions opened by `initialize` and not closed since
+    sessions: Arc<Mutex<HashSet<String>>>,
+    capabilities: Arc<serde_json::Value>,
+    /// Origins allowed besides those of the local machine
+    allowed_origins: Arc<Vec<String>>,
+}
+
+/// Serves MCP over streamable HTTP at `http://<addr>/mcp`.
+///
+/// Clients POST JSON-RPC messages and get the responses as JSON, or as an
+/// event stream carrying the progress notifications first when they send a
+/// progress token and accept `text/event-stream`. `initialize` opens a
+/// session whose ID, returned in the `Mcp-Session-Id` header, later
+/// requests must send; DELETE closes it. The web API keys apply: once one
+/// exists, every request needs a key, and read-only keys only see the tools
+/// that do not change the repository.
+///
+/// Browsers may only call the server from pages of the local machine or of
+/// `allowed_origins`. Until an API key exists, requests must also be
+/// addressed to a loopback host, so that a site reached through DNS
+/// rebinding cannot use the server.
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be opened or the address bound.
+pub async fn run_http_server(addr: SocketAddr, allowed_origins: Vec<String>) -> Result<()> {
+    let allowed_origins: Arc<Vec<String>> = Arc::new(
+        allowed_origins
+            .into_iter()
+            .map(|origin| origin.trim_end_matches('/').to_string())
+            .collect(),
+    );
+    let state = HttpState {
+        db: Arc::new(Mutex::new(Database::new()?)),
+        sessions: Arc::default(),
+        capabilities: Arc::new(json!({
+            "tools": get_tools(),
+            "resources": {}
+        })),
+        allowed_origins: allowed_origins.clone(),
+    };
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin.to_str().is_ok_and(|origin| origin_allowed(origin, &allowed_origins))
+        }))
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers([HeaderName::from_static(SESSION_HEADER)]);
+    let app = Router::new()
+        .route("/mcp", post(http_post).get(http_get).delete(http_delete))
+        .layer(cors)
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+/// Whether `authority`, a host with an optional port, names the local
+/// machine.
+fn is_loopback(authority: &str) -> bool {
+    let hostname = match authority.rsplit_once(':') {
+        Some((hostname, port)) if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => hostname,
+        _ => authority,
+    };
+    let hostname = hostname.trim_start_matches('[').trim_end_matches(']');
+    hostname.eq_ignore_ascii_case("localhost")
+        || hostname.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Whether a page at `origin` may call the server: pages of the local
+/// machine, or of one of the `allowed` origins.
+fn origin_allowed(origin: &str, allowed: &[String]) -> bool {
+    if allowed.iter().any(|allowed| allowed == origin) {
+        return true;
+    }
+    match origin.split_once("://") {
+        Some(("http" | "https", authority)) => is_loopback(authority),
+        _ => false,
+    }
+}
+
+/// Checks the API key of an HTTP request.
+///
+/// # Returns
+///
+/// Whether the client may only read, or the status and reason refusing the
+/// request
+fn authorize(state: &HttpState, request: &Request) -> std::result::Result<bool, (StatusCode, String)> {
+    let read_only = crate::config::get().read_only();
+    let db = state.db.lock().unwrap();
+    match db.count_api_keys() {
+        Ok(0) => {
+            let header = |name| request.headers().get(name).and_then(|v: &HeaderValue| v.to_str().ok());
+            let host = header(header::HOST).or_else(|| request.uri().authority().map(|a| a.as_str()));
+            if !host.is_some_and(is_loopback) {
+                return Err((StatusCode::FORBIDDEN, "Host not allowed without an API key".to_string()));
+            }
+            match header(header::ORIGIN) {
+                Some(origin) if !origin_allowed(origin, &state.allowed_origins) => {
+                    Err((StatusCode::FORBIDDEN, "Origin not allowed".to_string()))
                 }
+                _ => Ok(read_only),
+            }
+        }
+        Ok(_) => {
+            let origin = request.headers().get(header::ORIGIN).and_then(|v| v.to_str().ok());
+            if origin.is_some_and(|origin| !origin_allowed(origin, &state.allowed_origins)) {
+                return Err((StatusCode::FORBIDDEN, "Origin not allowed".to_string()));
             }
+            let scope = crate::web::auth::extract_token(request)
+                .and_then(|token| db.authenticate_api_key(&crate::web::auth::hash_token(&token)).ok().flatten());
+            match scope.as_deref() {
+                None => Err((StatusCode::UNAUTHORIZED, "Missing or invalid API key".to_string())),
+                Some(crate::web::auth::SCOPE_READ_ONLY) => Ok(true),
+                Some(_) => Ok(read_only),
+            }
+        }
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Answers a malformed HTTP request with a JSON-RPC error.
+fn http_error(status: StatusCode, code: i32, message: &str) -> Response {
+    let response = JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: None,
+        result: None,
+        error: Some(JsonRpcError {
+            code,
+            message: message.to_string(),
+        }),
+    };
+    (status, Json(response)).into_response()
+}
+
+/// Handles the JSON-RPC messages POSTed by a client.
+async fn http_post(State(state): State<HttpState>, request: Request) -> Response {
+    let read_only = match authorize(&state, &request) {
+        Ok(read_only) => read_only,
+        Err(refusal) => return refusal.into_response(),
+    };
+    let headers = request.headers().clone();
+    let body = match axum::body::to_bytes(request.into_body(), MAX_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(e) => return http_error(StatusCode::BAD_REQUEST, -32700, &e.to_string()),
+    };
+    let (messages, batch) = match serde_json::from_slice(&body) {
+        Ok(serde_json::Value::Array(messages)) => (messages, true),
+        Ok(message) => (vec![message], false),
+        Err(e) => return http_error(StatusCode::BAD_REQUEST, -32700, &format!("Parse error: {}", e)),
+    };
+    let requests: Vec<JsonRpcRequest> = match messages.into_iter().map(serde_json::from_value).collect() {
+        Ok(requests) => requests,
+        Err(e) => return http_error(StatusCode::BAD_REQUEST, -32600, &format!("Invalid Request: {}", e)),
+    };
+
+    let session = if requests.iter().any(|request| request.method == "initialize") {
+        if requests.len() > 1 {
+            return http_error(StatusCode::BAD_REQUEST, -32600, "Invalid Request: initialize must be sent alone");
+        }
+        let mut bytes = [0u8; 16];
+        if let Err(e) = getrandom::fill(&mut bytes) {
+            return http_error(StatusCode::INTERNAL_SERVER_ERROR, -32603, &e.to_string());
+        }
+        let session: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        state.sessions.lock().unwrap().insert(session.clone());
+        session
+    } else {
+        match headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()) {
+            None => return http_error(StatusCode::BAD_REQUEST, -32600, "Missing Mcp-Session-Id header"),
+            Some(session) if !state.sessions.lock().unwrap().contains(session) => {
+                return http_error(StatusCode::NOT_FOUND, -32600, "Unknown session");
+            }
+            Some(session) => session.to_string(),
         }
+    };
+    let session_header = [(HeaderName::from_static(SESSION_HEADER), session)];
+
+    // Notifications and responses get no answer
+    let requests: Vec<_> = requests.into_iter().filter(|request| request.id.is_some()).collect();
+    if requests.is_empty() {
+        return (StatusCode::ACCEPTED, session_header).into_response();
+    }
+
+    let accepts_stream = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"));
+    let wants_progress = requests.iter().any(|request| {
+        request
+            .params
+            .as_ref()
+            .is_some_and(|params| params.pointer("/_meta/progressToken").is_some())
+    });
+    if accepts_stream && wants_progress {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let context = RequestContext {
+            read_only,
+            notifier: Notifier::Stream(sender.clone()),
+        };
+        tokio::spawn(async move {
+            for request in &requests {
+                let response = handle_request(request, &state.capabilities, &context).await;
+                let _ = sender.send(json!(response));
+            }
+        });
+        // Ends once the task is done, dropping the last sender
+        let events = futures_util::stream::unfold(receiver, |mut receiver| async move {
+            let message = receiver.recv().await?;
+            Some((Ok::<_, Infallible>(Event::default().event("message").data(message.to_string())), receiver))
+        });
+        return (session_header, Sse::new(events)).into_response();
+    }
+
+    let context = RequestContext {
+        read_only,
+        notifier: Notifier::Discard,
+    };
+    let mut responses = Vec::new();
+    for request in &requests {
+        responses.push(handle_request(request, &state.capabilities, &context).await);
+    }
+    if batch {
+        (session_header, Json(responses)).into_response()
+    } else {
+        (session_header, Json(responses.remove(0))).into_response()
+    }
+}
+
+/// Refuses to open a stream for messages from the server, which only
+/// answers requests.
+async fn http_get() -> Response {
+    StatusCode::METHOD_NOT_ALLOWED.into_response()
+}
+
+/// Closes the session of a client.
+async fn http_delete(State(state): State<HttpState>, request: Request) -> Response {
+    if let Err(refusal) = authorize(&state, &request) {
+        return refusal.into_response();
+    }
+    match request.headers().get(SESSION_HEADER).and_then(|v| v.to_str().ok()) {
+        None => (StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header").into_response(),
+        Some(session) if state.sessions.lock().unwrap().remove(session) => StatusCode::NO_CONTENT.into_response(),
+        Some(_) => (StatusCode::NOT_FOUND, "Unknown session").into_response(),
     }
 }
 
 async fn handle_request(
     request: &JsonRpcRequest,
     capabilities: &serde_json::Value,
+    context: &RequestContext,
 ) -> JsonRpcResponse {
     let id = request.id.clone();
 
@@ -115,14 +429,14 @@ async fn handle_request(
                 jsonrpc: "2.0".to_string(),
                 id,
                 result: Some(json!({
-                    "tools": get_tools()
+                    "tools": available_tools(context.read_only)
                 })),
                 error: None,
             }
         }
         "tools/call" => {
             if let Some(params) = &request.params {
-                let result = call_tool(params).await;
+                let result = call_tool(params, context).await;
                 JsonRpcResponse {
                     jsonrpc: "2.0".to_string(),
                     id,
@@ -141,6 +455,36 @@ async fn handle_request(
                 }
             }
         }
+        "resources/list" => {
+            let cursor = request
+                .params
+                .as_ref()
+                .and_then(|params| params.get("cursor"))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            rpc_response(id, list_resources(cursor).await)
+        }
+        "resources/templates/list" => JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(json!({
+                "resourceTemplates": [{
+                    "uriTemplate": format!("{}{{checksum}}", RESOURCE_PREFIX),
+                    "name": "Snapshot",
+                    "description": "Content of the snapshot with this checksum"
+                }]
+            })),
+            error: None,
+        },
+        "resources/read" => {
+            let uri = request
+                .params
+                .as_ref()
+                .and_then(|params| params.get("uri"))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            rpc_response(id, read_resource(uri).await)
+        }
         _ => JsonRpcResponse {
             jsonrpc: "2.0".to_string(),
             id,
@@ -153,6 +497,193 @@ async fn handle_request(
     }
 }
 
+/// Builds the response to a request handled by a method returning its
+/// result or error.
+fn rpc_response(
+    id: Option<serde_json::Value>,
+    result: std::result::Result<serde_json::Value, JsonRpcError>,
+) -> JsonRpcResponse {
+    match result {
+        Ok(result) => JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        },
+        Err(error) => JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        },
+    }
+}
+
+/// Scheme and path of the URIs of snapshot resources, before the checksum.
+const RESOURCE_PREFIX: &str = "freeze://snapshot/";
+
+/// Resources listed per `resources/list` page.
+const RESOURCES_PER_PAGE: usize = 100;
+
+/// Size above which `resources/read` refuses a snapshot, like the default
+/// limit of `freeze_view`.
+const MAX_RESOURCE_SIZE: i64 = 5 * 1024 * 1024;
+
+/// Returns the checksum of a snapshot resource URI.
+fn checksum_from_uri(uri: &str) -> Option<&str> {
+    uri.strip_prefix(RESOURCE_PREFIX)
+        .filter(|checksum| !checksum.is_empty() && checksum.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Describes the snapshot of `path` with `checksum` as a resource.
+fn snapshot_resource(path: &std::path::Path, date: &str, size: i64, checksum: &str, mime: Option<&str>) -> serde_json::Value {
+    let mut resource = json!({
+        "uri": format!("{}{}", RESOURCE_PREFIX, checksum),
+        "name": path.display().to_string(),
+        "description": format!("Snapshot of {} taken {}", path.display(), format_date(date)),
+        "size": size,
+    });
+    if let Some(mime) = mime {
+        resource["mimeType"] = json!(mime);
+    }
+    resource
+}
+
+/// Lists a page of snapshots as resources, once per content, newest first.
+/// The cursor is the position of the page in the listing.
+async fn list_resources(cursor: Option<String>) -> std::result::Result<serde_json::Value, JsonRpcError> {
+    let start = match cursor.as_deref().map(str::parse::<usize>) {
+        None => 0,
+        Some(Ok(start)) => start,
+        Some(Err(_)) => {
+            return Err(JsonRpcError {
+                code: -32602,
+                message: "Invalid cursor".to_string(),
+            });
+        }
+    };
+    let result = tokio::task::spawn_blocking(move || {
+        let db = Database::new()?;
+        let mime_types = db.list_mime_types()?;
+        let mut seen = HashSet::new();
+        let snapshots: Vec<_> = db
+            .list_all_snapshots()?
+            .into_iter()
+            .filter(|(.., checksum)| seen.insert(checksum.clone()))
+            .collect();
+        let resources: Vec<_> = snapshots
+            .iter()
+            .skip(start)
+            .take(RESOURCES_PER_PAGE)
+            .map(|(id, path, date, size, checksum)| {
+                let mime = mime_types.get(id);
+                snapshot_resource(path, date, *size, checksum, mime.map(String::as_str))
+            })
+            .collect();
+        let mut result = json!({ "resources": resources });
+        if start + RESOURCES_PER_PAGE < snapshots.len() {
+            result["nextCursor"] = json!((start + RESOURCES_PER_PAGE).to_string());
+        }
+        Ok::<_, anyhow::Error>(result)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(e)) => Err(JsonRpcError { code: -32603, message: e.to_string() }),
+        Err(e) => Err(JsonRpcError { code: -32603, message: e.to_string() }),
+    }
+}
+
+/// Reads the content of a snapshot resource: as text, or base64-encoded
+/// for binary contents.
+async fn read_resource(uri: Option<String>) -> std::result::Result<serde_json::Value, JsonRpcError> {
+    let Some(uri) = uri else {
+        return Err(JsonRpcError {
+            code: -32602,
+            message: "Invalid params: missing uri".to_string(),
+        });
+    };
+    let Some(checksum) = checksum_from_uri(&uri).map(|s| s.to_string()) else {
+        return Err(JsonRpcError {
+            code: -32602,
+            message: format!("Invalid snapshot URI: {}", uri),
+        });
+    };
+    let result = tokio::task::spawn_blocking(move || {
+        let db = Database::new()?;
+        let Some(snapshot) = db.get_snapshot_by_checksum(&checksum)? else {
+            return Ok(None);
+        };
+        let mut content = json!({ "uri": uri });
+        if let Some(target) = &snapshot.symlink {
+            content["mimeType"] = json!("text/plain");
+            content["text"] = json!(format!("Symbolic link to {}", target.display()));
+            return Ok(Some(json!({ "contents": [content] })));
+        }
+        if snapshot.size > MAX_RESOURCE_SIZE {
+            anyhow::bail!(
+                "Snapshot too large ({} > {})",
+                format_size(snapshot.size),
+                format_size(MAX_RESOURCE_SIZE)
+            );
+        }
+        let data = snapshot.get_decompressed_content()?;
+        if snapshot.is_binary(&data) {
+            use base64::Engine;
+            content["mimeType"] = json!(snapshot.mime.as_deref().unwrap_or("application/octet-stream"));
+            content["blob"] = json!(base64::engine::general_purpose::STANDARD.encode(&data));
+        } else {
+            content["mimeType"] = json!(snapshot.mime.as_deref().unwrap_or("text/plain"));
+            content["text"] = json!(String::from_utf8_lossy(&data));
+        }
+        Ok::<_, anyhow::Error>(Some(json!({ "contents": [content] })))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(result))) => Ok(result),
+        Ok(Ok(None)) => Err(JsonRpcError {
+            code: -32002,
+            message: "Resource not found".to_string(),
+        }),
+        Ok(Err(e)) => Err(JsonRpcError { code: -32603, message: e.to_string() }),
+        Err(e) => Err(JsonRpcError { code: -32603, message: e.to_string() }),
+    }
+}
+
+/// Tools that change the repository, hidden and refused in read-only mode.
+const MUTATING_TOOLS: [&str; 6] = [
+    "freeze_save",
+    "freeze_clear",
+    "freeze_prune",
+    "freeze_delete_snapshot",
+    "freeze_exclusion_add",
+    "freeze_exclusion_remove",
+];
+
+/// Arguments of `freeze_search` passed on to the search filter.
+const SEARCH_FILTERS: [&str; 9] = [
+    "type",
+    "since",
+    "until",
+    "larger_than",
+    "smaller_than",
+    "checksum",
+    "ext",
+    "tag",
+    "latest",
+];
+
+/// The tools offered to clients, without those changing the repository if
+/// it is read-only.
+fn available_tools(read_only: bool) -> Vec<serde_json::Value> {
+    get_tools()
+        .into_iter()
+        .filter(|tool| !read_only || !MUTATING_TOOLS.contains(&tool["name"].as_str().unwrap_or_default()))
+        .collect()
+}
+
 fn get_tools() -> Vec<serde_json::Value> {
     vec![
         json!({
@@ -164,6 +695,14 @@ fn get_tools() -> Vec<serde_json::Value> {
                     "path": {
                         "type": "string",
                         "description": "Path to the file or directory to snapshot"
+                    },
+                    "message": {
+                        "type": "string",
+                        "description": "Optional message describing the snapshot (e.g. 'before upgrading deps')"
+                    },
+                    "keep_going": {
+                        "type": "boolean",
+                        "description": "Skip files that cannot be read and list them instead of failing (default: false)"
                     }
                 },
                 "required": ["path"]
@@ -215,6 +754,38 @@ fn get_tools() -> Vec<serde_json::Value> {
                 }
             }
         }),
+        json!({
+            "name": "freeze_directory_stats",
+            "description": "Break down the snapshots below a directory per directory inside it: snapshots, files, newest snapshot and total size",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "root": {
+                        "type": "string",
+                        "description": "Directory to break down (default: current directory, / for the top-level directories)"
+                    },
+                    "top": {
+                        "type": "integer",
+                        "description": "Most directories listed, largest first",
+                        "default": 20
+                    }
+                }
+            }
+        }),
+        json!({
+            "name": "freeze_stats",
+            "description": "Report how much space freeze uses and on what: snapshots, storage used, deduplication and compression savings, the directories and paths using the most space and the largest snapshots",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "top": {
+                        "type": "integer",
+                        "description": "Most directories, paths and snapshots listed, largest first",
+                        "default": 10
+                    }
+                }
+            }
+        }),
         json!({
             "name": "freeze_search",
             "description": "Search snapshots by pattern",
@@ -224,11 +795,69 @@ fn get_tools() -> Vec<serde_json::Value> {
                     "pattern": {
                         "type": "string",
                         "description": "Pattern to search for in snapshot paths"
+                    },
+                    "type": {
+                        "type": "string",
+                        "description": "Only snapshots of this MIME type (image/png) or top-level type (image, text, video...)"
+                    },
+                    "since": {
+                        "type": "string",
+                        "description": "Only snapshots taken since this date (YYYY-MM-DD, RFC3339, or an age like 7d)"
+                    },
+                    "until": {
+                        "type": "string",
+                        "description": "Only snapshots taken until this date (YYYY-MM-DD, RFC3339, or an age like 7d)"
+                    },
+                    "larger_than": {
+                        "type": "string",
+                        "description": "Only snapshots larger than this size (e.g. 500, 10K, 2M)"
+                    },
+                    "smaller_than": {
+                        "type": "string",
+                        "description": "Only snapshots smaller than this size (e.g. 500, 10K, 2M)"
+                    },
+                    "checksum": {
+                        "type": "string",
+                        "description": "Only snapshots whose checksum starts with this prefix"
+                    },
+                    "ext": {
+                        "type": "string",
+                        "description": "Only files with this extension (e.g. rs)"
+                    },
+                    "tag": {
+                        "type": "string",
+                        "description": "Only snapshots with this metadata entry: a key, or key=value"
+                    },
+                    "latest": {
+                        "type": "boolean",
+                        "description": "Only the newest matching snapshot of each path"
                     }
                 },
                 "required": ["pattern"]
             }
         }),
+        json!({
+            "name": "freeze_search_content",
+            "description": "Search snapshots by the words in their content, using the full-text index",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Words the content must hold; a trailing * matches words starting with the rest"
+                    },
+                    "type": {
+                        "type": "string",
+                        "description": "Only snapshots of this MIME type (text/x-rust) or top-level type (text, application...)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Most snapshots returned (default 50)"
+                    }
+                },
+                "required": ["query"]
+            }
+        }),
         json!({
             "name": "freeze_check",
             "description": "Check if files have changed since last snapshot",
@@ -261,6 +890,18 @@ fn get_tools() -> Vec<serde_json::Value> {
                     "checksum": {
                         "type": "string",
                         "description": "Checksum of the snapshot to view (optional, uses latest if not provided)"
+                    },
+                    "lines": {
+                        "type": "string",
+                        "description": "Only return lines FIRST:LAST, 1-indexed, either bound optional (e.g. 100:200)"
+                    },
+                    "head": {
+                        "type": "integer",
+                        "description": "Only return the first N lines"
+                    },
+                    "tail": {
+                        "type": "integer",
+                        "description": "Only return the last N lines"
                     }
                 },
                 "required": ["path"]
@@ -288,6 +929,28 @@ fn get_tools() -> Vec<serde_json::Value> {
                 "required": ["snapshot_path"]
             }
         }),
+        json!({
+            "name": "freeze_export_directory",
+            "description": "Reconstruct the snapshotted state of a whole directory in a destination folder, without touching the live directory. Use date to rebuild an older state",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path of the snapshotted directory"
+                    },
+                    "destination": {
+                        "type": "string",
+                        "description": "Folder to write the files to (created if missing)"
+                    },
+                    "date": {
+                        "type": "string",
+                        "description": "Use the latest snapshot of each file taken at or before this date (RFC3339 or YYYY-MM-DD [HH:MM[:SS]]). Defaults to the latest snapshots"
+                    }
+                },
+                "required": ["path", "destination"]
+            }
+        }),
         json!({
             "name": "freeze_clear",
             "description": "Clear snapshots",
@@ -306,6 +969,63 @@ fn get_tools() -> Vec<serde_json::Value> {
                 }
             }
         }),
+        json!({
+            "name": "freeze_prune",
+            "description": "Delete old snapshots by retention rule and report what was deleted, or with dry_run (the default) what would be. keep_last and older_than may be combined: a snapshot is then deleted only if both rules let it go. With neither, the given or configured retention schedule applies. Pinned snapshots are always kept",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Only prune snapshots of this file or below this directory (default: every path)"
+                    },
+                    "keep_last": {
+                        "type": "integer",
+                        "description": "Keep this many most recent snapshots of each file",
+                        "minimum": 1
+                    },
+                    "older_than": {
+                        "type": "string",
+                        "description": "Only delete snapshots older than this age (e.g. 12h, 30d, 8w)"
+                    },
+                    "schedule": {
+                        "type": "string",
+                        "description": "Retention schedule to apply instead of the configured ones, e.g. 'daily:30d, weekly:1y'"
+                    },
+                    "dry_run": {
+                        "type": "boolean",
+                        "description": "Only report the snapshots that would be deleted",
+                        "default": true
+                    }
+                }
+            }
+        }),
+        json!({
+            "name": "freeze_delete_snapshot",
+            "description": "Delete one snapshot by ID, or by checksum when that content was saved only once. Without confirm, only reports what would be deleted. Pinned snapshots cannot be deleted",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "checksum": {
+                        "type": "string",
+                        "description": "Checksum (or prefix) matching a single snapshot to delete; use id when the content was saved several times"
+                    },
+                    "id": {
+                        "type": "integer",
+                        "description": "ID of the snapshot to delete, as shown by freeze_list"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Only delete snapshots of this file or below this directory"
+                    },
+                    "confirm": {
+                        "type": "boolean",
+                        "description": "Must be true to actually delete",
+                        "default": false
+                    }
+                }
+            }
+        }),
         json!({
             "name": "freeze_snapshot_info",
             "description": "Get detailed information about a specific snapshot by checksum",
@@ -343,24 +1063,56 @@ fn get_tools() -> Vec<serde_json::Value> {
             }
         }),
         json!({
-            "name": "freeze_exclusion_add",
-            "description": "Add an exclusion pattern",
+            "name": "freeze_diff",
+            "description": "Show a unified diff between two snapshots of a file, or between a snapshot and the current file",
             "inputSchema": {
                 "type": "object",
                 "properties": {
-                    "pattern": {
+                    "path": {
                         "type": "string",
-                        "description": "Pattern to exclude"
+                        "description": "Path of the file to diff"
                     },
-                    "exclusion_type": {
+                    "source": {
                         "type": "string",
-                        "description": "Type of exclusion (directory, extension, file)",
-                        "enum": ["directory", "extension", "file"]
-                    }
-                },
-                "required": ["pattern", "exclusion_type"]
-            }
-        }),
+                        "description": "Old side: checksum (or prefix), 'current', or leave empty for the latest snapshot"
+                    },
+                    "target": {
+                        "type": "string",
+                        "description": "New side: checksum (or prefix), 'current', or leave empty for the current file"
+                    },
+                    "context_lines": {
+                        "type": "integer",
+                        "description": "Unchanged lines shown around each change",
+                        "default": 3
+                    },
+                    "max_size": {
+                        "type": "integer",
+                        "description": "Most bytes of diff returned; longer diffs are cut at a line boundary",
+                        "default": 65536
+                    }
+                },
+                "required": ["path"]
+            }
+        }),
+        json!({
+            "name": "freeze_exclusion_add",
+            "description": "Add an exclusion pattern",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Pattern to exclude"
+                    },
+                    "exclusion_type": {
+                        "type": "string",
+                        "description": "Type of exclusion (directory, extension, file), or include to only keep files whose name matches the glob pattern",
+                        "enum": ["directory", "extension", "file", "include"]
+                    }
+                },
+                "required": ["pattern", "exclusion_type"]
+            }
+        }),
         json!({
             "name": "freeze_exclusion_list",
             "description": "List all exclusion patterns",
@@ -386,22 +1138,42 @@ fn get_tools() -> Vec<serde_json::Value> {
     ]
 }
 
-async fn call_tool(params: &serde_json::Value) -> ToolResult {
+async fn call_tool(params: &serde_json::Value, context: &RequestContext) -> ToolResult {
     let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
     let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
 
+    if context.read_only && MUTATING_TOOLS.contains(&name) {
+        return ToolResult {
+            content: vec![ToolContent {
+                r#type: "text".to_string(),
+                text: format!("The repository is read-only; {} is not available", name),
+            }],
+            is_error: Some(true),
+        };
+    }
+
     match name {
-        "freeze_save" => freeze_save(&arguments).await,
+        "freeze_save" => {
+            let progress_token = params.pointer("/_meta/progressToken").cloned();
+            freeze_save(&arguments, progress_token, context.notifier.clone()).await
+        }
         "freeze_restore" => freeze_restore(&arguments).await,
         "freeze_list" => freeze_list(&arguments).await,
         "freeze_list_directory" => freeze_list_directory(&arguments).await,
         "freeze_search" => freeze_search(&arguments).await,
+        "freeze_directory_stats" => freeze_directory_stats(&arguments).await,
+        "freeze_stats" => freeze_stats(&arguments).await,
+        "freeze_search_content" => freeze_search_content(&arguments).await,
         "freeze_check" => freeze_check(&arguments).await,
         "freeze_view" => freeze_view(&arguments).await,
         "freeze_export" => freeze_export(&arguments).await,
+        "freeze_export_directory" => freeze_export_directory(&arguments).await,
         "freeze_clear" => freeze_clear(&arguments).await,
+        "freeze_prune" => freeze_prune(&arguments).await,
+        "freeze_delete_snapshot" => freeze_delete_snapshot(&arguments).await,
         "freeze_snapshot_info" => freeze_snapshot_info(&arguments).await,
         "freeze_compare" => freeze_compare(&arguments).await,
+        "freeze_diff" => freeze_diff(&arguments).await,
         "freeze_exclusion_add" => freeze_exclusion_add(&arguments).await,
         "freeze_exclusion_list" => freeze_exclusion_list().await,
         "freeze_exclusion_remove" => freeze_exclusion_remove(&arguments).await,
@@ -415,7 +1187,26 @@ async fn call_tool(params: &serde_json::Value) -> ToolResult {
     }
 }
 
-async fn freeze_save(args: &serde_json::Value) -> ToolResult {
+/// Sends an MCP progress notification for a save.
+fn notify_progress(notifier: &Notifier, token: &serde_json::Value, progress: &SaveProgress) {
+    notifier.send(json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/progress",
+        "params": {
+            "progressToken": token,
+            "progress": progress.bytes_done,
+            "total": progress.bytes_total,
+            "message": format!("{}/{} files", progress.files_done, progress.files_total),
+        }
+    }));
+}
+
+/// Saves a path, reporting progress when the client sent a progress token.
+async fn freeze_save(
+    args: &serde_json::Value,
+    progress_token: Option<serde_json::Value>,
+    notifier: Notifier,
+) -> ToolResult {
     let path_str = args.get("path").and_then(|v| v.as_str()).map(|s| s.to_string());
     if path_str.is_none() || path_str.as_ref().unwrap().is_empty() {
         return ToolResult {
@@ -428,6 +1219,8 @@ async fn freeze_save(args: &serde_json::Value) -> ToolResult {
     }
 
     let path_str = path_str.unwrap();
+    let message = args.get("message").and_then(|v| v.as_str()).map(|s| s.to_string());
+    let keep_going = args.get("keep_going").and_then(|v| v.as_bool()).unwrap_or(false);
     let result = tokio::task::spawn_blocking(move || {
         let path = PathBuf::from(&path_str).canonicalize();
         match path {
@@ -435,9 +1228,43 @@ async fn freeze_save(args: &serde_json::Value) -> ToolResult {
                 let db = Database::new();
                 match db {
                     Ok(db) => {
-                        let save_result = Snapshot::save_recursive(&path, &db);
+                        let mut last_report: Option<Instant> = None;
+                        let save_result = Snapshot::save_recursive_with_progress(&path, message.as_deref(), keep_going, false, &db, |progress| {
+                            let Some(token) = &progress_token else {
+                                return;
+                            };
+                            // At most ten notifications a second, plus the final one
+                            let finished = progress.files_done + progress.files_skipped == progress.files_total;
+                            if finished || last_report.is_none_or(|at| at.elapsed() >= Duration::from_millis(100)) {
+                                notify_progress(&notifier, token, progress);
+                                last_report = Some(Instant::now());
+                            }
+                        });
                         match save_result {
-                            Ok(_) => format!("Successfully saved snapshot for: {}", path.display()),
+                            Ok(summary) => {
+                                let mut text = format!(
+                                    "Successfully saved snapshot for: {} ({} files, {})",
+                                    path.display(),
+                                    summary.progress.files_done,
+                                    format_size(summary.progress.bytes_done as i64)
+                                );
+                                if !summary.skipped.is_empty() {
+                                    text.push_str(&format!("\nSkipped {} unreadable files:", summary.skipped.len()));
+                                    for skipped in &summary.skipped {
+                                        text.push_str(&format!("\n- {}: {}", skipped.path.display(), skipped.error));
+                                    }
+                                }
+                                if !summary.too_large.is_empty() {
+                                    text.push_str(&format!(
+                                        "\nSkipped {} files above the maximum size:",
+                                        summary.too_large.len()
+                                    ));
+                                    for (path, size) in &summary.too_large {
+                                        text.push_str(&format!("\n- {} ({})", path.display(), format_size(*size as i64)));
+                                    }
+                                }
+                                text
+                            }
                             Err(e) => format!("Error saving snapshot: {}", e),
                         }
                     }
@@ -503,17 +1330,7 @@ async fn freeze_restore(args: &serde_json::Value) -> ToolResult {
         let target_snapshot = db.get_snapshot_by_checksum(&target_checksum)?
             .ok_or_else(|| anyhow::anyhow!("Snapshot not found"))?;
 
-        let temp_path = target_snapshot.content_path.clone();
-        let content = fs::read(&temp_path).context("Failed to read snapshot content")?;
-        
-        if target_snapshot.content_path.extension().and_then(|s| s.to_str()) == Some("zstd") {
-            let decompressed = zstd::stream::decode_all(&content[..]).context("Failed to decompress")?;
-            let final_path = path.with_extension("tmp");
-            fs::write(&final_path, &decompressed).context("Failed to write restored file")?;
-            fs::rename(&final_path, &path).context("Failed to rename restored file")?;
-        } else {
-            fs::copy(&temp_path, &path).context("Failed to copy restored file")?;
-        }
+        Snapshot::restore_snapshot(&target_snapshot, &path).context("Failed to write restored file")?;
         Ok(format!("Successfully restored: {} from snapshot {}",
             path.display(),
             &target_checksum[..16]))
@@ -549,7 +1366,12 @@ async fn freeze_list(args: &serde_json::Value) -> ToolResult {
                         if snapshots.is_empty() {
                             "No snapshots found.".to_string()
                         } else {
-                            format_snapshots_list_with_id(&snapshots, Some(page as u32))
+                            format_snapshots_list_with_id(
+                                &snapshots,
+                                &db.list_messages().unwrap_or_default(),
+                                &db.list_pinned().unwrap_or_default(),
+                                Some(page as u32),
+                            )
                         }
                     }
                     Err(e) => format!("Error listing snapshots: {}", e),
@@ -585,7 +1407,12 @@ async fn freeze_list_directory(args: &serde_json::Value) -> ToolResult {
                                 if snapshots.is_empty() {
                                     format!("No snapshots found in current directory: {}", dir.display())
                                 } else {
-                                    format_snapshots_list_with_id(&snapshots, Some(page as u32))
+                                    format_snapshots_list_with_id(
+                                        &snapshots,
+                                        &db.list_messages().unwrap_or_default(),
+                                        &db.list_pinned().unwrap_or_default(),
+                                        Some(page as u32),
+                                    )
                                 }
                             }
                             Err(e) => format!("Error listing snapshots: {}", e),
@@ -621,17 +1448,39 @@ async fn freeze_search(args: &serde_json::Value) -> ToolResult {
     }
 
     let pattern = pattern.unwrap();
+    let mut filter = SearchFilter::default();
+    for name in SEARCH_FILTERS {
+        let value = match args.get(name) {
+            Some(serde_json::Value::String(value)) => value.clone(),
+            Some(value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => value.to_string(),
+            _ => continue,
+        };
+        if let Err(e) = filter.set(name, &value) {
+            return ToolResult {
+                content: vec![ToolContent {
+                    r#type: "text".to_string(),
+                    text: format!("Error: {}", e),
+                }],
+                is_error: Some(true),
+            };
+        }
+    }
     let result = tokio::task::spawn_blocking(move || {
         let db = Database::new();
         match db {
             Ok(db) => {
-                let snapshots = db.search_snapshots(&pattern);
+                let snapshots = db.search_snapshots(&pattern, &filter);
                 match snapshots {
                     Ok(snapshots) => {
                         if snapshots.is_empty() {
                             format!("No snapshots found matching: {}", pattern)
                         } else {
-                            format_snapshots_list(&snapshots, None)
+                            format_snapshots_list(
+                                &snapshots,
+                                &db.list_messages().unwrap_or_default(),
+                                &db.list_pinned().unwrap_or_default(),
+                                None,
+                            )
                         }
                     }
                     Err(e) => format!("Error searching snapshots: {}", e),
@@ -651,6 +1500,185 @@ async fn freeze_search(args: &serde_json::Value) -> ToolResult {
     }
 }
 
+async fn freeze_directory_stats(args: &serde_json::Value) -> ToolResult {
+    let root = args.get("root").and_then(|v| v.as_str()).unwrap_or(".").to_string();
+    let top = args.get("top").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let root = crate::utils::resolve_path(&root)?;
+        let db = Database::new().context("Failed to open database")?;
+        let directories = db.directory_totals(&root, top)?;
+        if directories.is_empty() {
+            return Ok(format!("No snapshots below {}", root.display()));
+        }
+        let mut text = format!("Snapshots below {}, largest first:\n", root.display());
+        for entry in directories {
+            text.push_str(&format!(
+                "\n{}: {} snapshot(s) of {} file(s), {}, newest {}",
+                entry.path.display(),
+                entry.snapshots,
+                entry.files,
+                format_size(entry.size),
+                crate::utils::format_date(&entry.newest)
+            ));
+        }
+        Ok::<String, anyhow::Error>(text)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(text)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text }],
+            is_error: None,
+        },
+        Ok(Err(e)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+        Err(e) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+    }
+}
+
+/// Formats the repository statistics and its largest snapshots, given as
+/// (id, path, date, size, checksum).
+fn format_stats(stats: &crate::stats::Stats, largest: &[crate::db::SnapshotWithId]) -> String {
+    let mut text = format!(
+        "Repository statistics:\n\n\
+         Snapshots: {} of {} path(s)\n\
+         Size of all snapshots: {}\n\
+         Distinct contents: {} ({})\n\
+         Storage used: {} (compression ratio {})\n\
+         Saved by deduplication: {}\n",
+        stats.snapshots,
+        stats.paths,
+        format_size(stats.logical_size),
+        stats.blobs,
+        format_size(stats.unique_size),
+        format_size(stats.stored_size),
+        format_ratio(stats.unique_size, stats.stored_size),
+        format_size(stats.dedup_savings)
+    );
+    let sections = [
+        ("Directories using the most space", &stats.directories),
+        ("Paths using the most space", &stats.top_paths),
+    ];
+    for (title, usage) in sections {
+        if usage.is_empty() {
+            continue;
+        }
+        text.push_str(&format!("\n{}:\n", title));
+        for entry in usage {
+            text.push_str(&format!(
+                "  {}: {} in {} snapshot(s)\n",
+                entry.path,
+                format_size(entry.size),
+                entry.snapshots
+            ));
+        }
+    }
+    if !largest.is_empty() {
+        text.push_str("\nLargest snapshots:\n");
+        for (_, path, date, size, checksum) in largest {
+            text.push_str(&format!(
+                "  {} ({}, {}, {})\n",
+                path.display(),
+                format_size(*size),
+                format_date(date),
+                &checksum[..checksum.len().min(16)]
+            ));
+        }
+    }
+    text
+}
+
+async fn freeze_stats(args: &serde_json::Value) -> ToolResult {
+    let top = args.get("top").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let db = Database::new().context("Failed to open database")?;
+        let stats = crate::stats::build(&db, top)?;
+        let mut largest = db.list_snapshots(&crate::db::SnapshotFilter {
+            sort: crate::db::SnapshotSort::Size,
+            ..Default::default()
+        })?;
+        largest.truncate(top);
+        Ok::<String, anyhow::Error>(format_stats(&stats, &largest))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(text)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text }],
+            is_error: None,
+        },
+        Ok(Err(e)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+        Err(e) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+    }
+}
+
+async fn freeze_search_content(args: &serde_json::Value) -> ToolResult {
+    let query = args.get("query").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    if query.trim().is_empty() {
+        return ToolResult {
+            content: vec![ToolContent {
+                r#type: "text".to_string(),
+                text: "Error: query is required".to_string(),
+            }],
+            is_error: Some(true),
+        };
+    }
+
+    let file_type = args.get("type").and_then(|v| v.as_str()).map(|s| s.to_string());
+    let limit = args
+        .get("limit")
+        .and_then(|v| v.as_u64())
+        .map(|limit| limit as usize)
+        .unwrap_or(crate::fulltext::DEFAULT_LIMIT);
+    let result = tokio::task::spawn_blocking(move || {
+        let db = Database::new().context("Failed to open database")?;
+        let matches = crate::fulltext::search(&query, file_type.as_deref(), limit, &db)?;
+        if matches.is_empty() {
+            return Ok(format!("No snapshot content matches: {}", query));
+        }
+        let mut text = format!("Found {} snapshot(s):\n", matches.len());
+        for m in matches {
+            text.push_str(&format!(
+                "\n{} [{}] {}\n  {}\n",
+                m.path.display(),
+                &m.checksum[..8.min(m.checksum.len())],
+                crate::utils::format_date(&m.date),
+                m.snippet
+            ));
+        }
+        Ok::<String, anyhow::Error>(text)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(text)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text }],
+            is_error: None,
+        },
+        Ok(Err(e)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+        Err(e) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+    }
+}
+
 async fn freeze_check(args: &serde_json::Value) -> ToolResult {
     let path_str = args.get("path").and_then(|v| v.as_str()).map(|s| s.to_string());
     if path_str.is_none() || path_str.as_ref().unwrap().is_empty() {
@@ -710,11 +1738,7 @@ fn check_single_file(path: &PathBuf, db: &Database) -> String {
 }
 
 fn check_directory(path: &PathBuf, db: &Database) -> String {
-    let all_snapshots = db.list_directory_snapshots(path).ok().unwrap_or_default();
-    let snapshot_map: std::collections::HashMap<String, String> = all_snapshots
-        .into_iter()
-        .map(|(p, _, _, c)| (p.display().to_string(), c))
-        .collect();
+    let snapshot_map = db.latest_snapshots_in_dir(path).ok().unwrap_or_default();
 
     let mut result = format!("Checking: {}\n", path.display());
     let walker = walkdir::WalkDir::new(path).into_iter();
@@ -726,19 +1750,12 @@ fn check_directory(path: &PathBuf, db: &Database) -> String {
         if let Ok(entry) = entry
             && entry.file_type().is_file() {
                 let entry_path = entry.path();
-                let path_str = entry_path.display().to_string();
 
                 files_checked += 1;
-                if let Some(saved_checksum) = snapshot_map.get(&path_str) {
-                    if let Ok(content) = fs::read(entry_path) {
-                        let mut hasher = Sha256::new();
-                        hasher.update(&content);
-                        let current_checksum = format!("{:x}", hasher.finalize());
-
-                        if &current_checksum != saved_checksum {
-                            files_modified += 1;
-                            result.push_str(&format!("M - {}\n", entry_path.display()));
-                        }
+                if let Some(snapshot) = snapshot_map.get(entry_path) {
+                    if snapshot.hash_file(entry_path).is_ok_and(|current| current != snapshot.checksum) {
+                        files_modified += 1;
+                        result.push_str(&format!("M - {}\n", entry_path.display()));
                     }
                 } else {
                     files_new += 1;
@@ -758,6 +1775,27 @@ async fn freeze_view(args: &serde_json::Value) -> ToolResult {
     let path_str = args.get("path").and_then(|v| v.as_str()).map(|s| s.to_string());
     let max_size = args.get("max_size").and_then(|v| v.as_u64()).unwrap_or(5);
     let checksum = args.get("checksum").and_then(|v| v.as_str()).map(|s| s.to_string());
+    let range = match (
+        args.get("lines").and_then(|v| v.as_str()),
+        args.get("head").and_then(|v| v.as_u64()),
+        args.get("tail").and_then(|v| v.as_u64()),
+    ) {
+        (Some(lines), _, _) => match LineRange::parse(lines) {
+            Ok(range) => Some(range),
+            Err(e) => {
+                return ToolResult {
+                    content: vec![ToolContent {
+                        r#type: "text".to_string(),
+                        text: format!("Error: {}", e),
+                    }],
+                    is_error: Some(true),
+                };
+            }
+        },
+        (_, Some(head), _) => Some(LineRange::Head(head as usize)),
+        (_, _, Some(tail)) => Some(LineRange::Tail(tail as usize)),
+        _ => None,
+    };
 
     if path_str.is_none() || path_str.as_ref().unwrap().is_empty() {
         return ToolResult {
@@ -794,14 +1832,32 @@ async fn freeze_view(args: &serde_json::Value) -> ToolResult {
         let target_snapshot = db.get_snapshot_by_checksum(&target_checksum)?
             .ok_or_else(|| anyhow::anyhow!("Snapshot not found"))?;
 
-        let metadata = fs::metadata(&target_snapshot.content_path).ok();
+        // A slice is streamed, so the size limit does not apply
+        if let Some(range) = range {
+            let (first, lines) = target_snapshot.read_lines(range)?;
+            if lines.is_empty() {
+                return Ok("No lines in this range".to_string());
+            }
+            return Ok(format!(
+                "Lines {}-{} of {}:\n{}",
+                first,
+                first + lines.len() - 1,
+                target_snapshot.path.display(),
+                lines.join("\n")
+            ));
+        }
+
+        let stored_size: u64 = crate::compression::stored_files(&target_snapshot.content_path)
+            .iter()
+            .filter_map(|file| fs::metadata(file).ok())
+            .map(|md| md.len())
+            .sum();
         let max_bytes = max_size * 1024 * 1024;
 
-        if let Some(md) = metadata
-            && md.len() > max_bytes {
+        if stored_size > max_bytes {
                 return Ok(format!(
                     "File too large ({} > {} MB limit)\nPath: {}\nDate: {}\nSize: {}\nChecksum: {}",
-                    format_size(md.len() as i64),
+                    format_size(stored_size as i64),
                     max_size,
                     target_snapshot.path.display(),
                     target_snapshot.date,
@@ -810,9 +1866,9 @@ async fn freeze_view(args: &serde_json::Value) -> ToolResult {
                 ));
             }
 
-        let content = fs::read(&target_snapshot.content_path).map_err(|e| anyhow::anyhow!("{}", e))?;
+        let content = target_snapshot.get_decompressed_content()?;
         
-        if content.iter().take(512).any(|&b| b == 0) {
+        if target_snapshot.is_binary(&content) {
             return Ok(format!(
                 "Binary content detected\nPath: {}\nDate: {}\nSize: {}\nChecksum: {}",
                 target_snapshot.path.display(),
@@ -918,7 +1974,7 @@ async fn freeze_export(args: &serde_json::Value) -> ToolResult {
             fs::create_dir_all(parent).ok();
         }
 
-        fs::copy(&target_snapshot.content_path, &export_path)
+        target_snapshot.export(&export_path)
             .map_err(|e| anyhow::anyhow!("{}", e))?;
         
         Ok(format!("Exported to: {}", export_path.display()))
@@ -941,6 +1997,57 @@ async fn freeze_export(args: &serde_json::Value) -> ToolResult {
     }
 }
 
+async fn freeze_export_directory(args: &serde_json::Value) -> ToolResult {
+    let path_str = args.get("path").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    let destination = args.get("destination").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    let date = args.get("date").and_then(|v| v.as_str()).map(|s| s.to_string());
+
+    if path_str.is_empty() || destination.is_empty() {
+        return ToolResult {
+            content: vec![ToolContent {
+                r#type: "text".to_string(),
+                text: "Error: path and destination are required".to_string(),
+            }],
+            is_error: Some(true),
+        };
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        let path = crate::utils::resolve_path(&path_str)?;
+        let destination = crate::utils::resolve_path(&destination)?;
+        if destination.starts_with(&path) {
+            anyhow::bail!("Destination must be outside of {}", path.display());
+        }
+        let as_of = date.as_deref().map(crate::utils::parse_timestamp).transpose()?;
+
+        let db = Database::new().context("Failed to open database")?;
+        let count = Snapshot::export_directory(&path, &destination, as_of, &db)?;
+        Ok::<String, anyhow::Error>(format!(
+            "Exported {} file(s) from {}{} to: {}",
+            count,
+            path.display(),
+            as_of.map(|d| format!(" as of {}", d.to_rfc3339())).unwrap_or_default(),
+            destination.display()
+        ))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(text)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text }],
+            is_error: None,
+        },
+        Ok(Err(e)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+        Err(e) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+    }
+}
+
 async fn freeze_clear(args: &serde_json::Value) -> ToolResult {
     let clear_all = args.get("all").and_then(|v| v.as_bool()).unwrap_or(false);
     let path_str = args.get("path").and_then(|v| v.as_str()).map(|s| s.to_string());
@@ -983,6 +2090,166 @@ async fn freeze_clear(args: &serde_json::Value) -> ToolResult {
     }
 }
 
+/// Builds the report of a prune: the snapshots deleted, or that would be
+/// with `dry_run`, and the storage freed.
+fn prune_report(expired: &[crate::db::SnapshotWithId], dry_run: bool, deleted: usize, freed: u64) -> serde_json::Value {
+    let snapshots: Vec<serde_json::Value> = expired
+        .iter()
+        .map(|(id, path, date, size, checksum)| {
+            json!({
+                "id": id,
+                "path": path.display().to_string(),
+                "date": date,
+                "size": size,
+                "checksum": checksum,
+            })
+        })
+        .collect();
+    let size: i64 = expired.iter().map(|(_, _, _, size, _)| size).sum();
+    let mut report = json!({
+        "dry_run": dry_run,
+        "count": expired.len(),
+        "size": size,
+        "size_human": format_size(size),
+        "snapshots": snapshots,
+    });
+    if !dry_run {
+        report["deleted"] = json!(deleted);
+        report["freed"] = json!(freed);
+        report["freed_human"] = json!(format_size(freed as i64));
+    }
+    report
+}
+
+async fn freeze_prune(args: &serde_json::Value) -> ToolResult {
+    let path_str = args.get("path").and_then(|v| v.as_str()).filter(|s| !s.is_empty()).map(|s| s.to_string());
+    let keep_last = args.get("keep_last").and_then(|v| v.as_u64());
+    let older_than = args.get("older_than").and_then(|v| v.as_str()).map(|s| s.to_string());
+    let schedule = args.get("schedule").and_then(|v| v.as_str()).map(|s| s.to_string());
+    let dry_run = args.get("dry_run").and_then(|v| v.as_bool()).unwrap_or(true);
+
+    let result = tokio::task::spawn_blocking(move || {
+        let root = path_str.as_deref().map(crate::utils::resolve_path).transpose()?;
+        let db = Database::new().context("Failed to open database")?;
+
+        let mut rules = Vec::new();
+        if let Some(age) = &older_than {
+            rules.push(crate::db::Retention::OlderThan(crate::utils::parse_age(age)?));
+        }
+        match keep_last {
+            Some(0) => anyhow::bail!("keep_last must be at least 1"),
+            Some(count) => rules.push(crate::db::Retention::KeepLast(count as usize)),
+            None => {}
+        }
+
+        let expired = if rules.is_empty() {
+            let policies = match &schedule {
+                Some(spec) => crate::retention::Policies::single(crate::retention::Schedule::parse(spec)?),
+                None => crate::retention::Policies::from_config(crate::config::get())?,
+            };
+            if policies.is_empty() {
+                anyhow::bail!("No retention schedule configured: pass keep_last, older_than or schedule");
+            }
+            crate::retention::expired_snapshots(root.as_deref(), &policies, chrono::Local::now(), &db)?
+        } else if schedule.is_some() {
+            anyhow::bail!("schedule cannot be combined with keep_last or older_than");
+        } else {
+            // Only the snapshots every rule lets go
+            let mut expired: Option<Vec<crate::db::SnapshotWithId>> = None;
+            for rule in rules {
+                let matching = db.expired_snapshots(root.as_deref(), rule)?;
+                expired = Some(match expired {
+                    None => matching,
+                    Some(previous) => {
+                        let ids: HashSet<i64> = matching.iter().map(|(id, ..)| *id).collect();
+                        previous.into_iter().filter(|(id, ..)| ids.contains(id)).collect()
+                    }
+                });
+            }
+            expired.unwrap_or_default()
+        };
+
+        let (deleted, freed) = if dry_run {
+            (0, 0)
+        } else {
+            let ids: Vec<i64> = expired.iter().map(|(id, ..)| *id).collect();
+            db.delete_snapshots(&ids)?
+        };
+        Ok::<String, anyhow::Error>(serde_json::to_string_pretty(&prune_report(&expired, dry_run, deleted, freed))?)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(text)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text }],
+            is_error: None,
+        },
+        Ok(Err(e)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+        Err(e) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+    }
+}
+
+async fn freeze_delete_snapshot(args: &serde_json::Value) -> ToolResult {
+    let checksum = args.get("checksum").and_then(|v| v.as_str()).filter(|s| !s.is_empty()).map(|s| s.to_string());
+    let id = args.get("id").and_then(|v| v.as_i64());
+    let path_str = args.get("path").and_then(|v| v.as_str()).filter(|s| !s.is_empty()).map(|s| s.to_string());
+    let confirm = args.get("confirm").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let result = tokio::task::spawn_blocking(move || {
+        let root = path_str.as_deref().map(crate::utils::resolve_path).transpose()?;
+        let db = Database::new().context("Failed to open database")?;
+        let doomed = match (checksum, id) {
+            (Some(_), Some(_)) => anyhow::bail!("Pass either checksum or id, not both"),
+            (None, None) => anyhow::bail!("checksum or id is required"),
+            (checksum, id) => db.removed_snapshots(root.as_deref(), &Vec::from_iter(checksum), &Vec::from_iter(id))?,
+        };
+
+        let mut text = if confirm {
+            let ids: Vec<i64> = doomed.iter().map(|(id, ..)| *id).collect();
+            let (deleted, freed) = db.delete_snapshots(&ids)?;
+            format!("Deleted {} snapshot(s), freeing {}:\n", deleted, format_size(freed as i64))
+        } else {
+            format!(
+                "Would delete {} snapshot(s), call again with confirm: true to delete:\n",
+                doomed.len()
+            )
+        };
+        for (id, path, date, size, checksum) in &doomed {
+            text.push_str(&format!(
+                "\n[{}] {} ({}, {}, {})",
+                id,
+                path.display(),
+                format_date(date),
+                format_size(*size),
+                &checksum[..16]
+            ));
+        }
+        Ok::<String, anyhow::Error>(text)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(text)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text }],
+            is_error: None,
+        },
+        Ok(Err(e)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+        Err(e) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+    }
+}
+
 async fn freeze_snapshot_info(args: &serde_json::Value) -> ToolResult {
     let checksum = args.get("checksum").and_then(|v| v.as_str()).map(|s| s.to_string());
 
@@ -1004,7 +2271,7 @@ async fn freeze_snapshot_info(args: &serde_json::Value) -> ToolResult {
                 let snapshot = db.get_snapshot_by_checksum(&checksum);
                 match snapshot {
                     Ok(Some(snapshot)) => {
-                        format!(
+                        let mut info = format!(
                             "Snapshot Information:\n\
                              Path: {}\n\
                              Date: {}\n\
@@ -1014,10 +2281,33 @@ async fn freeze_snapshot_info(args: &serde_json::Value) -> ToolResult {
                             snapshot.date,
                             format_size(snapshot.size),
                             snapshot.checksum
-                        )
-                    }
-                    Ok(None) => format!("No snapshot found with checksum: {}", checksum),
-                    Err(e) => format!("Error getting snapshot: {}", e),
+                        );
+                        if let Some(stored_size) = snapshot.stored_size {
+                            info.push_str(&format!(
+                                "\nStored: {} ({} compression ratio)",
+                                format_size(stored_size),
+                                format_ratio(snapshot.size, stored_size)
+                            ));
+                        }
+                        if let Some(message) = &snapshot.message {
+                            info.push_str(&format!("\nMessage: {}", message));
+                        }
+                        let metadata = db
+                            .get_snapshot_id_by_checksum(&checksum)
+                            .ok()
+                            .flatten()
+                            .and_then(|id| db.list_metadata(id).ok())
+                            .unwrap_or_default();
+                        if !metadata.is_empty() {
+                            info.push_str("\nMetadata:");
+                            for (key, value) in metadata {
+                                info.push_str(&format!("\n  {} = {}", key, value));
+                            }
+                        }
+                        info
+                    }
+                    Ok(None) => format!("No snapshot found with checksum: {}", checksum),
+                    Err(e) => format!("Error getting snapshot: {}", e),
                 }
             }
             Err(e) => format!("Error opening database: {}", e),
@@ -1067,7 +2357,7 @@ async fn freeze_compare(args: &serde_json::Value) -> ToolResult {
                     None
                 }
             } else if let Some(snap) = snapshot {
-                fs::read(&snap.content_path).ok().map(|c| (snap.checksum.clone(), c))
+                snap.get_decompressed_content().ok().map(|c| (snap.checksum.clone(), c))
             } else {
                 None
             }
@@ -1147,6 +2437,126 @@ async fn freeze_compare(args: &serde_json::Value) -> ToolResult {
     }
 }
 
+/// Largest content `freeze_diff` compares.
+const MAX_DIFF_INPUT_SIZE: usize = 5 * 1024 * 1024;
+
+/// Cuts a diff down to at most `max_size` bytes, at a line boundary, noting
+/// how much was left out.
+fn truncate_diff(diff: &str, max_size: usize) -> String {
+    if diff.len() <= max_size {
+        return diff.to_string();
+    }
+    let cut = diff.as_bytes()[..max_size]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    format!(
+        "{}... diff truncated, {} of {} shown (raise max_size to see more)\n",
+        &diff[..cut],
+        format_size(cut as i64),
+        format_size(diff.len() as i64)
+    )
+}
+
+async fn freeze_diff(args: &serde_json::Value) -> ToolResult {
+    let path_str = args.get("path").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    if path_str.is_empty() {
+        return ToolResult {
+            content: vec![ToolContent {
+                r#type: "text".to_string(),
+                text: "Error: path is required".to_string(),
+            }],
+            is_error: Some(true),
+        };
+    }
+    let source = args.get("source").and_then(|v| v.as_str()).map(|s| s.to_string());
+    let target = args.get("target").and_then(|v| v.as_str()).map(|s| s.to_string());
+    let context_lines = args.get("context_lines").and_then(|v| v.as_u64()).unwrap_or(3) as usize;
+    let max_size = args.get("max_size").and_then(|v| v.as_u64()).unwrap_or(64 * 1024) as usize;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let path = crate::utils::resolve_path(&path_str)?;
+        let db = Database::new().context("Failed to open database")?;
+        let snapshots = db.get_snapshots_for_path(&path)?;
+
+        // Each side is named after the path, with the checksum for a snapshot
+        let load = |spec: Option<&str>, current_by_default: bool| -> Result<(String, Vec<u8>)> {
+            let snapshot = match spec {
+                Some("current") => None,
+                None if current_by_default => None,
+                Some(prefix) => Some(
+                    snapshots
+                        .iter()
+                        .find(|s| s.checksum.starts_with(prefix))
+                        .with_context(|| format!("No snapshot of {} matches {}", path.display(), prefix))?,
+                ),
+                None => Some(
+                    snapshots
+                        .first()
+                        .with_context(|| format!("No snapshots found for: {}", path.display()))?,
+                ),
+            };
+            match snapshot {
+                Some(snapshot) => Ok((
+                    format!("{}@{}", path.display(), &snapshot.checksum[..16]),
+                    snapshot.get_decompressed_content()?,
+                )),
+                None => Ok((
+                    path.display().to_string(),
+                    fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?,
+                )),
+            }
+        };
+        let (source_name, source_bytes) = load(source.as_deref(), false)?;
+        let (target_name, target_bytes) = load(target.as_deref(), true)?;
+
+        if source_bytes.len().max(target_bytes.len()) > MAX_DIFF_INPUT_SIZE {
+            anyhow::bail!("Files too large for diff (limit {})", format_size(MAX_DIFF_INPUT_SIZE as i64));
+        }
+        if crate::utils::is_binary(&source_bytes) || crate::utils::is_binary(&target_bytes) {
+            return Ok(if source_bytes == target_bytes {
+                format!("Binary files {} and {} are identical", source_name, target_name)
+            } else {
+                format!(
+                    "Binary files {} ({}) and {} ({}) differ",
+                    source_name,
+                    format_size(source_bytes.len() as i64),
+                    target_name,
+                    format_size(target_bytes.len() as i64)
+                )
+            });
+        }
+
+        let diff = crate::utils::unified_diff(
+            &source_name,
+            &target_name,
+            &String::from_utf8_lossy(&source_bytes),
+            &String::from_utf8_lossy(&target_bytes),
+            context_lines,
+        );
+        if diff.is_empty() {
+            return Ok(format!("No differences between {} and {}", source_name, target_name));
+        }
+        Ok::<String, anyhow::Error>(truncate_diff(&diff, max_size))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(text)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text }],
+            is_error: None,
+        },
+        Ok(Err(e)) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+        Err(e) => ToolResult {
+            content: vec![ToolContent { r#type: "text".to_string(), text: format!("Error: {}", e) }],
+            is_error: Some(true),
+        },
+    }
+}
+
 async fn freeze_exclusion_add(args: &serde_json::Value) -> ToolResult {
     let pattern = args.get("pattern").and_then(|v| v.as_str()).map(|s| s.to_string());
     let exclusion_type = args.get("exclusion_type").and_then(|v| v.as_str()).map(|s| s.to_string());
@@ -1199,7 +2609,7 @@ async fn freeze_exclusion_list() -> ToolResult {
                             "No exclusions configured.".to_string()
                         } else {
                             let mut result = String::from("Exclusions:\n");
-                            result.push_str("─".repeat(50).as_str());
+                            result.push_str(&rule(false, 50));
                             result.push('\n');
                             for (pattern, exc_type) in exclusions {
                                 result.push_str(&format!("  - {} ({})\n", pattern, exc_type));
@@ -1224,10 +2634,195 @@ async fn freeze_exclusion_list() -> ToolResult {
     }
 }
 
+async fn freeze_exclusion_remove(args: &serde_json::Value) -> ToolResult {
+    let pattern = args.get("pattern").and_then(|v| v.as_str()).map(|s| s.to_string());
+    if pattern.is_none() || pattern.as_ref().unwrap().is_empty() {
+        return ToolResult {
+            content: vec![ToolContent {
+                r#type: "text".to_string(),
+                text: "Error: pattern is required".to_string(),
+            }],
+            is_error: Some(true),
+        };
+    }
+
+    let pattern = pattern.unwrap();
+    let result = tokio::task::spawn_blocking(move || {
+        let db = Database::new();
+        match db {
+            Ok(db) => {
+                match db.remove_exclusion(&pattern) {
+                    Ok(_) => format!("Removed exclusion: {}", pattern),
+                    Err(e) => format!("Error removing exclusion: {}", e),
+                }
+            }
+            Err(e) => format!("Error opening database: {}", e),
+        }
+    })
+    .await;
+
+    ToolResult {
+        content: vec![ToolContent {
+            r#type: "text".to_string(),
+            text: result.unwrap_or_else(|_| "Error removing exclusion".to_string()),
+        }],
+        is_error: None,
+    }
+}
+
+fn format_snapshots_list_with_id(
+    snapshots: &[(i64, PathBuf, String, i64, String)],
+    messages: &HashMap<i64, String>,
+    pinned: &HashSet<i64>,
+    page: Option<u32>,
+) -> String {
+    const ITEMS_PER_PAGE: usize = 10;
+
+    let mut result = String::from("Snapshots:\n");
+    result.push_str(&rule(false, 50));
+    result.push('\n');
+    result.push_str("ID      | Date/Time                      | Size      | Checksum            | Path\n");
+    result.push_str(&rule(false, 80));
+    result.push('\n');
+
+    let snapshots_iter: Vec<_> = snapshots.iter().collect();
+    let total = snapshots_iter.len();
+
+    let page_num = page.unwrap_or(1) as usize;
+    let total_pages = total.div_ceil(ITEMS_PER_PAGE);
+    let start = (page_num - 1) * ITEMS_PER_PAGE;
+    let end = std::cmp::min(start + ITEMS_PER_PAGE, total);
+
+    if page_num > total_pages && total > 0 {
+        return format!("Invalid page number. Total pages: {}", total_pages);
+    }
+
+    let page_snapshots: Vec<_> = if page.is_some() {
+        snapshots_iter[start..end].to_vec()
+    } else {
+        snapshots_iter
+    };
+
+    for (id, path, date, size, checksum) in page_snapshots {
+        let date_short = if date.len() > 22 { &date[..22] } else { date };
+        let file_name = path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        result.push_str(&format!(
+            "{:6}  | {:28} | {:>8}  | {:16} | {}",
+            id,
+            date_short,
+            format_size(*size),
+            &checksum[..16],
+            file_name
+        ));
+        if pinned.contains(id) {
+            result.push_str(" (pinned)");
+        }
+        if let Some(message) = messages.get(id) {
+            result.push_str(&format!(" - {}", message));
+        }
+        result.push('\n');
+    }
+
+    if page.is_some() {
+        result.push_str(&rule(false, 80));
+        result.push('\n');
+        result.push_str(&format!(
+            "Page {} of {} ({} items)\n",
+            page_num, total_pages, total
+        ));
+        result.push_str("Use checksum prefix with restore/view/export to select specific snapshot\n");
+    }
+
+    result
+}
+
+fn format_snapshots_list(
+    snapshots: &[crate::db::SnapshotWithId],
+    messages: &HashMap<i64, String>,
+    pinned: &HashSet<i64>,
+    page: Option<u32>,
+) -> String {
+    const ITEMS_PER_PAGE: usize = 10;
+
+    let mut result = String::from("Snapshots:\n");
+    result.push_str(&rule(false, 50));
+    result.push('\n');
+
+    let snapshots_iter: Vec<_> = snapshots.iter().collect();
+    let total = snapshots_iter.len();
+
+    let page_num = page.unwrap_or(1) as usize;
+    let total_pages = total.div_ceil(ITEMS_PER_PAGE);
+    let start = (page_num - 1) * ITEMS_PER_PAGE;
+    let end = std::cmp::min(start + ITEMS_PER_PAGE, total);
+
+    if page_num > total_pages && total > 0 {
+        return format!("Invalid page number. Total pages: {}", total_pages);
+    }
+
+    let page_snapshots: Vec<_> = if page.is_some() {
+        snapshots_iter[start..end].to_vec()
+    } else {
+        snapshots_iter
+    };
+
+    for (id, path, date, size, checksum) in page_snapshots {
+        let [folder, calendar, disk, lock] = if ascii_mode() {
+            ["", "Date: ", "Size: ", "Checksum: "]
+        } else {
+            ["📁 ", "📅 ", "💾 ", "🔐 "]
+        };
+        result.push_str(&format!(
+            "{}{}\n  {}{} | {}{} | {}{}\n",
+            folder,
+            path.display(),
+            calendar,
+            date,
+            disk,
+            format_size(*size),
+            lock,
+            &checksum[..16]
+        ));
+        if pinned.contains(id) {
+            result.push_str(&format!("  {}pinned\n", if ascii_mode() { "" } else { "📌 " }));
+        }
+        if let Some(message) = messages.get(id) {
+            result.push_str(&format!("  {}\n", message));
+        }
+    }
+
+    if page.is_some() {
+        result.push_str(&rule(false, 50));
+        result.push('\n');
+        result.push_str(&format!(
+            "Page {} of {} ({} items)\n",
+            page_num, total_pages, total
+        ));
+    }
+
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_available_tools_read_only() {
+        let names = |tools: Vec<serde_json::Value>| -> Vec<String> {
+            tools.iter().map(|tool| tool["name"].as_str().unwrap().to_string()).collect()
+        };
+        let all = names(available_tools(false));
+        let read_only = names(available_tools(true));
+        for tool in MUTATING_TOOLS {
+            assert!(all.iter().any(|name| name == tool));
+            assert!(!read_only.iter().any(|name| name == tool));
+        }
+        assert_eq!(read_only.len(), all.len() - MUTATING_TOOLS.len());
+    }
+
     #[test]
     fn test_format_snapshots_list_with_id() {
         let snapshots = vec![
@@ -1235,7 +2830,7 @@ mod tests {
             (2, PathBuf::from("/test/file2.txt"), "2024-01-15T11:30:00+00:00".to_string(), 2048, "def456ghi7890123".to_string()),
         ];
         
-        let result = format_snapshots_list_with_id(&snapshots, None);
+        let result = format_snapshots_list_with_id(&snapshots, &HashMap::new(), &HashSet::new(), None);
         assert!(result.contains("ID"));
         assert!(result.contains("abc123def4567890"));
         assert!(result.contains("file1.txt"));
@@ -1247,9 +2842,9 @@ mod tests {
             .map(|i| (i, PathBuf::from(format!("/test/file{}.txt", i)), "2024-01-15T10:00:00+00:00".to_string(), 1024, format!("checksum{:12}", i)))
             .collect();
         
-        let page1 = format_snapshots_list_with_id(&snapshots, Some(1));
-        let page2 = format_snapshots_list_with_id(&snapshots, Some(2));
-        let page3 = format_snapshots_list_with_id(&snapshots, Some(3));
+        let page1 = format_snapshots_list_with_id(&snapshots, &HashMap::new(), &HashSet::new(), Some(1));
+        let page2 = format_snapshots_list_with_id(&snapshots, &HashMap::new(), &HashSet::new(), Some(2));
+        let page3 = format_snapshots_list_with_id(&snapshots, &HashMap::new(), &HashSet::new(), Some(3));
         
         assert!(page1.contains("ID"));
         assert!(page2.contains("Page 2 of 3"));
@@ -1259,20 +2854,46 @@ mod tests {
     #[test]
     fn test_format_snapshots_list() {
         let snapshots = vec![
-            (PathBuf::from("/test/file1.txt"), "2024-01-15T10:30:00+00:00".to_string(), 1024, "abc123def4567890".to_string()),
-            (PathBuf::from("/test/file2.txt"), "2024-01-15T11:30:00+00:00".to_string(), 2048, "def456ghi7890123".to_string()),
+            (1, PathBuf::from("/test/file1.txt"), "2024-01-15T10:30:00+00:00".to_string(), 1024, "abc123def4567890".to_string()),
+            (2, PathBuf::from("/test/file2.txt"), "2024-01-15T11:30:00+00:00".to_string(), 2048, "def456ghi7890123".to_string()),
         ];
         
-        let result = format_snapshots_list(&snapshots, None);
+        let result = format_snapshots_list(&snapshots, &HashMap::new(), &HashSet::new(), None);
         assert!(result.contains("Snapshots:"));
         assert!(result.contains("file1.txt"));
         assert!(result.contains("abc123def4567890"));
     }
 
+    #[test]
+    fn test_format_snapshots_list_messages() {
+        let snapshots = vec![
+            (1, PathBuf::from("/test/file1.txt"), "2024-01-15T10:30:00+00:00".to_string(), 1024, "abc123def4567890".to_string()),
+            (2, PathBuf::from("/test/file2.txt"), "2024-01-15T11:30:00+00:00".to_string(), 2048, "def456ghi7890123".to_string()),
+        ];
+        let messages = HashMap::from([(1, "before upgrading deps".to_string())]);
+
+        let result = format_snapshots_list_with_id(&snapshots, &messages, &HashSet::new(), None);
+        assert!(result.contains("file1.txt - before upgrading deps"));
+        assert_eq!(result.matches("before upgrading deps").count(), 1);
+    }
+
+    #[test]
+    fn test_format_snapshots_list_pinned() {
+        let snapshots = vec![
+            (1, PathBuf::from("/test/file1.txt"), "2024-01-15T10:30:00+00:00".to_string(), 1024, "abc123def4567890".to_string()),
+            (2, PathBuf::from("/test/file2.txt"), "2024-01-15T11:30:00+00:00".to_string(), 2048, "def456ghi7890123".to_string()),
+        ];
+        let pinned = HashSet::from([2]);
+
+        let result = format_snapshots_list_with_id(&snapshots, &HashMap::new(), &pinned, None);
+        assert!(result.contains("file2.txt (pinned)"));
+        assert_eq!(result.matches("(pinned)").count(), 1);
+    }
+
     #[test]
     fn test_format_snapshots_list_empty() {
-        let snapshots: Vec<(PathBuf, String, i64, String)> = vec![];
-        let result = format_snapshots_list(&snapshots, Some(1));
+        let snapshots: Vec<crate::db::SnapshotWithId> = vec![];
+        let result = format_snapshots_list(&snapshots, &HashMap::new(), &HashSet::new(), Some(1));
         // When page is provided and list is empty, it should still show the header
         // but no items
         assert!(result.contains("Snapshots:"));
@@ -1281,7 +2902,7 @@ mod tests {
     #[test]
     fn test_format_snapshots_list_with_id_empty() {
         let snapshots: Vec<(i64, PathBuf, String, i64, String)> = vec![];
-        let result = format_snapshots_list_with_id(&snapshots, Some(1));
+        let result = format_snapshots_list_with_id(&snapshots, &HashMap::new(), &HashSet::new(), Some(1));
         // When page is provided and list is empty, it should still show the header
         assert!(result.contains("Snapshots:"));
     }
@@ -1391,6 +3012,24 @@ mod tests {
         assert!(required.iter().any(|r| r.as_str() == Some("path")));
     }
 
+    #[test]
+    fn test_freeze_export_directory_tool_schema() {
+        let tools = get_tools();
+        let export_tool = tools.iter()
+            .find(|t| t.get("name").and_then(|n| n.as_str()) == Some("freeze_export_directory"))
+            .unwrap();
+
+        let schema = export_tool.get("inputSchema").unwrap();
+        let props = schema.get("properties").unwrap();
+
+        assert!(props.get("path").is_some());
+        assert!(props.get("destination").is_some());
+        assert!(props.get("date").is_some());
+
+        let required = schema.get("required").unwrap().as_array().unwrap();
+        assert!(required.iter().any(|r| r.as_str() == Some("destination")));
+    }
+
     #[test]
     fn test_freeze_view_tool_schema() {
         let tools = get_tools();
@@ -1404,6 +3043,23 @@ mod tests {
         assert!(props.get("path").is_some());
         assert!(props.get("max_size").is_some());
         assert!(props.get("checksum").is_some());
+        assert!(props.get("lines").is_some());
+        assert!(props.get("head").is_some());
+        assert!(props.get("tail").is_some());
+    }
+
+    #[test]
+    fn test_freeze_search_tool_schema() {
+        let tools = get_tools();
+        let search_tool = tools.iter()
+            .find(|t| t.get("name").and_then(|n| n.as_str()) == Some("freeze_search"))
+            .unwrap();
+
+        let props = search_tool.get("inputSchema").and_then(|s| s.get("properties")).unwrap();
+        assert!(props.get("pattern").is_some());
+        for name in SEARCH_FILTERS {
+            assert!(props.get(name).is_some(), "{} is not documented", name);
+        }
     }
 
     #[test]
@@ -1454,150 +3110,116 @@ mod tests {
         assert!(enum_values.iter().any(|v| v.as_str() == Some("extension")));
         assert!(enum_values.iter().any(|v| v.as_str() == Some("file")));
     }
-}
 
-async fn freeze_exclusion_remove(args: &serde_json::Value) -> ToolResult {
-    let pattern = args.get("pattern").and_then(|v| v.as_str()).map(|s| s.to_string());
-    if pattern.is_none() || pattern.as_ref().unwrap().is_empty() {
-        return ToolResult {
-            content: vec![ToolContent {
-                r#type: "text".to_string(),
-                text: "Error: pattern is required".to_string(),
-            }],
-            is_error: Some(true),
-        };
+    #[test]
+    fn test_checksum_from_uri() {
+        assert_eq!(checksum_from_uri("freeze://snapshot/ab12cd"), Some("ab12cd"));
+        assert_eq!(checksum_from_uri("freeze://snapshot/"), None);
+        assert_eq!(checksum_from_uri("freeze://snapshot/../x"), None);
+        assert_eq!(checksum_from_uri("file:///etc/passwd"), None);
     }
 
-    let pattern = pattern.unwrap();
-    let result = tokio::task::spawn_blocking(move || {
-        let db = Database::new();
-        match db {
-            Ok(db) => {
-                match db.remove_exclusion(&pattern) {
-                    Ok(_) => format!("Removed exclusion: {}", pattern),
-                    Err(e) => format!("Error removing exclusion: {}", e),
-                }
-            }
-            Err(e) => format!("Error opening database: {}", e),
-        }
-    })
-    .await;
-
-    ToolResult {
-        content: vec![ToolContent {
-            r#type: "text".to_string(),
-            text: result.unwrap_or_else(|_| "Error removing exclusion".to_string()),
-        }],
-        is_error: None,
+    #[test]
+    fn test_snapshot_resource() {
+        let resource = snapshot_resource(
+            std::path::Path::new("/home/user/a.txt"),
+            "2024-01-01T10:00:00+00:00",
+            12,
+            "ab12cd",
+            Some("text/plain"),
+        );
+        assert_eq!(resource["uri"], "freeze://snapshot/ab12cd");
+        assert_eq!(resource["name"], "/home/user/a.txt");
+        assert_eq!(resource["description"], "Snapshot of /home/user/a.txt taken 2024-01-01 10:00");
+        assert_eq!(resource["mimeType"], "text/plain");
+        assert_eq!(resource["size"], 12);
+
+        let resource = snapshot_resource(std::path::Path::new("/a"), "2024-01-01T10:00:00+00:00", 0, "ab", None);
+        assert!(resource.get("mimeType").is_none());
     }
-}
 
-fn format_snapshots_list_with_id(
-    snapshots: &[(i64, PathBuf, String, i64, String)],
-    page: Option<u32>,
-) -> String {
-    const ITEMS_PER_PAGE: usize = 10;
-
-    let mut result = String::from("Snapshots:\n");
-    result.push_str("─".repeat(50).as_str());
-    result.push('\n');
-    result.push_str("ID      | Date/Time                      | Size      | Checksum            | Path\n");
-    result.push_str("─".repeat(80).as_str());
-    result.push('\n');
-
-    let snapshots_iter: Vec<_> = snapshots.iter().collect();
-    let total = snapshots_iter.len();
-
-    let page_num = page.unwrap_or(1) as usize;
-    let total_pages = total.div_ceil(ITEMS_PER_PAGE);
-    let start = (page_num - 1) * ITEMS_PER_PAGE;
-    let end = std::cmp::min(start + ITEMS_PER_PAGE, total);
-
-    if page_num > total_pages && total > 0 {
-        return format!("Invalid page number. Total pages: {}", total_pages);
+    #[test]
+    fn test_origin_allowed() {
+        let none: Vec<String> = Vec::new();
+        assert!(origin_allowed("http://localhost:5173", &none));
+        assert!(origin_allowed("http://127.0.0.1", &none));
+        assert!(origin_allowed("http://[::1]:8080", &none));
+        assert!(!origin_allowed("https://mcp.example.com", &none));
+        assert!(!origin_allowed("https://evil.example.com", &none));
+        assert!(!origin_allowed("http://localhost.evil.com", &none));
+        assert!(!origin_allowed("null", &none));
+
+        let allowed = vec!["https://app.example.com".to_string()];
+        assert!(origin_allowed("https://app.example.com", &allowed));
+        assert!(!origin_allowed("https://app.example.com.evil.com", &allowed));
     }
 
-    let page_snapshots: Vec<_> = if page.is_some() {
-        snapshots_iter[start..end].to_vec()
-    } else {
-        snapshots_iter
-    };
-
-    for (id, path, date, size, checksum) in page_snapshots {
-        let date_short = if date.len() > 22 { &date[..22] } else { date };
-        let file_name = path.file_name()
-            .map(|n| n.to_string_lossy().into_owned())
-            .unwrap_or_else(|| path.display().to_string());
-        result.push_str(&format!(
-            "{:6}  | {:28} | {:>8}  | {:16} | {}",
-            id,
-            date_short,
-            format_size(*size),
-            &checksum[..16],
-            file_name
-        ));
-        result.push('\n');
+    #[test]
+    fn test_is_loopback() {
+        assert!(is_loopback("127.0.0.1:7444"));
+        assert!(is_loopback("localhost"));
+        assert!(is_loopback("[::1]:7444"));
+        assert!(is_loopback("[::1]"));
+        assert!(!is_loopback("mcp.example.com"));
+        assert!(!is_loopback("192.168.1.10:7444"));
+        assert!(!is_loopback("localhost.evil.com:7444"));
     }
 
-    if page.is_some() {
-        result.push_str("─".repeat(80).as_str());
-        result.push('\n');
-        result.push_str(&format!(
-            "Page {} of {} ({} items)\n",
-            page_num, total_pages, total
-        ));
-        result.push_str("Use checksum prefix with restore/view/export to select specific snapshot\n");
+    #[test]
+    fn test_truncate_diff() {
+        let diff = "--- a\n+++ b\n-old\n+new\n";
+        assert_eq!(truncate_diff(diff, 1024), diff);
+        let truncated = truncate_diff(diff, 15);
+        assert!(truncated.starts_with("--- a\n+++ b\n... diff truncated"));
+        assert!(!truncated.contains("-old"));
     }
 
-    result
-}
-
-fn format_snapshots_list(
-    snapshots: &[(PathBuf, String, i64, String)],
-    page: Option<u32>,
-) -> String {
-    const ITEMS_PER_PAGE: usize = 10;
-
-    let mut result = String::from("Snapshots:\n");
-    result.push_str("─".repeat(50).as_str());
-    result.push('\n');
-
-    let snapshots_iter: Vec<_> = snapshots.iter().collect();
-    let total = snapshots_iter.len();
-
-    let page_num = page.unwrap_or(1) as usize;
-    let total_pages = total.div_ceil(ITEMS_PER_PAGE);
-    let start = (page_num - 1) * ITEMS_PER_PAGE;
-    let end = std::cmp::min(start + ITEMS_PER_PAGE, total);
-
-    if page_num > total_pages && total > 0 {
-        return format!("Invalid page number. Total pages: {}", total_pages);
+    #[test]
+    fn test_format_stats() {
+        let stats = crate::stats::Stats {
+            snapshots: 3,
+            paths: 2,
+            blobs: 2,
+            logical_size: 3072,
+            unique_size: 2048,
+            stored_size: 1024,
+            compression_ratio: Some(2.0),
+            dedup_savings: 1024,
+            top_paths: vec![crate::stats::Usage { path: "/a/big.txt".to_string(), snapshots: 2, size: 2048 }],
+            directories: vec![crate::stats::Usage { path: "/a".to_string(), snapshots: 3, size: 3072 }],
+        };
+        let largest = vec![(
+            1,
+            PathBuf::from("/a/big.txt"),
+            "2024-01-15T10:30:00+00:00".to_string(),
+            1024,
+            "abc123def4567890abc".to_string(),
+        )];
+
+        let text = format_stats(&stats, &largest);
+        assert!(text.contains("Snapshots: 3 of 2 path(s)"));
+        assert!(text.contains("compression ratio 2.0x"));
+        assert!(text.contains("Directories using the most space:\n  /a:"));
+        assert!(text.contains("Largest snapshots:\n  /a/big.txt"));
+        assert!(text.contains("abc123def4567890)"));
     }
 
-    let page_snapshots: Vec<_> = if page.is_some() {
-        snapshots_iter[start..end].to_vec()
-    } else {
-        snapshots_iter
-    };
+    #[test]
+    fn test_prune_report() {
+        let expired = vec![
+            (1, PathBuf::from("/a.txt"), "2024-01-15T10:30:00+00:00".to_string(), 1024, "abc".to_string()),
+            (2, PathBuf::from("/a.txt"), "2024-01-14T10:30:00+00:00".to_string(), 2048, "def".to_string()),
+        ];
 
-    for (path, date, size, checksum) in page_snapshots {
-        result.push_str(&format!(
-            "📁 {}\n  📅 {} | 💾 {} | 🔐 {}\n",
-            path.display(),
-            date,
-            format_size(*size),
-            &checksum[..16]
-        ));
-    }
+        let report = prune_report(&expired, true, 0, 0);
+        assert_eq!(report["count"], 2);
+        assert_eq!(report["size"], 3072);
+        assert_eq!(report["snapshots"][1]["checksum"], "def");
+        assert!(report.get("freed").is_none());
 
-    if page.is_some() {
-        result.push_str("─".repeat(50).as_str());
-        result.push('\n');
-        result.push_str(&format!(
-            "Page {} of {} ({} items)\n",
-            page_num, total_pages, total
-        ));
+        let report = prune_report(&expired, false, 2, 512);
+        assert_eq!(report["deleted"], 2);
+        assert_eq!(report["freed"], 512);
     }
 
-    result
 }
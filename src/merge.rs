@@ -0,0 +1,204 @@
+/*!
+Repository merges for the freeze application.
+
+`freeze db import` consolidates the history of another repository, such as
+the `~/.freeze` directory of another machine, into the current one. Its
+snapshots are recorded again, with new IDs, along with their metadata
+entries, pins and directory sessions. Stored contents are deduplicated by
+checksum: only those the current storage lacks are copied, with the chunks
+and delta bases they need. The other repository is only read.
+*/
+
+use crate::compression;
+use crate::db::Database;
+use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What an import added.
+#[derive(Debug, Default, PartialEq)]
+pub struct MergeSummary {
+    /// Snapshots recorded
+    pub snapshots: usize,
+    /// Snapshots already present, skipped
+    pub skipped: usize,
+    /// Storage files copied
+    pub files: usize,
+    /// Directory sessions recorded
+    pub sessions: usize,
+}
+
+/// Finds the database file and the data directory of a repository, given
+/// either.
+fn locate(source: &Path) -> Result<(PathBuf, PathBuf)> {
+    let (db_file, data_dir) = if source.is_dir() {
+        (source.join("data.sql"), source.to_path_buf())
+    } else {
+        let parent = source.parent().unwrap_or(Path::new("."));
+        (source.to_path_buf(), parent.to_path_buf())
+    };
+    if !db_file.is_file() {
+        bail!("{} is not a freeze repository", source.display());
+    }
+    Ok((db_file, data_dir))
+}
+
+/// Copies a stored content, with the files it depends on, from `source_storage`.
+///
+/// # Returns
+///
+/// The path of the content in the current storage
+///
+/// # Errors
+///
+/// Returns an error if the content is missing from the other repository or
+/// cannot be copied.
+fn copy_content(
+    content_path: &Path,
+    checksum: &str,
+    source_storage: &Path,
+    storage_dir: &Path,
+    summary: &mut MergeSummary,
+) -> Result<PathBuf> {
+    if let Some(existing) = compression::find_blob(storage_dir, checksum) {
+        return Ok(existing);
+    }
+
+    // Stored paths are absolute on the machine that saved them
+    let file_name = content_path
+        .file_name()
+        .with_context(|| format!("Invalid content path {}", content_path.display()))?;
+    let source = source_storage.join(file_name);
+    if !source.is_file() {
+        bail!("The other repository has no content for checksum {}", checksum);
+    }
+    for file in compression::stored_files(&source) {
+        let target = storage_dir.join(file.strip_prefix(source_storage)?);
+        if target.exists() {
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = target.with_extension("tmp");
+        fs::copy(&file, &temp_path).with_context(|| format!("Failed to copy {}", file.display()))?;
+        fs::rename(&temp_path, &target)?;
+        summary.files += 1;
+    }
+    Ok(storage_dir.join(file_name))
+}
+
+/// Imports the snapshots, contents and sessions of another repository.
+///
+/// # Arguments
+///
+/// * `source` - Data directory of the other repository, or its database file
+/// * `db` - Database connection
+///
+/// # Errors
+///
+/// Returns an error if `source` is not an unprotected freeze repository
+/// other than the current one, or if a snapshot or content cannot be
+/// imported.
+pub fn merge(source: &Path, db: &Database) -> Result<MergeSummary> {
+    let (db_file, data_dir) = locate(source)?;
+    if data_dir.join(crate::crypto::KEY_FILE).exists() {
+        bail!(
+            "{} is passphrase-protected; move its history with export-archive instead",
+            source.display()
+        );
+    }
+    if fs::canonicalize(&db_file).ok() == fs::canonicalize(Database::db_path()?).ok() {
+        bail!("Cannot import a repository into itself");
+    }
+
+    let other = Database::open_copy(&db_file)
+        .with_context(|| format!("{} is not a freeze repository", source.display()))?;
+    let source_storage = data_dir.join("storage");
+    let storage_dir = Database::storage_dir()?;
+    fs::create_dir_all(&storage_dir)?;
+
+    let ids: HashMap<(PathBuf, String, String), i64> = other
+        .list_all_snapshots_with_id()?
+        .into_iter()
+        .map(|(id, path, date, _, checksum)| ((path, checksum, date), id))
+        .collect();
+    let mut metadata = other.list_all_metadata()?;
+    let pinned = other.list_pinned()?;
+
+    let mut summary = MergeSummary::default();
+    let mut pins = HashSet::new();
+    for mut snapshot in other.list_all_snapshot_records()? {
+        // Pins apply to a content, whichever repository recorded it first
+        let key = (snapshot.path.clone(), snapshot.checksum.clone(), snapshot.date.clone());
+        if ids.get(&key).is_some_and(|old_id| pinned.contains(old_id)) {
+            pins.insert(snapshot.checksum.clone());
+        }
+        let exists = db
+            .get_snapshots_for_path(&snapshot.path)?
+            .iter()
+            .any(|existing| existing.checksum == snapshot.checksum);
+        if exists {
+            summary.skipped += 1;
+            continue;
+        }
+        if snapshot.symlink.is_none() {
+            snapshot.content_path = copy_content(
+                &snapshot.content_path,
+                &snapshot.checksum,
+                &source_storage,
+                &storage_dir,
+                &mut summary,
+            )?;
+        }
+
+        let id = db.insert_snapshot(&snapshot)?;
+        summary.snapshots += 1;
+        if let Some(entries) = ids.get(&key).and_then(|old_id| metadata.remove(old_id)) {
+            for (name, value) in entries {
+                db.set_metadata(id, &name, &value)?;
+            }
+        }
+    }
+    for checksum in pins {
+        db.set_pinned(&checksum, true)?;
+    }
+
+    for session in other.list_sessions(None)?.into_iter().rev() {
+        let exists = db
+            .list_sessions(Some(&session.root))?
+            .iter()
+            .any(|existing| existing.root == session.root && existing.date == session.date);
+        if exists {
+            continue;
+        }
+        db.create_session(
+            &session.root,
+            &session.date,
+            session.message.as_deref(),
+            &other.get_session_files(session.id)?,
+            &other.get_session_dirs(session.id)?,
+        )?;
+        summary.sessions += 1;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_file = temp_dir.path().join("data.sql");
+        assert!(locate(temp_dir.path()).is_err());
+
+        fs::write(&db_file, b"").unwrap();
+        let expected = (db_file.clone(), temp_dir.path().to_path_buf());
+        assert_eq!(locate(temp_dir.path()).unwrap(), expected);
+        assert_eq!(locate(&db_file).unwrap(), expected);
+        assert!(locate(&temp_dir.path().join("missing.sql")).is_err());
+    }
+}
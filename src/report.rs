@@ -0,0 +1,286 @@
+/*!
+Change reports for the freeze application.
+
+A report summarizes the activity of the last day or week: snapshots taken,
+paths that changed, storage growth and whether the stored content of the
+period still verifies. Reports render as plain text (for cron mails), JSON
+(for tooling and the web API) or a standalone HTML page.
+*/
+
+use crate::db::Database;
+use crate::i18n::t;
+use crate::utils::format_size;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+
+/// Time span covered by a report, ending now.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Period {
+    Day,
+    Week,
+}
+
+impl Period {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Period::Day => "day",
+            Period::Week => "week",
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        match self {
+            Period::Day => Duration::days(1),
+            Period::Week => Duration::weeks(1),
+        }
+    }
+}
+
+/// Summary of the snapshot activity over a period.
+#[derive(Serialize, Debug)]
+pub struct Report {
+    /// `day` or `week`
+    pub period: String,
+    /// Start of the period (RFC3339)
+    pub from: String,
+    /// End of the period (RFC3339)
+    pub to: String,
+    /// Number of snapshots taken during the period
+    pub snapshots_taken: usize,
+    /// Paths with at least one snapshot during the period, sorted
+    pub paths_changed: Vec<String>,
+    /// Number of changed paths that had no snapshot before the period
+    pub new_paths: usize,
+    /// Bytes of storage used by content first stored during the period
+    pub storage_added: u64,
+    /// Bytes of storage used by all snapshot content
+    pub storage_total: u64,
+    /// Number of snapshots of the period whose content was verified
+    pub verified: usize,
+    /// Paths whose stored content is missing or corrupt
+    pub corrupt: Vec<String>,
+}
+
+/// Builds the report for the period ending now.
+///
+/// # Arguments
+///
+/// * `db` - Database connection
+/// * `period` - Time span to cover
+///
+/// # Errors
+///
+/// Returns an error if the snapshots cannot be listed.
+pub fn build(db: &Database, period: Period) -> Result<Report> {
+    let to = Local::now().fixed_offset();
+    let from = to - period.duration();
+
+    let mut paths_before = HashSet::new();
+    let mut contents_seen = HashSet::new();
+    let mut paths_changed = BTreeSet::new();
+    let mut corrupt = BTreeSet::new();
+    let mut snapshots_taken = 0;
+    let mut verified = 0;
+    let mut storage_added = 0;
+    let mut storage_total = 0;
+
+    // Snapshots come oldest first, so everything before the period is seen first
+    for snapshot in db.list_all_snapshot_records()? {
+        // Content files and chunks are shared between snapshots, count each once
+        let new_content = crate::compression::stored_files(&snapshot.content_path)
+            .into_iter()
+            .filter(|file| contents_seen.insert(file.clone()))
+            .map(|file| fs::metadata(file).map(|m| m.len()).unwrap_or(0))
+            .sum::<u64>();
+        storage_total += new_content;
+
+        let in_period = DateTime::parse_from_rfc3339(&snapshot.date).is_ok_and(|date| date >= from);
+        if !in_period {
+            paths_before.insert(snapshot.path.clone());
+            continue;
+        }
+
+        snapshots_taken += 1;
+        storage_added += new_content;
+        paths_changed.insert(snapshot.path.clone());
+
+        if snapshot.verify() {
+            verified += 1;
+        } else {
+            corrupt.insert(snapshot.path.display().to_string());
+        }
+    }
+
+    let new_paths = paths_changed
+        .iter()
+        .filter(|path| !paths_before.contains(*path))
+        .count();
+
+    Ok(Report {
+        period: period.as_str().to_string(),
+        from: from.to_rfc3339(),
+        to: to.to_rfc3339(),
+        snapshots_taken,
+        paths_changed: paths_changed
+            .into_iter()
+            .map(|path| path.display().to_string())
+            .collect(),
+        new_paths,
+        storage_added,
+        storage_total,
+        verified,
+        corrupt: corrupt.into_iter().collect(),
+    })
+}
+
+/// The labelled summary lines shared by the text and HTML renderings.
+fn summary_lines(report: &Report) -> Vec<(String, String)> {
+    vec![
+        (t!("report-snapshots"), report.snapshots_taken.to_string()),
+        (
+            t!("report-paths"),
+            t!(
+                "report-paths-detail",
+                count = report.paths_changed.len(),
+                new = report.new_paths
+            ),
+        ),
+        (
+            t!("report-storage"),
+            t!(
+                "report-storage-detail",
+                added = format_size(report.storage_added as i64),
+                total = format_size(report.storage_total as i64)
+            ),
+        ),
+        (
+            t!("report-verify"),
+            if report.corrupt.is_empty() {
+                t!("report-verify-ok", count = report.verified)
+            } else {
+                t!("report-verify-failed", count = report.corrupt.len())
+            },
+        ),
+    ]
+}
+
+/// Renders the report as plain text.
+pub fn render_text(report: &Report) -> String {
+    let mut out = format!(
+        "{}\n{} {} {}\n\n",
+        t!("report-title", period = report.period.as_str()),
+        crate::utils::format_date(&report.from),
+        crate::utils::symbol("→", "->"),
+        crate::utils::format_date(&report.to)
+    );
+    for (label, value) in summary_lines(report) {
+        out.push_str(&format!("{:<20} {}\n", label, value));
+    }
+
+    if !report.corrupt.is_empty() {
+        out.push_str(&format!("\n{}\n", t!("report-corrupt-title")));
+        for path in &report.corrupt {
+            out.push_str(&format!("  {}\n", path));
+        }
+    }
+    if !report.paths_changed.is_empty() {
+        out.push_str(&format!("\n{}\n", t!("report-paths-title")));
+        for path in &report.paths_changed {
+            out.push_str(&format!("  {}\n", path));
+        }
+    }
+    out
+}
+
+/// Renders the report as a standalone HTML page.
+pub fn render_html(report: &Report) -> String {
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+    fn list(title: &str, items: &[String]) -> String {
+        if items.is_empty() {
+            return String::new();
+        }
+        let items: String = items
+            .iter()
+            .map(|item| format!("<li><code>{}</code></li>", escape(item)))
+            .collect();
+        format!("<h2>{}</h2><ul>{}</ul>", escape(title), items)
+    }
+
+    let title = t!("report-title", period = report.period.as_str());
+    let rows: String = summary_lines(report)
+        .iter()
+        .map(|(label, value)| format!("<tr><th>{}</th><td>{}</td></tr>", escape(label), escape(value)))
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="UTF-8">
+<title>{title}</title>
+<style>
+body {{ font-family: -apple-system, sans-serif; margin: 2rem; color: #222; }}
+th {{ text-align: left; padding-right: 1.5rem; }}
+td, th {{ padding: 0.25rem 0; }}
+code {{ font-size: 0.9em; }}
+.muted {{ color: #777; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p class="muted">{from} &rarr; {to}</p>
+<table>{rows}</table>
+{corrupt}{paths}
+</body>
+</html>
+"#,
+        title = escape(&title),
+        from = escape(&crate::utils::format_date(&report.from)),
+        to = escape(&crate::utils::format_date(&report.to)),
+        rows = rows,
+        corrupt = list(&t!("report-corrupt-title"), &report.corrupt),
+        paths = list(&t!("report-paths-title"), &report.paths_changed),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_report() -> Report {
+        Report {
+            period: "day".to_string(),
+            from: "2024-01-14T10:00:00+00:00".to_string(),
+            to: "2024-01-15T10:00:00+00:00".to_string(),
+            snapshots_taken: 3,
+            paths_changed: vec!["/project/a.txt".to_string(), "/project/<b>.txt".to_string()],
+            new_paths: 1,
+            storage_added: 2048,
+            storage_total: 4096,
+            verified: 2,
+            corrupt: vec!["/project/a.txt".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_render_text() {
+        let text = render_text(&sample_report());
+        assert!(text.contains("2024-01-14 10:00"));
+        assert!(text.contains("  /project/<b>.txt\n"));
+    }
+
+    #[test]
+    fn test_render_html_escapes_paths() {
+        let html = render_html(&sample_report());
+        assert!(html.contains("<code>/project/&lt;b&gt;.txt</code>"));
+        assert!(!html.contains("<b>"));
+    }
+}
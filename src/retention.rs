@@ -0,0 +1,305 @@
+/*!
+Retention schedules for the freeze application.
+
+A schedule keeps one snapshot per period for a while, the
+grandfather-father-son scheme of long-lived backup sets:
+`hourly:24h, daily:30d, weekly:1y` keeps the latest snapshot of each hour
+for a day, of each day for a month and of each week for a year. The latest
+snapshot of a file and pinned snapshots are always kept.
+
+`freeze prune` and the daemon apply the `retention` setting, or the
+`retention_rules` entry of the closest directory above each file.
+*/
+
+use crate::config::Config;
+use crate::db::{Database, SnapshotWithId};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// The periods a schedule keeps one snapshot of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Period {
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Period {
+    /// Every period, shortest first.
+    pub const ALL: [Period; 5] = [
+        Period::Hourly,
+        Period::Daily,
+        Period::Weekly,
+        Period::Monthly,
+        Period::Yearly,
+    ];
+
+    /// Name of the period in schedules.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Period::Hourly => "hourly",
+            Period::Daily => "daily",
+            Period::Weekly => "weekly",
+            Period::Monthly => "monthly",
+            Period::Yearly => "yearly",
+        }
+    }
+
+    /// Identifies the period a date falls in.
+    fn bucket(self, date: &DateTime<Local>) -> String {
+        let format = match self {
+            Period::Hourly => "%Y-%m-%dT%H",
+            Period::Daily => "%Y-%m-%d",
+            Period::Weekly => "%G-W%V",
+            Period::Monthly => "%Y-%m",
+            Period::Yearly => "%Y",
+        };
+        date.format(format).to_string()
+    }
+}
+
+/// Periods to keep one snapshot of, each for how long.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    tiers: Vec<(Period, chrono::Duration)>,
+}
+
+impl Schedule {
+    /// Parses a schedule such as `hourly:24h, daily:30d, weekly:1y`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a period is unknown or a duration is invalid.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow::anyhow!(
+                "Invalid retention schedule: {} (expected e.g. hourly:24h, daily:30d, weekly:1y)",
+                spec
+            )
+        };
+        let mut tiers = Vec::new();
+        for tier in spec.split(',').map(str::trim).filter(|tier| !tier.is_empty()) {
+            let (name, age) = tier.split_once(':').ok_or_else(invalid)?;
+            let period = Period::ALL
+                .into_iter()
+                .find(|period| period.as_str() == name.trim())
+                .ok_or_else(invalid)?;
+            tiers.push((period, crate::utils::parse_age(age)?));
+        }
+        if tiers.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self { tiers })
+    }
+
+    /// Decides which versions of a file the schedule keeps.
+    ///
+    /// # Arguments
+    ///
+    /// * `dates` - Dates of the versions, newest first; `None` for dates
+    ///   that cannot be read, which are kept
+    /// * `now` - The moment ages are measured from
+    ///
+    /// # Returns
+    ///
+    /// Whether each version is kept
+    pub fn keep(&self, dates: &[Option<DateTime<Local>>], now: DateTime<Local>) -> Vec<bool> {
+        let mut kept: Vec<bool> = dates
+            .iter()
+            .enumerate()
+            .map(|(i, date)| i == 0 || date.is_none())
+            .collect();
+        for (period, window) in &self.tiers {
+            let mut seen = HashSet::new();
+            for (i, date) in dates.iter().enumerate() {
+                let Some(date) = date else { continue };
+                if now - *date <= *window && seen.insert(period.bucket(date)) {
+                    kept[i] = true;
+                }
+            }
+        }
+        kept
+    }
+}
+
+/// The schedules that apply to each path.
+#[derive(Clone, Debug, Default)]
+pub struct Policies {
+    default: Option<Schedule>,
+    /// Schedules by directory, deepest first
+    rules: Vec<(PathBuf, Schedule)>,
+}
+
+impl Policies {
+    /// Applies one schedule to every path.
+    pub fn single(schedule: Schedule) -> Self {
+        Self {
+            default: Some(schedule),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Reads the `retention` and `retention_rules` settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a schedule is invalid.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let default = config.retention.as_deref().map(Schedule::parse).transpose()?;
+        let mut rules = config
+            .retention_rules
+            .iter()
+            .flatten()
+            .map(|(dir, spec)| Ok((expand_home(dir), Schedule::parse(spec)?)))
+            .collect::<Result<Vec<_>>>()?;
+        rules.sort_by_key(|(dir, _)| std::cmp::Reverse(dir.components().count()));
+        Ok(Self { default, rules })
+    }
+
+    /// Whether no schedule applies anywhere.
+    pub fn is_empty(&self) -> bool {
+        self.default.is_none() && self.rules.is_empty()
+    }
+
+    /// The schedule of the closest directory above `path`, or the default.
+    pub fn for_path(&self, path: &Path) -> Option<&Schedule> {
+        self.rules
+            .iter()
+            .find(|(dir, _)| path.starts_with(dir))
+            .map(|(_, schedule)| schedule)
+            .or(self.default.as_ref())
+    }
+}
+
+/// Expands a leading `~/` to the home directory.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Lists the snapshots the schedules no longer keep, below `root` or
+/// everywhere.
+///
+/// Paths no schedule applies to are left alone, as are pinned snapshots.
+///
+/// # Returns
+///
+/// A vector of tuples containing (id, path, date, size, checksum), by path
+/// then newest first
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn expired_snapshots(
+    root: Option<&Path>,
+    policies: &Policies,
+    now: DateTime<Local>,
+    db: &Database,
+) -> Result<Vec<SnapshotWithId>> {
+    let pinned = db.list_pinned()?;
+    let mut by_path: BTreeMap<PathBuf, Vec<SnapshotWithId>> = BTreeMap::new();
+    for snapshot in db.list_all_snapshots_with_id()? {
+        if root.is_none_or(|root| snapshot.1.starts_with(root)) {
+            by_path.entry(snapshot.1.clone()).or_default().push(snapshot);
+        }
+    }
+
+    let mut expired = Vec::new();
+    for (path, mut snapshots) in by_path {
+        let Some(schedule) = policies.for_path(&path) else {
+            continue;
+        };
+        let dates: Vec<Option<DateTime<Local>>> = snapshots
+            .iter()
+            .map(|(_, _, date, ..)| {
+                DateTime::parse_from_rfc3339(date)
+                    .ok()
+                    .map(|date| date.with_timezone(&Local))
+            })
+            .collect();
+        let mut order: Vec<usize> = (0..snapshots.len()).collect();
+        order.sort_by(|&a, &b| dates[b].cmp(&dates[a]).then(snapshots[b].0.cmp(&snapshots[a].0)));
+        let sorted: Vec<_> = order.iter().map(|&i| dates[i]).collect();
+        let kept = schedule.keep(&sorted, now);
+
+        let mut removed: Vec<usize> = order
+            .into_iter()
+            .zip(kept)
+            .filter(|&(_, kept)| !kept)
+            .map(|(i, _)| i)
+            .collect();
+        removed.retain(|&i| !pinned.contains(&snapshots[i].0));
+        for i in removed {
+            expired.push(std::mem::take(&mut snapshots[i]));
+        }
+    }
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_schedule() {
+        let schedule = Schedule::parse("hourly:24h, daily:30d,weekly:1y").unwrap();
+        assert_eq!(schedule.tiers.len(), 3);
+        assert_eq!(schedule.tiers[2], (Period::Weekly, chrono::Duration::days(365)));
+        assert!(Schedule::parse("").is_err());
+        assert!(Schedule::parse("daily").is_err());
+        assert!(Schedule::parse("fortnightly:1y").is_err());
+        assert!(Schedule::parse("daily:soon").is_err());
+    }
+
+    #[test]
+    fn test_keep() {
+        let now = Local.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let hours_ago = |hours: i64| Some(now - chrono::Duration::hours(hours));
+        let dates = [
+            hours_ago(0),
+            Some(now - chrono::Duration::minutes(50)),
+            hours_ago(1),
+            hours_ago(30),
+            hours_ago(31),
+            hours_ago(24 * 40),
+            None,
+        ];
+
+        let schedule = Schedule::parse("hourly:24h, daily:7d").unwrap();
+        assert_eq!(
+            schedule.keep(&dates, now),
+            [true, true, false, true, false, false, true]
+        );
+
+        let schedule = Schedule::parse("daily:1d").unwrap();
+        assert_eq!(
+            schedule.keep(&dates, now),
+            [true, false, false, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn test_policies_for_path() {
+        let config = Config {
+            retention: Some("daily:30d".to_string()),
+            retention_rules: Some(BTreeMap::from([
+                ("/data".to_string(), "weekly:1y".to_string()),
+                ("/data/logs".to_string(), "hourly:24h".to_string()),
+            ])),
+            ..Config::default()
+        };
+        let policies = Policies::from_config(&config).unwrap();
+        let period = |path: &str| policies.for_path(Path::new(path)).unwrap().tiers[0].0;
+        assert_eq!(period("/data/logs/app.log"), Period::Hourly);
+        assert_eq!(period("/data/db.sql"), Period::Weekly);
+        assert_eq!(period("/home/notes.txt"), Period::Daily);
+        assert!(Policies::default().for_path(Path::new("/data")).is_none());
+    }
+}
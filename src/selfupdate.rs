@@ -0,0 +1,339 @@
+/*!
+Self-update support for the freeze application.
+
+Checks the latest GitHub release, downloads the binary matching the current
+platform, verifies it against the release `SHA256SUMS` file and atomically
+replaces the running executable.
+
+Release assets are expected to be named `freeze-<os>-<arch>` (with `.exe` on
+Windows), e.g. `freeze-linux-x86_64`, next to a `SHA256SUMS` file in the
+`sha256sum` output format and its `SHA256SUMS.minisig` signature, made with
+`minisign -S -l`. The signature is checked against the minisign public key
+given in `FREEZE_RELEASE_PUBLIC_KEY` when freeze is built; builds without one
+refuse to update.
+*/
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use console::style;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/nils010485/freeze/releases/latest";
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+const SIGNATURE_ASSET: &str = "SHA256SUMS.minisig";
+const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("FREEZE_RELEASE_PUBLIC_KEY");
+
+/// Checks for a newer release and installs it unless `check_only` is set.
+///
+/// # Arguments
+///
+/// * `check_only` - Only report whether an update is available
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The release metadata cannot be fetched or parsed
+/// - This build has no release public key
+/// - No binary, checksum or checksum signature is published for this platform
+/// - The checksums are not signed by the release key
+/// - The downloaded binary does not match its checksum
+/// - The executable cannot be replaced
+pub fn run(check_only: bool) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    println!(
+        "{} {}",
+        style("Current version:").cyan().bold(),
+        style(current).green()
+    );
+
+    let release: Value = ureq::get(LATEST_RELEASE_URL)
+        .header("User-Agent", concat!("freeze/", env!("CARGO_PKG_VERSION")))
+        .header("Accept", "application/vnd.github+json")
+        .call()
+        .context("Failed to query the latest release")?
+        .body_mut()
+        .read_json()
+        .context("Failed to parse release metadata")?;
+
+    let tag = release["tag_name"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Release metadata has no tag name"))?;
+    println!(
+        "{} {}",
+        style("Latest version:").cyan().bold(),
+        style(tag).green()
+    );
+
+    if !is_newer(tag, current) {
+        println!("{}", style("freeze is already up to date.").green());
+        return Ok(());
+    }
+    if check_only {
+        println!(
+            "{}",
+            style("An update is available. Run `freeze self-update` to install it.").yellow()
+        );
+        return Ok(());
+    }
+
+    let public_key = RELEASE_PUBLIC_KEY.ok_or_else(|| {
+        anyhow::anyhow!("This build of freeze has no release public key and cannot verify updates")
+    })?;
+    let asset_name = platform_asset_name();
+    let binary_url = asset_url(&release, &asset_name)?;
+    let checksums_url = asset_url(&release, CHECKSUMS_ASSET)?;
+    let signature_url = asset_url(&release, SIGNATURE_ASSET)?;
+
+    let checksums = ureq::get(&checksums_url)
+        .header("User-Agent", concat!("freeze/", env!("CARGO_PKG_VERSION")))
+        .call()
+        .context("Failed to download checksums")?
+        .body_mut()
+        .read_to_string()?;
+    let signature = ureq::get(&signature_url)
+        .header("User-Agent", concat!("freeze/", env!("CARGO_PKG_VERSION")))
+        .call()
+        .context("Failed to download the checksums signature")?
+        .body_mut()
+        .read_to_string()?;
+    verify_signature(checksums.as_bytes(), &signature, public_key)
+        .with_context(|| format!("{} of release {} failed verification", CHECKSUMS_ASSET, tag))?;
+    let expected = find_checksum(&checksums, &asset_name)
+        .ok_or_else(|| anyhow::anyhow!("No checksum published for {}", asset_name))?;
+
+    let current_exe = std::env::current_exe()?.canonicalize()?;
+    let exe_dir = current_exe
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("Executable has no parent directory"))?;
+    let temp_path = exe_dir.join(format!(".{}.download", asset_name));
+
+    // Ensure the download is cleaned up on error
+    struct TempFileGuard<'a>(&'a Path);
+    impl<'a> Drop for TempFileGuard<'a> {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(self.0);
+        }
+    }
+    let _guard = TempFileGuard(&temp_path);
+
+    println!(
+        "{} {}",
+        style("Downloading:").cyan().bold(),
+        style(&asset_name).green()
+    );
+    let actual = download(&binary_url, &temp_path)?;
+    if actual != expected {
+        anyhow::bail!(
+            "Checksum mismatch for {} (expected {}, got {})",
+            asset_name,
+            expected,
+            actual
+        );
+    }
+
+    replace_executable(&temp_path, &current_exe)?;
+    println!(
+        "{} {}",
+        style("Updated freeze to").green().bold(),
+        style(tag).cyan()
+    );
+    Ok(())
+}
+
+/// Returns the release asset name for the current platform.
+fn platform_asset_name() -> String {
+    format!(
+        "freeze-{}-{}{}",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+/// Finds the download URL of a release asset by name.
+fn asset_url(release: &Value, name: &str) -> Result<String> {
+    release["assets"]
+        .as_array()
+        .and_then(|assets| {
+            assets
+                .iter()
+                .find(|asset| asset["name"].as_str() == Some(name))
+        })
+        .and_then(|asset| asset["browser_download_url"].as_str())
+        .map(String::from)
+        .ok_or_else(|| anyhow::anyhow!("No release asset named {} for this platform", name))
+}
+
+/// Looks up the checksum of `name` in a `sha256sum`-formatted listing.
+fn find_checksum(listing: &str, name: &str) -> Option<String> {
+    listing.lines().find_map(|line| {
+        let (hash, file) = line.split_once(char::is_whitespace)?;
+        (file.trim().trim_start_matches('*') == name).then(|| hash.to_lowercase())
+    })
+}
+
+/// Checks a minisign signature of `data` against a minisign `public_key`.
+///
+/// Only legacy signatures (`Ed`, made with `minisign -S -l`) over the data
+/// itself are accepted; the trusted comment must be signed too.
+fn verify_signature(data: &[u8], minisig: &str, public_key: &str) -> Result<()> {
+    let decode = |text: &str| base64::engine::general_purpose::STANDARD.decode(text.trim());
+    let key = decode(public_key).context("Invalid release public key")?;
+    if key.len() != 42 || &key[..2] != b"Ed" {
+        anyhow::bail!("Invalid release public key");
+    }
+
+    let mut lines = minisig.lines().filter(|line| !line.starts_with("untrusted comment:"));
+    let (Some(signature), Some(comment), Some(global)) = (lines.next(), lines.next(), lines.next()) else {
+        anyhow::bail!("Malformed signature");
+    };
+    let signature = decode(signature).context("Malformed signature")?;
+    let comment = comment
+        .strip_prefix("trusted comment: ")
+        .ok_or_else(|| anyhow::anyhow!("Signature has no trusted comment"))?;
+    let global = decode(global).context("Malformed signature")?;
+    if signature.len() != 74 {
+        anyhow::bail!("Malformed signature");
+    }
+    match &signature[..2] {
+        b"Ed" => {}
+        b"ED" => anyhow::bail!("Prehashed signatures are not supported, sign with `minisign -S -l`"),
+        _ => anyhow::bail!("Unknown signature algorithm"),
+    }
+    if signature[2..10] != key[2..10] {
+        anyhow::bail!("Signed with another key");
+    }
+
+    let verifier = UnparsedPublicKey::new(&ED25519, &key[10..]);
+    verifier
+        .verify(data, &signature[10..])
+        .map_err(|_| anyhow::anyhow!("Invalid signature"))?;
+    verifier
+        .verify(&[&signature[10..], comment.as_bytes()].concat(), &global)
+        .map_err(|_| anyhow::anyhow!("Invalid trusted comment signature"))?;
+    Ok(())
+}
+
+/// Returns `true` if release `tag` is strictly newer than `current`.
+fn is_newer(tag: &str, current: &str) -> bool {
+    fn parse(version: &str) -> Vec<u64> {
+        version
+            .trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map_while(|part| part.parse().ok())
+            .collect()
+    }
+    parse(tag) > parse(current)
+}
+
+/// Streams `url` into `dest` and returns the SHA256 checksum of the content.
+fn download(url: &str, dest: &Path) -> Result<String> {
+    let mut response = ureq::get(url)
+        .header("User-Agent", concat!("freeze/", env!("CARGO_PKG_VERSION")))
+        .call()
+        .context("Failed to download release binary")?;
+    let mut reader = response.body_mut().as_reader();
+
+    let mut file = fs::File::create(dest)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+        file.write_all(&buffer[..bytes_read])?;
+    }
+    file.sync_all()?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Atomically replaces `exe` with the verified download at `new`.
+fn replace_executable(new: &Path, exe: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(new, fs::Permissions::from_mode(0o755))?;
+    }
+
+    // A running executable cannot be overwritten on Windows, but it can be renamed
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("old.exe");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old)?;
+    }
+
+    fs::rename(new, exe).with_context(|| format!("Failed to replace {}", exe.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("v0.2.0", "0.1.1"));
+        assert!(is_newer("0.1.10", "0.1.9"));
+        assert!(!is_newer("v0.1.1", "0.1.1"));
+        assert!(!is_newer("v0.1.0", "0.1.1"));
+    }
+
+    #[test]
+    fn test_find_checksum() {
+        let listing = "abc123  freeze-linux-x86_64\nDEF456 *freeze-windows-x86_64.exe\n";
+        assert_eq!(
+            find_checksum(listing, "freeze-linux-x86_64").as_deref(),
+            Some("abc123")
+        );
+        assert_eq!(
+            find_checksum(listing, "freeze-windows-x86_64.exe").as_deref(),
+            Some("def456")
+        );
+        assert_eq!(find_checksum(listing, "freeze-macos-aarch64"), None);
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        use ring::rand::SystemRandom;
+        use ring::signature::{Ed25519KeyPair, KeyPair};
+
+        let encode = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let key_id = *b"freezeid";
+        let public_key = encode(&[b"Ed".as_slice(), &key_id, pair.public_key().as_ref()].concat());
+
+        let data = b"abc123  freeze-linux-x86_64\n";
+        let comment = "timestamp:1700000000";
+        let sign = |data: &[u8], algorithm: &[u8], comment: &str| {
+            let signature = pair.sign(data);
+            let global = pair.sign(&[signature.as_ref(), comment.as_bytes()].concat());
+            format!(
+                "untrusted comment: signature from minisign secret key\n{}\ntrusted comment: {}\n{}\n",
+                encode(&[algorithm, &key_id, signature.as_ref()].concat()),
+                comment,
+                encode(global.as_ref())
+            )
+        };
+
+        let minisig = sign(data, b"Ed", comment);
+        assert!(verify_signature(data, &minisig, &public_key).is_ok());
+        assert!(verify_signature(b"fff000  freeze-linux-x86_64\n", &minisig, &public_key).is_err());
+        let forged_comment = minisig.replace(comment, "timestamp:1800000000");
+        assert!(verify_signature(data, &forged_comment, &public_key).is_err());
+        assert!(verify_signature(data, &sign(data, b"ED", comment), &public_key).is_err());
+        assert!(verify_signature(data, "", &public_key).is_err());
+
+        let other = encode(&[b"Ed".as_slice(), b"otherkey", pair.public_key().as_ref()].concat());
+        assert!(verify_signature(data, &minisig, &other).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+/*!
+Directory sessions for the freeze application.
+
+Saving a directory records a session: the manifest of every file the
+directory contained at that moment, changed or not. Sessions can be listed,
+restored and compared as a unit, which individual file snapshots cannot.
+*/
+
+use crate::db::Database;
+use crate::snapshot::Snapshot;
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A recorded directory save.
+#[derive(Debug)]
+pub struct Session {
+    /// ID of the session
+    pub id: i64,
+    /// The directory that was saved
+    pub root: PathBuf,
+    /// RFC3339 timestamp of the save
+    pub date: String,
+    /// Optional message given at save time
+    pub message: Option<String>,
+    /// Number of files in the manifest
+    pub files: usize,
+}
+
+/// How a file differs between two directory states.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Modified,
+    /// Moved from this path, with the same content
+    Renamed(PathBuf),
+}
+
+/// Retrieves a session, failing if it does not exist.
+///
+/// # Errors
+///
+/// Returns an error if the session is unknown or the database query fails.
+pub fn get(id: i64, db: &Database) -> Result<Session> {
+    db.get_session(id)?
+        .ok_or_else(|| anyhow::anyhow!("Session not found: {}", id))
+}
+
+/// Restores every file of a session to its recorded content, and recreates
+/// its directories, empty ones included.
+///
+/// Files created after the session are left in place.
+///
+/// # Arguments
+///
+/// * `id` - ID of the session
+/// * `to` - Optional folder to restore into, keeping the layout relative to the session root
+/// * `db` - Database connection
+///
+/// # Returns
+///
+/// The number of files restored
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The session is unknown
+/// - The content of a file is no longer stored
+/// - A file cannot be decompressed or written
+pub fn restore(id: i64, to: Option<&Path>, db: &Database) -> Result<usize> {
+    let session = get(id, db)?;
+
+    let target_of = |path: &Path| match to {
+        Some(to) => to.join(path.strip_prefix(&session.root).unwrap_or(path)),
+        None => path.to_path_buf(),
+    };
+    let files = db.get_session_files(id)?;
+    for (path, checksum) in &files {
+        let snapshot = db
+            .get_path_snapshot(path, checksum)?
+            .ok_or_else(|| anyhow::anyhow!("Snapshot content missing for {}", path.display()))?;
+        Snapshot::restore_snapshot(&snapshot, &target_of(path))?;
+    }
+    let dirs: Vec<_> = db
+        .get_session_dirs(id)?
+        .into_iter()
+        .map(|(path, mode)| (target_of(&path), mode))
+        .collect();
+    create_dirs(&dirs)?;
+    Ok(files.len())
+}
+
+/// Returns the directories below `path` recorded by the latest session of
+/// `path` or of a directory above it, taken at or before `as_of` if given.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails.
+pub fn directories_at(
+    path: &Path,
+    as_of: Option<&DateTime<FixedOffset>>,
+    db: &Database,
+) -> Result<Vec<(PathBuf, Option<u32>)>> {
+    let session = db.list_sessions(None)?.into_iter().find(|session| {
+        path.starts_with(&session.root)
+            && as_of.is_none_or(|as_of| {
+                DateTime::parse_from_rfc3339(&session.date).is_ok_and(|date| date <= *as_of)
+            })
+    });
+    let Some(session) = session else {
+        return Ok(Vec::new());
+    };
+    Ok(db
+        .get_session_dirs(session.id)?
+        .into_iter()
+        .filter(|(dir, _)| dir.starts_with(path) && dir != path)
+        .collect())
+}
+
+/// Creates directories and gives them their recorded modes.
+///
+/// Modes are set deepest first, once every directory exists, so that a
+/// read-only directory does not prevent creating the ones below it.
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be created or its mode cannot be set.
+pub fn create_dirs(dirs: &[(PathBuf, Option<u32>)]) -> Result<()> {
+    for (dir, _) in dirs {
+        std::fs::create_dir_all(dir)?;
+    }
+    #[cfg(unix)]
+    for (dir, mode) in dirs.iter().rev() {
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(*mode))?;
+        }
+    }
+    Ok(())
+}
+
+/// Computes the manifest of the files currently below `root`.
+///
+/// Excluded files are skipped, as they would be on save.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be walked or a file cannot be read.
+pub fn current_files(root: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+    for entry in Snapshot::walk(root)
+        .into_iter()
+        .filter_entry(|e| !Snapshot::is_excluded(e.path()))
+    {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.loop_ancestor().is_some() => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if entry.file_type().is_file() {
+            let checksum = Snapshot::calculate_checksum(entry.path())?;
+            files.push((entry.into_path(), checksum));
+        } else if entry.file_type().is_symlink() {
+            let checksum = Snapshot::symlink_checksum(&std::fs::read_link(entry.path())?);
+            files.push((entry.into_path(), checksum));
+        }
+    }
+    Ok(files)
+}
+
+/// Compares two manifests.
+///
+/// A removed file and an added one with the same checksum are reported as a
+/// rename, like `git diff -M`. When several files share a checksum, they are
+/// paired in path order.
+///
+/// # Returns
+///
+/// The files that differ, sorted by path, with how they changed from `old` to
+/// `new`. Renames are listed under their new path.
+pub fn diff(old: &[(PathBuf, String)], new: &[(PathBuf, String)]) -> Vec<(PathBuf, Change)> {
+    let old: BTreeMap<_, _> = old.iter().map(|(path, sum)| (path, sum)).collect();
+    let new: BTreeMap<_, _> = new.iter().map(|(path, sum)| (path, sum)).collect();
+
+    let mut changes: Vec<(PathBuf, Change)> = old
+        .iter()
+        .filter_map(|(path, sum)| match new.get(path) {
+            None => Some(((*path).clone(), Change::Removed)),
+            Some(new_sum) if new_sum != sum => Some(((*path).clone(), Change::Modified)),
+            Some(_) => None,
+        })
+        .chain(
+            new.keys()
+                .filter(|path| !old.contains_key(*path))
+                .map(|path| ((*path).clone(), Change::Added)),
+        )
+        .collect();
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut removed: BTreeMap<&String, Vec<PathBuf>> = BTreeMap::new();
+    for (path, change) in changes.iter().rev() {
+        if *change == Change::Removed {
+            removed.entry(old[path]).or_default().push(path.clone());
+        }
+    }
+    let mut renamed = Vec::new();
+    for (path, change) in &mut changes {
+        if *change == Change::Added
+            && let Some(from) = removed.get_mut(new[path]).and_then(Vec::pop)
+        {
+            renamed.push(from.clone());
+            *change = Change::Renamed(from);
+        }
+    }
+    changes.retain(|(path, change)| !(*change == Change::Removed && renamed.contains(path)));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(files: &[(&str, &str)]) -> Vec<(PathBuf, String)> {
+        files
+            .iter()
+            .map(|(path, sum)| (PathBuf::from(path), sum.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = manifest(&[("/p/a", "1"), ("/p/b", "2"), ("/p/c", "3")]);
+        let new = manifest(&[("/p/a", "1"), ("/p/b", "9"), ("/p/d", "4")]);
+
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                (PathBuf::from("/p/b"), Change::Modified),
+                (PathBuf::from("/p/c"), Change::Removed),
+                (PathBuf::from("/p/d"), Change::Added),
+            ]
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_diff_renames() {
+        let old = manifest(&[("/p/a", "1"), ("/p/b", "2"), ("/p/c", "2"), ("/p/d", "3")]);
+        let new = manifest(&[("/p/a2", "1"), ("/p/b", "2"), ("/p/e", "2"), ("/p/d", "3"), ("/p/f", "3")]);
+
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                (PathBuf::from("/p/a2"), Change::Renamed(PathBuf::from("/p/a"))),
+                (PathBuf::from("/p/e"), Change::Renamed(PathBuf::from("/p/c"))),
+                (PathBuf::from("/p/f"), Change::Added),
+            ]
+        );
+    }
+}
@@ -5,21 +5,31 @@ This module provides the `Snapshot` struct which represents a file snapshot
 with associated metadata and methods for creating, restoring, and managing snapshots.
 */
 
+use crate::chunks;
+use crate::delta;
+use crate::compression::{self, Compression};
 use crate::db::Database;
+use crate::hash::{HashAlgorithm, Hasher};
+use crate::hooks::{Operation, Phase};
+use crate::xattrs::{self, Xattrs};
 use anyhow::{Context, Result};
-use chrono::Local;
-use indicatif::{ProgressBar, ProgressStyle};
-use sha2::{Digest, Sha256};
+use chrono::{DateTime, FixedOffset, Local};
+use indicatif::ProgressBar;
 use std::fs;
 use std::io::{Read, Write};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
 use walkdir::WalkDir;
 
 /// Represents a file snapshot with metadata.
 ///
 /// Contains information about a snapshot including the original path, storage location,
 /// checksum for integrity verification, timestamp, and file size.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Snapshot {
     /// Original path of the snapshotted file
     pub path: PathBuf,
@@ -31,6 +41,177 @@ pub struct Snapshot {
     pub date: String,
     /// Size of the original file in bytes
     pub size: i64,
+    /// Optional message describing why the snapshot was taken
+    pub message: Option<String>,
+    /// Modification time of the original file when it was saved, in
+    /// nanoseconds since the Unix epoch
+    pub mtime: Option<i64>,
+    /// Permission bits of the original file (Unix mode, without the file type)
+    pub mode: Option<u32>,
+    /// Target of the original path when it was a symbolic link; such
+    /// snapshots have no stored content
+    pub symlink: Option<PathBuf>,
+    /// Extended attributes (including POSIX ACLs) of the original file, when
+    /// saved with the `xattrs` setting
+    pub xattrs: Option<Xattrs>,
+    /// MIME type detected from the content when it was saved, such as
+    /// `image/png` or `text/plain`
+    pub mime: Option<String>,
+    /// Algorithm of the checksum
+    pub hash: HashAlgorithm,
+    /// Bytes the stored content uses on disk, compressed, when measured
+    pub stored_size: Option<i64>,
+}
+
+/// Metadata key recording the zstd level of contents a snapshot stored.
+pub const COMPRESSION_LEVEL_KEY: &str = "compression-level";
+
+/// Age from which a temporary file in storage is left over from an
+/// interrupted operation rather than being written.
+const STALE_TEMP_AGE: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// How one snapshot is chosen among the snapshots of a path.
+#[derive(Debug, Clone, Default)]
+pub enum Selection {
+    /// Ask the user when there is more than one snapshot
+    #[default]
+    Prompt,
+    /// Take the most recent snapshot
+    Latest,
+    /// Take the most recent snapshot whose checksum starts with this prefix
+    Checksum(String),
+    /// Take the most recent snapshot taken at or before this moment
+    AsOf(DateTime<FixedOffset>),
+}
+
+/// The lines of a text snapshot to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineRange {
+    /// Lines `first` to `last`, 1-indexed and inclusive (`None` for the end)
+    Lines(usize, Option<usize>),
+    /// The first lines
+    Head(usize),
+    /// The last lines
+    Tail(usize),
+}
+
+impl LineRange {
+    /// Parses a `FIRST:LAST` line range, either bound being optional.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bounds are not line numbers or are reversed.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Invalid line range '{}', expected FIRST:LAST (e.g. 100:200)", spec);
+        let (first, last) = spec.split_once(':').ok_or_else(invalid)?;
+        let bound = |value: &str| -> Result<Option<usize>> {
+            match value.trim() {
+                "" => Ok(None),
+                value => value.parse().map(Some).map_err(|_| invalid()),
+            }
+        };
+        let first = bound(first)?.unwrap_or(1);
+        let last = bound(last)?;
+        if first == 0 || last.is_some_and(|last| last < first) {
+            return Err(invalid());
+        }
+        Ok(LineRange::Lines(first, last))
+    }
+}
+
+/// What a restore does to a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreAction {
+    /// The file does not exist and would be created
+    Create,
+    /// The file exists with different content and would be overwritten
+    Overwrite,
+    /// The file already has the snapshot content
+    Unchanged,
+}
+
+/// A file a restore would write.
+#[derive(Debug)]
+pub struct PlannedRestore {
+    /// Where the content would be written
+    pub target: PathBuf,
+    /// The snapshot that would be restored
+    pub snapshot: Snapshot,
+    /// Checksum of the file currently at the target, if any
+    pub current: Option<String>,
+    /// Effect of the restore on the target
+    pub action: RestoreAction,
+}
+
+/// Files and bytes processed by a recursive save.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SaveProgress {
+    /// Files saved so far
+    pub files_done: usize,
+    /// Files to save
+    pub files_total: usize,
+    /// Bytes saved so far
+    pub bytes_done: u64,
+    /// Bytes to save
+    pub bytes_total: u64,
+    /// Files that could not be saved, with `keep_going`
+    pub files_skipped: usize,
+    /// Files among those saved whose content matched their latest snapshot,
+    /// so that no new snapshot was recorded
+    #[serde(default)]
+    pub files_unchanged: usize,
+}
+
+/// A file a recursive save could not read.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SkippedFile {
+    /// The file or directory
+    pub path: PathBuf,
+    /// Why it was skipped
+    pub error: String,
+}
+
+/// The result of a recursive save.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SaveSummary {
+    /// Final counters of the save
+    pub progress: SaveProgress,
+    /// Files that could not be saved, with `keep_going`
+    pub skipped: Vec<SkippedFile>,
+    /// Files left out for being above the `max_file_size` setting, with
+    /// their size
+    #[serde(default)]
+    pub too_large: Vec<(PathBuf, u64)>,
+}
+
+/// The result of [`Snapshot::compact`].
+#[derive(Debug, Default)]
+pub struct CompactSummary {
+    /// zstd contents rewritten at the new level
+    pub recompressed: usize,
+    /// Legacy uncompressed contents rewritten as zstd
+    pub migrated: usize,
+    /// Snapshots whose stored content does not match their checksum, left
+    /// as is
+    pub corrupt: Vec<PathBuf>,
+    /// Bytes the rewritten contents used on disk before
+    pub before: i64,
+    /// Bytes the rewritten contents use on disk now
+    pub after: i64,
+}
+
+/// A restore recorded so that it can be undone.
+#[derive(Debug)]
+pub struct RestoreRecord {
+    /// Restore ID
+    pub id: i64,
+    /// The restored file or directory
+    pub root: PathBuf,
+    /// When the restore happened (RFC3339)
+    pub date: String,
+    /// Each written file with the checksum of its previous content,
+    /// `None` if the restore created it
+    pub files: Vec<(PathBuf, Option<String>)>,
 }
 
 impl Snapshot {
@@ -54,80 +235,471 @@ impl Snapshot {
     /// - The storage directory cannot be created
     /// - The file cannot be compressed
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let path = path
+        Ok(Self::create(path, None)?.0)
+    }
+
+    /// Creates a snapshot like [`Snapshot::new`].
+    ///
+    /// New content is stored as a delta against `base`, the previous version
+    /// of the file, when one is given and the file is neither chunked nor
+    /// configured for another algorithm than zstd.
+    ///
+    /// # Returns
+    ///
+    /// The snapshot, and `true` if its content was compressed into storage
+    /// now rather than already stored by an earlier snapshot
+    fn create<P: AsRef<Path>>(path: P, base: Option<&Snapshot>) -> Result<(Self, bool)> {
+        let canonical = path
             .as_ref()
             .canonicalize()
             .with_context(|| format!("Failed to canonicalize path: {}", path.as_ref().display()))?;
+        // A followed link is recorded under its own name, with the content
+        // it points to
+        let path = match (path.as_ref().parent(), path.as_ref().file_name()) {
+            (Some(parent), Some(name)) if fs::symlink_metadata(path.as_ref())?.file_type().is_symlink() => {
+                let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+                parent.canonicalize()?.join(name)
+            }
+            _ => canonical,
+        };
 
         if !path.is_file() {
             anyhow::bail!("Path is not a file: {}", path.display());
         }
 
-        // Calculate checksum in chunks to avoid memory issues (BEFORE compression)
-        let checksum = Self::calculate_checksum(&path)?;
-
-        // Get file metadata for size
+        // Read the metadata before hashing: a change while hashing leaves
+        // an older modification time, which only costs a hash next time
         let metadata = fs::metadata(&path)?;
         let size = metadata.len() as i64;
+        let mtime = file_mtime(&metadata);
+        let mode = file_mode(&metadata);
+        let mime = Some(sniff_mime(&path)?);
+        let hash = crate::config::get().hash();
+        let xattrs = match crate::config::get().xattrs() {
+            true => Some(
+                xattrs::read(&path)
+                    .with_context(|| format!("Failed to read extended attributes of {}", path.display()))?,
+            ),
+            false => None,
+        };
 
         // Prepare storage directory
         let storage_dir = Self::get_storage_dir()?;
         fs::create_dir_all(&storage_dir)?;
 
-        // Create content path based on checksum with .zstd extension
-        let content_path = storage_dir.join(format!("{}.zstd", checksum));
+        let compression = compression::for_path(&path);
+        let delta_base = base.filter(|base| {
+            compression == Compression::Zstd
+                && (base.size as u64) < chunks::CHUNKING_THRESHOLD
+                && base.content_path.exists()
+        });
 
-        // Compress and copy file to storage if not already there (deduplication)
-        if !content_path.exists() {
-            Self::compress_and_copy(&path, &content_path)?;
+        // Plain contents are hashed while they are compressed, reading the
+        // file once
+        if (size as u64) < chunks::CHUNKING_THRESHOLD && delta_base.is_none() {
+            let (checksum, content_path, stored) = Self::hash_and_store(&path, &storage_dir, compression, hash)?;
+            let stored_size = compression::blob_size(&content_path);
+            let snapshot = Snapshot {
+                path,
+                content_path,
+                checksum,
+                date: Local::now().to_rfc3339(),
+                size,
+                message: None,
+                mtime,
+                mode,
+                symlink: None,
+                xattrs,
+                mime,
+                hash,
+                stored_size,
+            };
+            return Ok((snapshot, stored));
         }
 
-        Ok(Snapshot {
+        // Chunks and deltas are only worth computing for new contents, so
+        // the checksum comes first
+        let checksum = Self::checksum_file(&path, hash)?;
+
+        // Reuse the stored content whatever its algorithm (deduplication),
+        // otherwise compress and copy the file to storage
+        let (content_path, stored) = match compression::find_blob(&storage_dir, &checksum) {
+            Some(content_path) => (content_path, false),
+            // Large files are stored as chunks shared between their versions
+            None if size as u64 >= chunks::CHUNKING_THRESHOLD => {
+                let content_path = chunks::manifest_path(&storage_dir, &checksum);
+                chunks::store(&path, &content_path, compression)?;
+                (content_path, true)
+            }
+            None => {
+                let delta_path = delta::delta_path(&storage_dir, &checksum);
+                let as_delta = match delta_base {
+                    Some(base) => delta::store(&path, &delta_path, &base.content_path, &base.checksum)?,
+                    None => false,
+                };
+                let content_path = if as_delta {
+                    delta_path
+                } else {
+                    let content_path = compression::blob_path(&storage_dir, &checksum, compression);
+                    Self::compress_and_copy(&path, &content_path)?;
+                    content_path
+                };
+                (content_path, true)
+            }
+        };
+
+        let stored_size = compression::blob_size(&content_path);
+        let snapshot = Snapshot {
             path,
             content_path,
             checksum,
             date: Local::now().to_rfc3339(),
             size,
+            message: None,
+            mtime,
+            mode,
+            symlink: None,
+            xattrs,
+            mime,
+            hash,
+            stored_size,
+        };
+        Ok((snapshot, stored))
+    }
+
+    /// Hashes and compresses a file in a single read.
+    ///
+    /// The content is compressed to a temporary file in storage, which is
+    /// renamed after its checksum once the whole file is read, or removed if
+    /// that content is already stored.
+    ///
+    /// # Returns
+    ///
+    /// The checksum, the storage path of the content, and `true` if the
+    /// content was not stored before
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading, compression, or writing fails.
+    fn hash_and_store(
+        path: &Path,
+        storage_dir: &Path,
+        compression: Compression,
+        hash: HashAlgorithm,
+    ) -> Result<(String, PathBuf, bool)> {
+        static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+        let temp_path = storage_dir.join(format!(
+            "incoming-{}-{}.tmp",
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let result = (|| -> Result<(String, PathBuf, bool)> {
+            let mut source = HashingReader::new(fs::File::open(path)?, hash);
+            Self::write_compressed(&mut source, &temp_path, compression, crate::config::get().compression_level())?;
+            let checksum = source.checksum();
+
+            if let Some(content_path) = compression::find_blob(storage_dir, &checksum) {
+                fs::remove_file(&temp_path)?;
+                return Ok((checksum, content_path, false));
+            }
+            let content_path = compression::blob_path(storage_dir, &checksum, compression);
+            fs::rename(&temp_path, &content_path)?;
+            Ok((checksum, content_path, true))
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    /// Creates a snapshot of in-memory content recorded under `path`.
+    ///
+    /// The path does not need to exist on disk, which allows versioning
+    /// generated content such as command output.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path the content is recorded under
+    /// * `content` - The content to snapshot
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage directory cannot be created or the
+    /// content cannot be compressed.
+    pub fn from_content<P: AsRef<Path>>(path: P, content: &[u8]) -> Result<Self> {
+        let hash = crate::config::get().hash();
+        let checksum = hash.checksum(content);
+
+        let storage_dir = Self::get_storage_dir()?;
+        fs::create_dir_all(&storage_dir)?;
+
+        let content_path = match compression::find_blob(&storage_dir, &checksum) {
+            Some(content_path) => content_path,
+            None => {
+                let content_path =
+                    compression::blob_path(&storage_dir, &checksum, compression::for_path(path.as_ref()));
+                Self::compress_reader(content, &content_path, crate::config::get().compression_level())?;
+                content_path
+            }
+        };
+
+        Ok(Snapshot {
+            stored_size: compression::blob_size(&content_path),
+            path: path.as_ref().to_path_buf(),
+            content_path,
+            checksum,
+            date: Local::now().to_rfc3339(),
+            size: content.len() as i64,
+            message: None,
+            mtime: None,
+            mode: None,
+            symlink: None,
+            xattrs: None,
+            mime: Some(crate::utils::detect_mime(content)),
+            hash,
+        })
+    }
+
+    /// Creates a snapshot of a symbolic link, recording its target.
+    ///
+    /// Links have no stored content: the checksum identifies the target, and
+    /// restoring the snapshot recreates the link.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path cannot be made absolute or the link
+    /// cannot be read.
+    pub fn from_symlink<P: AsRef<Path>>(path: P) -> Result<Self> {
+        // The link itself is recorded, not what it points to
+        let path = std::path::absolute(path.as_ref())?;
+        let target = fs::read_link(&path)?;
+        let metadata = fs::symlink_metadata(&path)?;
+
+        Ok(Snapshot {
+            checksum: Self::symlink_checksum(&target),
+            path,
+            content_path: PathBuf::new(),
+            date: Local::now().to_rfc3339(),
+            size: target.as_os_str().len() as i64,
+            message: None,
+            mtime: file_mtime(&metadata),
+            mode: None,
+            symlink: Some(target),
+            xattrs: None,
+            mime: None,
+            hash: HashAlgorithm::Sha256,
+            stored_size: None,
         })
     }
 
+    /// Checksum identifying a symbolic link to `target`.
+    ///
+    /// The target is hashed with a prefix, so that a link never shares its
+    /// checksum with a file whose content is the target path. Links always
+    /// use SHA-256, whatever the `hash` setting.
+    pub fn symlink_checksum(target: &Path) -> String {
+        let mut hasher = HashAlgorithm::Sha256.hasher();
+        hasher.update(b"symlink\0");
+        hasher.update(target.as_os_str().as_encoded_bytes());
+        hasher.checksum()
+    }
+
+    /// Returns `true` if `path` is a symbolic link recorded as such, which is
+    /// the case unless the `follow_symlinks` setting is on.
+    pub fn is_recorded_symlink(path: &Path) -> bool {
+        !crate::config::get().follow_symlinks()
+            && fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+    }
+
+    /// Returns a walker over `root` that follows symbolic links if the
+    /// `follow_symlinks` setting is on, and stays on the file system of
+    /// `root` if the `one_file_system` setting is on.
+    ///
+    /// A followed link back to one of its ancestors yields an error with a
+    /// [`loop_ancestor`](walkdir::Error::loop_ancestor) instead of being
+    /// descended into.
+    pub fn walk(root: &Path) -> WalkDir {
+        let config = crate::config::get();
+        WalkDir::new(root)
+            .follow_links(config.follow_symlinks())
+            .same_file_system(config.one_file_system())
+    }
+
     /// Saves a file or directory recursively to the database.
     ///
     /// For directories, walks through all files and creates snapshots for each one,
-    /// excluding files matching exclusion patterns.
+    /// excluding files matching exclusion patterns and files above the
+    /// `max_file_size` setting. A progress bar shows the
+    /// files and bytes processed, the throughput and the remaining time.
     ///
     /// # Arguments
     ///
     /// * `path` - Path to the file or directory to save
+    /// * `message` - Optional message attached to every snapshot taken
+    /// * `keep_going` - Skip files that cannot be read instead of failing
+    /// * `force` - Record a snapshot even of files whose content matches
+    ///   their latest snapshot
     /// * `db` - Database connection to store snapshots in
     ///
+    /// # Returns
+    ///
+    /// The files and bytes saved, the files skipped with `keep_going` and
+    /// those left out for their size
+    ///
     /// # Errors
     ///
-    /// Returns an error if any file operation or database save fails.
-    pub fn save_recursive<P: AsRef<Path>>(path: P, db: &Database) -> Result<()> {
+    /// Returns an error if any file operation (unless `keep_going` is set) or
+    /// database save fails.
+    pub fn save_recursive<P: AsRef<Path>>(
+        path: P,
+        message: Option<&str>,
+        keep_going: bool,
+        force: bool,
+        db: &Database,
+    ) -> Result<SaveSummary> {
         let path = path.as_ref();
-
         if path.is_file() {
-            return Self::save_file(path, db);
+            return Self::save_recursive_with_progress(path, message, keep_going, force, db, |_| {});
         }
 
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} [{elapsed_precise}] {msg}")?,
-        );
+        let pb = crate::utils::transfer_progress_bar();
+        let summary = Self::save_recursive_with_progress(path, message, keep_going, force, db, |progress| {
+            pb.set_length(progress.bytes_total);
+            pb.set_position(progress.bytes_done);
+            pb.set_message(format!("{}/{} files", progress.files_done, progress.files_total));
+        })?;
+        pb.finish_and_clear();
+        Ok(summary)
+    }
+
+    /// Saves a file or directory like [`Snapshot::save_recursive`], reporting
+    /// progress to `on_progress` instead of drawing a progress bar.
+    ///
+    /// The eligible files are counted first, so totals are known from the
+    /// first report. `on_progress` is called once before the first file and
+    /// after every file, saved or skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any file operation (unless `keep_going` is set) or
+    /// database save fails.
+    pub fn save_recursive_with_progress<P, F>(
+        path: P,
+        message: Option<&str>,
+        keep_going: bool,
+        force: bool,
+        db: &Database,
+        mut on_progress: F,
+    ) -> Result<SaveSummary>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&SaveProgress),
+    {
+        let path = path.as_ref();
+        crate::hooks::run(Phase::Pre, Operation::Save, path, None)?;
+        let is_dir = path.is_dir();
+        let mut skipped = Vec::new();
+        let mut too_large = Vec::new();
+        let max_file_size = crate::config::get().max_file_size();
+
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        if is_dir {
+            let walker = Self::walk(path).into_iter();
+            for entry in walker.filter_entry(|e| !Self::is_excluded(e.path())) {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    // Symbolic link loops are listed, never descended into
+                    Err(e) if keep_going || e.loop_ancestor().is_some() => {
+                        let path = e.path().unwrap_or(path).to_path_buf();
+                        skipped.push(SkippedFile { path, error: e.to_string() });
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                // Links only show up here when they are recorded as links
+                if entry.file_type().is_file() || entry.file_type().is_symlink() {
+                    let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                    match max_file_size {
+                        Some(max) if size > max => too_large.push((entry.into_path(), size)),
+                        _ => files.push((entry.into_path(), size)),
+                    }
+                } else if entry.file_type().is_dir() && entry.depth() > 0 {
+                    let mode = entry.metadata().ok().and_then(|metadata| file_mode(&metadata));
+                    dirs.push((entry.into_path(), mode));
+                }
+            }
+        } else {
+            files.push((path.to_path_buf(), fs::metadata(path)?.len()));
+        }
 
-        let walker = WalkDir::new(path).into_iter();
-        for entry in walker.filter_entry(|e| !Self::is_excluded(e.path())) {
-            let entry = entry?;
-            if entry.file_type().is_file() {
-                pb.set_message(format!("Processing {}", entry.path().display()));
-                Self::save_file(entry.path(), db)?;
+        let mut progress = SaveProgress {
+            files_total: files.len(),
+            bytes_total: files.iter().map(|(_, size)| size).sum(),
+            ..SaveProgress::default()
+        };
+        on_progress(&progress);
+
+        // Files whose size and modification time match their latest
+        // snapshot are recorded again without being read
+        let latest = if crate::config::get().paranoid() || force {
+            HashMap::new()
+        } else if is_dir {
+            db.latest_snapshots_in_dir(path)?
+        } else {
+            db.get_snapshots_for_path(path)?
+                .into_iter()
+                .next()
+                .map(|latest| (latest.path.clone(), latest))
+                .into_iter()
+                .collect()
+        };
+
+        let date = Local::now().to_rfc3339();
+        let mut manifest = Vec::new();
+        for (file, size) in files {
+            let unchanged = latest
+                .get(&file)
+                .filter(|latest| fs::metadata(&file).is_ok_and(|metadata| latest.matches_metadata(&metadata)));
+            if let Some(latest) = unchanged {
+                manifest.push((latest.path.clone(), latest.checksum.clone()));
+                progress.files_done += 1;
+                progress.files_unchanged += 1;
+                progress.bytes_done += size;
+                on_progress(&progress);
+                continue;
             }
+            match Self::save_file(&file, message, force, db) {
+                Ok((snapshot, recorded)) => {
+                    manifest.push((snapshot.path, snapshot.checksum));
+                    progress.files_done += 1;
+                    if !recorded {
+                        progress.files_unchanged += 1;
+                    }
+                }
+                Err(e) if keep_going => {
+                    skipped.push(SkippedFile { path: file, error: e.to_string() });
+                    progress.files_skipped += 1;
+                }
+                Err(e) => return Err(e.context(format!("Failed to save {}", file.display()))),
+            }
+            progress.bytes_done += size;
+            on_progress(&progress);
         }
 
-        pb.finish_with_message("Done!");
-        Ok(())
+        // Record the whole directory state, unchanged files and empty
+        // directories included
+        if is_dir && !manifest.is_empty() {
+            db.create_session(&path.canonicalize()?, &date, message, &manifest, &dirs)?;
+        }
+        let checksum = manifest.first().filter(|_| !is_dir).map(|(_, checksum)| checksum.as_str());
+        crate::hooks::run(Phase::Post, Operation::Save, path, checksum)?;
+        Ok(SaveSummary {
+            progress,
+            skipped,
+            too_large,
+        })
     }
 
     /// Saves a single file to the database.
@@ -135,113 +707,805 @@ impl Snapshot {
     /// # Arguments
     ///
     /// * `path` - Path to the file
+    /// * `message` - Optional message attached to the snapshot
+    /// * `force` - Record a snapshot even if the content matches the latest
+    ///   snapshot of the path
     /// * `db` - Database connection
     ///
+    /// # Returns
+    ///
+    /// The snapshot of the file's current content, and `false` if it was
+    /// not recorded because the content matches the latest snapshot
+    ///
     /// # Errors
     ///
     /// Returns an error if snapshot creation or database save fails.
-    fn save_file<P: AsRef<Path>>(path: P, db: &Database) -> Result<()> {
-        let snapshot = Self::new(path)?;
-        db.save_snapshot(&snapshot)?;
+    fn save_file<P: AsRef<Path>>(path: P, message: Option<&str>, force: bool, db: &Database) -> Result<(Snapshot, bool)> {
+        let (snapshot, stored) = Self::store_current(path.as_ref(), message, db)?;
+        let latest = db.get_snapshots_for_path(&snapshot.path)?.into_iter().next();
+        let id = if !force && latest.is_some_and(|latest| latest.checksum == snapshot.checksum) {
+            // Only refreshes the recorded modification time and mode
+            db.save_snapshot(&snapshot)?
+        } else {
+            Some(db.insert_snapshot(&snapshot)?)
+        };
+        if let Some(id) = id {
+            Self::after_record(id, &snapshot, stored, db)?;
+        }
+        Ok((snapshot, id.is_some()))
+    }
+
+    /// Stores the current content of a file without recording a snapshot.
+    ///
+    /// # Returns
+    ///
+    /// The snapshot to record, and whether its content was newly stored
+    fn store_current(path: &Path, message: Option<&str>, db: &Database) -> Result<(Snapshot, bool)> {
+        if Self::is_recorded_symlink(path) {
+            let mut snapshot = Self::from_symlink(path)?;
+            snapshot.message = message.map(str::to_string);
+            return Ok((snapshot, false));
+        }
+        // With delta storage, new versions are patches against the latest one
+        let base = if crate::config::get().delta() {
+            let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+            db.get_snapshots_for_path(&path)?.into_iter().next()
+        } else {
+            None
+        };
+        let (mut snapshot, stored) = Self::create(path, base.as_ref())?;
+        snapshot.message = message.map(str::to_string);
+        Ok((snapshot, stored))
+    }
+
+    /// Indexes the content of the recorded snapshot `id` and records the
+    /// level of zstd contents it compressed.
+    fn after_record(id: i64, snapshot: &Snapshot, stored: bool, db: &Database) -> Result<()> {
+        if snapshot.symlink.is_some() {
+            return Ok(());
+        }
+        crate::fulltext::index_saved(snapshot, db)?;
+        if stored && Compression::from_path(&snapshot.content_path) == Some(Compression::Zstd) {
+            let level = crate::config::get().compression_level();
+            db.set_metadata(id, COMPRESSION_LEVEL_KEY, &level.to_string())?;
+        } else {
+            db.remove_metadata(id, COMPRESSION_LEVEL_KEY)?;
+        }
         Ok(())
     }
 
+    /// Saves a file unless its content matches its latest snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file
+    /// * `db` - Database connection
+    ///
+    /// # Returns
+    ///
+    /// The new snapshot, or `None` if the file was unchanged
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or the snapshot cannot be saved.
+    pub fn save_if_changed<P: AsRef<Path>>(path: P, db: &Database) -> Result<Option<Snapshot>> {
+        let path = path.as_ref();
+        let latest = db.get_snapshots_for_path(path)?.into_iter().next();
+        if let Some(latest) = &latest
+            && Self::is_unchanged(path, latest)?
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::save_file(path, None, false, db)?.0))
+    }
+
+    /// Returns `true` if the current content of `path` matches `latest`,
+    /// compared with the hash algorithm of `latest`.
+    fn is_unchanged(path: &Path, latest: &Snapshot) -> Result<bool> {
+        let checksum = if Self::is_recorded_symlink(path) {
+            Self::symlink_checksum(&fs::read_link(path)?)
+        } else {
+            Self::checksum_file(path, latest.hash)?
+        };
+        Ok(checksum == latest.checksum)
+    }
+
+    /// Replaces the latest snapshot of a file with its current content.
+    ///
+    /// The snapshot takes the new content in place, keeping its ID, its
+    /// metadata, its sessions and, unless `message` is given, its message.
+    ///
+    /// # Returns
+    ///
+    /// The checksum of the replaced snapshot and the new snapshot, or `None`
+    /// if the content and message are unchanged
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file has no snapshots, its latest snapshot is
+    /// pinned, or the file cannot be saved.
+    pub fn amend<P: AsRef<Path>>(
+        path: P,
+        message: Option<&str>,
+        db: &Database,
+    ) -> Result<Option<(String, Snapshot)>> {
+        let path = path.as_ref();
+        let (Some((id, ..)), Some(latest)) = (
+            db.get_snapshots_for_path_with_id(path)?.into_iter().next(),
+            db.get_snapshots_for_path(path)?.into_iter().next(),
+        ) else {
+            anyhow::bail!("No snapshots found for: {}", path.display());
+        };
+        if db.list_pinned()?.contains(&id) {
+            anyhow::bail!("The latest snapshot of {} is pinned, unpin it first", path.display());
+        }
+        if message.is_none_or(|message| latest.message.as_deref() == Some(message))
+            && Self::is_unchanged(path, &latest)?
+        {
+            return Ok(None);
+        }
+
+        let message = message.or(latest.message.as_deref());
+        let (snapshot, stored) = Self::store_current(path, message, db)?;
+        db.replace_snapshot(id, &snapshot)?;
+        Self::after_record(id, &snapshot, stored, db)?;
+        db.cleanup_orphaned_files()?;
+        Ok(Some((latest.checksum, snapshot)))
+    }
+
     /// Restores a file or directory from snapshots.
     ///
-    /// For directories, restores all files that have snapshots.
-    /// If multiple snapshots exist for a file, prompts the user to select one.
+    /// For directories, restores all files below it that have snapshots, so a
+    /// subdirectory of a saved tree can be restored on its own, and recreates
+    /// the directories the latest matching directory save recorded. The files
+    /// of a directory are all restored or, on failure, none of them.
+    /// If multiple snapshots exist for a file, `selection` decides which one is used.
+    ///
+    /// Files about to be overwritten are saved first in a snapshot flagged
+    /// with `pre-restore` metadata, and the restore is recorded so that
+    /// [`Snapshot::undo_restore`] can roll it back.
     ///
     /// # Arguments
     ///
     /// * `path` - Path to restore
+    /// * `to` - Optional folder to restore into instead of the original location.
+    ///   A file is written under its name, a directory's files keep their
+    ///   layout relative to `path`.
+    /// * `selection` - How to choose between several snapshots of a file
     /// * `db` - Database connection to retrieve snapshots from
     ///
     /// # Errors
     ///
     /// Returns an error if:
     /// - No snapshots are found for the path
+    /// - A checksum selection is used on a directory or matches no snapshot
+    /// - A file about to be overwritten cannot be saved
     /// - File decompression fails
     /// - File writing fails
-    pub fn restore<P: AsRef<Path>>(path: P, db: &Database) -> Result<()> {
+    pub fn restore<P: AsRef<Path>>(
+        path: P,
+        to: Option<&Path>,
+        selection: &Selection,
+        db: &Database,
+    ) -> Result<()> {
         let path = path.as_ref();
+        // Pick every version before the safety snapshots become the latest ones
+        let mut writes = Vec::new();
+        for (file_path, target) in Self::restore_files(path, to, selection, db)? {
+            let snapshot = Self::select_for_restore(&file_path, selection, db)?;
+            writes.push((file_path, target, snapshot));
+        }
+        crate::hooks::run(Phase::Pre, Operation::Restore, path, None)?;
+        Self::save_pre_restore(path, &writes, db)?;
 
         if path.is_file() {
-            return Self::restore_single(path, db);
+            let (_, target, snapshot) = &writes[0];
+            Self::restore_snapshot(snapshot, target)?;
+            return crate::hooks::run(Phase::Post, Operation::Restore, path, Some(&snapshot.checksum));
         }
 
         let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} [{elapsed_precise}] {msg}")?,
-        );
+        pb.set_style(crate::utils::spinner_style());
+        Self::restore_all(&writes, &pb)?;
 
-        let all_snapshots = db.list_directory_snapshots(path)?;
-        if all_snapshots.is_empty() {
-            anyhow::bail!("No snapshots found for directory: {}", path.display());
-        }
+        // Directories recorded by the matching directory save, empty ones included
+        let as_of = match selection {
+            Selection::AsOf(as_of) => Some(as_of),
+            _ => None,
+        };
+        let dirs: Vec<_> = crate::session::directories_at(path, as_of, db)?
+            .into_iter()
+            .map(|(dir, mode)| {
+                let target = match to {
+                    Some(root) => root.join(dir.strip_prefix(path).unwrap_or(&dir)),
+                    None => dir,
+                };
+                (target, mode)
+            })
+            .collect();
+        crate::session::create_dirs(&dirs)?;
 
-        for (file_path, _, _, _) in all_snapshots {
+        pb.finish_with_message("Directory restore completed!");
+        crate::hooks::run(Phase::Post, Operation::Restore, path, None)
+    }
+
+    /// Writes every file of a directory restore, or none of them.
+    ///
+    /// Each file is first written and verified next to its target under a
+    /// hidden staging name. Only once all of them are staged are they renamed
+    /// into place, the files they replace being moved aside until the end.
+    /// A failure at any point removes the staged files and puts the replaced
+    /// ones back, so the directory is never left half restored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be staged or moved into place.
+    fn restore_all(writes: &[(PathBuf, PathBuf, Snapshot)], pb: &ProgressBar) -> Result<()> {
+        let mut staged: Vec<(PathBuf, &Path)> = Vec::new();
+        for (file_path, target, snapshot) in writes {
             pb.set_message(format!("Restoring {}", file_path.display()));
-            Self::restore_single(&file_path, db)?;
+            let staging = side_path(target, "freeze-restore");
+            staged.push((staging.clone(), target));
+            if let Err(e) = Self::restore_snapshot(snapshot, &staging) {
+                for (staging, _) in &staged {
+                    let _ = fs::remove_file(staging);
+                }
+                return Err(e.context(format!("Failed to restore {}, no file was changed", target.display())));
+            }
         }
 
-        pb.finish_with_message("Directory restore completed!");
+        // Each target with the file it replaced, if any
+        let mut replaced: Vec<(&Path, Option<PathBuf>)> = Vec::new();
+        for (staging, target) in &staged {
+            let backup = side_path(target, "freeze-backup");
+            let replaces = fs::symlink_metadata(target).is_ok_and(|metadata| !metadata.is_dir());
+            let moved = match replaces {
+                true => fs::rename(target, &backup).and_then(|_| {
+                    fs::rename(staging, target).inspect_err(|_| {
+                        let _ = fs::rename(&backup, target);
+                    })
+                }),
+                false => fs::rename(staging, target),
+            };
+            if let Err(e) = moved {
+                for (target, backup) in replaced.iter().rev() {
+                    let _ = match backup {
+                        Some(backup) => fs::rename(backup, target),
+                        None => fs::remove_file(target),
+                    };
+                }
+                for (staging, _) in &staged {
+                    let _ = fs::remove_file(staging);
+                }
+                return Err(anyhow::Error::new(e)
+                    .context(format!("Failed to restore {}, no file was changed", target.display())));
+            }
+            replaced.push((target, replaces.then_some(backup)));
+        }
+
+        for (_, backup) in replaced {
+            if let Some(backup) = backup {
+                let _ = fs::remove_file(backup);
+            }
+        }
         Ok(())
     }
 
-    /// Restores a single file from snapshot.
+    /// Computes what a restore would do without writing anything.
+    ///
+    /// Files with several snapshots use the latest one unless the selection
+    /// names a checksum or a date.
     ///
     /// # Arguments
     ///
     /// * `path` - Path to restore
+    /// * `to` - Optional folder the restore would write into
+    /// * `selection` - How to choose between several snapshots of a file
     /// * `db` - Database connection
     ///
+    /// # Returns
+    ///
+    /// One entry per file the restore would write, sorted by path
+    ///
     /// # Errors
     ///
-    /// Returns an error if no snapshots are found or restoration fails.
-    fn restore_single<P: AsRef<Path>>(path: P, db: &Database) -> Result<()> {
-        let path = path.as_ref();
+    /// Returns the errors `restore` would fail with, or an error if a current
+    /// file cannot be read.
+    pub fn plan_restore(
+        path: &Path,
+        to: Option<&Path>,
+        selection: &Selection,
+        db: &Database,
+    ) -> Result<Vec<PlannedRestore>> {
+        let selection = match selection {
+            Selection::Prompt => &Selection::Latest,
+            selection => selection,
+        };
+
+        let mut plan = Vec::new();
+        for (file_path, target) in Self::restore_files(path, to, selection, db)? {
+            let snapshot = match selection {
+                Selection::AsOf(as_of) => db
+                    .get_snapshot_as_of(&file_path, as_of)?
+                    .ok_or_else(|| anyhow::anyhow!("No snapshots found for {}", file_path.display()))?,
+                selection => {
+                    let snapshots = db.get_snapshots_for_path(&file_path)?;
+                    crate::utils::select_snapshot(&snapshots, selection)?.clone()
+                }
+            };
+
+            let current = if target.is_file() {
+                Some(snapshot.hash_file(&target)?)
+            } else {
+                None
+            };
+            let action = match &current {
+                None => RestoreAction::Create,
+                Some(checksum) if *checksum == snapshot.checksum => RestoreAction::Unchanged,
+                Some(_) => RestoreAction::Overwrite,
+            };
+            plan.push(PlannedRestore {
+                target,
+                snapshot,
+                current,
+                action,
+            });
+        }
+        Ok(plan)
+    }
+
+    /// Lists the files a restore of `path` writes, with their destination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - No snapshots are found for the path (at the selected date)
+    /// - A checksum selection is used on a directory
+    fn restore_files(
+        path: &Path,
+        to: Option<&Path>,
+        selection: &Selection,
+        db: &Database,
+    ) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let target_of = |file: &Path| match to {
+            Some(root) => {
+                let relative = file
+                    .strip_prefix(path)
+                    .ok()
+                    .filter(|relative| !relative.as_os_str().is_empty())
+                    .or_else(|| file.file_name().map(Path::new))
+                    .unwrap_or(file);
+                root.join(relative)
+            }
+            None => file.to_path_buf(),
+        };
+
+        if path.is_file() {
+            return Ok(vec![(path.to_path_buf(), target_of(path))]);
+        }
+
+        // Any subtree of a saved directory can be restored on its own, even if
+        // it no longer exists; only files below it, component-wise, qualify
+        let all_snapshots: Vec<_> = db
+            .list_directory_snapshots(path)?
+            .into_iter()
+            .filter(|(file_path, ..)| file_path.starts_with(path))
+            .collect();
+        if all_snapshots.is_empty() {
+            anyhow::bail!("No snapshots found for directory: {}", path.display());
+        }
+        // A checksum identifies one file's version, not a directory state
+        if matches!(selection, Selection::Checksum(_))
+            && all_snapshots.iter().any(|(file_path, ..)| file_path != path)
+        {
+            anyhow::bail!("A checksum selects a single file version, use --latest for directories");
+        }
+
+        let mut files: Vec<(PathBuf, PathBuf)> = Vec::new();
+        for (file_path, _, _, _) in all_snapshots {
+            // Snapshots are ordered by path, each file is restored once
+            if files.last().is_some_and(|(last, _)| *last == file_path) {
+                continue;
+            }
+            // Files first saved after the date did not exist yet
+            if let Selection::AsOf(as_of) = selection
+                && db.get_snapshot_as_of(&file_path, as_of)?.is_none()
+            {
+                continue;
+            }
+            let target = target_of(&file_path);
+            files.push((file_path, target));
+        }
+
+        if let Selection::AsOf(as_of) = selection
+            && files.is_empty()
+        {
+            anyhow::bail!(
+                "No snapshots found for directory: {} as of {}",
+                path.display(),
+                as_of.to_rfc3339()
+            );
+        }
+        Ok(files)
+    }
+
+    /// Chooses the snapshot of `path` a restore writes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no snapshot matches the selection.
+    fn select_for_restore(path: &Path, selection: &Selection, db: &Database) -> Result<Snapshot> {
+        if let Selection::AsOf(as_of) = selection {
+            return db.get_snapshot_as_of(path, as_of)?.ok_or_else(|| {
+                anyhow::anyhow!("No snapshots found for {} as of {}", path.display(), as_of.to_rfc3339())
+            });
+        }
+
         let snapshots = db.get_snapshots_for_path(path)?;
 
         if snapshots.is_empty() {
             anyhow::bail!("No snapshots found for {}", path.display());
         }
 
-        if snapshots.len() == 1 {
-            return Self::restore_snapshot(&snapshots[0], path);
-        }
+        Ok(crate::utils::select_snapshot(&snapshots, selection)?.clone())
+    }
+
+    /// Saves the files a restore is about to change and records the restore.
+    ///
+    /// Each overwritten file gets a snapshot of its current content flagged
+    /// with the restore ID as `pre-restore` metadata. Files that already hold
+    /// the restored content are left out; nothing is recorded if no file changes.
+    ///
+    /// In a read-only repository nothing can be saved, so the restore is
+    /// refused if it would overwrite a file, and not recorded otherwise.
+    fn save_pre_restore(root: &Path, writes: &[(PathBuf, PathBuf, Snapshot)], db: &Database) -> Result<()> {
+        if crate::config::get().read_only() {
+            for (_, target, snapshot) in writes {
+                if target.is_file() && snapshot.hash_file(target)? != snapshot.checksum {
+                    anyhow::bail!(
+                        "The repository is read-only; restoring would overwrite {}",
+                        target.display()
+                    );
+                }
+            }
+            return Ok(());
+        }
+
+        let mut files = Vec::new();
+        let mut saved = Vec::new();
+        for (_, target, snapshot) in writes {
+            if !target.is_file() {
+                files.push((target.clone(), None));
+                continue;
+            }
+            if snapshot.hash_file(target)? != snapshot.checksum {
+                // Recorded with the checksum of the saved version, which may
+                // use another algorithm than the restored snapshot
+                let current = Self::save_file(target, Some("pre-restore"), false, db)?.0.checksum;
+                saved.push((target, current.clone()));
+                files.push((target.clone(), Some(current)));
+            }
+        }
+        if files.is_empty() {
+            return Ok(());
+        }
+
+        let id = db.record_restore(root, &Local::now().to_rfc3339(), &files)?;
+        for (target, checksum) in saved {
+            if let Some((snapshot_id, ..)) = db
+                .get_snapshots_for_path_with_id(target)?
+                .into_iter()
+                .find(|(.., sum)| *sum == checksum)
+            {
+                db.set_metadata(snapshot_id, "pre-restore", &id.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rolls back the most recent restore.
+    ///
+    /// Overwritten files get their pre-restore content back and files the
+    /// restore created are removed. Files changed since the restore are saved
+    /// first, so undoing never loses content.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - Database connection
+    ///
+    /// # Returns
+    ///
+    /// The restore that was undone
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - No restore has been recorded
+    /// - The pre-restore content of a file is no longer stored
+    /// - A file cannot be saved, written or removed
+    pub fn undo_restore(db: &Database) -> Result<RestoreRecord> {
+        let restore = db
+            .last_restore()?
+            .ok_or_else(|| anyhow::anyhow!("No restore to undo"))?;
+
+        for (path, checksum) in &restore.files {
+            let snapshot = match checksum {
+                Some(checksum) => Some(db.get_path_snapshot(path, checksum)?.ok_or_else(|| {
+                    anyhow::anyhow!("Content of {} before the restore is no longer stored", path.display())
+                })?),
+                None => None,
+            };
+            // Hashed with the algorithm of the content to put back
+            let current = if path.is_file() {
+                Some(match &snapshot {
+                    Some(snapshot) => snapshot.hash_file(path)?,
+                    None => Self::calculate_checksum(path)?,
+                })
+            } else {
+                None
+            };
+            if current == *checksum {
+                continue;
+            }
+            if current.is_some() {
+                Self::save_if_changed(path, db)?;
+            }
+
+            match snapshot {
+                Some(snapshot) => Self::restore_snapshot(&snapshot, path)?,
+                None => fs::remove_file(path)?,
+            }
+        }
+
+        db.delete_restore(restore.id)?;
+        Ok(restore)
+    }
+
+    /// Rewrites the contents stored as deltas as full zstd copies.
+    ///
+    /// The snapshots below `root`, or all of them, then no longer depend on
+    /// earlier versions. Delta files left unused are removed by
+    /// [`Database::cleanup_orphaned_files`].
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - Only materialize snapshots below this path
+    /// * `db` - Database connection
+    ///
+    /// # Returns
+    ///
+    /// The number of contents rewritten
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a delta cannot be rebuilt or its copy cannot be written.
+    pub fn materialize(root: Option<&Path>, db: &Database) -> Result<usize> {
+        let snapshots = db.list_all_snapshot_records()?;
+        let mut deltas: Vec<&Snapshot> = snapshots
+            .iter()
+            .filter(|snapshot| root.is_none_or(|root| snapshot.path.starts_with(root)))
+            .filter(|snapshot| delta::is_delta(&snapshot.content_path))
+            .collect();
+        deltas.sort_by(|a, b| a.content_path.cmp(&b.content_path));
+        deltas.dedup_by(|a, b| a.content_path == b.content_path);
+
+        let storage_dir = Self::get_storage_dir()?;
+        for snapshot in &deltas {
+            let content_path = compression::blob_path(&storage_dir, &snapshot.checksum, Compression::Zstd);
+            let content = compression::open(&snapshot.content_path)
+                .with_context(|| format!("Failed to rebuild {}", snapshot.path.display()))?;
+            Self::compress_reader(content, &content_path, crate::config::get().compression_level())?;
+            db.replace_content_path(&snapshot.content_path, &content_path)?;
+        }
+        Ok(deltas.len())
+    }
+
+    /// Rewrites stored contents at zstd `level`, e.g. after raising the
+    /// `compression_level` setting.
+    ///
+    /// zstd contents compressed at a lower or unknown level are recompressed,
+    /// and replaced only when the result is smaller. Legacy uncompressed
+    /// contents are migrated to zstd. Chunked contents, deltas and contents
+    /// stored with another algorithm are left as they are.
+    ///
+    /// Each content is checked against its checksum while it is rewritten,
+    /// then put in place by an atomic rename before the snapshots point at
+    /// it, so an interrupted compaction loses nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - Only compact the contents of snapshots below this path
+    /// * `level` - zstd level to compress at
+    /// * `db` - Database connection
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a content cannot be read or written, or the
+    /// database cannot be updated.
+    pub fn compact(root: Option<&Path>, level: i32, db: &Database) -> Result<CompactSummary> {
+        let snapshots = db.list_all_snapshot_records()?;
+        let mut contents: Vec<&Snapshot> = snapshots
+            .iter()
+            .filter(|snapshot| snapshot.symlink.is_none())
+            .filter(|snapshot| root.is_none_or(|root| snapshot.path.starts_with(root)))
+            .filter(|snapshot| {
+                !chunks::is_manifest(&snapshot.content_path)
+                    && !delta::is_delta(&snapshot.content_path)
+                    && matches!(Compression::from_path(&snapshot.content_path), None | Some(Compression::Zstd))
+            })
+            .collect();
+        contents.sort_by(|a, b| a.content_path.cmp(&b.content_path));
+        contents.dedup_by(|a, b| a.content_path == b.content_path);
+
+        struct TempFileGuard<'a>(&'a Path);
+        impl<'a> Drop for TempFileGuard<'a> {
+            fn drop(&mut self) {
+                let _ = fs::remove_file(self.0);
+            }
+        }
+
+        let levels = db.content_levels(COMPRESSION_LEVEL_KEY)?;
+        let storage_dir = Self::get_storage_dir()?;
+        let mut summary = CompactSummary::default();
+        for snapshot in contents {
+            let legacy = Compression::from_path(&snapshot.content_path).is_none();
+            if !legacy && levels.get(&snapshot.content_path).is_some_and(|known| *known >= level) {
+                continue;
+            }
+            // Missing contents are left to `gc`
+            let Some(before) = compression::blob_size(&snapshot.content_path) else {
+                continue;
+            };
+
+            let dest = compression::blob_path(&storage_dir, &snapshot.checksum, Compression::Zstd);
+            let temp_path = dest.with_extension("compact");
+            let _guard = TempFileGuard(&temp_path);
+            let mut source = HashingReader::new(compression::open(&snapshot.content_path)?, snapshot.hash);
+            Self::write_compressed(&mut source, &temp_path, Compression::Zstd, level)?;
+            if source.checksum() != snapshot.checksum {
+                summary.corrupt.push(snapshot.path.clone());
+                continue;
+            }
+
+            let after = fs::metadata(&temp_path)?.len() as i64;
+            if legacy || after < before {
+                fs::rename(&temp_path, &dest)?;
+                if legacy {
+                    db.replace_content_path(&snapshot.content_path, &dest)?;
+                    fs::remove_file(&snapshot.content_path)?;
+                    summary.migrated += 1;
+                } else {
+                    db.set_stored_size(&dest, after)?;
+                    summary.recompressed += 1;
+                }
+                summary.before += before;
+                summary.after += after;
+            }
+            db.set_content_metadata(&dest, COMPRESSION_LEVEL_KEY, &level.to_string())?;
+        }
+        Ok(summary)
+    }
+
+    /// Reverts a file to its previous version.
+    ///
+    /// Takes a safety snapshot of the current content first, then restores the
+    /// most recent snapshot whose content differs from it. A deleted file is
+    /// brought back from its latest snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to revert
+    /// * `db` - Database connection
+    ///
+    /// # Returns
+    ///
+    /// The snapshot that was restored
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The path is a directory
+    /// - No earlier version exists
+    /// - The safety snapshot or the restoration fails
+    pub fn undo<P: AsRef<Path>>(path: P, db: &Database) -> Result<Snapshot> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            anyhow::bail!("Undo only works on files: {}", path.display());
+        }
+
+        let current_checksum = if path.exists() {
+            Some(Self::save_file(path, None, false, db)?.0.checksum)
+        } else {
+            None
+        };
+
+        let previous = db
+            .get_snapshots_for_path(path)?
+            .into_iter()
+            .find(|snapshot| Some(&snapshot.checksum) != current_checksum.as_ref())
+            .ok_or_else(|| anyhow::anyhow!("No earlier version of {}", path.display()))?;
+
+        Self::restore_snapshot(&previous, path)?;
+        Ok(previous)
+    }
+
+    /// Reconstructs the snapshotted state of a directory in another folder.
+    ///
+    /// Each file is written from its latest snapshot, or from its latest
+    /// snapshot taken at or before `as_of`, and the directories of the
+    /// matching directory save are created. The live directory is untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The snapshotted directory
+    /// * `dest` - Folder to write the files to, keeping their relative layout
+    /// * `as_of` - Only consider snapshots taken up to this moment
+    /// * `db` - Database connection
+    ///
+    /// # Returns
+    ///
+    /// The number of files written
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - No snapshots exist under the directory (at that date)
+    /// - A file cannot be decompressed or written
+    pub fn export_directory(
+        dir: &Path,
+        dest: &Path,
+        as_of: Option<DateTime<FixedOffset>>,
+        db: &Database,
+    ) -> Result<usize> {
+        let mut exported = 0;
+        let mut last_path: Option<PathBuf> = None;
+
+        // Snapshots are ordered by path, newest first
+        for (file_path, date, _, checksum) in db.list_directory_snapshots(dir)? {
+            if last_path.as_ref() == Some(&file_path) {
+                continue;
+            }
+            let Ok(relative) = file_path.strip_prefix(dir) else {
+                continue;
+            };
+            if let Some(as_of) = as_of
+                && DateTime::parse_from_rfc3339(&date).is_ok_and(|date| date > as_of)
+            {
+                continue;
+            }
 
-        println!("\nAvailable snapshots for {}:", path.display());
-        for (i, snapshot) in snapshots.iter().enumerate() {
-            println!(
-                "{}. {} ({}) - Checksum: {}",
-                i + 1,
-                crate::utils::format_date(&snapshot.date),
-                crate::utils::format_size(snapshot.size),
-                &snapshot.checksum[..8]
-            );
+            let snapshot = db
+                .get_path_snapshot(&file_path, &checksum)?
+                .ok_or_else(|| anyhow::anyhow!("Snapshot content missing for {}", file_path.display()))?;
+            Self::restore_snapshot(&snapshot, &dest.join(relative))?;
+            exported += 1;
+            last_path = Some(file_path);
         }
 
-        let mut input = String::new();
-        print!("\nSelect snapshot number (1-{}): ", snapshots.len());
-        std::io::stdout().flush()?;
-        std::io::stdin().read_line(&mut input)?;
-
-        let selection = input
-            .trim()
-            .parse::<usize>()
-            .map_err(|_| anyhow::anyhow!("Invalid selection"))?;
-
-        if selection < 1 || selection > snapshots.len() {
-            anyhow::bail!("Invalid selection: {}", selection);
+        if exported == 0 {
+            match as_of {
+                Some(as_of) => anyhow::bail!(
+                    "No snapshots found for directory: {} as of {}",
+                    dir.display(),
+                    as_of.to_rfc3339()
+                ),
+                None => anyhow::bail!("No snapshots found for directory: {}", dir.display()),
+            }
         }
 
-        Self::restore_snapshot(&snapshots[selection - 1], path)
+        let dirs: Vec<_> = crate::session::directories_at(dir, as_of.as_ref(), db)?
+            .into_iter()
+            .map(|(path, mode)| (dest.join(path.strip_prefix(dir).unwrap_or(&path)), mode))
+            .collect();
+        crate::session::create_dirs(&dirs)?;
+        Ok(exported)
     }
 
     /// Performs the actual file restoration from a snapshot.
     ///
-    /// Handles both compressed (.zstd) and legacy uncompressed snapshots.
+    /// Handles every compression algorithm and legacy uncompressed snapshots,
+    /// and gives the file its recorded mode and modification time.
     ///
     /// # Arguments
     ///
@@ -251,23 +1515,23 @@ impl Snapshot {
     /// # Errors
     ///
     /// Returns an error if decompression or file writing fails.
-    fn restore_snapshot(snapshot: &Snapshot, path: &Path) -> Result<()> {
+    pub(crate) fn restore_snapshot(snapshot: &Snapshot, path: &Path) -> Result<()> {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
 
-        // Check if the file is compressed (has .zstd extension)
-        if snapshot.content_path.extension().and_then(|s| s.to_str()) == Some("zstd") {
-            Self::decompress_and_copy(&snapshot.content_path, path)?;
-        } else {
-            // Legacy file (not compressed)
-            fs::copy(&snapshot.content_path, path)?;
-        }
-        Ok(())
+        snapshot.export(path)
     }
 
     /// Checks if a path should be excluded based on exclusion patterns.
     ///
+    /// The repository's own files are always excluded. Include rules come
+    /// next: when there are any, a file must match one of them to be kept
+    /// (directories are always walked). Both the database
+    /// exclusions, the `.freezeignore` files of the path's parent directories
+    /// and, with the `git_aware` setting, the repository's gitignore rules
+    /// then apply.
+    ///
     /// # Arguments
     ///
     /// * `path` - The path to check
@@ -276,15 +1540,26 @@ impl Snapshot {
     ///
     /// `true` if the path matches an exclusion pattern, `false` otherwise
     pub fn is_excluded(path: &Path) -> bool {
-        let db = match Database::new() {
-            Ok(db) => db,
-            Err(_) => return false,
-        };
+        if Database::own_paths().iter().any(|own| path.starts_with(own)) {
+            return true;
+        }
 
-        let exclusions = match db.get_exclusions() {
-            Ok(excl) => excl,
-            Err(_) => return false,
-        };
+        let exclusions = Database::new()
+            .and_then(|db| db.get_exclusions())
+            .unwrap_or_default();
+
+        let includes: Vec<&str> = exclusions
+            .iter()
+            .filter(|(_, exc_type)| exc_type == "include")
+            .map(|(pattern, _)| pattern.as_str())
+            .collect();
+        if !includes.is_empty() && !path.is_dir() && !matches_include(path, &includes) {
+            return true;
+        }
+
+        if Self::is_freezeignored(path) || Self::is_gitignored(path) {
+            return true;
+        }
 
         for (pattern, exc_type) in exclusions {
             match exc_type.as_str() {
@@ -313,7 +1588,74 @@ impl Snapshot {
         false
     }
 
-    /// Calculates the SHA256 checksum of a file in chunks.
+    /// Checks the `.freezeignore` files of the parent directories of `path`.
+    ///
+    /// Files use gitignore syntax. The closest file with a matching pattern
+    /// decides, so a nested `!pattern` can re-include what a parent ignores.
+    fn is_freezeignored(path: &Path) -> bool {
+        let is_dir = path.is_dir();
+        for dir in path.ancestors().skip(1) {
+            if let Some(rules) = freezeignore_rules(dir) {
+                match rules.matched_path_or_any_parents(path, is_dir) {
+                    ignore::Match::Ignore(_) => return true,
+                    ignore::Match::Whitelist(_) => return false,
+                    ignore::Match::None => {}
+                }
+            }
+        }
+        false
+    }
+
+    /// Checks the git ignore rules of the repository `path` is in, when the
+    /// `git_aware` setting is on.
+    fn is_gitignored(path: &Path) -> bool {
+        crate::config::get().git_aware() && git_ignores(path)
+    }
+
+    /// Returns `true` if a file with `metadata` can be taken as unchanged
+    /// since this snapshot without hashing it: its size, modification time
+    /// and mode match, and the `paranoid` setting is off.
+    ///
+    /// A changed mode alone does not change the content, but the file is
+    /// then saved again so that its new mode is recorded. The same goes for
+    /// a snapshot without extended attributes while the `xattrs` setting is
+    /// on.
+    pub fn matches_metadata(&self, metadata: &fs::Metadata) -> bool {
+        let config = crate::config::get();
+        !config.paranoid()
+            && (self.xattrs.is_some() || !config.xattrs())
+            && self.symlink.is_none()
+            && self.mtime.is_some()
+            && self.size == metadata.len() as i64
+            && self.mtime == file_mtime(metadata)
+            && self.mode == file_mode(metadata)
+    }
+
+    /// Returns `true` if `path` still has the content of this snapshot,
+    /// hashing it only when its metadata does not tell.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read.
+    pub fn matches_file(&self, path: &Path) -> Result<bool> {
+        if let Some(target) = &self.symlink {
+            return Ok(fs::read_link(path).is_ok_and(|current| current == *target));
+        }
+        Ok(self.matches_metadata(&fs::metadata(path)?) || self.checksum == self.hash_file(path)?)
+    }
+
+    /// Checksums the file at `path` with the algorithm of this snapshot, so
+    /// that the result compares with its checksum.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or read.
+    pub fn hash_file(&self, path: &Path) -> Result<String> {
+        Self::checksum_file(path, self.hash)
+    }
+
+    /// Calculates the checksum of a file in chunks, with the algorithm of
+    /// the `hash` setting.
     ///
     /// Uses a 64KB buffer to avoid loading large files entirely into memory.
     ///
@@ -323,46 +1665,38 @@ impl Snapshot {
     ///
     /// # Returns
     ///
-    /// Hexadecimal string representation of the SHA256 checksum
+    /// Hexadecimal string representation of the checksum
     ///
     /// # Errors
     ///
     /// Returns an error if the file cannot be opened or read.
-    fn calculate_checksum<P: AsRef<Path>>(path: P) -> Result<String> {
-        let mut file = fs::File::open(path)?;
-        let mut hasher = Sha256::new();
-        let mut buffer = [0; 64 * 1024]; // 64KB buffer
-
-        loop {
-            let bytes_read = file.read(&mut buffer)?;
-            if bytes_read == 0 {
-                break;
-            }
-            hasher.update(&buffer[..bytes_read]);
-        }
+    pub(crate) fn calculate_checksum<P: AsRef<Path>>(path: P) -> Result<String> {
+        Self::checksum_file(path.as_ref(), crate::config::get().hash())
+    }
 
-        Ok(format!("{:x}", hasher.finalize()))
+    /// Calculates the checksum of a file with `hash`.
+    fn checksum_file(path: &Path, hash: HashAlgorithm) -> Result<String> {
+        hash.checksum_reader(fs::File::open(path)?)
     }
 
     /// Gets the storage directory path for compressed files.
     ///
     /// # Returns
     ///
-    /// Path to `~/.freeze/storage`
+    /// Path to the `storage` directory next to the database
     ///
     /// # Errors
     ///
     /// Returns an error if the home directory cannot be determined.
     fn get_storage_dir() -> Result<PathBuf> {
-        let data_dir = dirs::home_dir()
-            .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?
-            .join(".freeze/storage");
-        Ok(data_dir)
+        Database::storage_dir()
     }
 
     /// Cleans up any orphaned temporary files from the storage directory.
     ///
-    /// Removes all `.tmp` files that may have been left from interrupted operations.
+    /// Removes the `.tmp` files left from interrupted operations, in storage
+    /// and among the chunks. Files written in the last hour are kept: they
+    /// may belong to a save running in another process.
     ///
     /// # Errors
     ///
@@ -374,14 +1708,25 @@ impl Snapshot {
             return Ok(());
         }
 
-        for entry in fs::read_dir(&storage_dir)? {
-            let entry = entry?;
-            let path = entry.path();
+        let chunks_dir = storage_dir.join(chunks::CHUNKS_DIR);
+        for dir in [&storage_dir, &chunks_dir] {
+            if !dir.exists() {
+                continue;
+            }
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let path = entry.path();
 
-            if path.extension().and_then(|s| s.to_str()) == Some("tmp")
-                && let Err(e) = fs::remove_file(&path)
-            {
-                eprintln!("Warning: Failed to remove temp file {:?}: {}", path, e);
+                let stale = entry
+                    .metadata()
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age >= STALE_TEMP_AGE));
+                if path.extension().and_then(|s| s.to_str()) == Some("tmp")
+                    && stale
+                    && let Err(e) = fs::remove_file(&path)
+                {
+                    eprintln!("Warning: Failed to remove temp file {:?}: {}", path, e);
+                }
             }
         }
         Ok(())
@@ -389,7 +1734,8 @@ impl Snapshot {
 
     /// Compresses a file and copies it to storage using a temporary file.
     ///
-    /// Uses zstd compression level 3. Writes to a temporary file first,
+    /// Uses the algorithm given by the extension of `dest` and the configured
+    /// zstd compression level. Writes to a temporary file first,
     /// then atomically renames to ensure data integrity.
     ///
     /// # Arguments
@@ -401,7 +1747,33 @@ impl Snapshot {
     ///
     /// Returns an error if reading, compression, or writing fails.
     fn compress_and_copy<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dest: Q) -> Result<()> {
-        let src = src.as_ref();
+        Self::compress_and_copy_with_level(src, dest, crate::config::get().compression_level())
+    }
+
+    /// Compresses a file to `dest` using the given zstd level.
+    ///
+    /// The algorithm is given by the extension of `dest`, zstd if it has none.
+    ///
+    /// Same as `compress_and_copy`, with an explicit compression level.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading, compression, or writing fails.
+    pub(crate) fn compress_and_copy_with_level<P: AsRef<Path>, Q: AsRef<Path>>(
+        src: P,
+        dest: Q,
+        level: i32,
+    ) -> Result<()> {
+        let source_file = fs::File::open(src)?;
+        Self::compress_reader(source_file, dest, level)
+    }
+
+    /// Compresses everything read from `source` into `dest` using a temporary file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading, compression, or writing fails.
+    pub(crate) fn compress_reader<R: Read, Q: AsRef<Path>>(mut source: R, dest: Q, level: i32) -> Result<()> {
         let dest = dest.as_ref();
 
         let temp_path = dest.with_extension("tmp");
@@ -415,22 +1787,24 @@ impl Snapshot {
         }
         let _guard = TempFileGuard(&temp_path);
 
-        let mut source_file = fs::File::open(src)?;
-
-        let dest_file = fs::File::create(&temp_path)?;
-        let mut writer = std::io::BufWriter::new(dest_file);
-
-        zstd::stream::copy_encode(&mut source_file, &mut writer, 3)?;
-
-        writer.flush()?;
+        let compression = Compression::from_path(dest).unwrap_or_default();
+        Self::write_compressed(&mut source, &temp_path, compression, level)?;
 
         fs::rename(&temp_path, dest)?;
         Ok(())
     }
 
+    /// Compresses and encrypts everything read from `source` into a new file.
+    fn write_compressed<R: Read>(source: &mut R, file: &Path, compression: Compression, level: i32) -> Result<()> {
+        let dest_file = fs::File::create(file)?;
+        let writer = crate::crypto::content_writer(std::io::BufWriter::new(dest_file))?;
+        compression.compress(source, writer, level)?.finish()?.flush()?;
+        Ok(())
+    }
+
     /// Decompresses a file and copies it to the destination using a temporary file.
     ///
-    /// Reads a zstd-compressed file, decompresses it, and writes to a temporary
+    /// Reads a stored file, decompresses it, and writes to a temporary
     /// file first, then atomically renames to ensure data integrity.
     ///
     /// # Arguments
@@ -441,11 +1815,28 @@ impl Snapshot {
     /// # Errors
     ///
     /// Returns an error if reading, decompression, or writing fails.
-    fn decompress_and_copy<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dest: Q) -> Result<()> {
-        let src = src.as_ref();
-        let dest = dest.as_ref();
+    pub(crate) fn decompress_and_copy<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dest: Q) -> Result<()> {
+        Self::decompress_checked(src.as_ref(), dest.as_ref(), None)
+    }
 
-        let temp_path = dest.with_extension("tmp");
+    /// Same as `decompress_and_copy`, checking the written content against
+    /// the checksum of this snapshot before the final rename.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stored content does not match the checksum,
+    /// leaving `dest` untouched.
+    fn decompress_verified(&self, dest: &Path) -> Result<()> {
+        Self::decompress_checked(&self.content_path, dest, Some((&self.checksum, self.hash)))
+    }
+
+    /// Decompresses `src` to `dest`, comparing the content with `expected`
+    /// when given.
+    ///
+    /// The content is written to a hidden file next to `dest` first, so that
+    /// neither `dest` nor its neighbours are touched until it is complete.
+    fn decompress_checked(src: &Path, dest: &Path, expected: Option<(&str, HashAlgorithm)>) -> Result<()> {
+        let temp_path = side_path(dest, "freeze-partial");
 
         struct TempFileGuard<'a>(&'a Path);
         impl<'a> Drop for TempFileGuard<'a> {
@@ -455,25 +1846,182 @@ impl Snapshot {
         }
         let _guard = TempFileGuard(&temp_path);
 
-        let mut source_file = fs::File::open(src)?;
+        let source_file = compression::open(src)?;
+        let mut source = HashingReader::new(source_file, expected.map_or_else(HashAlgorithm::default, |(_, hash)| hash));
         let dest_file = fs::File::create(&temp_path)?;
         let mut writer = std::io::BufWriter::new(dest_file);
 
-        zstd::stream::copy_decode(&mut source_file, &mut writer)?;
+        std::io::copy(&mut source, &mut writer)?;
 
         writer.flush()?;
 
+        if let Some((checksum, _)) = expected {
+            let actual = source.checksum();
+            if actual != checksum {
+                anyhow::bail!(
+                    "Stored content {} is corrupt: restoring {} would give checksum {} instead of {}",
+                    src.display(),
+                    dest.display(),
+                    actual,
+                    checksum
+                );
+            }
+        }
+
         fs::rename(&temp_path, dest)?;
         Ok(())
     }
 
+    /// Checks that the stored content still matches the snapshot checksum.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the content is intact, `false` if it is missing or corrupt
+    pub fn verify(&self) -> bool {
+        if let Some(target) = &self.symlink {
+            return self.checksum == Self::symlink_checksum(target);
+        }
+        let checksum = compression::open(&self.content_path)
+            .and_then(|source| self.hash.checksum_reader(source))
+            .ok();
+        checksum.as_ref() == Some(&self.checksum)
+    }
+
+    /// Reads a range of lines of the content.
+    ///
+    /// Content is decompressed as a stream: reading stops after the last
+    /// requested line, and a tail keeps only the lines it returns in memory.
+    ///
+    /// # Returns
+    ///
+    /// The number of the first returned line and the lines, without their
+    /// line endings. Invalid UTF-8 is replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content cannot be read or is binary.
+    pub fn read_lines(&self, range: LineRange) -> Result<(usize, Vec<String>)> {
+        let mut reader = std::io::BufReader::new(self.open_content()?);
+        if self.is_binary(std::io::BufRead::fill_buf(&mut reader)?) {
+            anyhow::bail!("{} is binary", self.path.display());
+        }
+
+        let (first, last) = match range {
+            LineRange::Lines(first, last) => (first, last),
+            LineRange::Head(count) => (1, Some(count)),
+            LineRange::Tail(_) => (1, None),
+        };
+        let mut lines = std::collections::VecDeque::new();
+        let mut number = 0;
+        let mut buffer = Vec::new();
+        while last.is_none_or(|last| number < last) {
+            buffer.clear();
+            if std::io::BufRead::read_until(&mut reader, b'\n', &mut buffer)? == 0 {
+                break;
+            }
+            number += 1;
+            if number < first {
+                continue;
+            }
+            let line = String::from_utf8_lossy(&buffer);
+            lines.push_back(line.trim_end_matches(['\n', '\r']).to_string());
+            if let LineRange::Tail(count) = range
+                && lines.len() > count
+            {
+                lines.pop_front();
+            }
+        }
+
+        let first = match range {
+            LineRange::Tail(_) => number + 1 - lines.len(),
+            _ => first,
+        };
+        Ok((first, lines.into()))
+    }
+
+    /// Finds the lines of the content `matches` accepts.
+    ///
+    /// Content is decompressed as a stream, one line at a time. Binary
+    /// content has no lines and matches nothing.
+    ///
+    /// # Returns
+    ///
+    /// The number and text of each matching line, without its line ending.
+    /// Invalid UTF-8 is replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content cannot be read.
+    pub fn find_lines(&self, matches: impl Fn(&str) -> bool) -> Result<Vec<(usize, String)>> {
+        let mut reader = std::io::BufReader::new(self.open_content()?);
+        if self.is_binary(std::io::BufRead::fill_buf(&mut reader)?) {
+            return Ok(Vec::new());
+        }
+
+        let mut found = Vec::new();
+        let mut number = 0;
+        let mut buffer = Vec::new();
+        loop {
+            buffer.clear();
+            if std::io::BufRead::read_until(&mut reader, b'\n', &mut buffer)? == 0 {
+                break;
+            }
+            number += 1;
+            let line = String::from_utf8_lossy(&buffer);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if matches(line) {
+                found.push((number, line.to_string()));
+            }
+        }
+        Ok(found)
+    }
+
+    /// Returns `true` if the content of this snapshot cannot be shown as
+    /// text.
+    ///
+    /// The type detected at save time decides; snapshots saved before types
+    /// were recorded fall back to looking for null bytes in `content`.
+    pub fn is_binary(&self, content: &[u8]) -> bool {
+        match &self.mime {
+            Some(mime) => !crate::utils::is_text_mime(mime),
+            None => crate::utils::is_binary(content),
+        }
+    }
+
     pub fn get_decompressed_content(&self) -> Result<Vec<u8>> {
-        let mut source_file = fs::File::open(&self.content_path)?;
         let mut buffer = Vec::new();
-        zstd::stream::copy_decode(&mut source_file, &mut buffer)?;
+        self.open_content()?.read_to_end(&mut buffer)?;
         Ok(buffer)
     }
 
+    /// Streams the decompressed content to `writer`, byte for byte.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes written
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content cannot be read or written, or if it
+    /// does not match the snapshot checksum once written.
+    pub fn write_content(&self, writer: &mut impl Write) -> Result<u64> {
+        let mut source = HashingReader::new(self.open_content()?, self.hash);
+        let written = std::io::copy(&mut source, writer)?;
+        writer.flush()?;
+        if source.checksum() != self.checksum {
+            anyhow::bail!("Stored content of {} is corrupt", self.path.display());
+        }
+        Ok(written)
+    }
+
+    /// Opens the stored content, which symbolic links do not have.
+    pub(crate) fn open_content(&self) -> Result<Box<dyn Read + Send>> {
+        if let Some(target) = &self.symlink {
+            anyhow::bail!("{} is a symbolic link to {}", self.path.display(), target.display());
+        }
+        compression::open(&self.content_path)
+    }
+
     /// Reads the first `limit` bytes of decompressed content.
     ///
     /// # Arguments
@@ -484,8 +2032,7 @@ impl Snapshot {
     ///
     /// A vector containing up to `limit` bytes of decompressed content.
     pub fn peek_decompressed_content(&self, limit: usize) -> Result<Vec<u8>> {
-        let mut source_file = fs::File::open(&self.content_path)?;
-        let mut decoder = zstd::stream::Decoder::new(&mut source_file)?;
+        let mut decoder = self.open_content()?;
         let mut buffer = vec![0; limit];
         let mut bytes_read = 0;
 
@@ -503,14 +2050,506 @@ impl Snapshot {
 
     /// Exports the snapshot to a destination path using streaming.
     ///
+    /// The file gets the mode and modification time it had when it was
+    /// saved, unless the `restore_times` setting is off. A symbolic link is
+    /// recreated, replacing any file at `dest`.
+    ///
     /// # Arguments
     ///
     /// * `dest` - Destination path
     ///
     /// # Errors
     ///
-    /// Returns an error if reading, decompression, or writing fails.
+    /// Returns an error if reading, decompression, or writing fails, or if
+    /// the mode or modification time cannot be set.
     pub fn export(&self, dest: &Path) -> Result<()> {
-        Self::decompress_and_copy(&self.content_path, dest)
+        if let Some(target) = &self.symlink {
+            return create_symlink(target, dest);
+        }
+        self.decompress_verified(dest)?;
+        self.apply_metadata(dest)
+    }
+
+    /// Gives `path` the recorded mode and extended attributes of this
+    /// snapshot, and its modification time unless the `restore_times` setting
+    /// is off.
+    ///
+    /// Attributes that cannot be set (some need privileges) only print a
+    /// warning.
+    fn apply_metadata(&self, path: &Path) -> Result<()> {
+        if let Some(xattrs) = &self.xattrs {
+            for (name, e) in xattrs::apply(path, xattrs) {
+                eprintln!("Warning: Failed to set {} on {:?}: {}", name, path, e);
+            }
+        }
+        if let Some(mtime) = self.mtime
+            && crate::config::get().restore_times()
+        {
+            let time = filetime::FileTime::from_unix_time(
+                mtime.div_euclid(1_000_000_000),
+                mtime.rem_euclid(1_000_000_000) as u32,
+            );
+            filetime::set_file_mtime(path, time)
+                .with_context(|| format!("Failed to set the modification time of {}", path.display()))?;
+        }
+        #[cfg(unix)]
+        if let Some(mode) = self.mode {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))
+                .with_context(|| format!("Failed to set the mode of {}", path.display()))?;
+        }
+        #[cfg(not(unix))]
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// Reader that hashes what it reads, so that a file can be checksummed while
+/// it is compressed.
+struct HashingReader<R> {
+    inner: R,
+    hasher: Hasher,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R, hash: HashAlgorithm) -> Self {
+        Self {
+            inner,
+            hasher: hash.hasher(),
+        }
+    }
+
+    /// Hexadecimal checksum of everything read so far.
+    fn checksum(&self) -> String {
+        self.hasher.checksum()
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Hidden path next to `path` for a temporary copy of it, such as
+/// `dir/.name.suffix`.
+fn side_path(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+/// Creates a symbolic link to `target` at `dest`, replacing a file there.
+fn create_symlink(target: &Path, dest: &Path) -> Result<()> {
+    if fs::symlink_metadata(dest).is_ok_and(|metadata| !metadata.is_dir()) {
+        fs::remove_file(dest)?;
+    }
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, dest)
+            .with_context(|| format!("Failed to create symbolic link {}", dest.display()))?;
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = target;
+        anyhow::bail!("Symbolic links can only be restored on Unix: {}", dest.display())
+    }
+}
+
+/// Permission bits of a file, on platforms with Unix modes.
+pub(crate) fn file_mode(metadata: &fs::Metadata) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode() & 0o7777)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Modification time of a file in nanoseconds since the Unix epoch, if the
+/// platform reports it.
+pub(crate) fn file_mtime(metadata: &fs::Metadata) -> Option<i64> {
+    let modified = metadata.modified().ok()?;
+    let nanos = match modified.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(after) => i64::try_from(after.as_nanos()).ok()?,
+        Err(before) => -i64::try_from(before.duration().as_nanos()).ok()?,
+    };
+    Some(nanos)
+}
+
+/// Detects the MIME type of the file at `path` from its first bytes.
+fn sniff_mime(path: &Path) -> Result<String> {
+    let mut sample = Vec::with_capacity(crate::utils::MIME_SAMPLE_SIZE);
+    fs::File::open(path)?
+        .take(crate::utils::MIME_SAMPLE_SIZE as u64)
+        .read_to_end(&mut sample)?;
+    Ok(crate::utils::detect_mime(&sample))
+}
+
+/// Returns `true` if the file name of `path` matches one of the include
+/// `patterns` (globs such as `*.rs`, in gitignore syntax).
+fn matches_include(path: &Path, patterns: &[&str]) -> bool {
+    let Some(name) = path.file_name() else {
+        return false;
+    };
+    let mut builder = GitignoreBuilder::new("");
+    for pattern in patterns {
+        // Invalid globs match nothing
+        let _ = builder.add_line(None, pattern);
+    }
+    builder
+        .build()
+        .is_ok_and(|rules| rules.matched(Path::new(name), false).is_ignore())
+}
+
+/// Name of the per-directory ignore file.
+pub const FREEZEIGNORE_FILE: &str = ".freezeignore";
+
+/// Parsed ignore files by path, with the modification time they were read at.
+type IgnoreCache = HashMap<PathBuf, (Option<SystemTime>, Option<Arc<Gitignore>>)>;
+
+/// Returns the rules of the `.freezeignore` file in `dir`, if there is one.
+fn freezeignore_rules(dir: &Path) -> Option<Arc<Gitignore>> {
+    ignore_file_rules(dir, &dir.join(FREEZEIGNORE_FILE))
+}
+
+/// Returns the rules of the ignore file `file`, relative to `dir`, if it
+/// exists.
+///
+/// Parsed files are cached and re-read when they change, which keeps
+/// directory walks and long-running watches cheap.
+fn ignore_file_rules(dir: &Path, file: &Path) -> Option<Arc<Gitignore>> {
+    static CACHE: OnceLock<Mutex<IgnoreCache>> = OnceLock::new();
+
+    let modified = fs::metadata(file).and_then(|m| m.modified()).ok();
+
+    let mut cache = CACHE.get_or_init(Default::default).lock().unwrap();
+    if let Some((cached_modified, rules)) = cache.get(file)
+        && *cached_modified == modified
+    {
+        return rules.clone();
+    }
+
+    let rules = modified.and_then(|_| {
+        let mut builder = GitignoreBuilder::new(dir);
+        builder.add(file);
+        builder.build().ok().map(Arc::new)
+    });
+    cache.insert(file.to_path_buf(), (modified, rules.clone()));
+    rules
+}
+
+/// Returns the rules of the user's global git excludes file
+/// (`core.excludesFile`).
+fn global_gitignore() -> &'static Gitignore {
+    static GLOBAL: OnceLock<Gitignore> = OnceLock::new();
+    GLOBAL.get_or_init(|| GitignoreBuilder::new("").build_global().0)
+}
+
+/// Returns `true` if git ignores `path` in the repository it is in.
+///
+/// The `.gitignore` files of the parent directories up to the repository
+/// root come first (the closest one deciding), then `.git/info/exclude` and
+/// the global excludes file. The `.git` directory itself is ignored too.
+/// Paths outside a repository are not.
+fn git_ignores(path: &Path) -> bool {
+    if path.file_name().is_some_and(|name| name == ".git") {
+        return true;
+    }
+    let Some(repository) = path.ancestors().skip(1).find(|dir| dir.join(".git").exists()) else {
+        return false;
+    };
+    let is_dir = path.is_dir();
+    let mut files: Vec<(&Path, PathBuf)> = path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(repository))
+        .map(|dir| (dir, dir.join(".gitignore")))
+        .collect();
+    files.push((repository, repository.join(".git").join("info").join("exclude")));
+    for (dir, file) in files {
+        if let Some(rules) = ignore_file_rules(dir, &file) {
+            match rules.matched_path_or_any_parents(path, is_dir) {
+                ignore::Match::Ignore(_) => return true,
+                ignore::Match::Whitelist(_) => return false,
+                ignore::Match::None => {}
+            }
+        }
+    }
+    let relative = path.strip_prefix(repository).unwrap_or(path);
+    global_gitignore().matched_path_or_any_parents(relative, is_dir).is_ignore()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_matches_include() {
+        let patterns = ["*.rs", "Cargo.toml"];
+        assert!(matches_include(Path::new("/project/src/main.rs"), &patterns));
+        assert!(matches_include(Path::new("/project/Cargo.toml"), &patterns));
+        assert!(!matches_include(Path::new("/project/README.md"), &patterns));
+        assert!(!matches_include(Path::new("/project/src/main.rs.orig"), &patterns));
+    }
+
+    #[test]
+    fn test_write_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let content = b"raw\x00bytes\r\n";
+        let content_path = temp_dir.path().join("content.zstd");
+        Snapshot::compress_reader(&content[..], &content_path, 3).unwrap();
+        let mut snapshot = Snapshot {
+            path: PathBuf::from("/test/file.bin"),
+            content_path,
+            checksum: HashAlgorithm::Sha256.checksum(content),
+            date: String::new(),
+            size: content.len() as i64,
+            message: None,
+            mtime: None,
+            mode: None,
+            symlink: None,
+            xattrs: None,
+            mime: None,
+            hash: HashAlgorithm::Sha256,
+            stored_size: None,
+        };
+
+        let mut out = Vec::new();
+        assert_eq!(snapshot.write_content(&mut out).unwrap(), content.len() as u64);
+        assert_eq!(out, content);
+
+        snapshot.checksum = "0".repeat(64);
+        assert!(snapshot.write_content(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_read_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let content: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        let content_path = temp_dir.path().join("content.zstd");
+        Snapshot::compress_reader(content.as_bytes(), &content_path, 3).unwrap();
+        let snapshot = Snapshot {
+            path: PathBuf::from("/test/file.txt"),
+            content_path,
+            checksum: String::new(),
+            date: String::new(),
+            size: content.len() as i64,
+            message: None,
+            mtime: None,
+            mode: None,
+            symlink: None,
+            xattrs: None,
+            mime: None,
+            hash: HashAlgorithm::Sha256,
+            stored_size: None,
+        };
+
+        let (first, lines) = snapshot.read_lines(LineRange::parse("3:4").unwrap()).unwrap();
+        assert_eq!((first, lines), (3, vec!["line 3".to_string(), "line 4".to_string()]));
+        assert_eq!(snapshot.read_lines(LineRange::Head(2)).unwrap().1, ["line 1", "line 2"]);
+        assert_eq!(snapshot.read_lines(LineRange::Tail(2)).unwrap(), (9, vec!["line 9".to_string(), "line 10".to_string()]));
+        assert_eq!(snapshot.read_lines(LineRange::parse("9:").unwrap()).unwrap().1.len(), 2);
+        assert!(snapshot.read_lines(LineRange::parse("20:30").unwrap()).unwrap().1.is_empty());
+
+        let found = snapshot.find_lines(|line| line.ends_with('1')).unwrap();
+        assert_eq!(found, [(1, "line 1".to_string())]);
+        assert_eq!(snapshot.find_lines(|line| line.contains("line 1")).unwrap().len(), 2);
+
+        assert!(LineRange::parse("5:2").is_err());
+        assert!(LineRange::parse("0:2").is_err());
+        assert!(LineRange::parse("abc").is_err());
+    }
+
+    #[test]
+    fn test_export_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let content_path = temp_dir.path().join("content.zstd");
+        Snapshot::compress_reader(&b"#!/bin/sh\n"[..], &content_path, 3).unwrap();
+        let mut snapshot = Snapshot {
+            path: PathBuf::from("/test/run.sh"),
+            content_path,
+            checksum: HashAlgorithm::Sha256.checksum(b"#!/bin/sh\n"),
+            date: String::new(),
+            size: 10,
+            message: None,
+            mtime: Some(1_600_000_000_250_000_000),
+            mode: Some(0o750),
+            symlink: None,
+            xattrs: None,
+            mime: None,
+            hash: HashAlgorithm::Sha256,
+            stored_size: None,
+        };
+
+        let dest = temp_dir.path().join("run.sh");
+        snapshot.export(&dest).unwrap();
+        let metadata = fs::metadata(&dest).unwrap();
+        assert_eq!(file_mtime(&metadata), snapshot.mtime);
+        #[cfg(unix)]
+        assert_eq!(file_mode(&metadata), snapshot.mode);
+
+        // Content that does not match the checksum is never put in place
+        snapshot.checksum = HashAlgorithm::Sha256.checksum(b"other");
+        fs::write(&dest, b"current").unwrap();
+        assert!(snapshot.export(&dest).is_err());
+        assert_eq!(fs::read(&dest).unwrap(), b"current");
+        assert!(!side_path(&dest, "freeze-partial").exists());
+
+        // Files named like a temporary copy are neither used nor removed
+        snapshot.checksum = HashAlgorithm::Sha256.checksum(b"#!/bin/sh\n");
+        let sibling = temp_dir.path().join("run.tmp");
+        fs::write(&sibling, b"sibling").unwrap();
+        snapshot.export(&dest).unwrap();
+        assert_eq!(fs::read(&sibling).unwrap(), b"sibling");
+        let dest = temp_dir.path().join("notes.tmp");
+        snapshot.export(&dest).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"#!/bin/sh\n");
+    }
+
+    #[test]
+    fn test_restore_all_or_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let stored = |name: &str, content: &[u8]| {
+            let content_path = temp_dir.path().join(format!("{}.zstd", name));
+            Snapshot::compress_reader(content, &content_path, 3).unwrap();
+            Snapshot {
+                path: PathBuf::from(name),
+                content_path,
+                checksum: HashAlgorithm::Sha256.checksum(content),
+                date: String::new(),
+                size: content.len() as i64,
+                message: None,
+                mtime: None,
+                mode: None,
+                symlink: None,
+                xattrs: None,
+                mime: None,
+                hash: HashAlgorithm::Sha256,
+                stored_size: None,
+            }
+        };
+        let first = temp_dir.path().join("dir/first.txt");
+        let second = temp_dir.path().join("dir/second.txt");
+        fs::create_dir_all(first.parent().unwrap()).unwrap();
+        fs::write(&first, b"current").unwrap();
+
+        let mut corrupt = stored("second", b"second");
+        corrupt.checksum = HashAlgorithm::Sha256.checksum(b"other");
+        let writes = vec![
+            (first.clone(), first.clone(), stored("first", b"first")),
+            (second.clone(), second.clone(), corrupt),
+        ];
+        let pb = ProgressBar::hidden();
+        assert!(Snapshot::restore_all(&writes, &pb).is_err());
+        assert_eq!(fs::read(&first).unwrap(), b"current");
+        assert!(!second.exists());
+        assert_eq!(fs::read_dir(first.parent().unwrap()).unwrap().count(), 1);
+
+        let writes = vec![
+            (first.clone(), first.clone(), stored("first", b"first")),
+            (second.clone(), second.clone(), stored("second", b"second")),
+        ];
+        Snapshot::restore_all(&writes, &pb).unwrap();
+        assert_eq!(fs::read(&first).unwrap(), b"first");
+        assert_eq!(fs::read(&second).unwrap(), b"second");
+        assert_eq!(fs::read_dir(first.parent().unwrap()).unwrap().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let link = temp_dir.path().join("link");
+        std::os::unix::fs::symlink("missing/target", &link).unwrap();
+
+        let snapshot = Snapshot::from_symlink(&link).unwrap();
+        assert_eq!(snapshot.symlink.as_deref(), Some(Path::new("missing/target")));
+        assert_eq!(snapshot.checksum, Snapshot::symlink_checksum(Path::new("missing/target")));
+        assert!(snapshot.matches_file(&link).unwrap());
+
+        // Restoring replaces whatever is at the destination with the link
+        let dest = temp_dir.path().join("restored");
+        fs::write(&dest, b"file").unwrap();
+        snapshot.export(&dest).unwrap();
+        assert_eq!(fs::read_link(&dest).unwrap(), Path::new("missing/target"));
+        assert!(!snapshot.matches_file(&temp_dir.path().join("missing")).unwrap());
+    }
+
+    #[test]
+    fn test_hashing_reader() {
+        let temp_dir = TempDir::new().unwrap();
+        let content = b"freeze freeze freeze".repeat(10_000);
+        let source = temp_dir.path().join("source.txt");
+        fs::write(&source, &content).unwrap();
+
+        for hash in HashAlgorithm::ALL {
+            let mut reader = HashingReader::new(fs::File::open(&source).unwrap(), hash);
+            let compressed = Compression::Zstd.compress(&mut reader, Vec::new(), 3).unwrap();
+            assert_eq!(reader.checksum(), Snapshot::checksum_file(&source, hash).unwrap());
+            assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn test_freezeignore_rules() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("build/keep")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join(FREEZEIGNORE_FILE), "*.log\nbuild/\n").unwrap();
+        fs::write(root.join("src").join(FREEZEIGNORE_FILE), "!important.log\n").unwrap();
+
+        assert!(Snapshot::is_freezeignored(&root.join("debug.log")));
+        assert!(Snapshot::is_freezeignored(&root.join("build")));
+        assert!(Snapshot::is_freezeignored(&root.join("build/keep/a.txt")));
+        assert!(Snapshot::is_freezeignored(&root.join("src/other.log")));
+        assert!(!Snapshot::is_freezeignored(&root.join("src/important.log")));
+        assert!(!Snapshot::is_freezeignored(&root.join("src/main.rs")));
+    }
+
+    #[test]
+    fn test_git_ignores() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(root.join("src/.gitignore"), "!keep.log\n").unwrap();
+        fs::write(root.join(".git/info/exclude"), "notes.txt\n").unwrap();
+
+        assert!(git_ignores(&root.join(".git")));
+        assert!(git_ignores(&root.join("target")));
+        assert!(git_ignores(&root.join("target/debug/app")));
+        assert!(git_ignores(&root.join("debug.log")));
+        assert!(git_ignores(&root.join("notes.txt")));
+        assert!(!git_ignores(&root.join("src/keep.log")));
+        assert!(!git_ignores(&root.join("src/main.rs")));
+        // Outside a repository, .gitignore files do not apply
+        fs::remove_dir_all(root.join(".git")).unwrap();
+        assert!(!git_ignores(&root.join("debug.log")));
+    }
+
+    #[test]
+    fn test_save_skips_repository() {
+        // The repository lives in a directory that is itself saved
+        let temp_dir = TempDir::new().unwrap();
+        Database::set_home(&temp_dir.path().join("repo")).unwrap();
+        let db = Database::new().unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), b"notes").unwrap();
+
+        Snapshot::save_recursive_with_progress(temp_dir.path(), None, false, false, &db, |_| {}).unwrap();
+        Snapshot::save_recursive_with_progress(temp_dir.path(), None, false, false, &db, |_| {}).unwrap();
+        let paths: Vec<PathBuf> = db.list_all_snapshots().unwrap().into_iter().map(|(_, path, ..)| path).collect();
+        assert_eq!(paths, [temp_dir.path().join("notes.txt")]);
+        assert!(Snapshot::is_excluded(&temp_dir.path().join("repo/data.sql-wal")));
     }
 }
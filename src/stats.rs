@@ -0,0 +1,377 @@
+/*!
+Storage statistics for the freeze application.
+
+Summarizes what the store holds: how many snapshots and distinct contents
+it keeps, how much deduplication and compression save, and which paths and
+directories use the most space. Statistics render as tables or as JSON.
+
+The deduplication report (`freeze stats --dedup`) details the savings: how
+many snapshots share each stored content, what compression saves on top, and
+which paths reuse the most content.
+
+The directory breakdown (`freeze stats --by-dir`) totals, in the database,
+the snapshots below each directory of a root: their number, the files they
+cover, the newest one and their size.
+*/
+
+use crate::db::{Database, DirectoryTotals};
+use crate::i18n::t;
+use crate::utils::{format_date, format_ratio, format_size, style_table};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tabled::builder::Builder;
+
+/// Space used by the snapshots of one path or directory.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Usage {
+    /// The file or directory
+    pub path: String,
+    /// Number of snapshots
+    pub snapshots: usize,
+    /// Sum of the original sizes of the snapshots, in bytes
+    pub size: i64,
+}
+
+/// Storage usage of the whole store.
+#[derive(Serialize, Debug)]
+pub struct Stats {
+    /// Number of snapshots
+    pub snapshots: usize,
+    /// Number of distinct paths with snapshots
+    pub paths: usize,
+    /// Number of distinct content blobs in storage
+    pub blobs: usize,
+    /// Sum of the original sizes of every snapshot, in bytes
+    pub logical_size: i64,
+    /// Sum of the original sizes of the distinct contents, in bytes
+    pub unique_size: i64,
+    /// Bytes used by the compressed contents on disk
+    pub stored_size: i64,
+    /// How many times smaller the distinct contents are once compressed
+    pub compression_ratio: Option<f64>,
+    /// Bytes saved by storing identical contents once
+    pub dedup_savings: i64,
+    /// Paths using the most space, largest first
+    pub top_paths: Vec<Usage>,
+    /// Directories using the most space, largest first
+    pub directories: Vec<Usage>,
+}
+
+/// Savings of deduplication and compression.
+#[derive(Serialize, Debug)]
+pub struct Dedup {
+    /// Number of snapshots referencing a stored content
+    pub references: usize,
+    /// Number of distinct content blobs in storage
+    pub blobs: usize,
+    /// Sum of the original sizes of every snapshot, in bytes
+    pub logical_size: i64,
+    /// Sum of the original sizes of the distinct contents, in bytes
+    pub unique_size: i64,
+    /// Bytes used by the compressed contents on disk
+    pub stored_size: i64,
+    /// Bytes saved by storing identical contents once
+    pub dedup_savings: i64,
+    /// Bytes saved by compressing the distinct contents
+    pub compression_savings: i64,
+    /// How many times smaller the distinct contents are once compressed
+    pub compression_ratio: Option<f64>,
+    /// Paths reusing the most stored content: deduplicated snapshots and
+    /// bytes not stored again, largest first
+    pub top_paths: Vec<Usage>,
+}
+
+/// Computes the storage statistics.
+///
+/// # Arguments
+///
+/// * `db` - Database connection
+/// * `top` - Number of paths and directories to list
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub fn build(db: &Database, top: usize) -> Result<Stats> {
+    let path_totals = db.path_totals()?;
+    let blobs = db.content_blobs()?;
+
+    let logical_size = path_totals.iter().map(|(_, _, size)| size).sum();
+    let unique_size = blobs.iter().map(|(_, size)| size).sum();
+    // Chunks shared between contents are counted once
+    let stored_files: HashSet<PathBuf> = blobs
+        .iter()
+        .flat_map(|(content_path, _)| crate::compression::stored_files(content_path))
+        .collect();
+    let stored_size = stored_files
+        .iter()
+        .map(|file| fs::metadata(file).map(|m| m.len()).unwrap_or(0) as i64)
+        .sum();
+
+    Ok(Stats {
+        snapshots: path_totals.iter().map(|(_, count, _)| count).sum(),
+        paths: path_totals.len(),
+        blobs: blobs.len(),
+        logical_size,
+        unique_size,
+        stored_size,
+        compression_ratio: ratio(unique_size, stored_size),
+        dedup_savings: logical_size - unique_size,
+        top_paths: path_totals
+            .iter()
+            .take(top)
+            .map(|(path, snapshots, size)| Usage {
+                path: path.display().to_string(),
+                snapshots: *snapshots,
+                size: *size,
+            })
+            .collect(),
+        directories: by_directory(&path_totals, top),
+    })
+}
+
+/// Computes the deduplication report.
+///
+/// Contents saved before their size on disk was recorded are measured
+/// first, and their size recorded.
+///
+/// # Arguments
+///
+/// * `db` - Database connection
+/// * `top` - Number of paths to list
+///
+/// # Errors
+///
+/// Returns an error if a database query or update fails.
+pub fn build_dedup(db: &Database, top: usize) -> Result<Dedup> {
+    for content_path in db.unmeasured_blobs()? {
+        if let Some(stored_size) = crate::compression::blob_size(&content_path) {
+            db.set_stored_size(&content_path, stored_size)?;
+        }
+    }
+
+    let (references, blobs, logical_size, unique_size, stored_size) = db.dedup_totals()?;
+    Ok(Dedup {
+        references,
+        blobs,
+        logical_size,
+        unique_size,
+        stored_size,
+        dedup_savings: logical_size - unique_size,
+        compression_savings: unique_size - stored_size,
+        compression_ratio: ratio(unique_size, stored_size),
+        top_paths: db
+            .dedup_paths(top)?
+            .into_iter()
+            .map(|(path, snapshots, size)| Usage {
+                path: path.display().to_string(),
+                snapshots,
+                size,
+            })
+            .collect(),
+    })
+}
+
+/// Sums path totals per parent directory, largest first.
+fn by_directory(path_totals: &[(PathBuf, usize, i64)], top: usize) -> Vec<Usage> {
+    let mut directories: BTreeMap<&Path, (usize, i64)> = BTreeMap::new();
+    for (path, snapshots, size) in path_totals {
+        let entry = directories
+            .entry(path.parent().unwrap_or(path))
+            .or_default();
+        entry.0 += snapshots;
+        entry.1 += size;
+    }
+
+    let mut usage: Vec<Usage> = directories
+        .into_iter()
+        .map(|(path, (snapshots, size))| Usage {
+            path: path.display().to_string(),
+            snapshots,
+            size,
+        })
+        .collect();
+    usage.sort_by_key(|entry| std::cmp::Reverse(entry.size));
+    usage.truncate(top);
+    usage
+}
+
+/// Renders a list of usages as a table.
+fn usage_table(usage: &[Usage], columns: [String; 3]) -> String {
+    let mut builder = Builder::default();
+    builder.push_record(columns);
+    for entry in usage {
+        builder.push_record([
+            entry.path.clone(),
+            entry.snapshots.to_string(),
+            format_size(entry.size),
+        ]);
+    }
+    style_table(&mut builder.build()).to_string()
+}
+
+/// How many times smaller `stored_size` is than `size`, if anything is stored.
+fn ratio(size: i64, stored_size: i64) -> Option<f64> {
+    (stored_size > 0).then(|| size as f64 / stored_size as f64)
+}
+
+/// Formats `part` as a percentage of `whole`, empty when `whole` is zero.
+fn percent(part: i64, whole: i64) -> String {
+    if whole > 0 {
+        format!(" ({:.0}%)", part as f64 * 100.0 / whole as f64)
+    } else {
+        String::new()
+    }
+}
+
+/// Renders the statistics as a summary followed by tables.
+pub fn render_text(stats: &Stats) -> String {
+    let lines = [
+        (t!("stats-snapshots"), stats.snapshots.to_string()),
+        (t!("stats-paths"), stats.paths.to_string()),
+        (t!("stats-blobs"), stats.blobs.to_string()),
+        (t!("stats-logical"), format_size(stats.logical_size)),
+        (t!("stats-unique"), format_size(stats.unique_size)),
+        (
+            t!("stats-stored"),
+            format!(
+                "{}{}",
+                format_size(stats.stored_size),
+                percent(stats.stored_size, stats.logical_size)
+            ),
+        ),
+        (t!("stats-ratio"), format_ratio(stats.unique_size, stats.stored_size)),
+        (
+            t!("stats-dedup"),
+            format!(
+                "{}{}",
+                format_size(stats.dedup_savings),
+                percent(stats.dedup_savings, stats.logical_size)
+            ),
+        ),
+    ];
+
+    let mut out = String::new();
+    for (label, value) in lines {
+        out.push_str(&format!("{:<24} {}\n", label, value));
+    }
+    if !stats.top_paths.is_empty() {
+        out.push_str(&format!("\n{}\n", t!("stats-top-paths")));
+        out.push_str(&usage_table(
+            &stats.top_paths,
+            [t!("column-path"), t!("column-snapshots"), t!("column-size")],
+        ));
+        out.push('\n');
+        out.push_str(&format!("\n{}\n", t!("stats-directories")));
+        out.push_str(&usage_table(
+            &stats.directories,
+            [t!("column-directory"), t!("column-snapshots"), t!("column-size")],
+        ));
+        out.push('\n');
+    }
+    out
+}
+
+/// Renders the deduplication report as a summary followed by a table.
+pub fn render_dedup_text(dedup: &Dedup) -> String {
+    let lines = [
+        (t!("stats-references"), dedup.references.to_string()),
+        (t!("stats-blobs"), dedup.blobs.to_string()),
+        (t!("stats-logical"), format_size(dedup.logical_size)),
+        (t!("stats-unique"), format_size(dedup.unique_size)),
+        (
+            t!("stats-stored"),
+            format!(
+                "{}{}",
+                format_size(dedup.stored_size),
+                percent(dedup.stored_size, dedup.logical_size)
+            ),
+        ),
+        (
+            t!("stats-dedup"),
+            format!(
+                "{}{}",
+                format_size(dedup.dedup_savings),
+                percent(dedup.dedup_savings, dedup.logical_size)
+            ),
+        ),
+        (
+            t!("stats-compression"),
+            format!(
+                "{}{}",
+                format_size(dedup.compression_savings),
+                percent(dedup.compression_savings, dedup.unique_size)
+            ),
+        ),
+        (t!("stats-ratio"), format_ratio(dedup.unique_size, dedup.stored_size)),
+    ];
+
+    let mut out = String::new();
+    for (label, value) in lines {
+        out.push_str(&format!("{:<24} {}\n", label, value));
+    }
+    if !dedup.top_paths.is_empty() {
+        out.push_str(&format!("\n{}\n", t!("stats-dedup-paths")));
+        out.push_str(&usage_table(
+            &dedup.top_paths,
+            [t!("column-path"), t!("column-deduplicated"), t!("column-saved")],
+        ));
+        out.push('\n');
+    }
+    out
+}
+
+/// Renders the directory breakdown of `root` as a table.
+pub fn render_directories_text(root: &Path, directories: &[DirectoryTotals]) -> String {
+    if directories.is_empty() {
+        return format!("{}\n", t!("stats-by-dir-empty", path = root.display().to_string()));
+    }
+    let mut builder = Builder::default();
+    builder.push_record([
+        t!("column-directory"),
+        t!("column-snapshots"),
+        t!("column-files"),
+        t!("column-newest"),
+        t!("column-size"),
+    ]);
+    for entry in directories {
+        builder.push_record([
+            entry.path.display().to_string(),
+            entry.snapshots.to_string(),
+            entry.files.to_string(),
+            format_date(&entry.newest),
+            format_size(entry.size),
+        ]);
+    }
+    format!(
+        "{}\n{}\n",
+        t!("stats-by-dir", path = root.display().to_string()),
+        style_table(&mut builder.build())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_by_directory() {
+        let totals = vec![
+            (PathBuf::from("/a/big"), 1, 500),
+            (PathBuf::from("/b/x"), 2, 300),
+            (PathBuf::from("/b/y"), 3, 300),
+            (PathBuf::from("/c/z"), 1, 10),
+        ];
+
+        let usage = by_directory(&totals, 2);
+        assert_eq!(
+            usage,
+            vec![
+                Usage { path: "/b".to_string(), snapshots: 5, size: 600 },
+                Usage { path: "/a".to_string(), snapshots: 1, size: 500 },
+            ]
+        );
+    }
+}
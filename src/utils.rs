@@ -5,42 +5,310 @@ This module provides helper functions for formatting, validation,
 and user interface elements like progress bars and tables.
 */
 
-use crate::db::Database;
-use crate::snapshot::Snapshot;
+use crate::db::{Database, SnapshotWithId};
+use crate::i18n::t;
+use crate::snapshot::{Selection, Snapshot};
 use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
 use colored::*;
 use console::{style, Term};
 use indicatif::{ProgressBar, ProgressStyle};
-use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
+use tabled::builder::Builder;
 use tabled::settings::{object::Columns, Modify, Style, Width};
-use tabled::{Table, Tabled};
-use walkdir::WalkDir;
+use tabled::Table;
 
-#[derive(Tabled)]
-struct SnapshotDisplay {
-    #[tabled(rename = "Date")]
-    date: String,
-    #[tabled(rename = "Path")]
-    path: String,
-    #[tabled(rename = "Size")]
-    size: String,
-    #[tabled(rename = "Checksum")]
-    checksum: String,
+static ASCII_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables ASCII-only output.
+///
+/// In ASCII mode emoji, box-drawing characters and block progress
+/// characters are replaced by plain ASCII, for screen readers, limited
+/// fonts and log files.
+pub fn set_ascii_mode(enabled: bool) {
+    ASCII_MODE.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns `true` when ASCII-only output is enabled.
+pub fn ascii_mode() -> bool {
+    ASCII_MODE.load(Ordering::Relaxed)
+}
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+static NO_INPUT: AtomicBool = AtomicBool::new(false);
+
+/// Sets how questions are answered.
+///
+/// With `yes`, confirmations are accepted and the latest snapshot is picked
+/// instead of prompting. With `no_input`, standard input is never read and
+/// a question that needs an answer fails instead.
+pub fn set_interaction(yes: bool, no_input: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+    NO_INPUT.store(no_input, Ordering::Relaxed);
+}
+
+/// Returns `true` when the user can be asked a question on standard input.
+fn can_prompt() -> bool {
+    use std::io::IsTerminal;
+    !NO_INPUT.load(Ordering::Relaxed) && std::io::stdin().is_terminal()
+}
+
+/// Asks a yes/no question, defaulting to no.
+///
+/// # Returns
+///
+/// `true` if the user agreed or `--yes` was given
+///
+/// # Errors
+///
+/// Returns an error if the question cannot be asked because input is
+/// disabled or standard input is not a terminal.
+pub fn confirm(question: &str) -> Result<bool> {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        return Ok(true);
+    }
+    if !can_prompt() {
+        anyhow::bail!(t!("confirm-no-input", question = question));
+    }
+
+    print!("{} {} ", question, style(t!("confirm-choices")).dim());
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(matches!(
+        input.trim().to_lowercase().as_str(),
+        "y" | "yes" | "o" | "oui"
+    ))
+}
+
+/// Asks for a secret without echoing it.
+///
+/// # Arguments
+///
+/// * `question` - The prompt
+/// * `env` - Environment variable that can provide the secret instead, named
+///   in the error when the question cannot be asked
+///
+/// # Errors
+///
+/// Returns an error if the question cannot be asked because input is
+/// disabled or standard input is not a terminal.
+pub fn prompt_secret(question: &str, env: &str) -> Result<String> {
+    if !can_prompt() {
+        anyhow::bail!(t!("secret-no-input", question = question, env = env));
+    }
+    Ok(rpassword::prompt_password(question)?)
+}
+
+/// Returns `unicode`, or `ascii` when ASCII-only output is enabled.
+pub fn symbol<'a>(unicode: &'a str, ascii: &'a str) -> &'a str {
+    if ascii_mode() { ascii } else { unicode }
+}
+
+/// Returns a horizontal rule of `width` characters.
+///
+/// Uses `═` for heavy rules and `─` for light ones, or `=` and `-` in ASCII mode.
+pub fn rule(heavy: bool, width: usize) -> String {
+    let ch = match (heavy, ascii_mode()) {
+        (true, false) => "═",
+        (false, false) => "─",
+        (true, true) => "=",
+        (false, true) => "-",
+    };
+    ch.repeat(width)
+}
+
+/// Strips emoji and symbols from `text` when ASCII-only output is enabled.
+///
+/// Known symbols get an ASCII replacement; letters, including accented
+/// ones, are kept.
+pub fn plain(text: &str) -> String {
+    if !ascii_mode() {
+        return text.to_string();
+    }
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '✅' => result.push_str("[OK]"),
+            '❌' => result.push_str("[X]"),
+            '⚠' => result.push_str("[!]"),
+            '→' => result.push_str("->"),
+            '─' => result.push('-'),
+            '═' => result.push('='),
+            c if c.is_ascii() || c.is_alphanumeric() => result.push(c),
+            _ => {}
+        }
+    }
+    result.trim_start().to_string()
+}
+
+/// Applies the table border style matching the output mode.
+pub fn style_table(table: &mut Table) -> &mut Table {
+    if ascii_mode() {
+        table.with(Style::ascii())
+    } else {
+        table.with(Style::rounded())
+    }
+}
+
+/// Returns the spinner style used for long-running operations.
+pub fn spinner_style() -> ProgressStyle {
+    let style = ProgressStyle::default_spinner()
+        .template("{spinner:.green} [{elapsed_precise}] {msg}")
+        .expect("valid spinner template");
+    if ascii_mode() {
+        style.tick_chars("|/-\\ ")
+    } else {
+        style
+    }
+}
+
+/// Renders snapshots as a table with localized headers, wrapping the path column.
+///
+/// A message column is added when one of the snapshots has a message.
+/// Pinned snapshots are marked next to their checksum. With `stored`, the
+/// sizes of the stored contents, the stored size and compression ratio of
+/// each snapshot follow its size.
+fn snapshot_table(
+    snapshots: &[SnapshotWithId],
+    messages: &HashMap<i64, String>,
+    pinned: &HashSet<i64>,
+    stored: Option<&HashMap<i64, i64>>,
+    width: usize,
+) -> String {
+    let with_messages = snapshots.iter().any(|(id, ..)| messages.contains_key(id));
+
+    let mut builder = Builder::default();
+    let mut header = vec![
+        t!("column-date"),
+        t!("column-path"),
+        t!("column-size"),
+        t!("column-checksum"),
+    ];
+    if stored.is_some() {
+        header.splice(3..3, [t!("column-stored"), t!("column-ratio")]);
+    }
+    if with_messages {
+        header.push(t!("column-message"));
+    }
+    let header_len = header.len();
+    builder.push_record(header);
+    for (id, path, date, size, checksum) in snapshots {
+        let mut short = checksum.get(..8).unwrap_or(checksum).to_string();
+        if pinned.contains(id) {
+            short = format!("{} {}", short, symbol("📌", "*"));
+        }
+        let mut record = vec![format_date(date), path.display().to_string(), format_size(*size), short];
+        if let Some(stored) = stored {
+            let stored_size = stored.get(id);
+            record.splice(
+                3..3,
+                [
+                    stored_size.map_or_else(|| "-".to_string(), |stored_size| format_size(*stored_size)),
+                    stored_size.map_or_else(|| "-".to_string(), |stored_size| format_ratio(*size, *stored_size)),
+                ],
+            );
+        }
+        if with_messages {
+            record.push(messages.get(id).cloned().unwrap_or_default());
+        }
+        builder.push_record(record);
+    }
+
+    let mut table = builder.build();
+    // The stored size and ratio columns take about 20 more characters
+    let fixed = if stored.is_some() { 70 } else { 50 };
+    if with_messages {
+        let path_width = width.saturating_sub(fixed) / 2;
+        let message = header_len - 1;
+        style_table(&mut table)
+            .with(Modify::new(Columns::new(1..2)).with(Width::wrap(path_width)))
+            .with(Modify::new(Columns::new(message..message + 1)).with(Width::wrap(path_width).keep_words(true)))
+            .to_string()
+    } else {
+        style_table(&mut table)
+            .with(Modify::new(Columns::new(1..2)).with(Width::wrap(width.saturating_sub(fixed))))
+            .to_string()
+    }
+}
+
+/// A snapshot as printed by the `--json` output mode.
+#[derive(Serialize, Debug)]
+pub struct SnapshotEntry {
+    pub path: String,
+    pub date: String,
+    pub size: i64,
+    pub checksum: String,
+    pub message: Option<String>,
+    /// Whether deletions leave the snapshot alone
+    pub pinned: bool,
+}
+
+/// Converts listed snapshots to their JSON form, attaching their messages.
+pub fn snapshot_entries(
+    snapshots: &[SnapshotWithId],
+    messages: &HashMap<i64, String>,
+    pinned: &HashSet<i64>,
+) -> Vec<SnapshotEntry> {
+    snapshots
+        .iter()
+        .map(|(id, path, date, size, checksum)| SnapshotEntry {
+            path: path.display().to_string(),
+            date: date.clone(),
+            size: *size,
+            checksum: checksum.clone(),
+            message: messages.get(id).cloned(),
+            pinned: pinned.contains(id),
+        })
+        .collect()
+}
+
+/// Returns the snapshots of a 1-indexed page, or all of them without a page.
+///
+/// # Errors
+///
+/// Returns an error if the page does not exist.
+pub fn page_of<T>(items: &[T], page: Option<u32>) -> Result<&[T]> {
+    let Some(page) = page else {
+        return Ok(items);
+    };
+    let per_page = crate::config::get().page_size();
+    let total_pages = items.len().div_ceil(per_page);
+    let page = page as usize;
+    if page == 0 || page > total_pages {
+        anyhow::bail!(t!("page-invalid", total = total_pages));
+    }
+    let start = (page - 1) * per_page;
+    Ok(&items[start..items.len().min(start + per_page)])
+}
+
+/// Prints a value as pretty JSON on standard output.
+///
+/// # Errors
+///
+/// Returns an error if the value cannot be serialized.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
 }
 
-/// Allows the user to interactively select a snapshot from a list.
+/// Selects a snapshot from a list.
 ///
-/// If there's only one snapshot, returns it immediately.
-/// If there are multiple, displays them and prompts for selection.
+/// If there's only one snapshot, returns it immediately. Otherwise the
+/// selection decides: the latest snapshot, the latest one matching a
+/// checksum prefix or taken by a date, or an interactive prompt. With
+/// `--yes` the prompt picks the latest snapshot.
 ///
 /// # Arguments
 ///
-/// * `snapshots` - Slice of available snapshots
+/// * `snapshots` - Slice of available snapshots, newest first
+/// * `selection` - How to choose between several snapshots
 ///
 /// # Returns
 ///
@@ -48,39 +316,76 @@ struct SnapshotDisplay {
 ///
 /// # Errors
 ///
-/// Returns an error if no snapshots are available or if user input is invalid.
-pub fn select_snapshot(snapshots: &[Snapshot]) -> Result<&Snapshot> {
+/// Returns an error if no snapshots are available, if no snapshot matches
+/// the checksum prefix or date, if a prompt is needed but input is not
+/// available, or if user input is invalid.
+pub fn select_snapshot<'a>(snapshots: &'a [Snapshot], selection: &Selection) -> Result<&'a Snapshot> {
     if snapshots.is_empty() {
-        anyhow::bail!("No snapshots available");
+        anyhow::bail!(t!("select-none"));
+    }
+
+    match selection {
+        Selection::Latest => return Ok(&snapshots[0]),
+        Selection::Checksum(prefix) => {
+            return snapshots
+                .iter()
+                .find(|snapshot| snapshot.checksum.starts_with(prefix.as_str()))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(t!(
+                        "select-no-checksum",
+                        checksum = prefix.as_str(),
+                        path = snapshots[0].path.display().to_string()
+                    ))
+                });
+        }
+        Selection::AsOf(as_of) => {
+            return snapshots
+                .iter()
+                .find(|snapshot| {
+                    DateTime::parse_from_rfc3339(&snapshot.date).is_ok_and(|date| date <= *as_of)
+                })
+                .ok_or_else(|| anyhow::anyhow!(t!("select-no-date", date = as_of.to_rfc3339())));
+        }
+        Selection::Prompt => {}
     }
 
-    if snapshots.len() == 1 {
+    if snapshots.len() == 1 || ASSUME_YES.load(Ordering::Relaxed) {
         return Ok(&snapshots[0]);
     }
+    if !can_prompt() {
+        anyhow::bail!(t!(
+            "select-no-input",
+            count = snapshots.len(),
+            path = snapshots[0].path.display().to_string()
+        ));
+    }
 
-    println!("\nAvailable snapshots:");
+    println!("\n{}", t!("select-title"));
     for (i, snapshot) in snapshots.iter().enumerate() {
         println!(
-            "{}. {} ({}) - Checksum: {}",
-            i + 1,
-            format_date(&snapshot.date),
-            format_size(snapshot.size),
-            &snapshot.checksum[..8]
+            "{}",
+            t!(
+                "select-entry",
+                index = i + 1,
+                date = format_date(&snapshot.date),
+                size = format_size(snapshot.size),
+                checksum = &snapshot.checksum[..8]
+            )
         );
     }
 
     let mut input = String::new();
-    print!("\nSelect snapshot number (1-{}): ", snapshots.len());
+    print!("\n{} ", t!("select-prompt", max = snapshots.len()));
     std::io::stdout().flush()?;
     std::io::stdin().read_line(&mut input)?;
 
     let selection = input
         .trim()
         .parse::<usize>()
-        .map_err(|_| anyhow::anyhow!("Invalid selection"))?;
+        .map_err(|_| anyhow::anyhow!(t!("select-invalid")))?;
 
     if selection < 1 || selection > snapshots.len() {
-        anyhow::bail!("Invalid selection: {}", selection);
+        anyhow::bail!(t!("select-invalid-number", selection = selection));
     }
 
     Ok(&snapshots[selection - 1])
@@ -97,9 +402,9 @@ pub fn print_header(text: &str) {
     let term = Term::stdout();
     let (_, width) = term.size();
     let width = width as usize;
-    println!("\n{}", "═".repeat(width).bright_blue());
-    println!("{}", style(text).cyan().bold());
-    println!("{}\n", "═".repeat(width).bright_blue());
+    println!("\n{}", rule(true, width).bright_blue());
+    println!("{}", style(plain(text)).cyan().bold());
+    println!("{}\n", rule(true, width).bright_blue());
 }
 
 /// Prints snapshot information in a table format.
@@ -109,28 +414,18 @@ pub fn print_header(text: &str) {
 ///
 /// # Arguments
 ///
-/// * `snapshots` - Slice of tuples containing (path, date, size, checksum)
-pub fn print_snapshot_info(snapshots: &[(PathBuf, String, i64, String)]) {
-    let snapshot_displays: Vec<SnapshotDisplay> = snapshots
-        .iter()
-        .map(|(path, date, size, checksum)| SnapshotDisplay {
-            date: format_date(date),
-            path: path.display().to_string(),
-            size: format_size(*size),
-            checksum: checksum.get(..8).unwrap_or(checksum).to_string(),
-        })
-        .collect();
-
+/// * `snapshots` - Slice of tuples containing (id, path, date, size, checksum)
+/// * `messages` - Snapshot messages keyed by snapshot ID
+/// * `pinned` - The IDs of pinned snapshots
+pub fn print_snapshot_info(
+    snapshots: &[SnapshotWithId],
+    messages: &HashMap<i64, String>,
+    pinned: &HashSet<i64>,
+) {
     let term = Term::stdout();
     let (_, width) = term.size();
-    let width = width as usize;
 
-    let table = Table::new(snapshot_displays)
-        .with(Style::rounded())
-        .with(Modify::new(Columns::new(1..2)).with(Width::wrap(width.saturating_sub(50))))
-        .to_string();
-
-    println!("{}", table);
+    println!("{}", snapshot_table(snapshots, messages, pinned, None, width as usize));
 }
 
 /// Prints snapshot information with pagination support.
@@ -140,82 +435,67 @@ pub fn print_snapshot_info(snapshots: &[(PathBuf, String, i64, String)]) {
 ///
 /// # Arguments
 ///
-/// * `snapshots` - Slice of tuples containing (path, date, size, checksum)
-/// * `page` - Optional page number (1-indexed, 10 items per page)
+/// * `snapshots` - Slice of tuples containing (id, path, date, size, checksum)
+/// * `messages` - Snapshot messages keyed by snapshot ID
+/// * `pinned` - The IDs of pinned snapshots
+/// * `stored` - Stored content sizes keyed by snapshot ID, to show the
+///   stored size and compression ratio of each snapshot
+/// * `page` - Optional page number (1-indexed, `page_size` items per page)
 pub fn print_snapshot_info_paginated(
-    snapshots: &[(PathBuf, String, i64, String)],
+    snapshots: &[SnapshotWithId],
+    messages: &HashMap<i64, String>,
+    pinned: &HashSet<i64>,
+    stored: Option<&HashMap<i64, i64>>,
     page: Option<u32>,
 ) {
-    const ITEMS_PER_PAGE: usize = 10;
+    let items_per_page = crate::config::get().page_size();
 
     let total_snapshots = snapshots.len();
 
+    let term = Term::stdout();
+    let (_, width) = term.size();
+    let width = width as usize;
+
     if page.is_none() {
-        print_snapshot_info(snapshots);
+        println!("{}", snapshot_table(snapshots, messages, pinned, stored, width));
         return;
     }
 
-    let total_pages = total_snapshots.div_ceil(ITEMS_PER_PAGE);
+    let total_pages = total_snapshots.div_ceil(items_per_page);
     let page_num = page.unwrap() as usize;
 
     if page_num == 0 || page_num > total_pages {
-        println!(
-            "{}",
-            style(format!(
-                "Invalid page number. Must be between 1 and {}.",
-                total_pages
-            ))
-            .red()
-        );
+        println!("{}", style(t!("page-invalid", total = total_pages)).red());
         return;
     }
 
-    let start_index = (page_num - 1) * ITEMS_PER_PAGE;
-    let end_index = std::cmp::min(start_index + ITEMS_PER_PAGE, total_snapshots);
+    let start_index = (page_num - 1) * items_per_page;
+    let end_index = std::cmp::min(start_index + items_per_page, total_snapshots);
 
     let page_snapshots = &snapshots[start_index..end_index];
 
-    let snapshot_displays: Vec<SnapshotDisplay> = page_snapshots
-        .iter()
-        .map(|(path, date, size, checksum)| SnapshotDisplay {
-            date: format_date(date),
-            path: path.display().to_string(),
-            size: format_size(*size),
-            checksum: checksum.get(..8).unwrap_or(checksum).to_string(),
-        })
-        .collect();
-
-    let term = Term::stdout();
-    let (_, width) = term.size();
-    let width = width as usize;
-
-    let table = Table::new(snapshot_displays)
-        .with(Style::rounded())
-        .with(Modify::new(Columns::new(1..2)).with(Width::wrap(width.saturating_sub(50))))
-        .to_string();
+    println!("{}", snapshot_table(page_snapshots, messages, pinned, stored, width));
 
-    println!("{}", table);
-
-    println!("{}", style("─".repeat(width.min(50))).dim());
+    println!("{}", style(rule(false, width.min(50))).dim());
     println!(
         "{} {} {} {} {}",
-        style("Page:").cyan(),
+        style(t!("page-label")).cyan(),
         style(page_num).yellow(),
-        style("of").cyan(),
+        style(t!("page-of")).cyan(),
         style(total_pages).yellow(),
-        style(format!("({} items)", total_snapshots)).dim()
+        style(t!("pagination-items", count = total_snapshots)).dim()
     );
 
     if total_pages > 1 {
         let navigation = if page_num == 1 {
-            format!("Next: --page {}", page_num + 1)
+            t!("page-next", next = page_num + 1)
         } else if page_num == total_pages {
-            format!("Previous: --page {}", page_num - 1)
+            t!("page-previous", previous = page_num - 1)
         } else {
-            format!(
-                "Previous: --page {} | Next: --page {}",
-                page_num - 1,
-                page_num + 1
+            t!(
+                "page-previous-next",
+                previous = page_num - 1,
+                next = page_num + 1
             )
         };
 
@@ -238,12 +518,39 @@ pub fn create_progress_bar(len: u64) -> ProgressBar {
         ProgressStyle::default_bar()
             .template("{spinner:.green} [{bar:40.gradient(red,yellow,green)}] {percent}% {msg}")
             .unwrap()
-            .progress_chars("█▓▒░"),
+            .progress_chars(symbol("█▓▒░", "#=-.")),
+    );
+    pb.enable_steady_tick(Duration::from_millis(120));
+    pb
+}
+
+/// Creates a progress bar measuring bytes, with throughput and remaining time.
+///
+/// The length is set once the total is known.
+pub fn transfer_progress_bar() -> ProgressBar {
+    let pb = ProgressBar::new(0);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{bar:40.gradient(red,yellow,green)}] {bytes}/{total_bytes} ({binary_bytes_per_sec}, ETA {eta}) {msg}",
+            )
+            .unwrap()
+            .progress_chars(symbol("█▓▒░", "#=-.")),
     );
     pb.enable_steady_tick(Duration::from_millis(120));
     pb
 }
 
+/// Formats how many times smaller than `size` its stored content is, e.g.
+/// `3.2x`.
+pub fn format_ratio(size: i64, stored_size: i64) -> String {
+    if stored_size > 0 {
+        format!("{:.1}x", size as f64 / stored_size as f64)
+    } else {
+        "-".to_string()
+    }
+}
+
 /// Formats a byte count into a human-readable size string.
 ///
 /// # Arguments
@@ -287,6 +594,108 @@ pub fn format_date(date: &str) -> String {
     }
 }
 
+/// Parses a user-supplied point in time.
+///
+/// Accepts RFC3339 (`2024-01-15T10:00:00+01:00`), local date and time
+/// (`2024-01-15 10:00` or `2024-01-15 10:00:00`) and plain dates
+/// (`2024-01-15`, meaning the end of that day in local time).
+///
+/// # Errors
+///
+/// Returns an error if the string matches none of these formats.
+pub fn parse_timestamp(input: &str) -> Result<DateTime<FixedOffset>> {
+    let input = input.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(input) {
+        return Ok(date);
+    }
+
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(23, 59, 59))
+        })
+        .ok_or_else(|| anyhow::anyhow!("Invalid date: {} (expected YYYY-MM-DD [HH:MM[:SS]])", input))?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|date| date.fixed_offset())
+        .ok_or_else(|| anyhow::anyhow!("Invalid local time: {}", input))
+}
+
+/// Parses an age such as `90s`, `30m`, `12h`, `7d` or `2w`.
+///
+/// # Errors
+///
+/// Returns an error if the string is not a number followed by one of these units.
+pub fn parse_age(input: &str) -> Result<chrono::Duration> {
+    let input = input.trim();
+    let invalid = || anyhow::anyhow!("Invalid age: {} (expected e.g. 30m, 12h, 7d, 2w or 1y)", input);
+    let split = input.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (amount, unit) = input.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    match unit {
+        "s" => chrono::Duration::try_seconds(amount),
+        "m" => chrono::Duration::try_minutes(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        "d" => chrono::Duration::try_days(amount),
+        "w" => chrono::Duration::try_weeks(amount),
+        "y" => amount.checked_mul(365).and_then(chrono::Duration::try_days),
+        _ => None,
+    }
+    .ok_or_else(invalid)
+}
+
+/// Parses one end of a date range: an age such as `7d`, meaning that long
+/// ago, or a point in time accepted by [`parse_timestamp`].
+///
+/// A plain date means the start of that day when `start` is set, its end
+/// otherwise, so that the day itself is part of the range.
+///
+/// # Errors
+///
+/// Returns an error if the string is neither an age nor a date.
+pub fn parse_date_bound(input: &str, start: bool) -> Result<DateTime<FixedOffset>> {
+    if let Ok(age) = parse_age(input) {
+        return Ok((Local::now() - age).fixed_offset());
+    }
+    if start && let Ok(date) = NaiveDate::parse_from_str(input.trim(), "%Y-%m-%d") {
+        return Local
+            .from_local_datetime(&date.and_time(chrono::NaiveTime::MIN))
+            .earliest()
+            .map(|date| date.fixed_offset())
+            .ok_or_else(|| anyhow::anyhow!("Invalid local time: {}", input));
+    }
+    parse_timestamp(input)
+}
+
+/// Parses a size in bytes, optionally followed by a K, M or G unit
+/// (powers of 1024, e.g. `500`, `10K`, `2.5M`, `1G`).
+///
+/// # Errors
+///
+/// Returns an error if the string is not a valid size.
+pub fn parse_size(input: &str) -> Result<i64> {
+    let input = input.trim();
+    let invalid = || anyhow::anyhow!("Invalid size: {} (expected e.g. 500, 10K, 2M or 1G)", input);
+    let upper = input.to_ascii_uppercase();
+    let number = upper.strip_suffix('B').unwrap_or(&upper);
+    let (number, multiplier) = match number.chars().last() {
+        Some('K') => (&number[..number.len() - 1], 1024.0),
+        Some('M') => (&number[..number.len() - 1], 1024.0 * 1024.0),
+        Some('G') => (&number[..number.len() - 1], 1024.0 * 1024.0 * 1024.0),
+        _ => (number, 1.0),
+    };
+    let value: f64 = number.trim().parse().map_err(|_| invalid())?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(invalid());
+    }
+    Ok((value * multiplier) as i64)
+}
+
 /// Detects if content contains binary data.
 ///
 /// Checks the first 512 bytes for null bytes, which indicates binary content.
@@ -302,6 +711,41 @@ pub fn is_binary(content: &[u8]) -> bool {
     content.iter().take(512).any(|&byte| byte == 0)
 }
 
+/// Number of leading bytes examined to detect the type of content.
+pub const MIME_SAMPLE_SIZE: usize = 8192;
+
+/// Detects the MIME type of content from its first bytes.
+///
+/// Known signatures (images, audio, video, archives, documents...) come
+/// first. Other content is `text/plain` if it is valid UTF-8 without null
+/// bytes, and `application/octet-stream` otherwise.
+///
+/// # Arguments
+///
+/// * `sample` - The start of the content, [`MIME_SAMPLE_SIZE`] bytes are enough
+///
+/// # Returns
+///
+/// The MIME type, such as `image/png`
+pub fn detect_mime(sample: &[u8]) -> String {
+    if let Some(kind) = infer::get(sample) {
+        return kind.mime_type().to_string();
+    }
+    let sample = &sample[..sample.len().min(MIME_SAMPLE_SIZE)];
+    let is_text = !sample.contains(&0)
+        && match std::str::from_utf8(sample) {
+            Ok(_) => true,
+            // The sample may end in the middle of a character
+            Err(e) => e.error_len().is_none(),
+        };
+    if is_text { "text/plain" } else { "application/octet-stream" }.to_string()
+}
+
+/// Returns `true` if content of type `mime` can be shown as text.
+pub fn is_text_mime(mime: &str) -> bool {
+    mime.starts_with("text/") || matches!(mime, "application/json" | "application/xml" | "application/javascript")
+}
+
 /// Validates that a path exists.
 ///
 /// # Arguments
@@ -314,11 +758,88 @@ pub fn is_binary(content: &[u8]) -> bool {
 pub fn validate_path<P: AsRef<Path>>(path: P) -> Result<()> {
     let path = path.as_ref();
     if !path.exists() {
-        anyhow::bail!("Path does not exist: {}", path.display());
+        anyhow::bail!(t!("path-not-found", path = path.display().to_string()));
     }
     Ok(())
 }
 
+/// State of a file compared to its latest snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    /// The file matches its latest snapshot
+    UpToDate,
+    /// The file changed since its latest snapshot
+    Modified,
+    /// The file has never been snapshotted
+    New,
+    /// The file has a snapshot but no longer exists
+    Deleted,
+}
+
+/// Compares a file or every file of a directory with its latest snapshot.
+///
+/// # Arguments
+///
+/// * `path` - Absolute path of the file or directory to check
+/// * `db` - Database connection to retrieve snapshots from
+/// * `on_file` - Called with each file before it is checked
+///
+/// # Returns
+///
+/// The status of each checked file, in walk order, followed by the
+/// snapshotted files that no longer exist, sorted by path.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read or the database query fails.
+pub fn check_report(
+    path: &Path,
+    db: &Database,
+    mut on_file: impl FnMut(&Path),
+) -> Result<Vec<(PathBuf, CheckStatus)>> {
+    if path.is_file() {
+        on_file(path);
+        let status = match db.get_snapshots_for_path(path)?.first() {
+            None => CheckStatus::New,
+            Some(latest) if latest.matches_file(path)? => CheckStatus::UpToDate,
+            Some(_) => CheckStatus::Modified,
+        };
+        return Ok(vec![(path.to_path_buf(), status)]);
+    }
+
+    let snapshot_map = db.latest_snapshots_in_dir(path)?;
+
+    let mut report = Vec::new();
+    let walker = Snapshot::walk(path).into_iter();
+    for entry in walker.filter_entry(|e| !Snapshot::is_excluded(e.path())) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.loop_ancestor().is_some() => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if !entry.file_type().is_file() && !entry.file_type().is_symlink() {
+            continue;
+        }
+        let file = entry.path();
+        on_file(file);
+        let status = match snapshot_map.get(file) {
+            None => CheckStatus::New,
+            Some(latest) if latest.matches_file(file)? => CheckStatus::UpToDate,
+            Some(_) => CheckStatus::Modified,
+        };
+        report.push((file.to_path_buf(), status));
+    }
+
+    let mut deleted: Vec<PathBuf> = snapshot_map
+        .into_keys()
+        .filter(|file| file.starts_with(path) && fs::symlink_metadata(file).is_err())
+        .collect();
+    deleted.sort();
+    report.extend(deleted.into_iter().map(|file| (file, CheckStatus::Deleted)));
+    Ok(report)
+}
+
 /// Checks if files have changed since their last snapshot.
 ///
 /// For files, compares the current checksum with the stored one.
@@ -343,108 +864,231 @@ pub fn check_path(path: &str, db: &Database) -> Result<()> {
     Ok(())
 }
 fn check_single_file(path: &Path, db: &Database) -> Result<()> {
-    let content = fs::read(path)?;
-    let mut hasher = Sha256::new();
-    hasher.update(&content);
-    let current_checksum = format!("{:x}", hasher.finalize());
-
-    let snapshots = db.get_snapshots_for_path(path)?;
-
-    if snapshots.is_empty() {
-        println!(
-            "{} {} {}",
-            style("❌").red(),
-            style(path.display()).cyan(),
-            style("(No snapshot found)").red()
-        );
-        return Ok(());
-    }
-
-    let latest_snapshot = &snapshots[0];
-    if latest_snapshot.checksum == current_checksum {
-        println!(
-            "{} {} {}",
-            style("✅").green(),
-            style(path.display()).cyan(),
-            style("(Up to date)").green()
-        );
-    } else {
-        println!(
-            "{} {} {}",
-            style("⚠️").yellow(),
-            style(path.display()).cyan(),
-            style("(Modified since last snapshot)").yellow()
-        );
-    }
+    let (_, status) = check_report(path, db, |_| {})?[0];
+    let (icon, message) = match status {
+        CheckStatus::New => (style(symbol("❌", "[X]")).red(), style(t!("check-no-snapshot")).red()),
+        CheckStatus::UpToDate => (
+            style(symbol("✅", "[OK]")).green(),
+            style(t!("check-up-to-date")).green(),
+        ),
+        CheckStatus::Modified => (
+            style(symbol("⚠️", "[!]")).yellow(),
+            style(t!("check-modified-since")).yellow(),
+        ),
+        CheckStatus::Deleted => unreachable!("an existing file cannot be deleted"),
+    };
+    println!("{} {} {}", icon, style(path.display()).cyan(), message);
 
     Ok(())
 }
 fn check_directory(dir: &Path, db: &Database) -> Result<()> {
     let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner().template("{spinner:.green} [{elapsed_precise}] {msg}")?,
-    );
+    pb.set_style(spinner_style());
 
-    let all_snapshots = db.list_directory_snapshots(dir)?;
-    let snapshot_map: HashMap<String, String> = all_snapshots
-        .into_iter()
-        .map(|(path, _, _, checksum)| (path.display().to_string(), checksum))
-        .collect();
+    let report = check_report(dir, db, |file| {
+        pb.set_message(t!("check-progress", path = file.display().to_string()));
+    })?;
+
+    pb.finish_and_clear();
 
     let mut files_checked = 0;
     let mut files_modified = 0;
     let mut files_new = 0;
-
-    let walker = WalkDir::new(dir).into_iter();
-    for entry in walker.filter_entry(|e| !Snapshot::is_excluded(e.path())) {
-        let entry = entry?;
-        if entry.file_type().is_file() {
-            pb.set_message(format!("Checking {}", entry.path().display()));
-
-            let path = entry.path();
-            let content = fs::read(path)?;
-            let mut hasher = Sha256::new();
-            hasher.update(&content);
-            let current_checksum = format!("{:x}", hasher.finalize());
-
-            let path_str = path.display().to_string();
-            match snapshot_map.get(&path_str) {
-                Some(saved_checksum) => {
-                    files_checked += 1;
-                    if &current_checksum != saved_checksum {
-                        files_modified += 1;
-                        println!(
-                            "{} {} {}",
-                            style("⚠️").yellow(),
-                            style(path.display()).cyan(),
-                            style("(Modified)").yellow()
-                        );
-                    }
-                }
-                None => {
-                    files_new += 1;
-                    println!(
-                        "{} {} {}",
-                        style("❌").red(),
-                        style(path.display()).cyan(),
-                        style("(New file)").red()
-                    );
-                }
+    let mut files_deleted = 0;
+    for (path, status) in &report {
+        match status {
+            CheckStatus::UpToDate => files_checked += 1,
+            CheckStatus::Modified => {
+                files_checked += 1;
+                files_modified += 1;
+                println!(
+                    "{} {} {}",
+                    style(symbol("⚠️", "[!]")).yellow(),
+                    style(path.display()).cyan(),
+                    style(t!("check-modified")).yellow()
+                );
+            }
+            CheckStatus::New => {
+                files_new += 1;
+                println!(
+                    "{} {} {}",
+                    style(symbol("❌", "[X]")).red(),
+                    style(path.display()).cyan(),
+                    style(t!("check-new")).red()
+                );
+            }
+            CheckStatus::Deleted => {
+                files_deleted += 1;
+                println!(
+                    "{} {} {}",
+                    style(symbol("🗑️", "[-]")).red(),
+                    style(path.display()).cyan(),
+                    style(t!("check-deleted")).red()
+                );
             }
         }
     }
 
-    pb.finish_and_clear();
+    println!("\n{}", style(t!("check-summary")).cyan().bold());
+    println!("{} {}", t!("check-files-checked"), style(files_checked).green());
+    println!("{} {}", t!("check-files-modified"), style(files_modified).yellow());
+    println!("{} {}", t!("check-files-new"), style(files_new).red());
+    println!("{} {}", t!("check-files-deleted"), style(files_deleted).red());
+
+    Ok(())
+}
 
-    println!("\n{}", style("Summary:").cyan().bold());
-    println!("Files checked: {}", style(files_checked).green());
-    println!("Modified files: {}", style(files_modified).yellow());
-    println!("New files: {}", style(files_new).red());
+/// Prints a compact, `git status`-like summary of the changes below `dir`.
+///
+/// Paths are shown relative to the current directory when possible.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read or the database query fails.
+pub fn print_status(dir: &Path, db: &Database) -> Result<()> {
+    let mut report = check_report(dir, db, |_| {})?;
+    report.sort_by(|a, b| a.0.cmp(&b.0));
+    let cwd = std::env::current_dir()?;
+
+    let mut counts = HashMap::new();
+    for (path, status) in &report {
+        *counts.entry(*status).or_insert(0usize) += 1;
+        let code = match status {
+            CheckStatus::UpToDate => continue,
+            CheckStatus::Modified => style("M ").yellow(),
+            CheckStatus::New => style("??").red(),
+            CheckStatus::Deleted => style("D ").red(),
+        };
+        let shown = path.strip_prefix(&cwd).unwrap_or(path);
+        println!("{} {}", code, shown.display());
+    }
 
+    let count = |status| counts.get(&status).copied().unwrap_or(0);
+    let changed = count(CheckStatus::Modified) + count(CheckStatus::New) + count(CheckStatus::Deleted);
+    if changed == 0 {
+        println!("{}", style(t!("status-clean", count = count(CheckStatus::UpToDate))).green());
+    } else {
+        println!(
+            "\n{}",
+            style(t!(
+                "status-summary",
+                modified = count(CheckStatus::Modified),
+                new = count(CheckStatus::New),
+                deleted = count(CheckStatus::Deleted),
+                unchanged = count(CheckStatus::UpToDate)
+            ))
+            .dim()
+        );
+    }
     Ok(())
 }
 
-fn is_checksum(s: &str) -> bool {
+/// Resolves a command-line path to an absolute path.
+///
+/// Existing paths are canonicalized; paths that no longer exist (e.g. a
+/// deleted file being restored) are made absolute against the current
+/// directory. Trailing slashes and `.` components are dropped so that the
+/// result matches the paths recorded in snapshots.
+///
+/// # Errors
+///
+/// Returns an error if the current directory cannot be determined.
+pub fn resolve_path(path: &str) -> Result<PathBuf> {
+    let path_buf: PathBuf = Path::new(path).components().collect();
+    if path_buf.is_absolute() {
+        return Ok(path_buf);
+    }
+    match path_buf.canonicalize() {
+        Ok(canonical_path) => Ok(canonical_path),
+        Err(_) => Ok(std::env::current_dir()?.join(path_buf)),
+    }
+}
+
+/// Expands a leading `@alias` in a command-line path.
+///
+/// `@name` becomes the aliased path and `@name/rest` is joined onto it;
+/// arguments without a leading `@` are returned unchanged.
+///
+/// # Errors
+///
+/// Returns an error if the alias does not exist or the lookup fails.
+pub fn expand_alias(arg: &str, db: &Database) -> Result<String> {
+    let Some(rest) = arg.strip_prefix('@') else {
+        return Ok(arg.to_string());
+    };
+    let (name, suffix) = rest.split_once('/').unwrap_or((rest, ""));
+    let path = db
+        .get_alias(name)?
+        .ok_or_else(|| anyhow::anyhow!(t!("alias-unknown", name = name)))?;
+    let path = if suffix.is_empty() { path } else { path.join(suffix) };
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Returns `true` if `name` can be used as an alias name.
+pub fn is_valid_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Commands whose arguments can be completed from the snapshot database.
+const COMPLETABLE_COMMANDS: [&str; 7] = [
+    "restore", "view", "export", "inspect", "diff", "cp", "undo",
+];
+
+/// Returns completion candidates for the argument `current` of `command`.
+///
+/// Suggests snapshotted paths starting with `current` (relative to `cwd`
+/// when `current` is relative) and, when `current` looks like hex, the
+/// checksums it prefixes.
+///
+/// # Arguments
+///
+/// * `command` - The subcommand being completed
+/// * `current` - The word typed so far
+/// * `cwd` - Directory relative words are resolved against
+/// * `snapshots` - Known snapshots as (id, path, date, size, checksum)
+pub fn completion_candidates(
+    command: &str,
+    current: &str,
+    cwd: &Path,
+    snapshots: &[SnapshotWithId],
+) -> Vec<String> {
+    if !COMPLETABLE_COMMANDS.contains(&command) {
+        return Vec::new();
+    }
+
+    let mut candidates = Vec::new();
+    for (_, path, _, _, checksum) in snapshots {
+        let shown = if Path::new(current).is_absolute() {
+            Some(path.display().to_string())
+        } else {
+            path.strip_prefix(cwd)
+                .ok()
+                .map(|relative| relative.display().to_string())
+        };
+        if let Some(shown) = shown
+            && shown.starts_with(current)
+        {
+            candidates.push(shown);
+        }
+
+        if !current.is_empty()
+            && current.chars().all(|c| c.is_ascii_hexdigit())
+            && checksum.starts_with(current)
+        {
+            candidates.push(checksum.clone());
+        }
+    }
+
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+/// Returns `true` for a full SHA-256 checksum in hexadecimal.
+pub(crate) fn is_checksum(s: &str) -> bool {
     s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
 }
 
@@ -463,7 +1107,7 @@ pub fn compare(first: &str, second: &str, db: &Database) -> Result<()> {
     let diff = compute_unified_diff(&left_name, &right_name, &left_str, &right_str);
 
     if diff.is_empty() {
-        println!("{}", style("✅ Files are identical").green().bold());
+        println!("{}", style(plain(&t!("diff-identical"))).green().bold());
     } else {
         print!("{}", diff);
     }
@@ -480,7 +1124,7 @@ pub fn inspect_file(path: &Path, db: &Database) -> Result<()> {
     if snapshots.is_empty() {
         println!(
             "{} {}",
-            style("No snapshots found for:").yellow(),
+            style(t!("no-snapshots-for")).yellow(),
             style(path.display()).cyan()
         );
         return Ok(());
@@ -491,10 +1135,10 @@ pub fn inspect_file(path: &Path, db: &Database) -> Result<()> {
 
     println!(
         "{} {}",
-        style("Evolution of:").cyan().bold(),
+        style(t!("inspect-title")).cyan().bold(),
         style(path.display()).green()
     );
-    println!("{}", style("─".repeat(50)).dim());
+    println!("{}", style(rule(false, 50)).dim());
 
     let mut prev_snapshot: Option<&Snapshot> = None;
 
@@ -521,14 +1165,14 @@ pub fn inspect_file(path: &Path, db: &Database) -> Result<()> {
         if let Some(prev) = prev_snapshot {
             // Compare with previous
             if prev.checksum == snapshot.checksum {
-                println!("{} {}", header, style("(No change)").dim());
+                println!("{} {}", header, style(t!("inspect-no-change")).dim());
             } else {
                 // Calculate diff stats
                 let prev_content = prev.get_decompressed_content()?;
                 let curr_content = snapshot.get_decompressed_content()?;
 
-                if is_binary(&curr_content) || is_binary(&prev_content) {
-                    println!("{} {}", header, style("(Binary changed)").yellow());
+                if snapshot.is_binary(&curr_content) || prev.is_binary(&prev_content) {
+                    println!("{} {}", header, style(t!("inspect-binary-changed")).yellow());
                 } else {
                     let prev_str = String::from_utf8_lossy(&prev_content);
                     let curr_str_copy = String::from_utf8_lossy(&curr_content);
@@ -575,7 +1219,7 @@ pub fn inspect_file(path: &Path, db: &Database) -> Result<()> {
 
                     for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
                         if lines_printed >= MAX_LINES {
-                            println!("{}", style("  ... (diff truncated)").dim());
+                            println!("  {}", style(t!("inspect-truncated")).dim());
                             break;
                         }
 
@@ -603,10 +1247,10 @@ pub fn inspect_file(path: &Path, db: &Database) -> Result<()> {
             }
         } else {
             // First snapshot
-            println!("{} {}", header, style("(Initial)").green());
+            println!("{} {}", header, style(t!("inspect-initial")).green());
 
             if let Ok(content) = snapshot.peek_decompressed_content(1024)
-                && !is_binary(&content)
+                && !snapshot.is_binary(&content)
                 && let Ok(text) = String::from_utf8(content)
             {
                 let lines: Vec<&str> = text.lines().take(5).collect();
@@ -621,7 +1265,7 @@ pub fn inspect_file(path: &Path, db: &Database) -> Result<()> {
 
         prev_snapshot = Some(snapshot);
     }
-    println!("{}", style("─".repeat(50)).dim());
+    println!("{}", style(rule(false, 50)).dim());
 
     Ok(())
 }
@@ -630,14 +1274,14 @@ fn resolve_content(arg: &str, other_arg: &str, db: &Database) -> Result<(Vec<u8>
     if is_checksum(arg) {
         if let Some(snapshot) = db.get_snapshot_by_checksum(arg)? {
             let content = snapshot.get_decompressed_content()?;
-            let is_bin = is_binary(&content);
+            let is_bin = snapshot.is_binary(&content);
             return Ok((
                 content,
                 format!("snapshot:{}:{}", snapshot.path.display(), &arg[..8]),
                 is_bin,
             ));
         }
-        anyhow::bail!("Checksum not found: {}", &arg[..8]);
+        anyhow::bail!(t!("checksum-not-found", checksum = &arg[..8]));
     }
 
     let path = PathBuf::from(arg);
@@ -652,7 +1296,7 @@ fn resolve_content(arg: &str, other_arg: &str, db: &Database) -> Result<(Vec<u8>
         return Ok((content, name, is_bin));
     }
 
-    anyhow::bail!("Path or checksum not found: {}", arg);
+    anyhow::bail!(t!("path-or-checksum-not-found", arg = arg));
 }
 
 use similar::{ChangeTag, TextDiff};
@@ -684,26 +1328,46 @@ fn compute_unified_diff(old_name: &str, new_name: &str, old: &str, new: &str) ->
     result
 }
 
+/// Builds a plain unified diff of two texts, with `@@` hunk headers and
+/// `context` unchanged lines around each change.
+///
+/// # Returns
+///
+/// The diff, or an empty string if the texts are identical
+pub fn unified_diff(old_name: &str, new_name: &str, old: &str, new: &str, context: usize) -> String {
+    if old == new {
+        return String::new();
+    }
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(context)
+        .header(old_name, new_name)
+        .missing_newline_hint(true)
+        .to_string()
+}
+
 fn print_binary_diff(left_name: &str, right_name: &str, left: &[u8], right: &[u8]) {
-    println!("{}", style("Binary files differ").yellow().bold());
+    println!("{}", style(t!("binary-differ")).yellow().bold());
     println!("\n{}:", style(left_name).cyan());
-    println!("  Size: {}", format_size(left.len() as i64));
+    println!("  {}", t!("detail-size", value = format_size(left.len() as i64)));
     println!("\n{}:", style(right_name).cyan());
-    println!("  Size: {}", format_size(right.len() as i64));
+    println!("  {}", t!("detail-size", value = format_size(right.len() as i64)));
 
     if left.len() != right.len() {
         let diff = (right.len() as i64) - (left.len() as i64);
         if diff > 0 {
             println!(
-                "\n{} +{} bytes",
-                style("Size difference:").yellow(),
-                style(format!("+{}", diff)).green()
+                "\n{} {} {}",
+                style(t!("binary-size-difference")).yellow(),
+                style(format!("+{}", diff)).green(),
+                t!("bytes")
             );
         } else {
             println!(
-                "\n{} {} bytes",
-                style("Size difference:").yellow(),
-                style(diff).red()
+                "\n{} {} {}",
+                style(t!("binary-size-difference")).yellow(),
+                style(diff).red(),
+                t!("bytes")
             );
         }
     }
@@ -713,6 +1377,133 @@ fn print_binary_diff(left_name: &str, right_name: &str, left: &[u8], right: &[u8
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(
+            parse_timestamp("2024-01-15T10:00:00+01:00").unwrap().to_rfc3339(),
+            "2024-01-15T10:00:00+01:00"
+        );
+        let local = |s: &str| {
+            let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+            Local.from_local_datetime(&naive).unwrap()
+        };
+        assert_eq!(
+            parse_timestamp("2024-01-15 10:30").unwrap(),
+            local("2024-01-15 10:30:00")
+        );
+        assert_eq!(
+            parse_timestamp("2024-01-15").unwrap(),
+            local("2024-01-15 23:59:59")
+        );
+        assert!(parse_timestamp("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("30d").unwrap(), chrono::Duration::days(30));
+        assert_eq!(parse_age("12h").unwrap(), chrono::Duration::hours(12));
+        assert_eq!(parse_age("2w").unwrap(), chrono::Duration::weeks(2));
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("d").is_err());
+        assert_eq!(parse_age("1y").unwrap(), chrono::Duration::days(365));
+        assert!(parse_age("3x").is_err());
+    }
+
+    #[test]
+    fn test_parse_date_bound() {
+        let start = parse_date_bound("2024-01-15", true).unwrap();
+        let end = parse_date_bound("2024-01-15", false).unwrap();
+        assert_eq!(start.format("%H:%M:%S").to_string(), "00:00:00");
+        assert_eq!(end.format("%H:%M:%S").to_string(), "23:59:59");
+
+        let week_ago = parse_date_bound("7d", true).unwrap();
+        let expected = Local::now() - chrono::Duration::days(7);
+        assert!((week_ago.timestamp() - expected.timestamp()).abs() <= 1);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("500").unwrap(), 500);
+        assert_eq!(parse_size("10K").unwrap(), 10 * 1024);
+        assert_eq!(parse_size("2.5mb").unwrap(), 2_621_440);
+        assert_eq!(parse_size("1G").unwrap(), 1024 * 1024 * 1024);
+        assert!(parse_size("ten").is_err());
+        assert!(parse_size("-1").is_err());
+    }
+
+    #[test]
+    fn test_resolve_path_normalizes() {
+        assert_eq!(
+            resolve_path("/project/src/module/").unwrap(),
+            PathBuf::from("/project/src/module")
+        );
+        assert_eq!(
+            resolve_path("/project/./src").unwrap(),
+            PathBuf::from("/project/src")
+        );
+        assert_eq!(
+            resolve_path("missing/dir/").unwrap(),
+            std::env::current_dir().unwrap().join("missing/dir")
+        );
+    }
+
+    #[test]
+    fn test_completion_candidates() {
+        let cwd = Path::new("/home/user/project");
+        let entry = |path: &str, checksum: &str| {
+            (1, PathBuf::from(path), String::new(), 1, checksum.to_string())
+        };
+        let snapshots = vec![
+            entry("/home/user/project/src/main.rs", "abc123"),
+            entry("/home/user/project/README.md", "abd456"),
+            entry("/etc/hosts", "fff000"),
+        ];
+
+        assert_eq!(
+            completion_candidates("restore", "src/", cwd, &snapshots),
+            vec!["src/main.rs"]
+        );
+        assert_eq!(
+            completion_candidates("view", "/etc", cwd, &snapshots),
+            vec!["/etc/hosts"]
+        );
+        assert_eq!(
+            completion_candidates("export", "ab", cwd, &snapshots),
+            vec!["abc123", "abd456"]
+        );
+        assert!(completion_candidates("save", "", cwd, &snapshots).is_empty());
+    }
+
+    #[test]
+    fn test_plain_in_ascii_mode() {
+        set_ascii_mode(true);
+        assert_eq!(plain("♻️  Restauration"), "Restauration");
+        assert_eq!(plain("✅ done → next"), "[OK] done -> next");
+        assert_eq!(rule(true, 3), "===");
+        set_ascii_mode(false);
+        assert_eq!(plain("✅ done"), "✅ done");
+    }
+
+    #[test]
+    fn test_is_valid_alias_name() {
+        assert!(is_valid_alias_name("thesis"));
+        assert!(is_valid_alias_name("my-project_2.0"));
+        assert!(!is_valid_alias_name(""));
+        assert!(!is_valid_alias_name("a/b"));
+        assert!(!is_valid_alias_name("@a"));
+        assert!(!is_valid_alias_name("a b"));
+    }
+
+    #[test]
+    fn test_page_of() {
+        let items: Vec<u32> = (1..=25).collect();
+        assert_eq!(page_of(&items, None).unwrap().len(), 25);
+        assert_eq!(page_of(&items, Some(1)).unwrap(), &items[..10]);
+        assert_eq!(page_of(&items, Some(3)).unwrap(), &items[20..]);
+        assert!(page_of(&items, Some(0)).is_err());
+        assert!(page_of(&items, Some(4)).is_err());
+    }
+
     #[test]
     fn test_format_size_bytes() {
         assert_eq!(format_size(0), "0 B");
@@ -738,6 +1529,13 @@ mod tests {
         assert!(result.contains("GB"));
     }
 
+    #[test]
+    fn test_format_ratio() {
+        assert_eq!(format_ratio(3000, 1000), "3.0x");
+        assert_eq!(format_ratio(100, 400), "0.2x");
+        assert_eq!(format_ratio(100, 0), "-");
+    }
+
     #[test]
     fn test_is_binary_with_text() {
         let content = b"Hello, world!";
@@ -755,4 +1553,25 @@ mod tests {
         let content = b"";
         assert!(!is_binary(content));
     }
+
+    #[test]
+    fn test_detect_mime() {
+        assert_eq!(detect_mime(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), "image/png");
+        assert_eq!(detect_mime(b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(detect_mime("caf\u{e9}\n".as_bytes()), "text/plain");
+        // A sample cut in the middle of a character is still text
+        assert_eq!(detect_mime(&"\u{e9}".as_bytes()[..1]), "text/plain");
+        assert_eq!(detect_mime(b"\xff\xfe\0\x01"), "application/octet-stream");
+        assert!(is_text_mime("text/plain"));
+        assert!(!is_text_mime("image/png"));
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\n";
+        let new = "a\nb\nC\nd\ne\n";
+        let diff = unified_diff("old", "new", old, new, 1);
+        assert_eq!(diff, "--- old\n+++ new\n@@ -2,3 +2,3 @@\n b\n-c\n+C\n d\n");
+        assert!(unified_diff("old", "new", old, old, 3).is_empty());
+    }
 }
@@ -0,0 +1,162 @@
+/*!
+Watch mode for the freeze application.
+
+Monitors a file or directory and snapshots every file whose content changed,
+once the burst of file system events around an edit has settled.
+*/
+
+use crate::db::Database;
+use crate::i18n::t;
+use crate::snapshot::Snapshot;
+use crate::utils::format_size;
+use anyhow::Result;
+use chrono::Local;
+use console::style;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+/// Parameters of a watch session.
+pub struct WatchOptions {
+    /// Quiet time after the last event before changes are saved
+    pub debounce: Duration,
+    /// Minimum time between two rounds of snapshots
+    pub min_interval: Duration,
+}
+
+/// Watches `path` and saves changed files until interrupted.
+///
+/// # Arguments
+///
+/// * `path` - Absolute path of the file or directory to watch
+/// * `options` - Debounce delay and minimum interval
+/// * `db` - Database connection to store snapshots in
+///
+/// # Errors
+///
+/// Returns an error if the path cannot be watched.
+pub fn run(path: &Path, options: &WatchOptions, db: &Database) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+
+    // Editors often save by replacing the file, so a single file is watched
+    // through its parent directory
+    let single_file = path.is_file();
+    if single_file {
+        let parent = path
+            .parent()
+            .ok_or_else(|| anyhow::anyhow!("{} has no parent directory", path.display()))?;
+        watcher.watch(parent, RecursiveMode::NonRecursive)?;
+    } else {
+        watcher.watch(path, RecursiveMode::Recursive)?;
+    }
+
+    // Never snapshot freeze's own database and storage
+    let own_files = Database::own_paths();
+    let is_own = |p: &Path| own_files.iter().any(|own| p.starts_with(own));
+
+    println!(
+        "{} {} {}",
+        style(t!("watch-started")).cyan().bold(),
+        style(path.display()).green(),
+        style(t!("watch-stop-hint")).dim()
+    );
+
+    // Record the starting state so that the first edit can be undone
+    let mut pending: BTreeSet<PathBuf> = if single_file {
+        BTreeSet::from([path.to_path_buf()])
+    } else {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_entry(|e| {
+                !Snapshot::is_excluded(e.path()) && !is_own(e.path())
+            })
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect()
+    };
+    save_pending(&mut pending, db);
+    let mut last_flush = Instant::now();
+
+    loop {
+        let event = if pending.is_empty() {
+            match rx.recv() {
+                Ok(event) => Some(event),
+                Err(_) => break,
+            }
+        } else {
+            match rx.recv_timeout(options.debounce) {
+                Ok(event) => Some(event),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        };
+
+        match event {
+            Some(Ok(event)) => {
+                if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    continue;
+                }
+                for changed in event.paths {
+                    let relevant = if single_file {
+                        changed == path
+                    } else {
+                        !is_own(&changed) && !is_excluded_below(path, &changed)
+                    };
+                    if relevant {
+                        pending.insert(changed);
+                    }
+                }
+            }
+            Some(Err(e)) => eprintln!("{} {}", style(t!("watch-error")).red(), e),
+            None => {
+                // Events have settled; honour the minimum interval between rounds
+                let elapsed = last_flush.elapsed();
+                if elapsed < options.min_interval {
+                    std::thread::sleep(options.min_interval - elapsed);
+                }
+                save_pending(&mut pending, db);
+                last_flush = Instant::now();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns `true` if `file` or one of its parents up to `root` is excluded.
+fn is_excluded_below(root: &Path, file: &Path) -> bool {
+    file.ancestors()
+        .take_while(|ancestor| ancestor.starts_with(root) && *ancestor != root)
+        .any(Snapshot::is_excluded)
+}
+
+/// Snapshots the pending files whose content changed and clears the set.
+fn save_pending(pending: &mut BTreeSet<PathBuf>, db: &Database) {
+    for file in std::mem::take(pending) {
+        // Temporary files may be gone by the time the events settle
+        if !file.is_file() {
+            continue;
+        }
+        match Snapshot::save_if_changed(&file, db) {
+            Ok(Some(snapshot)) => println!(
+                "{} {} {} {}",
+                style(format!("[{}]", Local::now().format("%H:%M:%S"))).dim(),
+                style(t!("watch-saved")).green(),
+                style(file.display()).cyan(),
+                style(format!("({}, {})", format_size(snapshot.size), &snapshot.checksum[..8])).dim()
+            ),
+            Ok(None) => {}
+            Err(e) => eprintln!(
+                "{} {}: {}",
+                style(t!("watch-error")).red(),
+                file.display(),
+                e
+            ),
+        }
+    }
+}
@@ -1,9 +1,13 @@
 // src/web/api.rs - Simplified API handlers
-use crate::snapshot::Snapshot;
-use crate::utils::format_size;
+use crate::report::{Period, Report};
+use crate::db::{Database, SearchFilter};
+use crate::snapshot::{SaveProgress, Selection, SkippedFile, Snapshot};
+use crate::utils::{check_report, format_ratio, format_size, CheckStatus};
+use crate::web::auth::{generate_token, hash_token, SCOPE_READ_ONLY, SCOPE_READ_WRITE};
 use crate::web::server::AppState;
 use axum::{response::Json, extract::State};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 #[derive(Serialize)]
@@ -14,6 +18,40 @@ pub struct SnapshotDto {
     pub date: String,
     pub size: i64,
     pub size_formatted: String,
+    pub message: Option<String>,
+    pub metadata: BTreeMap<String, String>,
+    /// Type detected when the snapshot was saved
+    pub mime: Option<String>,
+    /// Whether clear and retention rules leave the snapshot alone
+    pub pinned: bool,
+    /// Bytes used by the stored content on disk, if recorded
+    pub stored_size: Option<i64>,
+    pub stored_size_formatted: Option<String>,
+    /// How many times smaller the stored content is, e.g. `3.2x`
+    pub compression_ratio: Option<String>,
+}
+
+impl SnapshotDto {
+    /// Fills in the stored size and compression ratio.
+    fn with_stored_size(mut self, stored_size: Option<i64>) -> Self {
+        self.stored_size_formatted = stored_size.map(format_size);
+        self.compression_ratio = stored_size.map(|stored_size| format_ratio(self.size, stored_size));
+        self.stored_size = stored_size;
+        self
+    }
+}
+
+/// A background save and how far it got.
+#[derive(Serialize, Clone)]
+pub struct JobDto {
+    pub id: u64,
+    pub path: String,
+    /// `running`, `done` or `failed`
+    pub state: String,
+    pub progress: SaveProgress,
+    /// Files skipped with `keep_going`
+    pub skipped: Vec<SkippedFile>,
+    pub err: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -31,6 +69,45 @@ pub struct StatsDto {
     pub total_exclusions: i64,
 }
 
+/// Snapshots below one directory, for the overview page.
+#[derive(Serialize)]
+pub struct DirectoryStatsDto {
+    pub path: String,
+    pub snapshots: usize,
+    /// Distinct files with snapshots
+    pub files: usize,
+    /// Date of the newest snapshot
+    pub newest: String,
+    pub size: i64,
+    pub size_formatted: String,
+}
+
+#[derive(Serialize)]
+pub struct CheckReportDto {
+    pub path: String,
+    pub modified: Vec<String>,
+    pub new: Vec<String>,
+    pub deleted: Vec<String>,
+    pub up_to_date: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ApiKeyDto {
+    pub id: i64,
+    pub name: String,
+    pub scope: String,
+    pub created: String,
+    pub last_used: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedApiKeyDto {
+    pub id: i64,
+    pub name: String,
+    pub scope: String,
+    pub token: String,
+}
+
 #[derive(Serialize)]
 pub struct ApiResponse<T> {
     pub ok: bool,
@@ -50,16 +127,32 @@ impl<T> From<Result<T, String>> for ApiResponse<T> {
 pub async fn api_list_snapshots(State(app_state): State<AppState>) -> Json<Vec<SnapshotDto>> {
     let db = app_state.0.lock().unwrap();
     let snapshots = db.list_all_snapshots_with_id().unwrap_or_default();
+    let mut metadata = db.list_all_metadata().unwrap_or_default();
+    let mut messages = db.list_messages().unwrap_or_default();
+    let mut types = db.list_mime_types().unwrap_or_default();
+    let pinned = db.list_pinned().unwrap_or_default();
+    let stored = db.list_stored_sizes().unwrap_or_default();
     drop(db);
     let result: Vec<SnapshotDto> = snapshots
         .into_iter()
-        .map(|(id, path, date, size, checksum)| SnapshotDto {
-            id,
-            path: path.to_string_lossy().to_string(),
-            checksum,
-            date,
-            size,
-            size_formatted: format_size(size),
+        .map(|(id, path, date, size, checksum)| {
+            let stored_size = stored.get(&id).copied();
+            SnapshotDto {
+                id,
+                pinned: pinned.contains(&id),
+                message: messages.remove(&id),
+                mime: types.remove(&id),
+                path: path.to_string_lossy().to_string(),
+                checksum,
+                date,
+                size,
+                size_formatted: format_size(size),
+                metadata: metadata.remove(&id).unwrap_or_default(),
+                stored_size: None,
+                stored_size_formatted: None,
+                compression_ratio: None,
+            }
+            .with_stored_size(stored_size)
         })
         .collect();
     Json(result)
@@ -68,72 +161,117 @@ pub async fn api_list_snapshots(State(app_state): State<AppState>) -> Json<Vec<S
 pub async fn api_search_snapshots(
     State(app_state): State<AppState>,
     axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
-) -> Json<Vec<SnapshotDto>> {
+) -> Result<Json<Vec<SnapshotDto>>, (axum::http::StatusCode, String)> {
     let pattern = params.get("q").cloned().unwrap_or_default();
+    let mut filter = SearchFilter::default();
+    for (name, value) in params.iter().filter(|(name, _)| name.as_str() != "q") {
+        filter
+            .set(name, value)
+            .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e.to_string()))?;
+    }
     let db = app_state.0.lock().unwrap();
-    let results = db.search_snapshots(&pattern).unwrap_or_default();
-    let all_with_id = db.list_all_snapshots_with_id().unwrap_or_default();
+    let results = db.search_snapshots(&pattern, &filter).unwrap_or_default();
+    let metadata = db.list_all_metadata().unwrap_or_default();
+    let messages = db.list_messages().unwrap_or_default();
+    let types = db.list_mime_types().unwrap_or_default();
+    let pinned = db.list_pinned().unwrap_or_default();
+    let stored = db.list_stored_sizes().unwrap_or_default();
     drop(db);
-    let path_to_id: std::collections::HashMap<String, i64> = all_with_id
-        .iter()
-        .map(|(id, path, _, _, _)| (path.to_string_lossy().to_string(), *id))
-        .collect();
 
     let result: Vec<SnapshotDto> = results
         .into_iter()
-        .map(|(path, date, size, checksum)| {
-            let path_str = path.to_string_lossy().to_string();
+        .map(|(id, path, date, size, checksum)| {
+            let stored_size = stored.get(&id).copied();
             SnapshotDto {
-                id: path_to_id.get(&path_str).copied().unwrap_or(0),
-                path: path_str,
+                id,
+                pinned: pinned.contains(&id),
+                message: messages.get(&id).cloned(),
+                mime: types.get(&id).cloned(),
+                path: path.to_string_lossy().to_string(),
                 checksum,
                 date,
                 size,
                 size_formatted: format_size(size),
+                metadata: metadata.get(&id).cloned().unwrap_or_default(),
+                stored_size: None,
+                stored_size_formatted: None,
+                compression_ratio: None,
             }
+            .with_stored_size(stored_size)
         })
         .collect();
-    Json(result)
+    Ok(Json(result))
+}
+
+pub async fn api_search_content(
+    State(app_state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Json<ApiResponse<Vec<crate::fulltext::ContentMatch>>> {
+    let text = params.get("q").cloned().unwrap_or_default();
+    let file_type = params.get("type").filter(|file_type| !file_type.is_empty());
+    let limit = match params.get("limit") {
+        Some(limit) => match limit.parse() {
+            Ok(limit) => limit,
+            Err(_) => return Json(ApiResponse { ok: false, data: None, err: Some(format!("Invalid limit: {}", limit)) }),
+        },
+        None => crate::fulltext::DEFAULT_LIMIT,
+    };
+
+    let db = app_state.0.lock().unwrap();
+    let matches = crate::fulltext::search(&text, file_type.map(String::as_str), limit, &db);
+    drop(db);
+    Json(matches.map_err(|e| e.to_string()).into())
 }
 
 pub async fn api_get_snapshot(State(app_state): State<AppState>, axum::extract::Path(id): axum::extract::Path<i64>) -> Json<Option<SnapshotDto>> {
     let db = app_state.0.lock().unwrap();
     let snapshot = db.get_snapshot_by_id(id).ok().flatten();
+    let metadata = db.list_metadata(id).unwrap_or_default();
+    let pinned = db.list_pinned().unwrap_or_default();
     drop(db);
     Json(snapshot.map(|s| SnapshotDto {
         id,
+        pinned: pinned.contains(&id),
         path: s.path.to_string_lossy().to_string(),
         checksum: s.checksum,
         date: s.date,
         size: s.size,
         size_formatted: format_size(s.size),
-    }))
+        message: s.message,
+        metadata,
+        mime: s.mime,
+        stored_size: None,
+        stored_size_formatted: None,
+        compression_ratio: None,
+    }
+    .with_stored_size(s.stored_size)))
 }
 
 pub async fn api_create_snapshot(State(app_state): State<AppState>, Json(input): Json<CreateSnapshotInput>) -> Json<ApiResponse<SnapshotDto>> {
-    // Expand tilde to home directory
-    let expanded_path = if input.path.starts_with("~/") {
-        match std::env::var("HOME") {
-            Ok(home) => input.path.replacen("~", &home, 1),
-            Err(_) => input.path,
-        }
-    } else {
-        input.path
-    };
-    let path = PathBuf::from(&expanded_path);
+    let path = expand_home(input.path);
     let db = app_state.0.lock().unwrap();
-    match Snapshot::save_recursive(&path, &db) {
+    match Snapshot::save_recursive(&path, input.message.as_deref(), input.keep_going, false, &db) {
         Ok(_) => {
             let snapshots = db.get_snapshots_for_path_with_id(&path).unwrap_or_default();
             if let Some((id, path, date, size, checksum)) = snapshots.first() {
+                let snapshot = db.get_snapshot_by_id(*id).ok().flatten();
+                let stored_size = snapshot.as_ref().and_then(|s| s.stored_size);
                 let dto = SnapshotDto {
                     id: *id,
+                    pinned: db.list_pinned().unwrap_or_default().contains(id),
+                    message: snapshot.as_ref().and_then(|s| s.message.clone()),
+                    mime: snapshot.and_then(|s| s.mime),
                     path: path.to_string_lossy().to_string(),
                     checksum: checksum.clone(),
                     date: date.clone(),
                     size: *size,
                     size_formatted: format_size(*size),
-                };
+                    metadata: db.list_metadata(*id).unwrap_or_default(),
+                    stored_size: None,
+                    stored_size_formatted: None,
+                    compression_ratio: None,
+                }
+                .with_stored_size(stored_size);
                 Json(ApiResponse { ok: true, data: Some(dto), err: None })
             } else {
                 Json(ApiResponse { ok: false, data: None, err: Some("Snapshot created but not found".to_string()) })
@@ -143,10 +281,69 @@ pub async fn api_create_snapshot(State(app_state): State<AppState>, Json(input):
     }
 }
 
+/// Expands a leading `~/` to the home directory.
+fn expand_home(path: String) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Starts saving a path in the background; poll `/api/jobs/{id}` for progress.
+pub async fn api_start_save_job(State(app_state): State<AppState>, Json(input): Json<CreateSnapshotInput>) -> Json<ApiResponse<JobDto>> {
+    let path = expand_home(input.path);
+    let jobs = app_state.1.clone();
+    let job = {
+        let mut jobs = jobs.lock().unwrap();
+        let job = JobDto {
+            id: jobs.keys().max().unwrap_or(&0) + 1,
+            path: path.to_string_lossy().to_string(),
+            state: "running".to_string(),
+            progress: SaveProgress::default(),
+            skipped: Vec::new(),
+            err: None,
+        };
+        jobs.insert(job.id, job.clone());
+        job
+    };
+
+    let id = job.id;
+    tokio::task::spawn_blocking(move || {
+        let update = |change: &dyn Fn(&mut JobDto)| {
+            if let Some(job) = jobs.lock().unwrap().get_mut(&id) {
+                change(job);
+            }
+        };
+        let result = Database::new().and_then(|db| {
+            Snapshot::save_recursive_with_progress(&path, input.message.as_deref(), input.keep_going, false, &db, |progress| {
+                update(&|job| job.progress = *progress);
+            })
+        });
+        update(&|job| match &result {
+            Ok(summary) => {
+                job.state = "done".to_string();
+                job.skipped = summary.skipped.clone();
+            }
+            Err(e) => {
+                job.state = "failed".to_string();
+                job.err = Some(e.to_string());
+            }
+        });
+    });
+    Json(ApiResponse { ok: true, data: Some(job), err: None })
+}
+
+pub async fn api_get_job(State(app_state): State<AppState>, axum::extract::Path(id): axum::extract::Path<u64>) -> Json<ApiResponse<JobDto>> {
+    match app_state.1.lock().unwrap().get(&id) {
+        Some(job) => Json(ApiResponse { ok: true, data: Some(job.clone()), err: None }),
+        None => Json(ApiResponse { ok: false, data: None, err: Some("Job not found".to_string()) }),
+    }
+}
+
 pub async fn api_restore_snapshot(State(app_state): State<AppState>, axum::extract::Path(id): axum::extract::Path<i64>) -> Json<ApiResponse<()>> {
     let db = app_state.0.lock().unwrap();
     if let Some(snapshot) = db.get_snapshot_by_id(id).ok().flatten() {
-        match Snapshot::restore(&snapshot.path, &db) {
+        match Snapshot::restore(&snapshot.path, None, &Selection::Checksum(snapshot.checksum.clone()), &db) {
             Ok(_) => Json(ApiResponse { ok: true, data: Some(()), err: None }),
             Err(e) => Json(ApiResponse { ok: false, data: None, err: Some(e.to_string()) }),
         }
@@ -217,6 +414,38 @@ pub async fn api_get_stats(State(app_state): State<AppState>) -> Json<StatsDto>
     })
 }
 
+pub async fn api_get_directory_stats(
+    State(app_state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Json<ApiResponse<Vec<DirectoryStatsDto>>> {
+    let root = PathBuf::from(params.get("root").filter(|root| !root.is_empty()).map_or("/", String::as_str));
+    let top = match params.get("top") {
+        Some(top) => match top.parse() {
+            Ok(top) => top,
+            Err(_) => return Json(ApiResponse { ok: false, data: None, err: Some(format!("Invalid top: {}", top)) }),
+        },
+        None => 50,
+    };
+
+    let db = app_state.0.lock().unwrap();
+    let directories = db.directory_totals(&root, top);
+    drop(db);
+    let directories = directories.map(|directories| {
+        directories
+            .into_iter()
+            .map(|totals| DirectoryStatsDto {
+                path: totals.path.to_string_lossy().to_string(),
+                snapshots: totals.snapshots,
+                files: totals.files,
+                newest: totals.newest,
+                size: totals.size,
+                size_formatted: format_size(totals.size),
+            })
+            .collect()
+    });
+    Json(directories.map_err(|e| e.to_string()).into())
+}
+
 #[derive(Deserialize)]
 pub struct ExportInput {
     pub destination: Option<String>,
@@ -397,6 +626,74 @@ fn generate_diff(name1: &str, name2: &str, content1: &[u8], content2: &[u8]) ->
     diff
 }
 
+#[derive(Deserialize)]
+pub struct CheckInput {
+    pub path: String,
+}
+
+pub async fn api_check_path(State(app_state): State<AppState>, Json(input): Json<CheckInput>) -> Json<ApiResponse<CheckReportDto>> {
+    // Expand tilde to home directory
+    let expanded_path = if input.path.starts_with("~/") {
+        match std::env::var("HOME") {
+            Ok(home) => input.path.replacen("~", &home, 1),
+            Err(_) => input.path,
+        }
+    } else {
+        input.path
+    };
+    let path = match PathBuf::from(&expanded_path).canonicalize() {
+        Ok(p) => p,
+        Err(e) => return Json(ApiResponse { ok: false, data: None, err: Some(format!("{}: {}", expanded_path, e)) }),
+    };
+
+    let db = app_state.0.lock().unwrap();
+    let report = check_report(&path, &db, |_| {});
+    drop(db);
+
+    match report {
+        Ok(entries) => {
+            let mut dto = CheckReportDto {
+                path: path.to_string_lossy().to_string(),
+                modified: Vec::new(),
+                new: Vec::new(),
+                deleted: Vec::new(),
+                up_to_date: Vec::new(),
+            };
+            for (file, status) in entries {
+                let file = file.to_string_lossy().to_string();
+                match status {
+                    CheckStatus::Modified => dto.modified.push(file),
+                    CheckStatus::New => dto.new.push(file),
+                    CheckStatus::Deleted => dto.deleted.push(file),
+                    CheckStatus::UpToDate => dto.up_to_date.push(file),
+                }
+            }
+            Json(ApiResponse { ok: true, data: Some(dto), err: None })
+        }
+        Err(e) => Json(ApiResponse { ok: false, data: None, err: Some(e.to_string()) }),
+    }
+}
+
+pub async fn api_get_report(
+    State(app_state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Json<ApiResponse<Report>> {
+    use clap::ValueEnum;
+
+    let period = match params.get("period") {
+        Some(name) => match Period::from_str(name, true) {
+            Ok(period) => period,
+            Err(_) => return Json(ApiResponse { ok: false, data: None, err: Some(format!("Invalid period: {}", name)) }),
+        },
+        None => Period::Day,
+    };
+
+    let db = app_state.0.lock().unwrap();
+    let report = crate::report::build(&db, period);
+    drop(db);
+    Json(report.map_err(|e| e.to_string()).into())
+}
+
 pub async fn api_get_snapshot_content(State(app_state): State<AppState>, axum::extract::Path(id): axum::extract::Path<i64>) -> Json<Option<String>> {
     let db = app_state.0.lock().unwrap();
     let snapshot = db.get_snapshot_by_id(id).ok().flatten();
@@ -407,6 +704,9 @@ pub async fn api_get_snapshot_content(State(app_state): State<AppState>, axum::e
     {
         // Read only first 50KB + buffer for truncated message
         match s.peek_decompressed_content(50000) {
+            Ok(content) if s.is_binary(&content) => {
+                return Json(Some("[Binary content - cannot display as text]".to_string()));
+            }
             Ok(content) => {
                 match String::from_utf8(content) {
                     Ok(text) => {
@@ -427,6 +727,11 @@ pub async fn api_get_snapshot_content(State(app_state): State<AppState>, axum::e
 #[derive(Deserialize)]
 pub struct CreateSnapshotInput {
     pub path: String,
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Skip unreadable files instead of failing
+    #[serde(default)]
+    pub keep_going: bool,
 }
 
 #[derive(Deserialize)]
@@ -434,3 +739,57 @@ pub struct AddExclusionInput {
     pub pattern: String,
     pub exclusion_type: String,
 }
+
+#[derive(Deserialize)]
+pub struct CreateApiKeyInput {
+    pub name: String,
+    pub scope: Option<String>,
+}
+
+pub async fn api_list_keys(State(app_state): State<AppState>) -> Json<Vec<ApiKeyDto>> {
+    let db = app_state.0.lock().unwrap();
+    let keys = db.list_api_keys().unwrap_or_default();
+    drop(db);
+    let result: Vec<ApiKeyDto> = keys
+        .into_iter()
+        .map(|(id, name, scope, created, last_used)| ApiKeyDto {
+            id,
+            name,
+            scope,
+            created,
+            last_used,
+        })
+        .collect();
+    Json(result)
+}
+
+pub async fn api_create_key(State(app_state): State<AppState>, Json(input): Json<CreateApiKeyInput>) -> Json<ApiResponse<CreatedApiKeyDto>> {
+    let scope = input.scope.unwrap_or_else(|| SCOPE_READ_ONLY.to_string());
+    if scope != SCOPE_READ_ONLY && scope != SCOPE_READ_WRITE {
+        return Json(ApiResponse { ok: false, data: None, err: Some(format!("Invalid scope: {}", scope)) });
+    }
+
+    let token = match generate_token() {
+        Ok(t) => t,
+        Err(e) => return Json(ApiResponse { ok: false, data: None, err: Some(e.to_string()) }),
+    };
+
+    let db = app_state.0.lock().unwrap();
+    match db.add_api_key(&input.name, &hash_token(&token), &scope) {
+        Ok(id) => Json(ApiResponse {
+            ok: true,
+            data: Some(CreatedApiKeyDto { id, name: input.name, scope, token }),
+            err: None,
+        }),
+        Err(e) => Json(ApiResponse { ok: false, data: None, err: Some(e.to_string()) }),
+    }
+}
+
+pub async fn api_revoke_key(State(app_state): State<AppState>, axum::extract::Path(id): axum::extract::Path<i64>) -> Json<ApiResponse<()>> {
+    let db = app_state.0.lock().unwrap();
+    match db.remove_api_key(id) {
+        Ok(true) => Json(ApiResponse { ok: true, data: Some(()), err: None }),
+        Ok(false) => Json(ApiResponse { ok: false, data: None, err: Some("API key not found".to_string()) }),
+        Err(e) => Json(ApiResponse { ok: false, data: None, err: Some(e.to_string()) }),
+    }
+}
@@ -0,0 +1,138 @@
+// src/web/auth.rs - API key generation and request authentication
+use crate::web::server::AppState;
+use axum::{
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+pub const SCOPE_READ_ONLY: &str = "read-only";
+pub const SCOPE_READ_WRITE: &str = "read-write";
+
+/// Generates a new random API token.
+pub fn generate_token() -> anyhow::Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow::anyhow!("Failed to generate token: {}", e))?;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("frz_{}", hex))
+}
+
+/// Hashes a token for storage; clear tokens are never persisted.
+pub fn hash_token(token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(token.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Extracts the token from `Authorization: Bearer <token>` or `X-Api-Key: <token>`.
+pub(crate) fn extract_token(request: &Request) -> Option<String> {
+    let headers = request.headers();
+    if let Some(value) = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok())
+        && let Some(token) = value.strip_prefix("Bearer ")
+    {
+        return Some(token.trim().to_string());
+    }
+    headers
+        .get("x-api-key")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+}
+
+/// Rejects API requests without a valid key once at least one key exists.
+///
+/// Read-only keys may only issue GET requests (and `POST /api/check`, which
+/// does not modify anything); everything else, including key management,
+/// requires a read-write key.
+pub async fn require_api_key(
+    State(app_state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !request.uri().path().starts_with("/api/") {
+        return next.run(request).await;
+    }
+
+    let token = extract_token(&request);
+    let (key_count, scope) = {
+        let db = app_state.0.lock().unwrap();
+        let key_count = db.count_api_keys();
+        let scope = match token {
+            Some(token) => db.authenticate_api_key(&hash_token(&token)).ok().flatten(),
+            None => None,
+        };
+        (key_count, scope)
+    };
+
+    match key_count {
+        Ok(0) => return next.run(request).await,
+        Ok(_) => {}
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+
+    match scope.as_deref() {
+        None => (StatusCode::UNAUTHORIZED, "Missing or invalid API key").into_response(),
+        Some(SCOPE_READ_ONLY)
+            if request.method() != Method::GET && request.uri().path() != "/api/check" =>
+        {
+            (StatusCode::FORBIDDEN, "API key is read-only").into_response()
+        }
+        Some(SCOPE_READ_ONLY) if request.uri().path().starts_with("/api/keys") => {
+            (StatusCode::FORBIDDEN, "API key management requires a read-write key").into_response()
+        }
+        Some(_) => next.run(request).await,
+    }
+}
+
+/// Whether an API request changes the repository.
+///
+/// Exports and restores write outside of it, a restore overwriting files
+/// being refused when it runs.
+fn modifies_repository(method: &Method, path: &str) -> bool {
+    if *method == Method::GET || !path.starts_with("/api/") {
+        return false;
+    }
+    !(path == "/api/check"
+        || path == "/api/diff"
+        || (path.starts_with("/api/snapshots/") && (path.ends_with("/export") || path.ends_with("/restore"))))
+}
+
+/// Rejects API requests that would change the repository in read-only mode.
+pub async fn require_writable(request: Request, next: Next) -> Response {
+    if crate::config::get().read_only() && modifies_repository(request.method(), request.uri().path()) {
+        return (StatusCode::FORBIDDEN, "Repository is read-only").into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_token_is_unique() {
+        let first = generate_token().unwrap();
+        let second = generate_token().unwrap();
+        assert!(first.starts_with("frz_"));
+        assert_eq!(first.len(), 68);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_hash_token_is_stable() {
+        assert_eq!(hash_token("abc"), hash_token("abc"));
+        assert_ne!(hash_token("abc"), hash_token("abd"));
+    }
+
+    #[test]
+    fn test_modifies_repository() {
+        assert!(!modifies_repository(&Method::GET, "/api/snapshots"));
+        assert!(!modifies_repository(&Method::POST, "/api/check"));
+        assert!(!modifies_repository(&Method::POST, "/api/snapshots/3/restore"));
+        assert!(modifies_repository(&Method::POST, "/api/snapshots"));
+        assert!(modifies_repository(&Method::DELETE, "/api/snapshots/3"));
+        assert!(modifies_repository(&Method::POST, "/api/exclusions"));
+        assert!(modifies_repository(&Method::POST, "/api/jobs"));
+    }
+}
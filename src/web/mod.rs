@@ -1,5 +1,6 @@
 // src/web/mod.rs
 pub mod server;
 pub mod api;
+pub mod auth;
 
 pub use server::run_server;
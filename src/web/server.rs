@@ -1,18 +1,25 @@
 // src/web/server.rs
 use crate::db::Database;
 use crate::web::api::*;
+use crate::web::auth::{require_api_key, require_writable};
 use axum::{
+    middleware,
     routing::{get, post, delete},
     Router,
     response::Html,
 };
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 use tower_http::cors::{CorsLayer, Any};
 
-/// AppState wrapper for thread-safe database access
+/// AppState wrapper for thread-safe database access, with the background
+/// jobs started through `/api/jobs`
 #[derive(Clone)]
-pub struct AppState(pub Arc<Mutex<Database>>);
+pub struct AppState(pub Arc<Mutex<Database>>, pub Jobs);
+
+/// Background jobs by ID.
+pub type Jobs = Arc<Mutex<HashMap<u64, JobDto>>>;
 
 const HTML_PAGE: &str = r##"<!DOCTYPE html>
 <html lang="en">
@@ -106,7 +113,8 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
 
         /* Detail info */
         .detail-path { font-family: 'JetBrains Mono', monospace; font-size: 0.9rem; word-break: break-all; margin-bottom: 1rem; padding: 0.75rem; background: rgba(0,0,0,0.3); border-radius: 6px; }
-        .detail-grid { display: grid; grid-template-columns: repeat(3, 1fr); gap: 1rem; margin-bottom: 1.5rem; }
+        .detail-message { font-style: italic; color: var(--text-muted); margin: -0.5rem 0 1rem; }
+        .detail-grid { display: grid; grid-template-columns: repeat(4, 1fr); gap: 1rem; margin-bottom: 1.5rem; }
         .detail-item { text-align: center; padding: 0.75rem; background: rgba(0,0,0,0.2); border-radius: 6px; }
         .detail-value { font-size: 1.1rem; font-weight: 600; color: var(--accent); }
         .detail-label { font-size: 0.7rem; color: var(--text-muted); text-transform: uppercase; margin-top: 0.25rem; }
@@ -216,6 +224,12 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
                     <div class="nav-item" data-page="diff">
                         <span>&#8614;</span> Compare
                     </div>
+                    <div class="nav-item" data-page="check">
+                        <span>&#10004;</span> Check
+                    </div>
+                    <div class="nav-item" data-page="overview">
+                        <span>&#128202;</span> Overview
+                    </div>
                 </div>
             </div>
             <div class="stats-bar">
@@ -268,6 +282,9 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
                         <input type="text" class="form-input" id="save-path" placeholder="/path/to/file_or_directory" onkeypress="if(event.key==='Enter')handleSave()">
                         <button class="btn btn-primary" onclick="handleSave()">Save Snapshot</button>
                     </div>
+                    <div class="form-row" style="margin-top: 0.75rem;">
+                        <input type="text" class="form-input" id="save-comment" placeholder="Message (optional), e.g. before upgrading deps" onkeypress="if(event.key==='Enter')handleSave()">
+                    </div>
                     <div id="save-message" style="margin-top: 1rem;"></div>
                 </div>
                 <button class="btn" onclick="navigateTo('snapshots')">Back to Snapshots</button>
@@ -282,6 +299,14 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
                 <div class="form-section">
                     <div class="form-row">
                         <input type="text" class="form-input" id="search-input" placeholder="Search pattern (e.g., *.py, /home/)" onkeypress="if(event.key==='Enter')performSearch()">
+                        <select class="form-input" id="search-type" style="width: 140px;">
+                            <option value="">Any type</option>
+                            <option value="text">Text</option>
+                            <option value="image">Image</option>
+                            <option value="video">Video</option>
+                            <option value="audio">Audio</option>
+                            <option value="application">Application</option>
+                        </select>
                         <button class="btn btn-primary" onclick="performSearch()">Search</button>
                     </div>
                 </div>
@@ -303,6 +328,7 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
                             <option value="file">File</option>
                             <option value="directory">Directory</option>
                             <option value="extension">Extension</option>
+                            <option value="include">Include only</option>
                         </select>
                         <button class="btn btn-primary" onclick="handleAddExclusion()">Add</button>
                     </div>
@@ -342,6 +368,37 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
                 <!-- Diff Results -->
                 <div id="diff-results" style="margin-top: 1.5rem;"></div>
             </div>
+
+            <!-- Check Page -->
+            <div id="check" class="page">
+                <div class="header">
+                    <h1 style="font-size: 1.5rem; font-weight: 600;">Check</h1>
+                    <p style="color: var(--text-muted); font-size: 0.85rem; margin-top: 0.25rem;">See which files changed since their last snapshot</p>
+                </div>
+                <div class="form-section">
+                    <div class="form-row">
+                        <input type="text" class="form-input" id="check-path" placeholder="/path/to/file_or_directory" onkeypress="if(event.key==='Enter')handleCheck()">
+                        <button class="btn btn-primary" id="check-btn" onclick="handleCheck()">Check</button>
+                    </div>
+                </div>
+                <div id="check-results"></div>
+                <button class="btn" onclick="navigateTo('snapshots')" style="margin-top: 1rem;">Back to Snapshots</button>
+            </div>
+
+            <!-- Overview Page -->
+            <div id="overview" class="page">
+                <div class="header">
+                    <h1 style="font-size: 1.5rem; font-weight: 600;">Overview</h1>
+                    <p style="color: var(--text-muted); font-size: 0.85rem; margin-top: 0.25rem;">Snapshots per directory; click a directory to break it down</p>
+                </div>
+                <div class="form-section">
+                    <div class="form-row">
+                        <input type="text" class="form-input" id="overview-root" placeholder="/" onkeypress="if(event.key==='Enter')loadOverview()">
+                        <button class="btn btn-primary" onclick="loadOverview()">Show</button>
+                    </div>
+                </div>
+                <div id="overview-results"></div>
+            </div>
         </main>
     </div>
 
@@ -354,11 +411,16 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
             </div>
             <div class="modal-body">
                 <div class="detail-path" id="modal-path"></div>
+                <div class="detail-message" id="modal-message"></div>
                 <div class="detail-grid">
                     <div class="detail-item">
                         <div class="detail-value" id="modal-size">-</div>
                         <div class="detail-label">Size</div>
                     </div>
+                    <div class="detail-item">
+                        <div class="detail-value" id="modal-stored">-</div>
+                        <div class="detail-label">Stored</div>
+                    </div>
                     <div class="detail-item">
                         <div class="detail-value" id="modal-date">-</div>
                         <div class="detail-label">Date</div>
@@ -409,6 +471,25 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
     <script>
         var API = "";
         var currentSnapshots = [];
+
+        // Attach the stored API key to every request and ask for one on 401
+        var nativeFetch = window.fetch.bind(window);
+        window.fetch = async function(url, options) {
+            options = options || {};
+            var token = localStorage.getItem('freezeApiKey');
+            if (token) {
+                options.headers = Object.assign({}, options.headers, {'Authorization': 'Bearer ' + token});
+            }
+            var res = await nativeFetch(url, options);
+            if (res.status === 401) {
+                var entered = prompt('This Freeze server requires an API key:');
+                if (entered) {
+                    localStorage.setItem('freezeApiKey', entered.trim());
+                    return window.fetch(url, options);
+                }
+            }
+            return res;
+        };
         var selectedSnapshot = null;
 
         // Navigation
@@ -430,6 +511,7 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
             if (page === 'snapshots') loadSnapshots();
             if (page === 'exclusions') loadExclusions();
             if (page === 'diff') loadDiffPage();
+            if (page === 'overview') loadOverview();
         }
 
         // Load stats
@@ -447,6 +529,16 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
             loadStats();
         }
 
+        function fileIcon(mime) {
+            if (!mime) return '&#128196;';
+            if (mime.indexOf('image/') === 0) return '&#128444;';
+            if (mime.indexOf('video/') === 0) return '&#127916;';
+            if (mime.indexOf('audio/') === 0) return '&#127925;';
+            if (mime.indexOf('text/') === 0) return '&#128221;';
+            if (/zip|tar|gzip|bzip|xz|7z|rar|zstd/.test(mime)) return '&#128230;';
+            return '&#128196;';
+        }
+
         function renderSnapshots(snapshots) {
             var tbody = document.getElementById('snapshots-list');
             if (snapshots.length === 0) {
@@ -456,7 +548,7 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
             var html = '';
             for (var i = 0; i < snapshots.length; i++) {
                 var s = snapshots[i];
-                html += '<tr onclick="openDetail(' + s.id + ')"><td class="path-cell" title="' + s.path + '">' + s.path + '</td><td class="size-cell">' + s.size_formatted + '</td><td class="date-cell">' + s.date.split('T')[0] + '</td><td><span class="checksum-cell">' + s.checksum.substring(0, 16) + '</span></td></tr>';
+                html += '<tr onclick="openDetail(' + s.id + ')"><td class="path-cell" title="' + s.path + '">' + fileIcon(s.mime) + ' ' + s.path + (s.pinned ? ' <span title="Pinned">📌</span>' : '') + '</td><td class="size-cell">' + s.size_formatted + '</td><td class="date-cell">' + s.date.split('T')[0] + '</td><td><span class="checksum-cell">' + s.checksum.substring(0, 16) + '</span></td></tr>';
             }
             tbody.innerHTML = html;
         }
@@ -477,7 +569,13 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
             selectedSnapshot = snapshot;
 
             document.getElementById('modal-path').textContent = snapshot.path;
+            var messageEl = document.getElementById('modal-message');
+            messageEl.textContent = snapshot.message || '';
+            messageEl.style.display = snapshot.message ? '' : 'none';
             document.getElementById('modal-size').textContent = snapshot.size_formatted;
+            document.getElementById('modal-stored').textContent = snapshot.stored_size_formatted
+                ? snapshot.stored_size_formatted + ' (' + snapshot.compression_ratio + ')'
+                : '-';
             document.getElementById('modal-date').textContent = snapshot.date.replace('T', ' ').split('.')[0];
             document.getElementById('modal-checksum').textContent = snapshot.checksum.substring(0, 16) + '...';
             if (snapshot.size > 100000) {
@@ -758,6 +856,7 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
         // Save
         async function handleSave() {
             var path = document.getElementById('save-path').value;
+            var comment = document.getElementById('save-comment').value.trim();
             var msg = document.getElementById('save-message');
             var btn = document.querySelector('#save .btn-primary');
             if (!path) { showToast('Please enter a path', 'error'); return; }
@@ -768,11 +867,12 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
             msg.innerHTML = '';
 
             try {
-                var res = await fetch(API + '/api/snapshots', { method: 'POST', headers: {'Content-Type': 'application/json'}, body: JSON.stringify({path: path}) });
+                var res = await fetch(API + '/api/snapshots', { method: 'POST', headers: {'Content-Type': 'application/json'}, body: JSON.stringify({path: path, message: comment || null}) });
                 var data = await res.json();
                 if (data.ok) {
                     showToast('Snapshot saved successfully!', 'success');
                     document.getElementById('save-path').value = '';
+                    document.getElementById('save-comment').value = '';
                     loadSnapshots();
                 } else {
                     showToast('Error: ' + data.err, 'error');
@@ -791,7 +891,8 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
             var container = document.getElementById('search-results');
             if (!query) { container.innerHTML = ''; return; }
 
-            var snapshots = await fetch(API + '/api/snapshots/search?q=' + encodeURIComponent(query)).then(function(r) { return r.json(); });
+            var type = document.getElementById('search-type').value;
+            var snapshots = await fetch(API + '/api/snapshots/search?q=' + encodeURIComponent(query) + '&type=' + encodeURIComponent(type)).then(function(r) { return r.json(); });
 
             if (snapshots.length === 0) {
                 container.innerHTML = '<div class="empty"><div class="empty-icon">&#128269;</div><p>No results found</p></div>';
@@ -801,7 +902,7 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
             var html = '<div class="table-container"><table><thead><tr><th>Path</th><th>Size</th><th>Date</th><th>Actions</th></tr></thead><tbody>';
             for (var i = 0; i < snapshots.length; i++) {
                 var s = snapshots[i];
-                html += '<tr onclick="openDetail(' + s.id + ')"><td class="path-cell">' + s.path + '</td><td class="size-cell">' + s.size_formatted + '</td><td class="date-cell">' + s.date.split('T')[0] + '</td><td class="actions-cell"><button class="btn btn-sm" onclick="event.stopPropagation();quickRestore(' + s.id + ')">Restore</button></td></tr>';
+                html += '<tr onclick="openDetail(' + s.id + ')"><td class="path-cell">' + fileIcon(s.mime) + ' ' + s.path + (s.pinned ? ' <span title="Pinned">📌</span>' : '') + '</td><td class="size-cell">' + s.size_formatted + '</td><td class="date-cell">' + s.date.split('T')[0] + '</td><td class="actions-cell"><button class="btn btn-sm" onclick="event.stopPropagation();quickRestore(' + s.id + ')">Restore</button></td></tr>';
             }
             html += '</tbody></table></div>';
             container.innerHTML = html;
@@ -872,6 +973,78 @@ const HTML_PAGE: &str = r##"<!DOCTYPE html>
             if (e.key === 'Escape') closeModal();
         });
 
+        // Check
+        async function handleCheck() {
+            var path = document.getElementById('check-path').value;
+            var container = document.getElementById('check-results');
+            var btn = document.getElementById('check-btn');
+            if (!path) { showToast('Please enter a path', 'error'); return; }
+
+            btn.disabled = true;
+            btn.textContent = 'Checking...';
+            container.innerHTML = '';
+
+            try {
+                var res = await fetch(API + '/api/check', { method: 'POST', headers: {'Content-Type': 'application/json'}, body: JSON.stringify({path: path}) });
+                var data = await res.json();
+                if (!data.ok) { showToast('Error: ' + data.err, 'error'); return; }
+
+                var report = data.data;
+                var changed = report.modified.length + report.new.length + report.deleted.length;
+                var html = '<p style="margin-bottom: 1rem; color: ' + (changed === 0 ? 'var(--success)' : 'var(--warning)') + ';">'
+                    + (changed === 0 ? 'In sync with its snapshots' : changed + ' file(s) differ from their snapshots')
+                    + ' &middot; ' + report.up_to_date.length + ' up to date, ' + report.modified.length + ' modified, ' + report.new.length + ' new, ' + report.deleted.length + ' deleted</p>';
+                if (changed > 0) {
+                    html += '<div class="table-container"><table><thead><tr><th>Path</th><th>Status</th></tr></thead><tbody>';
+                    for (var i = 0; i < report.modified.length; i++) {
+                        html += '<tr><td class="path-cell">' + escapeHtml(report.modified[i]) + '</td><td style="color: var(--warning);">Modified</td></tr>';
+                    }
+                    for (var j = 0; j < report.new.length; j++) {
+                        html += '<tr><td class="path-cell">' + escapeHtml(report.new[j]) + '</td><td style="color: var(--danger);">New</td></tr>';
+                    }
+                    for (var k = 0; k < report.deleted.length; k++) {
+                        html += '<tr><td class="path-cell">' + escapeHtml(report.deleted[k]) + '</td><td style="color: var(--danger);">Deleted</td></tr>';
+                    }
+                    html += '</tbody></table></div>';
+                }
+                container.innerHTML = html;
+            } catch (err) {
+                showToast('Error: ' + err, 'error');
+            } finally {
+                btn.disabled = false;
+                btn.textContent = 'Check';
+            }
+        }
+
+        async function loadOverview(root) {
+            var input = document.getElementById('overview-root');
+            if (root !== undefined) input.value = root;
+            var container = document.getElementById('overview-results');
+            try {
+                var data = await fetch(API + '/api/stats/directories?root=' + encodeURIComponent(input.value)).then(function(r) { return r.json(); });
+                if (!data.ok) { showToast('Error: ' + data.err, 'error'); return; }
+
+                var directories = data.data;
+                if (directories.length === 0) {
+                    container.innerHTML = '<p style="color: var(--text-muted);">No snapshots below ' + escapeHtml(input.value || '/') + '</p>';
+                    return;
+                }
+                var current = (input.value || '/').replace(/\/+$/, '') || '/';
+                var html = '<div class="table-container"><table><thead><tr><th>Directory</th><th>Snapshots</th><th>Files</th><th>Newest</th><th>Size</th></tr></thead><tbody>';
+                for (var i = 0; i < directories.length; i++) {
+                    var d = directories[i];
+                    // The root itself holds the files directly inside it
+                    var drill = d.path !== current ? ' style="cursor: pointer;" onclick="loadOverview(this.dataset.path)" data-path="' + escapeHtml(d.path) + '"' : '';
+                    html += '<tr' + drill + '><td class="path-cell">' + escapeHtml(d.path) + '</td><td>' + d.snapshots + '</td><td>' + d.files
+                        + '</td><td>' + escapeHtml(formatDate(d.newest)) + '</td><td>' + escapeHtml(d.size_formatted) + '</td></tr>';
+                }
+                html += '</tbody></table></div>';
+                container.innerHTML = html;
+            } catch (err) {
+                showToast('Error: ' + err, 'error');
+            }
+        }
+
         // Initial load
         loadSnapshots();
     </script>
@@ -883,7 +1056,7 @@ pub async fn run_server(port: u16, open_browser: bool) -> Result<(), anyhow::Err
     let addr = SocketAddr::from(([127, 0, 0, 1], port));
 
     let db = Database::new()?;
-    let app_state = AppState(Arc::new(Mutex::new(db)));
+    let app_state = AppState(Arc::new(Mutex::new(db)), Jobs::default());
     let cors = CorsLayer::new().allow_origin(Any);
 
     let app = Router::new()
@@ -891,17 +1064,28 @@ pub async fn run_server(port: u16, open_browser: bool) -> Result<(), anyhow::Err
         .route("/index.html", get(|| async { Html(HTML_PAGE) }))
         .route("/api/snapshots", get(api_list_snapshots))
         .route("/api/snapshots/search", get(api_search_snapshots))
+        .route("/api/search/content", get(api_search_content))
         .route("/api/snapshots", post(api_create_snapshot))
-        .route("/api/snapshots/:id", get(api_get_snapshot))
-        .route("/api/snapshots/:id/content", get(api_get_snapshot_content))
-        .route("/api/snapshots/:id/export", post(api_export_snapshot))
-        .route("/api/snapshots/:id/restore", post(api_restore_snapshot))
-        .route("/api/snapshots/:id", delete(api_delete_snapshot))
+        .route("/api/snapshots/{id}", get(api_get_snapshot))
+        .route("/api/snapshots/{id}/content", get(api_get_snapshot_content))
+        .route("/api/snapshots/{id}/export", post(api_export_snapshot))
+        .route("/api/snapshots/{id}/restore", post(api_restore_snapshot))
+        .route("/api/snapshots/{id}", delete(api_delete_snapshot))
         .route("/api/diff", post(api_diff_snapshots))
+        .route("/api/check", post(api_check_path))
         .route("/api/exclusions", get(api_list_exclusions))
         .route("/api/exclusions", post(api_add_exclusion))
-        .route("/api/exclusions/:pattern", delete(api_remove_exclusion))
+        .route("/api/exclusions/{pattern}", delete(api_remove_exclusion))
+        .route("/api/jobs", post(api_start_save_job))
+        .route("/api/jobs/{id}", get(api_get_job))
         .route("/api/stats", get(api_get_stats))
+        .route("/api/stats/directories", get(api_get_directory_stats))
+        .route("/api/report", get(api_get_report))
+        .route("/api/keys", get(api_list_keys))
+        .route("/api/keys", post(api_create_key))
+        .route("/api/keys/{id}", delete(api_revoke_key))
+        .layer(middleware::from_fn(require_writable))
+        .layer(middleware::from_fn_with_state(app_state.clone(), require_api_key))
         .layer(cors)
         .with_state(app_state);
 
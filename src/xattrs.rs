@@ -0,0 +1,106 @@
+/*!
+Extended attributes of saved files for the freeze application.
+
+With the `xattrs` setting (or `freeze save --xattrs`), the extended
+attributes of each saved file are recorded with its snapshot and set again
+when it is restored. POSIX ACLs are extended attributes too
+(`system.posix_acl_access` and `system.posix_acl_default`), so they are kept
+the same way. Attributes are stored in the database as a JSON object mapping
+each name to its value bytes.
+*/
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Extended attributes of a file, by name.
+pub type Xattrs = BTreeMap<String, Vec<u8>>;
+
+/// Reads the extended attributes of `path`, or of what it points to when it
+/// is a symbolic link.
+///
+/// # Returns
+///
+/// The attributes, empty on platforms without extended attributes
+///
+/// # Errors
+///
+/// Returns an error if the attributes cannot be listed or read.
+pub fn read(path: &Path) -> Result<Xattrs> {
+    let mut xattrs = Xattrs::new();
+    if !xattr::SUPPORTED_PLATFORM {
+        return Ok(xattrs);
+    }
+    for name in xattr::list_deref(path)? {
+        // Names that are not UTF-8 cannot be stored as JSON keys
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if let Some(value) = xattr::get_deref(path, name)? {
+            xattrs.insert(name.to_string(), value);
+        }
+    }
+    Ok(xattrs)
+}
+
+/// Sets extended attributes on `path`.
+///
+/// Every attribute is tried: some need privileges or a file system that
+/// supports them.
+///
+/// # Returns
+///
+/// The attributes that could not be set, with the reason
+pub fn apply(path: &Path, xattrs: &Xattrs) -> Vec<(String, std::io::Error)> {
+    xattrs
+        .iter()
+        .filter_map(|(name, value)| xattr::set(path, name, value).err().map(|e| (name.clone(), e)))
+        .collect()
+}
+
+/// Serializes attributes as stored in the database.
+pub fn to_json(xattrs: &Xattrs) -> String {
+    serde_json::to_string(xattrs).unwrap_or_default()
+}
+
+/// Parses attributes as stored in the database.
+///
+/// # Errors
+///
+/// Returns an error if `json` is not a valid attribute object.
+pub fn from_json(json: &str) -> Result<Xattrs> {
+    Ok(serde_json::from_str(json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let xattrs = Xattrs::from([
+            ("user.comment".to_string(), b"hello".to_vec()),
+            ("system.posix_acl_access".to_string(), vec![2, 0, 0, 0, 1, 0, 6, 0]),
+        ]);
+        assert_eq!(from_json(&to_json(&xattrs)).unwrap(), xattrs);
+        assert!(from_json("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn test_read_and_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        let dest = dir.path().join("dest");
+        std::fs::write(&source, b"").unwrap();
+        std::fs::write(&dest, b"").unwrap();
+        // Not every file system used for tests supports user attributes
+        if !xattr::SUPPORTED_PLATFORM || xattr::set(&source, "user.freeze", b"yes").is_err() {
+            return;
+        }
+
+        let xattrs = read(&source).unwrap();
+        assert_eq!(xattrs.get("user.freeze").map(Vec::as_slice), Some(&b"yes"[..]));
+        assert!(apply(&dest, &xattrs).is_empty());
+        assert_eq!(read(&dest).unwrap(), xattrs);
+    }
+}